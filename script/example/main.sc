@@ -25,7 +25,7 @@ fn main(){
   for i in 0..33{
     let a = i;
   }
-  let b = 1000;
+  let mut b = 1000;
   let mut c = &mut b;
 
   let paths = [
@@ -0,0 +1,9 @@
+// 標準入出力で通信する言語サーバー(診断・ホバー・定義と参照・シンボル・補完)
+use tanucc_script::{lsp, messages};
+
+fn main() -> Result<(), String> {
+    // ログは標準エラー出力に出る(標準出力はエディタとの通信に使う)
+    env_logger::init();
+    messages::set_locale(messages::Locale::detect(None)?);
+    lsp::run()
+}
@@ -1,5 +1,6 @@
 use indexmap::IndexMap;
 use serde_json::Value;
+use std::collections::HashSet;
 use uuid::Uuid;
 
 // 変数情報
@@ -20,6 +21,7 @@ pub struct Context {
     pub type_context: IndexMap<String, String>,    // グローバルス型定義スコープ
     pub comment_lists: IndexMap<(usize, usize), Vec<String>>, // コメントリスト
     pub used_context: IndexMap<String, (usize, usize, bool)>, // 参照カウント(変数名,(行数,列数,参照カウント))
+    pub scope_stack: Vec<IndexMap<String, Variable>>, // 退避中のローカルスコープ(ブロック,関数呼び出し元)
 }
 impl Context {
     pub fn new() -> Self {
@@ -29,6 +31,33 @@ impl Context {
             type_context: IndexMap::new(),
             comment_lists: IndexMap::new(),
             used_context: IndexMap::new(),
+            scope_stack: Vec::new(),
+        }
+    }
+
+    // GCのルートとなる全スコープの変数アドレスを集める
+    pub fn root_addresses(&self) -> HashSet<Uuid> {
+        self.global_context
+            .values()
+            .chain(self.local_context.values())
+            .chain(self.scope_stack.iter().flat_map(|scope| scope.values()))
+            .map(|variable| variable.address)
+            .filter(|address| !address.is_nil())
+            .collect()
+    }
+
+    // 参照経由で書き換えた値を、同じアドレスを持つ全スコープの変数に反映する
+    pub fn sync_variable_value(&mut self, address: Uuid, value: &Value) {
+        let scopes = self
+            .scope_stack
+            .iter_mut()
+            .chain([&mut self.local_context, &mut self.global_context]);
+        for scope in scopes {
+            for variable in scope.values_mut() {
+                if variable.address == address {
+                    variable.value = value.clone();
+                }
+            }
         }
     }
 }
@@ -0,0 +1,552 @@
+use crate::error::{CompilerError, Diagnostic, ScriptError};
+use crate::msg;
+use crate::parser::syntax::Node;
+use crate::source_map::Span;
+use crate::types::*;
+use std::collections::HashMap;
+
+// 束縛(変数・引数)の情報
+#[derive(Debug, Clone)]
+struct Binding {
+    is_mutable: bool,        // 可変性
+    is_parameter: bool,      // 関数の引数かどうか
+    site: Site,              // 宣言位置
+    reference: Option<bool>, // 参照を保持している場合はその可変性
+}
+
+// 借用の情報
+#[derive(Debug, Clone)]
+struct Borrow {
+    target: String,         // 借用元の変数名
+    is_mutable: bool,       // &mut かどうか
+    site: Site,             // 借用位置
+    holder: Option<String>, // 借用を保持している変数名(一時的な借用ならNone)
+}
+
+// 指摘する位置(行数,列数)とソース上の範囲
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) struct Site {
+    pub(super) line: usize,
+    pub(super) column: usize,
+    pub(super) span: Span,
+}
+
+// スコープごとの束縛と借用
+#[derive(Debug, Default)]
+struct Scope {
+    bindings: HashMap<String, Binding>,
+    borrows: Vec<Borrow>,
+}
+
+// 実行前に可変性と借用規則(可変参照1つ、または共有参照複数)を検査する
+pub struct Checker {
+    scopes: Vec<Scope>,                 // スコープのスタック(先頭がグローバル)
+    remaining: Vec<(Vec<Node>, usize)>, // ブロックごとの文の列と現在位置
+    temporaries: Vec<Borrow>,           // 現在の文の中だけで有効な借用
+    errors: CompilerError,
+    error_count: usize,
+}
+
+impl Checker {
+    fn new() -> Self {
+        Checker {
+            scopes: vec![Scope::default()],
+            remaining: Vec::new(),
+            temporaries: Vec::new(),
+            errors: CompilerError::new(),
+            error_count: 0,
+        }
+    }
+
+    // ファイル全体を検査し、違反があればまとめて1つのエラーとして返す
+    pub fn check(node: &Node, file_name: &str, source: &str) -> Result<(), ScriptError> {
+        let mut checker = Checker::new();
+        let statements: Vec<Node> = node.iter().cloned().collect();
+        checker.check_statements(&statements);
+        if checker.error_count == 0 {
+            Ok(())
+        } else {
+            Err(checker.errors.into_error(file_name, source))
+        }
+    }
+
+    fn check_statements(&mut self, statements: &[Node]) {
+        self.remaining.push((statements.to_vec(), 0));
+        for (i, statement) in statements.iter().enumerate() {
+            if let Some(last) = self.remaining.last_mut() {
+                last.1 = i;
+            }
+            self.check_node(statement);
+            self.temporaries.clear();
+        }
+        self.remaining.pop();
+    }
+
+    fn check_node(&mut self, node: &Node) {
+        match &node.value {
+            NodeValue::Declaration(Declaration::Variable(var_name, _, value, _, is_mutable)) => {
+                let name = variable_name(var_name).unwrap_or_default();
+                let reference = self.check_held_borrow(value, &name);
+                self.declare(
+                    &name,
+                    Binding {
+                        is_mutable: *is_mutable,
+                        is_parameter: false,
+                        site: site(var_name),
+                        reference,
+                    },
+                );
+            }
+            NodeValue::Assign(target, value, index) => {
+                self.check_assign(node, target, value, index)
+            }
+            NodeValue::Operator(Operator::Increment(target))
+            | NodeValue::Operator(Operator::Decrement(target)) => {
+                if let Some(name) = variable_name(target) {
+                    self.check_write(&name, site(node));
+                }
+            }
+            NodeValue::Operator(Operator::AddAssign(target, value))
+            | NodeValue::Operator(Operator::SubAssign(target, value))
+            | NodeValue::Operator(Operator::MulAssign(target, value))
+            | NodeValue::Operator(Operator::DivAssign(target, value))
+            | NodeValue::Operator(Operator::BitAndAssign(target, value))
+            | NodeValue::Operator(Operator::BitOrAssign(target, value))
+            | NodeValue::Operator(Operator::BitXorAssign(target, value))
+            | NodeValue::Operator(Operator::ShiftLeftAssign(target, value))
+            | NodeValue::Operator(Operator::ShiftRightAssign(target, value)) => {
+                self.check_node(value);
+                if let Some(name) = variable_name(target) {
+                    self.check_write(&name, site(node));
+                }
+            }
+            NodeValue::Variable(_, name, is_mutable, true) => {
+                // 文の中だけで使われる一時的な借用
+                let borrow = Borrow {
+                    target: name.clone(),
+                    is_mutable: *is_mutable,
+                    site: site(node),
+                    holder: None,
+                };
+                self.check_borrow(&borrow);
+                self.temporaries.push(borrow);
+            }
+            NodeValue::Block(block) => {
+                let statements: Vec<Node> = block.iter().flat_map(|b| b.iter()).cloned().collect();
+                let temporaries = std::mem::take(&mut self.temporaries);
+                self.scopes.push(Scope::default());
+                self.check_statements(&statements);
+                self.scopes.pop();
+                self.temporaries = temporaries;
+            }
+            NodeValue::ControlFlow(ControlFlow::For(variable, iterator, body)) => {
+                self.check_node(iterator);
+                self.scopes.push(Scope::default());
+                if let Some(name) = variable_name(variable) {
+                    // ループ変数は実行時と同じく可変として扱う
+                    self.declare(
+                        &name,
+                        Binding {
+                            is_mutable: true,
+                            is_parameter: false,
+                            site: site(variable),
+                            reference: None,
+                        },
+                    );
+                }
+                self.check_node(body);
+                self.scopes.pop();
+            }
+            NodeValue::Declaration(Declaration::Function(_, args, body, _, _))
+            | NodeValue::Declaration(Declaration::CallBackFunction(_, args, body, _, _)) => {
+                self.check_function(args, body);
+            }
+            _ => {
+                for child in node.children() {
+                    self.check_node(child);
+                }
+            }
+        }
+    }
+
+    // 関数本体は呼び出し元のローカルスコープを見ずに、引数のスコープから検査する
+    fn check_function(&mut self, args: &[(Box<Node>, String, bool)], body: &Node) {
+        let outer_scopes = self.scopes.split_off(1);
+        let outer_remaining = std::mem::take(&mut self.remaining);
+        let outer_temporaries = std::mem::take(&mut self.temporaries);
+
+        let mut scope = Scope::default();
+        for (data_type, name, is_mutable) in args {
+            scope.bindings.insert(
+                name.clone(),
+                Binding {
+                    is_mutable: *is_mutable,
+                    is_parameter: true,
+                    site: site(data_type),
+                    reference: None,
+                },
+            );
+        }
+        self.scopes.push(scope);
+        self.check_node(body);
+
+        self.scopes.truncate(1);
+        self.scopes.extend(outer_scopes);
+        self.remaining = outer_remaining;
+        self.temporaries = outer_temporaries;
+    }
+
+    fn check_assign(&mut self, node: &Node, target: &Node, value: &Node, index: &Node) {
+        self.check_node(index);
+        match &target.value {
+            // *参照 = 値 は参照が &mut であることを確認する
+            NodeValue::Dereference(reference) => {
+                self.check_node(value);
+                if let Some(name) = variable_name(reference) {
+                    self.check_write_through(&name, site(node));
+                }
+            }
+            NodeValue::Variable(_, name, _, _) => {
+                let has_index = !matches!(index.value, NodeValue::Null);
+                let holds_reference = self
+                    .lookup(name)
+                    .map_or(false, |binding| binding.reference.is_some());
+                if has_index && holds_reference {
+                    // 参照を持つ変数への添字付き代入は参照先への書き込み
+                    self.check_node(value);
+                    self.check_write_through(name, site(node));
+                    return;
+                }
+                let reference = self.check_held_borrow(value, name);
+                self.check_write(name, site(node));
+                if let Some(binding) = self.lookup_mut(name) {
+                    binding.reference = reference;
+                }
+            }
+            _ => self.check_node(value),
+        }
+    }
+
+    // 右辺が &x / &mut x なら変数holderが保持する借用として登録し、その可変性を返す
+    fn check_held_borrow(&mut self, value: &Node, holder: &str) -> Option<bool> {
+        match &value.value {
+            NodeValue::Variable(_, target, is_mutable, true) => {
+                let borrow = Borrow {
+                    target: target.clone(),
+                    is_mutable: *is_mutable,
+                    site: site(value),
+                    holder: Some(holder.to_string()),
+                };
+                self.check_borrow(&borrow);
+                // 以前に保持していた借用は代入で終わる
+                for scope in self.scopes.iter_mut() {
+                    scope
+                        .borrows
+                        .retain(|b| b.holder.as_deref() != Some(holder));
+                }
+                let depth = self.binding_depth(holder).unwrap_or(self.scopes.len() - 1);
+                self.scopes[depth].borrows.push(borrow);
+                Some(*is_mutable)
+            }
+            _ => {
+                self.check_node(value);
+                None
+            }
+        }
+    }
+
+    // 新しい借用が既存の有効な借用と衝突しないか確認
+    fn check_borrow(&mut self, borrow: &Borrow) {
+        if borrow.is_mutable {
+            if let Some(binding) = self.lookup(&borrow.target).cloned() {
+                if !binding.is_mutable {
+                    let diagnostic = Diagnostic::new(
+                        "error",
+                        &msg!("check.borrow_immutable_as_mutable", borrow.target),
+                    )
+                    .code("E0203");
+                    let diagnostic = primary(
+                        diagnostic,
+                        borrow.site,
+                        &msg!("check.borrow_immutable_as_mutable.label"),
+                    );
+                    let diagnostic = secondary(
+                        diagnostic,
+                        binding.site,
+                        borrow.site,
+                        &msg!("check.declared_without_mut", borrow.target),
+                    );
+                    self.report(diagnostic.help(&mutable_hint(&borrow.target, &binding)));
+                }
+            }
+        }
+        let conflict = self
+            .live_borrows(&borrow.target)
+            .into_iter()
+            .find(|existing| existing.is_mutable || borrow.is_mutable);
+        if let Some(existing) = conflict {
+            let message = match (existing.is_mutable, borrow.is_mutable) {
+                (true, true) => msg!("check.mutable_borrow_twice", borrow.target),
+                (true, false) => msg!("check.shared_while_mutable", borrow.target),
+                _ => msg!("check.mutable_while_shared", borrow.target),
+            };
+            let diagnostic = Diagnostic::new("error", &message).code("E0202");
+            let diagnostic = primary(diagnostic, borrow.site, &msg!("check.second_borrow.label"));
+            let mut diagnostic = secondary(
+                diagnostic,
+                existing.site,
+                borrow.site,
+                &msg!("check.first_borrow.label"),
+            );
+            if let Some(holder) = &existing.holder {
+                diagnostic = diagnostic.note(&msg!("check.first_borrow_used_by", holder));
+            }
+            self.report(diagnostic);
+        }
+    }
+
+    // 変数への書き込み(代入,++,--,複合代入)を確認
+    fn check_write(&mut self, name: &str, at: Site) {
+        let binding = match self.lookup(name) {
+            Some(binding) => binding.clone(),
+            None => return, // 未定義の変数は実行時に報告される
+        };
+        if !binding.is_mutable {
+            let message = if binding.is_parameter {
+                msg!("check.assign_immutable_parameter", name)
+            } else {
+                msg!("check.assign_twice", name)
+            };
+            let diagnostic = Diagnostic::new("error", &message).code("E0201");
+            let diagnostic = primary(diagnostic, at, &msg!("check.assign.label"));
+            let diagnostic = secondary(
+                diagnostic,
+                binding.site,
+                at,
+                &msg!("check.declared_without_mut", name),
+            );
+            self.report(diagnostic.help(&mutable_hint(name, &binding)));
+        }
+        if let Some(borrow) = self.live_borrows(name).into_iter().next() {
+            let diagnostic =
+                Diagnostic::new("error", &msg!("check.assign_borrowed", name)).code("E0204");
+            let diagnostic = primary(diagnostic, at, &msg!("check.assign_borrowed.label"));
+            let mut diagnostic = secondary(
+                diagnostic,
+                borrow.site,
+                at,
+                &msg!("check.borrowed_here", name),
+            );
+            if let Some(holder) = &borrow.holder {
+                diagnostic = diagnostic.note(&msg!("check.borrow_used_by", holder));
+            }
+            self.report(diagnostic);
+        }
+    }
+
+    // 参照を経由した書き込みには &mut が必要
+    fn check_write_through(&mut self, name: &str, at: Site) {
+        let binding = match self.lookup(name) {
+            Some(binding) => binding.clone(),
+            None => return,
+        };
+        if binding.reference == Some(false) {
+            let diagnostic =
+                Diagnostic::new("error", &msg!("check.assign_through_shared", name)).code("E0205");
+            let diagnostic = primary(diagnostic, at, &msg!("check.assign_through_shared.label"));
+            let diagnostic = secondary(
+                diagnostic,
+                binding.site,
+                at,
+                &msg!("check.holds_shared_reference", name),
+            );
+            self.report(diagnostic.help(&msg!("check.assign_through_shared.help")));
+        }
+    }
+
+    // 現在も有効な借用(一時的な借用と、保持している変数が以降でまだ使われる借用)
+    fn live_borrows(&self, target: &str) -> Vec<Borrow> {
+        self.scopes
+            .iter()
+            .flat_map(|scope| scope.borrows.iter())
+            .chain(self.temporaries.iter())
+            .filter(|borrow| borrow.target == target)
+            .filter(|borrow| match &borrow.holder {
+                Some(holder) => self.is_used_later(holder),
+                None => true,
+            })
+            .cloned()
+            .collect()
+    }
+
+    // 現在の文以降(外側のブロックの残りの文も含む)で変数が使われるかどうか
+    fn is_used_later(&self, name: &str) -> bool {
+        self.remaining.iter().any(|(statements, current)| {
+            statements[*current..]
+                .iter()
+                .any(|statement| mentions(statement, name))
+        })
+    }
+
+    fn declare(&mut self, name: &str, binding: Binding) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.bindings.insert(name.to_string(), binding);
+        }
+    }
+
+    fn binding_depth(&self, name: &str) -> Option<usize> {
+        self.scopes
+            .iter()
+            .rposition(|scope| scope.bindings.contains_key(name))
+    }
+
+    fn lookup(&self, name: &str) -> Option<&Binding> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.bindings.get(name))
+    }
+
+    fn lookup_mut(&mut self, name: &str) -> Option<&mut Binding> {
+        self.scopes
+            .iter_mut()
+            .rev()
+            .find_map(|scope| scope.bindings.get_mut(name))
+    }
+
+    // 違反を1件記録する
+    fn report(&mut self, diagnostic: Diagnostic) {
+        self.errors.push(diagnostic);
+        self.error_count += 1;
+    }
+}
+
+pub(super) fn variable_name(node: &Node) -> Option<String> {
+    match &node.value {
+        NodeValue::Variable(_, name, _, _) => Some(name.clone()),
+        _ => None,
+    }
+}
+
+// 位置情報が欠けたノードでもエラー表示できるよう1始まりに補正
+pub(super) fn site(node: &Node) -> Site {
+    Site {
+        line: node.line.max(1),
+        column: node.column.max(1),
+        span: node.span,
+    }
+}
+
+pub(super) fn primary(diagnostic: Diagnostic, site: Site, label: &str) -> Diagnostic {
+    diagnostic.primary_span(site.span, site.line, site.column, label)
+}
+
+// 原因の位置が違反の位置と異なる場合だけ補足として指摘する
+pub(super) fn secondary(diagnostic: Diagnostic, site: Site, at: Site, label: &str) -> Diagnostic {
+    if site == at {
+        diagnostic
+    } else {
+        diagnostic.secondary_span(site.span, site.line, site.column, label)
+    }
+}
+
+fn mutable_hint(name: &str, binding: &Binding) -> String {
+    if binding.is_parameter {
+        msg!("check.mutable_parameter_hint", name)
+    } else {
+        msg!("check.mutable_binding_hint", name)
+    }
+}
+
+// ノード以下で変数が参照されているかどうか
+fn mentions(node: &Node, name: &str) -> bool {
+    if let NodeValue::Variable(_, ref v, _, _) = node.value {
+        if v == name {
+            return true;
+        }
+    }
+    node.children()
+        .into_iter()
+        .any(|child| mentions(child, name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::tokenizer::Lexer;
+    use crate::parser::syntax::Parser;
+
+    // 検査で報告された診断コード
+    fn codes(source: &str) -> Vec<String> {
+        let tokens = Lexer::from_tokenize("checker_test.sc", source.to_string()).unwrap();
+        let node = Parser::from_parse(&tokens, "checker_test.sc", source.to_string()).unwrap();
+        match Checker::check(&node, "checker_test.sc", source) {
+            Ok(()) => Vec::new(),
+            Err(e) => e
+                .diagnostics()
+                .iter()
+                .filter_map(|diagnostic| diagnostic.code.clone())
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn reports_assignment_to_immutable_binding() {
+        assert_eq!(codes("let a = 1;\na = 2;\n"), ["E0201"]);
+        assert_eq!(codes("fn f(n) {\n    n = 2;\n}\n"), ["E0201"]);
+        assert!(codes("let mut a = 1;\na = 2;\n").is_empty());
+    }
+
+    #[test]
+    fn reports_conflicting_borrows() {
+        assert_eq!(
+            codes("let mut a = 1;\nlet r = &mut a;\nlet s = &a;\n@println(r);\n"),
+            ["E0202"]
+        );
+        assert_eq!(
+            codes("let mut a = 1;\nlet r = &mut a;\nlet s = &mut a;\n@println(r);\n"),
+            ["E0202"]
+        );
+    }
+
+    #[test]
+    fn reports_mutable_borrow_of_immutable_binding() {
+        assert_eq!(codes("let a = 1;\nlet r = &mut a;\n"), ["E0203"]);
+    }
+
+    #[test]
+    fn reports_assignment_to_borrowed_variable() {
+        assert_eq!(
+            codes("let mut a = 1;\nlet r = &a;\na = 2;\n@println(r);\n"),
+            ["E0204"]
+        );
+    }
+
+    #[test]
+    fn reports_write_through_shared_reference() {
+        assert_eq!(codes("let mut a = 1;\nlet r = &a;\n*r = 2;\n"), ["E0205"]);
+    }
+
+    #[test]
+    fn accepts_mutable_borrow() {
+        assert!(codes("let mut a = 1;\nlet r = &mut a;\n*r = 2;\n@println(a);\n").is_empty());
+    }
+
+    #[test]
+    fn accepts_shared_borrows() {
+        assert!(codes("let a = 1;\nlet r = &a;\nlet s = &a;\n@println(r, s, a);\n").is_empty());
+    }
+
+    // 同梱のサンプルは検査を通る
+    #[test]
+    fn accepts_shipped_examples() {
+        let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("script/example");
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().is_some_and(|ext| ext == "sc") {
+                let source = std::fs::read_to_string(&path).unwrap();
+                assert!(codes(&source).is_empty(), "{}", path.display());
+            }
+        }
+    }
+}
@@ -1,11 +1,17 @@
 use crate::compile_error;
 use crate::compile_group_error;
 use crate::context::*;
-use crate::error::CompilerError;
+use crate::decoder::checker::Checker;
+use crate::encoding;
+use crate::error::{CompilerError, Frame, ScriptError};
+use crate::error_log;
 use crate::lexer::tokenizer::{Lexer, Token};
 use crate::memory_mgr::*;
+use crate::msg;
 use crate::parser::syntax::Node;
 use crate::parser::syntax::Parser;
+use crate::parser::syntax::{collect_docs, DocItem};
+use crate::source_map::with_source_map;
 use crate::traits::Size;
 use crate::types::NodeValue;
 use crate::types::*;
@@ -19,6 +25,7 @@ use rodio::{source::Source, OutputStream};
 use serde::{Deserialize, Serialize};
 use serde_json::to_string_pretty;
 use serde_json::{Number, Value};
+use std::any::Any;
 use std::collections::HashMap;
 use std::collections::HashSet;
 use std::fs::File;
@@ -44,6 +51,76 @@ use win_msgbox::{
     YesNo, YesNoCancel,
 };
 
+// 組み込み関数の表(名前,引数)
+// @名前(...) で呼び出す。説明はメッセージカタログの builtin.doc.名前 にある
+pub static BUILTINS: &[(&str, &str)] = &[
+    ("list_files", "dir"),
+    ("play_music", "file"),
+    ("str", "value"),
+    ("show_msg_box", "kind, title, message, icon"),
+    ("write_at_file", "file, text, position"),
+    ("open_recent", ""),
+    ("sleep", "seconds"),
+    ("read_file", "file, encoding?"),
+    ("write_file", "file, content, encoding?"),
+    ("print", "format, values"),
+    ("println", "values..."),
+    ("exit", "status"),
+    ("args", ""),
+    ("cmd", "command, args?"),
+    ("gc", ""),
+    ("heap_stats", ""),
+    ("heap_used", ""),
+    ("heap_blocks", ""),
+    ("sizeof", "value"),
+    ("ord", "char"),
+    ("chr", "code"),
+];
+
+// 値を表示用の文字列に変換する(@println,@print,@str と補間文字列で共通)
+pub fn display_value(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Array(array) => {
+            let elements: Vec<String> = array
+                .iter()
+                .map(|element| match element {
+                    // 配列の中の文字列は区別できるよう引用符付きで表示
+                    Value::String(_) => element.to_string(),
+                    _ => display_value(element),
+                })
+                .collect();
+            format!("[{}]", elements.join(", "))
+        }
+        _ => value.to_string(),
+    }
+}
+
+// HTMLに埋め込む文字列をエスケープする
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+// 値がUnicodeスカラー値1つだけの文字列であればその文字を返す
+fn as_char(value: &Value) -> Option<char> {
+    let mut chars = value.as_str()?.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(c),
+        _ => None,
+    }
+}
+
+// ノードが書かれたファイルの名前(ソースマップ上の範囲を持たなければNone)
+fn node_file(node: &Node) -> Option<String> {
+    let span = node.span();
+    if span.is_dummy() {
+        return None;
+    }
+    with_source_map(|map| map.get(span.file_id)).map(|file| file.name.clone())
+}
+
 fn get_duration(file_path: &str) -> Option<Duration> {
     // ファイルを開く
     let file = File::open(file_path).ok()?;
@@ -163,11 +240,11 @@ impl TypeChecker {
     }
 
     // 型変換
-    pub fn convert_to_value(&mut self, value: &SystemValue) -> R<SystemValue, String> {
+    pub fn convert_to_value(&mut self, value: &SystemValue) -> R<SystemValue, ScriptError> {
         Ok(SystemValue::I32(0))
     }
     // 型チェック
-    pub fn check_type(&mut self,value: &SystemValue) -> R<(), String> {
+    pub fn check_type(&mut self, value: &SystemValue) -> R<(), ScriptError> {
         Ok(())
     }
 }
@@ -187,6 +264,8 @@ pub struct Decoder {
     file_contents: IndexMap<String, String>, // ファイルの内容(ファイル名,ファイルの内容)
     #[property(get)]
     current_node: Option<(String, Box<Node>)>, // 現在のノード(ファイル名,現在のNode)
+    #[property(get)]
+    call_trace: Vec<Frame>, // 評価中の呼び出し(呼び出された関数名と呼び出し位置)
 
     #[property(get)]
     generated_ast_file: bool, // ASTの生成をするかどうか
@@ -203,7 +282,11 @@ pub struct Decoder {
     #[property(get)]
     generated_doc: bool,
     #[property(get)]
+    reported_heap_usage: bool, // 終了時にヒープの使用状況を報告するかどうか
+    #[property(get)]
     entry_func: (bool, String), // main関数の有無(フラグ,見つかった関数名(main|Main))
+    #[property(get)]
+    source_encoding: Option<String>, // BOMや@encodingのないスクリプトの文字コード(未指定ならUTF-8)
 }
 impl Decoder {
     pub fn generate_doc(self, flag: bool) -> Self {
@@ -231,6 +314,60 @@ impl Decoder {
             ..self
         }
     }
+    pub fn heap_limit(self, size: usize) -> Self {
+        let mut memory_mgr = self.memory_mgr.clone();
+        memory_mgr.heap_size = size;
+        Decoder { memory_mgr, ..self }
+    }
+    pub fn report_heap_usage(self, flag: bool) -> Self {
+        Decoder {
+            reported_heap_usage: flag,
+            ..self
+        }
+    }
+
+    // 実行終了時のヒープ使用状況とリーク(到達不能なまま残ったブロック)を報告
+    fn print_heap_report(&mut self) {
+        let before = self.memory_mgr.stats.clone();
+        let leaked = self.collect_garbage();
+        let after = &self.memory_mgr.stats;
+        eprintln!("{}", msg!("heap.report.title"));
+        eprintln!("{}", msg!("heap.report.limit", self.memory_mgr.heap_size));
+        eprintln!("{}", msg!("heap.report.peak", after.peak_bytes));
+        eprintln!(
+            "{}",
+            msg!("heap.report.in_use", before.used_bytes, before.live_blocks)
+        );
+        eprintln!(
+            "{}",
+            msg!(
+                "heap.report.unreachable",
+                leaked,
+                before.used_bytes - after.used_bytes
+            )
+        );
+        eprintln!(
+            "{}",
+            msg!("heap.report.reachable", after.used_bytes, after.live_blocks)
+        );
+        eprintln!(
+            "{}",
+            msg!(
+                "heap.report.total",
+                after.total_allocations,
+                after.total_frees,
+                after.collections
+            )
+        );
+    }
+
+    // 読み込んだ全ファイルのドキュメントコメント付きの宣言
+    pub fn doc_items(&self) -> Vec<DocItem> {
+        self.ast_map
+            .values()
+            .flat_map(|node| collect_docs(node))
+            .collect()
+    }
 
     fn generate_html_from_comments(&mut self) -> String {
         let mut html = String::from(
@@ -263,6 +400,22 @@ impl Decoder {
         </div>\n",
         );
 
+        // 宣言に付いたドキュメントコメント(ASTから集めるので実行の有無に依らない)
+        html.push_str("<div id=\"declarations\">\n");
+        for item in self.doc_items() {
+            html.push_str(&format!(
+                "<div class=\"comment-container\">\n\
+                <b>{} {}</b> (Line {} Column {})\n\
+                <div class=\"comment\"><pre>{}</pre></div>\n</div>\n",
+                item.kind,
+                escape_html(&item.name),
+                item.line,
+                item.column,
+                escape_html(&item.doc)
+            ));
+        }
+        html.push_str("</div>\n");
+
         html.push_str("<div id=\"comments\">\n");
 
         // コメントを行ごとに処理
@@ -338,10 +491,11 @@ impl Decoder {
     }
 
     // 現在のASTのマップの先頭に指定スクリプトのASTを追加
-    pub fn add_first_ast_from_file(&mut self, file_name: &str) -> R<&mut Self, String> {
-        let content = std::fs::read_to_string(file_name).map_err(|e| e.to_string())?;
+    pub fn add_first_ast_from_file(&mut self, file_name: &str) -> R<&mut Self, ScriptError> {
+        let content = encoding::read_source(file_name, self.source_encoding.as_deref())?;
         let tokens = Lexer::from_tokenize(file_name, content.clone())?;
         let nodes = Parser::from_parse(&tokens, file_name, content.clone())?;
+        self.file_contents.insert(file_name.to_string(), content);
         // 最初に要素を挿入するために新しい IndexMap を作る
         let mut new_ast_map = IndexMap::new();
         new_ast_map.insert(file_name.to_string(), nodes.clone());
@@ -356,16 +510,21 @@ impl Decoder {
     }
 
     // 現在のASTのマップに指定スクリプトのASTを追加
-    pub fn add_ast_from_file(&mut self, file_name: &str) -> R<&mut Self, String> {
-        let content = std::fs::read_to_string(file_name).map_err(|e| e.to_string())?;
+    pub fn add_ast_from_file(&mut self, file_name: &str) -> R<&mut Self, ScriptError> {
+        let content = encoding::read_source(file_name, self.source_encoding.as_deref())?;
         let tokens = Lexer::from_tokenize(file_name, content.clone())?;
         let nodes = Parser::from_parse(&tokens, file_name, content.clone())?;
+        self.file_contents.insert(file_name.to_string(), content);
         self.ast_map.insert(file_name.to_string(), nodes.clone());
         Ok(self)
     }
 
     // 現在のASTのマップに文字列でスクリプトのASTを追加
-    pub fn add_ast_from_text(&mut self, file_name: &str, content: &str) -> R<&mut Self, String> {
+    pub fn add_ast_from_text(
+        &mut self,
+        file_name: &str,
+        content: &str,
+    ) -> R<&mut Self, ScriptError> {
         // トークン化処理
         let tokens = Lexer::from_tokenize(file_name, content.to_string())?;
 
@@ -373,18 +532,81 @@ impl Decoder {
         let nodes = Parser::from_parse(&tokens, file_name, content.to_string())?;
 
         // ASTをマップに追加
+        self.file_contents
+            .insert(file_name.to_string(), content.to_string());
         self.ast_map.insert(file_name.to_string(), nodes.clone());
         //panic!("ast_map: {:?}",self.ast_map.clone());
 
         // 成功時にselfを返す
         Ok(self)
     }
+    // 文字列のスクリプトを追加し、それまでの状態を引き継いで追加した部分だけを評価する
+    // (最後の文の値を返す。宣言の文は値を持たない)
+    pub fn eval_text(&mut self, file_name: &str, content: &str) -> R<Value, ScriptError> {
+        self.add_ast_from_text(file_name, content)?;
+        let node = self.ast_map[file_name].clone();
+        Checker::check(&node, file_name, content)?;
+        let original_node = self.current_node.clone();
+        let mut value = Value::Null;
+        for current_node in node.iter() {
+            self.current_node = Some((file_name.to_string(), Box::new(current_node.clone())));
+            if let NodeValue::EndStatement | NodeValue::Null = current_node.value {
+                continue;
+            }
+            value = match self.execute_node(current_node) {
+                Ok(v) => v,
+                Err(e) => {
+                    let e = e.with_backtrace(self.script_backtrace());
+                    self.current_node = original_node;
+                    return Err(e);
+                }
+            };
+            if let NodeValue::Declaration(ref declaration) = current_node.value {
+                if !matches!(declaration, Declaration::Array(..)) {
+                    value = Value::Null;
+                }
+            }
+            if self.memory_mgr.should_collect() {
+                self.collect_garbage();
+            }
+        }
+        self.current_node = original_node;
+        Ok(value)
+    }
+    // 評価の済んだテキストの構文木とソースを捨てる
+    // (関数・構造体を定義したテキストは、後で本体を評価するときにエラーの位置を表示するので残す)
+    pub fn discard_text(&mut self, file_name: &str) {
+        let defines_items = self.ast_map.get(file_name).is_some_and(|node| {
+            node.iter().any(|node| {
+                matches!(
+                    node.value,
+                    NodeValue::Declaration(
+                        Declaration::Function(..)
+                            | Declaration::CallBackFunction(..)
+                            | Declaration::Struct(..)
+                            | Declaration::Impl(..)
+                    )
+                )
+            })
+        });
+        if defines_items {
+            return;
+        }
+        self.ast_map.shift_remove(file_name);
+        self.file_contents.remove(file_name);
+        with_source_map(|map| map.remove_file(file_name));
+    }
     // スクリプトを読み込む
-    pub fn load_script(file_name: &str) -> R<Self, String> {
+    pub fn load_script(file_name: &str) -> R<Self, ScriptError> {
+        Self::load_script_with_encoding(file_name, None)
+    }
+    // 文字コードを指定してスクリプトを読み込む(BOMや@encodingがあればそちらを優先)
+    pub fn load_script_with_encoding(
+        file_name: &str,
+        source_encoding: Option<&str>,
+    ) -> R<Self, ScriptError> {
         let mut ast_map: IndexMap<String, Box<Node>> = IndexMap::new();
-        let file_content = std::fs::read_to_string(file_name)
-            .map_err(|e| e.to_string())
-            .expect("Failed to script file");
+        let file_content = encoding::read_source(file_name, source_encoding)?;
 
         let tokens = Lexer::from_tokenize(file_name, file_content.clone())?;
 
@@ -396,33 +618,39 @@ impl Decoder {
         ast_map.insert(file_name.to_string(), nodes.clone());
         Ok(Decoder {
             generated_doc: false,
+            reported_heap_usage: false,
             ast_mod: IndexMap::new(),
             ast_map,
             memory_mgr: MemoryManager::new(1024 * 1024),
             file_contents: IndexMap::new(),
             current_node: None,
+            call_trace: Vec::new(),
             context: Context::new(),
             generated_ast_file: false,
             generated_error_log_file: false,
             measure_decode_time: false,
             decode_time: 0.0,
             entry_func: (false, String::new()),
+            source_encoding: source_encoding.map(str::to_string),
         })
     }
     pub fn new() -> Self {
         Self {
             generated_doc: false,
+            reported_heap_usage: false,
             ast_mod: IndexMap::new(),
             ast_map: IndexMap::new(),
             memory_mgr: MemoryManager::new(1024 * 1024),
             file_contents: IndexMap::new(),
             current_node: None,
+            call_trace: Vec::new(),
             context: Context::new(),
             generated_ast_file: false,
             generated_error_log_file: false,
             measure_decode_time: false,
             decode_time: 0.0,
             entry_func: (false, String::new()),
+            source_encoding: None,
         }
     }
     fn get_value_size(&self, v_type: &str, v_value: &Value) -> usize {
@@ -432,6 +660,7 @@ impl Decoder {
             "i64" => std::mem::size_of::<i64>(),
             "f32" => std::mem::size_of::<f32>(),
             "f64" => std::mem::size_of::<f64>(),
+            "char" => std::mem::size_of::<char>(),
             "string" => {
                 if let Value::String(ref s) = v_value {
                     s.len()
@@ -442,36 +671,50 @@ impl Decoder {
             _ => serde_json::to_vec(v_value).unwrap().len(),
         }
     }
-    fn infer_type(&self, value: &Value) -> String {
-        match value {
-            Value::Array(_) => "array".to_string(),
-            Value::Null => "void".to_string(),
-            Value::Number(num) => {
-                if num.is_i64() {
-                    let i_value = num.as_i64().unwrap();
-                    if i_value >= i32::MIN as i64 && i_value <= i32::MAX as i64 {
-                        "i32".to_string()
-                    } else {
-                        "i64".to_string()
-                    }
-                } else if num.is_f64() {
-                    let f_value = num.as_f64().unwrap();
-                    if f_value >= f32::MIN as f64 && f_value <= f32::MAX as f64 {
-                        "f32".to_string()
-                    } else {
-                        "f64".to_string()
-                    }
-                } else {
-                    "unknown".to_string()
-                }
-            }
-            Value::String(_) => "string".to_string(),
-            Value::Bool(_) => "bool".to_string(),
-            _ => "unknown".to_string(),
+    pub fn infer_type(&self, value: &Value) -> String {
+        infer_type(value)
+    }
+
+    // char型・[char]型の宣言では値がUnicodeスカラー値1つの文字であることを検査する
+    fn check_char_type(&self, name: &str, v_type: &str, value: &Value) -> R<(), ScriptError> {
+        let is_valid = match (v_type, value) {
+            (_, Value::Null) => true,
+            ("char", v) => as_char(v).is_some(),
+            ("[char]", Value::Array(elements)) => elements.iter().all(|e| as_char(e).is_some()),
+            ("[char]", _) => false,
+            _ => true,
+        };
+        if is_valid {
+            return Ok(());
         }
+        Err(self.runtime_error(&msg!(
+            "runtime.mismatched_types",
+            name,
+            v_type,
+            display_value(value)
+        )))
     }
 
-    pub fn decode(&mut self) -> Result<Value, String> {
+    pub fn decode(&mut self) -> Result<Value, ScriptError> {
+        // 最初に読み込んだスクリプトをエラーログに記録する
+        let script = self.ast_map.keys().next().cloned().unwrap_or_default();
+        let result = self.decode_all();
+        if let Err(e) = &result {
+            self.log_error(&script, e);
+        }
+        result
+    }
+    // エラーログを生成する設定であればエラーを記録する
+    pub fn log_error(&self, script: &str, error: &ScriptError) {
+        if !self.generated_error_log_file {
+            return;
+        }
+        if let Err(e) = error_log::append(script, error) {
+            eprintln!("{}", msg!("log.write_failed", e));
+        }
+    }
+
+    fn decode_all(&mut self) -> Result<Value, ScriptError> {
         // 実行にかかった時間を計測
         let start_time = if self.measure_decode_time {
             Some(Instant::now())
@@ -492,7 +735,9 @@ impl Decoder {
             evaluated_files.insert(file_name.clone());
 
             self.current_node = Some((file_name.clone(), Box::new(Node::default())));
-            let content = std::fs::read_to_string(file_name.clone()).map_err(|e| e.to_string())?;
+            let content = encoding::read_source(file_name, self.source_encoding.as_deref())?;
+            // 実行前に可変性と借用規則を検査
+            Checker::check(node, file_name, &content)?;
             self.file_contents.insert(file_name.clone(), content);
 
             // Nodeのイテレータを使用してノードを処理
@@ -501,7 +746,12 @@ impl Decoder {
                 if let NodeValue::EndStatement | NodeValue::Null = current_node.value {
                     continue;
                 }
-                value = self.execute_node(current_node)?;
+                value = self
+                    .execute_node(current_node)
+                    .map_err(|e| e.with_backtrace(self.script_backtrace()))?;
+                if self.memory_mgr.should_collect() {
+                    self.collect_garbage();
+                }
             }
             // 最後のノードも評価する（イテレータ内で自動処理されるため不要）
         }
@@ -509,31 +759,42 @@ impl Decoder {
         // メインエントリーが定義されていたら実行
         if self.entry_func.0 {
             self.add_ast_from_text("main-entry", &format!("{}();", self.entry_func.1))?;
-            if let Some((_, value_node)) = self.ast_map.clone().iter().last() {
+            if let Some((file_name, value_node)) = self.ast_map.clone().iter().last() {
                 for current_node in value_node.iter() {
+                    self.current_node = Some((file_name.clone(), Box::new(current_node.clone())));
                     if let NodeValue::EndStatement | NodeValue::Null = current_node.value {
                         continue;
                     }
-                    value = self.execute_node(current_node)?;
+                    value = self
+                        .execute_node(current_node)
+                        .map_err(|e| e.with_backtrace(self.script_backtrace()))?;
                 }
             }
         }
 
         self.current_node = original_node;
 
+        if self.reported_heap_usage {
+            self.print_heap_report();
+        }
         if self.generated_ast_file {
             // ディレクトリが存在しない場合は作成
-            std::fs::create_dir_all("./script-analysis").map_err(|e| e.to_string())?;
+            std::fs::create_dir_all("./script-analysis")
+                .map_err(|e| ScriptError::io("./script-analysis", e))?;
             // IndexMapをHashMapに変換
             let ast_map: std::collections::HashMap<_, _> =
                 self.ast_map.clone().into_iter().collect();
-            let ast_json = serde_json::to_string_pretty(&ast_map).map_err(|e| e.to_string())?;
-            std::fs::write("./script-analysis/ast.json", ast_json).map_err(|e| e.to_string())?;
+            let ast_json = serde_json::to_string_pretty(&ast_map)
+                .map_err(|e| ScriptError::io("./script-analysis/ast.json", e))?;
+            std::fs::write("./script-analysis/ast.json", ast_json)
+                .map_err(|e| ScriptError::io("./script-analysis/ast.json", e))?;
         }
         if self.generated_doc {
             let html_doc = self.generate_html_from_comments();
-            std::fs::create_dir_all("./script-doc").map_err(|e| e.to_string())?;
-            std::fs::write("./script-doc/doc.html", html_doc).map_err(|e| e.to_string())?;
+            std::fs::create_dir_all("./script-doc")
+                .map_err(|e| ScriptError::io("./script-doc", e))?;
+            std::fs::write("./script-doc/doc.html", html_doc)
+                .map_err(|e| ScriptError::io("./script-doc/doc.html", e))?;
         }
         if let Some(start) = start_time {
             let duration = start.elapsed();
@@ -545,14 +806,41 @@ impl Decoder {
         Ok(value)
     }
 
-    fn eval_block(&mut self, block: &Vec<Box<Node>>) -> Result<Value, String> {
-        let mut result = Value::Null;
-        let initial_local_context = self.context.local_context.clone(); // 現在のローカルコンテキストを保存
+    fn eval_block(&mut self, block: &Vec<Box<Node>>) -> Result<Value, ScriptError> {
+        // 現在のローカルコンテキストを退避(GCのルートとしても参照される)
+        self.context
+            .scope_stack
+            .push(self.context.local_context.clone());
+        let result = self.eval_block_statements(block);
+        // ブロックの処理が終わったらローカルコンテキストを元に戻す
+        let block_context = std::mem::replace(
+            &mut self.context.local_context,
+            self.context.scope_stack.pop().unwrap_or_default(),
+        );
+        // ブロック内で宣言された変数はスコープを抜けた時点で解放する
+        for (name, variable) in block_context {
+            let is_outer = self
+                .context
+                .local_context
+                .get(&name)
+                .map_or(false, |outer| outer.address == variable.address);
+            if !is_outer {
+                self.memory_mgr.deallocate(variable.address);
+            }
+        }
+        result
+    }
 
-        for _b in block {
+    fn eval_block_statements(&mut self, block: &Vec<Box<Node>>) -> Result<Value, ScriptError> {
+        let mut result = Value::Null;
+        'block: for _b in block {
             for b in _b.iter() {
                 // ノードを評価
                 result = self.execute_node(b)?;
+                // 関数内でreturn文が実行された場合、入れ子のブロックも含めて評価を終了
+                if self.memory_mgr.is_returning() {
+                    break 'block;
+                }
                 // return 文が評価された場合、ブロックの評価を終了
                 if let NodeValue::ControlFlow(ControlFlow::Return(_)) = b.value() {
                     break;
@@ -561,29 +849,218 @@ impl Decoder {
                 if let NodeValue::EndStatement = b.value() {
                     continue; // EndStatementは単なる区切りなので、次のノードの評価を続行
                 }
+                // 文の区切りで確保回数が閾値を超えていればGC
+                if self.memory_mgr.should_collect() {
+                    self.collect_garbage();
+                }
             }
         }
-
-        // ブロックの処理が終わったらローカルコンテキストを元に戻す
-        self.context.local_context = initial_local_context;
         Ok(result)
     }
-    fn eval_include(&mut self, file_name: &String) -> Result<Value, String> {
+
+    // コンテキストの全スコープをルートにしてGCを実行し、回収したブロック数を返す
+    pub fn collect_garbage(&mut self) -> usize {
+        let roots = self.context.root_addresses();
+        let collected = self.memory_mgr.collect_garbage(&roots);
+        info!(
+            "GC: collected = {}, live_blocks = {}",
+            collected, self.memory_mgr.stats.live_blocks
+        );
+        collected
+    }
+
+    // &変数 / &mut 変数 を評価して参照値を作る
+    fn eval_reference(&mut self, name: &String, is_mutable: bool) -> R<Value, ScriptError> {
+        let variable = self
+            .context
+            .local_context
+            .get(name)
+            .or_else(|| self.context.global_context.get(name))
+            .cloned();
+        let variable = match variable {
+            Some(variable) => variable,
+            None => {
+                return Err(self.runtime_error(&msg!("runtime.variable_not_defined", name)));
+            }
+        };
+        if is_mutable && !variable.is_mutable {
+            return Err(self.runtime_error(&msg!("runtime.borrow_immutable_as_mutable", name)));
+        }
+        let line = self.current_node.clone().unwrap().1.line();
+        let column = self.current_node.clone().unwrap().1.column();
+        self.context
+            .used_context
+            .insert(name.clone(), (line, column, true));
+        Ok(new_reference(variable.address, is_mutable, name))
+    }
+
+    // *参照 を評価して参照先の値を返す
+    fn eval_dereference(&mut self, reference: &Value) -> R<Value, ScriptError> {
+        match as_reference(reference) {
+            Some((address, _, target)) => self
+                .memory_mgr
+                .get_value::<Value>(address)
+                .cloned()
+                .ok_or_else(|| self.dangling_reference_error(&target)),
+            None => Err(self.runtime_error(&msg!("runtime.deref_non_reference", reference))),
+        }
+    }
+
+    // 補間文字列の各部分を評価して表示用の文字列をつなげる
+    fn eval_interpolation(&mut self, parts: &Vec<Box<Node>>) -> R<Value, ScriptError> {
+        let mut string = String::new();
+        for part in parts {
+            let value = self.eval_operand(part)?;
+            string.push_str(&display_value(&value));
+        }
+        Ok(Value::String(string))
+    }
+
+    // 演算子などの被演算子を評価する(参照は参照先の値まで自動で外す)
+    fn eval_operand(&mut self, node: &Node) -> R<Value, ScriptError> {
+        let mut value = self.execute_node(node)?;
+        while as_reference(&value).is_some() {
+            value = self.eval_dereference(&value)?;
+        }
+        Ok(value)
+    }
+
+    // 参照先に値を書き込み、同じアドレスを持つ変数にも反映する
+    fn write_through_reference(
+        &mut self,
+        reference: &Value,
+        new_value: Value,
+    ) -> R<(), ScriptError> {
+        let (address, is_mutable, target) = match as_reference(reference) {
+            Some(reference) => reference,
+            None => {
+                return Err(
+                    self.runtime_error(&msg!("runtime.assign_through_non_reference", reference))
+                );
+            }
+        };
+        if !is_mutable {
+            return Err(self.runtime_error(&msg!("runtime.assign_through_shared", target)));
+        }
+        if !self.memory_mgr.is_allocated(address) {
+            return Err(self.dangling_reference_error(&target));
+        }
+        self.memory_mgr
+            .update_value(address, new_value.clone())
+            .map_err(|e| self.runtime_error(&e))?;
+        self.context.sync_variable_value(address, &new_value);
+        Ok(())
+    }
+
+    fn dangling_reference_error(&self, target: &str) -> ScriptError {
+        self.runtime_error(&msg!("runtime.dangling_reference", target))
+    }
+
+    // ヒープに値を確保する(上限を超える場合は先にGCで空きを作る)
+    fn allocate<T: 'static + Any + Size>(&mut self, value: T) -> R<Uuid, ScriptError> {
+        if self.memory_mgr.stats.used_bytes + value.size() > self.memory_mgr.heap_size {
+            self.collect_garbage();
+        }
+        self.memory_mgr
+            .allocate(value)
+            .map_err(|e| self.runtime_error(&e))
+    }
+
+    // 現在のノードの位置でランタイムエラーを生成
+    fn runtime_error(&self, message: &str) -> ScriptError {
+        let (file_name, node) = self.current_node.clone().unwrap();
+        compile_error!(
+            "error",
+            node.line(),
+            node.column(),
+            &file_name,
+            &self
+                .file_contents
+                .get(&file_name)
+                .cloned()
+                .unwrap_or_default(),
+            "{}",
+            message
+        )
+        .with_backtrace(self.script_backtrace())
+    }
+    // 現在位置からのスクリプトの呼び出し履歴(先頭が最も内側のフレーム)
+    fn script_backtrace(&self) -> Vec<Frame> {
+        let Some((file, node)) = &self.current_node else {
+            return Vec::new();
+        };
+        let mut frames = Vec::new();
+        let mut location = (file.clone(), node.line(), node.column());
+        for call in self.call_trace.iter().rev() {
+            frames.push(Frame {
+                function: call.function.clone(),
+                file: location.0,
+                line: location.1,
+                column: location.2,
+            });
+            location = (call.file.clone(), call.line, call.column);
+        }
+        frames.push(Frame {
+            function: "<top-level>".to_string(),
+            file: location.0,
+            line: location.1,
+            column: location.2,
+        });
+        frames
+    }
+    // 現在位置を呼び出し位置として記録する
+    fn enter_frame(&mut self, function: &str) {
+        if let Some((file, node)) = &self.current_node {
+            self.call_trace.push(Frame {
+                function: function.to_string(),
+                file: file.clone(),
+                line: node.line(),
+                column: node.column(),
+            });
+        }
+    }
+    // 組み込み関数の省略可能な文字コード引数を評価する
+    fn eval_encoding_arg(
+        &mut self,
+        arg: Option<&Node>,
+        name: &str,
+    ) -> R<Option<String>, ScriptError> {
+        match arg {
+            None => Ok(None),
+            Some(arg) => match self.execute_node(arg)? {
+                Value::String(label) => {
+                    encoding::lookup(&label).map_err(|e| self.runtime_error(&e))?;
+                    Ok(Some(label))
+                }
+                _ => Err(self.runtime_error(&msg!("builtin.expects_encoding", name))),
+            },
+        }
+    }
+    fn eval_include(&mut self, file_name: &String) -> Result<Value, ScriptError> {
         self.add_first_ast_from_file(file_name)?;
         let ast_map = self.ast_map.clone();
         let _node = ast_map.get(file_name).unwrap();
-        let mut result = Value::Null;
-        let mut current_node = _node.clone();
-        for node in current_node.iter() {
-            result = self.execute_node(&node)?;
+        // 読み込んだファイルのトップレベルを1つのフレームとして評価する
+        let caller_node = self.current_node.clone();
+        self.enter_frame("<top-level>");
+        self.current_node = Some((file_name.clone(), Box::new(Node::default())));
+        let mut result = Ok(Value::Null);
+        for node in _node.iter() {
+            result = self.execute_node(node);
+            if result.is_err() {
+                break;
+            }
         }
-        Ok(result)
+        let result = result.map_err(|e| e.with_backtrace(self.script_backtrace()));
+        self.call_trace.pop();
+        self.current_node = caller_node;
+        result
     }
     fn eval_single_comment(
         &mut self,
         content: &String,
         lines: &(usize, usize),
-    ) -> R<Value, String> {
+    ) -> R<Value, ScriptError> {
         self.context
             .comment_lists
             .insert((lines.0, lines.1), vec![content.clone()]);
@@ -594,7 +1071,7 @@ impl Decoder {
         &mut self,
         content: &Vec<String>,
         lines: &(usize, usize),
-    ) -> R<Value, String> {
+    ) -> R<Value, ScriptError> {
         self.context
             .comment_lists
             .insert((lines.0, lines.1), content.clone().to_vec());
@@ -606,7 +1083,7 @@ impl Decoder {
         &mut self,
         data_type: &Box<Node>,
         values: &Vec<Box<Node>>,
-    ) -> Result<Value, String> {
+    ) -> Result<Value, ScriptError> {
         // 型を評価
         let v_type = match data_type.value {
             NodeValue::DataType(ref d) => self.execute_node(&Node {
@@ -629,10 +1106,8 @@ impl Decoder {
             }
         }
 
-        // 配列全体をヒープにコピー
-        self.memory_mgr.allocate(array.clone());
-        // 結果を返す
-        Ok(Value::Array(array.clone()))
+        // 結果を返す(ヒープへの確保は値を格納する変数の側で行う)
+        Ok(Value::Array(array))
     }
     fn eval_assign(
         &mut self,
@@ -640,12 +1115,13 @@ impl Decoder {
         var_name: &Box<Node>,
         value: &Box<Node>,
         index: &Box<Node>,
-    ) -> Result<Value, String> {
+    ) -> Result<Value, ScriptError> {
         let mut result = Value::Null;
 
         // ステートメントフラグのチェック
         if !node.is_statement {
             return Err(compile_error!(
+                code = "E0307",
                 "error",
                 self.current_node.clone().unwrap().1.line,
                 self.current_node.clone().unwrap().1.column,
@@ -654,10 +1130,20 @@ impl Decoder {
                     .file_contents
                     .get(&self.current_node.clone().unwrap().0)
                     .unwrap(),
-                "Variable Assign must be a statement"
+                "{}",
+                msg!("runtime.assign_not_statement")
             ));
         }
 
+        // *参照 = 値 は参照先に書き込む
+        if let NodeValue::Dereference(ref target) = var_name.value {
+            let reference = self.execute_node(target)?;
+            let new_value = self.execute_node(value)?;
+            self.write_through_reference(&reference, new_value.clone())?;
+            info!("Assign: *{:?} = {:?}", reference, new_value);
+            return Ok(new_value);
+        }
+
         let name = match var_name.value {
             NodeValue::Variable(_, ref v, _, _) => v.clone(),
             _ => String::new(),
@@ -670,6 +1156,35 @@ impl Decoder {
             .cloned()
             .or_else(|| self.context.global_context.get(&name).cloned());
 
+        // 参照を持つ変数への添字付き代入は参照先の配列を書き換える
+        let has_index = !matches!(index.value, NodeValue::Null);
+        if let Some(variable) = variable_data.as_ref().filter(|_| has_index) {
+            let current = self
+                .memory_mgr
+                .get_value::<Value>(variable.address)
+                .cloned()
+                .unwrap_or(Value::Null);
+            if as_reference(&current).is_some() {
+                let new_value = self.execute_node(&value)?;
+                let index_value = self.execute_node(&index)?;
+                let mut target = self.eval_dereference(&current)?;
+                match (&mut target, index_value.as_u64()) {
+                    (Value::Array(array), Some(i)) if (i as usize) < array.len() => {
+                        array[i as usize] = new_value.clone();
+                    }
+                    (Value::Array(_), Some(_)) => {
+                        return Err(self.runtime_error(&msg!("runtime.index_out_of_bounds")));
+                    }
+                    (Value::Array(_), None) => {
+                        return Err(self.runtime_error(&msg!("runtime.index_not_number")));
+                    }
+                    _ => return Err(self.runtime_error(&msg!("runtime.index_non_array"))),
+                }
+                self.write_through_reference(&current, target)?;
+                return Ok(new_value);
+            }
+        }
+
         if let Some(mut variable) = variable_data {
             if variable.is_mutable {
                 let new_value = self.execute_node(&value)?;
@@ -685,6 +1200,7 @@ impl Decoder {
                                 result = new_value.clone();
                             } else {
                                 return Err(compile_error!(
+                                    code = "E0305",
                                     "error",
                                     self.current_node.clone().unwrap().1.line,
                                     self.current_node.clone().unwrap().1.column,
@@ -693,11 +1209,13 @@ impl Decoder {
                                         .file_contents
                                         .get(&self.current_node.clone().unwrap().0)
                                         .unwrap(),
-                                    "Index out of bounds"
+                                    "{}",
+                                    msg!("runtime.index_out_of_bounds")
                                 ));
                             }
                         } else {
                             return Err(compile_error!(
+                                code = "E0305",
                                 "error",
                                 self.current_node.clone().unwrap().1.line,
                                 self.current_node.clone().unwrap().1.column,
@@ -706,7 +1224,8 @@ impl Decoder {
                                     .file_contents
                                     .get(&self.current_node.clone().unwrap().0)
                                     .unwrap(),
-                                "Index is not a number"
+                                "{}",
+                                msg!("runtime.index_not_number")
                             ));
                         }
                     }
@@ -717,7 +1236,8 @@ impl Decoder {
                 }
 
                 self.memory_mgr
-                    .update_value(variable.address.clone(), variable.value.clone());
+                    .update_value(variable.address.clone(), variable.value.clone())
+                    .map_err(|e| self.runtime_error(&e))?;
 
                 if self.context.local_context.contains_key(&name) {
                     self.context.local_context.insert(name.clone(), variable);
@@ -729,6 +1249,7 @@ impl Decoder {
                 Ok(result)
             } else {
                 Err(compile_error!(
+                    code = "E0304",
                     "error",
                     self.current_node.clone().unwrap().1.line,
                     self.current_node.clone().unwrap().1.column,
@@ -737,12 +1258,13 @@ impl Decoder {
                         .file_contents
                         .get(&self.current_node.clone().unwrap().0)
                         .unwrap(),
-                    "Variable '{}' is not mutable",
-                    name
+                    "{}",
+                    msg!("runtime.variable_not_mutable", name)
                 ))
             }
         } else {
             Err(compile_error!(
+                code = "E0301",
                 "error",
                 self.current_node.clone().unwrap().1.line,
                 self.current_node.clone().unwrap().1.column,
@@ -751,17 +1273,27 @@ impl Decoder {
                     .file_contents
                     .get(&self.current_node.clone().unwrap().0)
                     .unwrap(),
-                "Variable '{}' is not defined",
-                name
+                "{}",
+                msg!("runtime.variable_not_defined", name)
             ))
         }
     }
 
-    fn eval_call(&mut self, name: &String, args: &Vec<Node>, is_system: &bool) -> R<Value, String> {
+    fn eval_call(
+        &mut self,
+        name: &String,
+        args: &Vec<Node>,
+        is_system: &bool,
+    ) -> R<Value, ScriptError> {
         let mut result = Value::Null;
         let mut evaluated_args = Vec::new();
         for arg in args {
-            let evaluated_arg = self.execute_node(&arg)?;
+            // システム関数には参照を外した値を渡す
+            let evaluated_arg = if *is_system {
+                self.eval_operand(arg)?
+            } else {
+                self.execute_node(arg)?
+            };
             info!("args: {:?}", evaluated_arg);
             evaluated_args.push(evaluated_arg);
         }
@@ -772,12 +1304,20 @@ impl Decoder {
                 match name.as_str() {
                     "list_files" => {
                         if args.len() != 1 {
-                            return Err("list_files expects exactly one argument".into());
+                            return Err(self.runtime_error(&msg!(
+                                "builtin.expects_one_argument",
+                                "list_files"
+                            )));
                         }
 
                         let dir = match self.execute_node(&args[0])? {
                             Value::String(v) => v,
-                            _ => return Err("list_files expects a string as the file name".into()),
+                            _ => {
+                                return Err(self.runtime_error(&msg!(
+                                    "builtin.expects_file_name",
+                                    "list_files"
+                                )))
+                            }
                         };
 
                         let mut paths = Vec::new(); // ファイルパスを格納するベクタ
@@ -798,11 +1338,19 @@ impl Decoder {
                     }
                     "play_music" => {
                         if args.len() != 1 {
-                            return Err("play_music expects exactly one argument".into());
+                            return Err(self.runtime_error(&msg!(
+                                "builtin.expects_one_argument",
+                                "play_music"
+                            )));
                         }
                         let file_path = match self.execute_node(&args[0])? {
                             Value::String(v) => v,
-                            _ => return Err("play_music expects a string as the file name".into()),
+                            _ => {
+                                return Err(self.runtime_error(&msg!(
+                                    "builtin.expects_file_name",
+                                    "play_music"
+                                )))
+                            }
                         };
 
                         // 出力ストリームを作成
@@ -828,35 +1376,44 @@ impl Decoder {
                     }
                     "str" => {
                         if args.len() != 1 {
-                            return Err("to_str expects exactly one argument".into());
+                            return Err(
+                                self.runtime_error(&msg!("builtin.expects_one_argument", "to_str"))
+                            );
                         }
-                        let n = match self.execute_node(&args[0])? {
-                            Value::Number(v) => v,
-                            _ => return Err("to_str expects a string as the file name".into()),
-                        };
-                        let string = n.to_string();
-                        return Ok(serde_json::json!(string));
+                        return Ok(Value::String(display_value(&evaluated_args[0])));
                     }
                     "show_msg_box" => {
                         if args.len() != 4 {
-                            return Err("show_msg_box expects exactly two arguments".into());
+                            return Err(self.runtime_error(&msg!(
+                                "builtin.expects_two_arguments",
+                                "show_msg_box"
+                            )));
                         }
                         let message_type = match self.execute_node(&args[0])? {
                             Value::String(v) => v,
                             _ => {
-                                return Err("show_msg_box expects a string as the file name".into())
+                                return Err(self.runtime_error(&msg!(
+                                    "builtin.expects_file_name",
+                                    "show_msg_box"
+                                )))
                             }
                         };
                         let title = match self.execute_node(&args[1])? {
                             Value::String(v) => v,
                             _ => {
-                                return Err("show_msg_box expects a string as the file name".into())
+                                return Err(self.runtime_error(&msg!(
+                                    "builtin.expects_file_name",
+                                    "show_msg_box"
+                                )))
                             }
                         };
                         let message = match self.execute_node(&args[2])? {
                             Value::String(v) => v,
                             _ => {
-                                return Err("show_msg_box expects a string as the file name".into())
+                                return Err(self.runtime_error(&msg!(
+                                    "builtin.expects_file_name",
+                                    "show_msg_box"
+                                )))
                             }
                         };
                         let icon = match self.execute_node(&args[3])? {
@@ -869,11 +1426,19 @@ impl Decoder {
                     }
                     "write_at_file" => {
                         if args.len() != 3 {
-                            return Err("write_file expects exactly two arguments".into());
+                            return Err(self.runtime_error(&msg!(
+                                "builtin.expects_two_arguments",
+                                "write_file"
+                            )));
                         }
                         let file_name = match self.execute_node(&args[0])? {
                             Value::String(v) => v,
-                            _ => return Err("write_file expects a string as the file name".into()),
+                            _ => {
+                                return Err(self.runtime_error(&msg!(
+                                    "builtin.expects_file_name",
+                                    "write_file"
+                                )))
+                            }
                         };
                         let insert_str = match self.execute_node(&args[1])? {
                             Value::String(v) => v,
@@ -881,18 +1446,22 @@ impl Decoder {
                                 .into_iter()
                                 .map(|v| {
                                     if let Value::String(s) = v {
-                                        Ok::<String, String>(s)
+                                        Ok::<String, ScriptError>(s)
                                     } else {
-                                        Err("write_file expects an array of strings as the content"
-                                            .into())
+                                        Err(self.runtime_error(&msg!(
+                                            "builtin.expects_string_array_content",
+                                            "write_file"
+                                        )))
                                     }
                                 })
-                                .collect::<Result<Vec<String>, String>>()?
+                                .collect::<Result<Vec<String>, ScriptError>>()?
                                 .join("\n"),
-                            _ => return Err(
-                                "write_file expects a string or an array of strings as the content"
-                                    .into(),
-                            ),
+                            _ => {
+                                return Err(self.runtime_error(&msg!(
+                                    "builtin.expects_string_content",
+                                    "write_file"
+                                )))
+                            }
                         };
                         let pos = match self.execute_node(&args[2])? {
                             Value::Number(v) => v.as_u64().unwrap(),
@@ -936,7 +1505,10 @@ impl Decoder {
                     }
                     "open_recent" => {
                         if !args.is_empty() {
-                            return Err("open_recent expects no arguments".into());
+                            return Err(self.runtime_error(&msg!(
+                                "builtin.expects_no_arguments",
+                                "open_recent"
+                            )));
                         }
 
                         // 最近使用したアイテムフォルダのパス
@@ -956,37 +1528,66 @@ impl Decoder {
 
                     "sleep" => {
                         if args.len() != 1 {
-                            return Err("sleep expects exactly one argument".into());
+                            return Err(
+                                self.runtime_error(&msg!("builtin.expects_one_argument", "sleep"))
+                            );
                         }
                         let duration = match self.execute_node(&args[0])? {
                             Value::Number(v) => v,
-                            _ => return Err("read_file expects a string as the file name".into()),
+                            _ => {
+                                return Err(self.runtime_error(&msg!(
+                                    "builtin.expects_file_name",
+                                    "read_file"
+                                )))
+                            }
                         };
                         sleep(std::time::Duration::from_secs(duration.as_u64().unwrap()));
                         return Ok(Value::Null);
                     }
 
                     "read_file" => {
-                        if args.len() != 1 {
-                            return Err("read_file expects exactly one argument".into());
+                        if args.is_empty() || args.len() > 2 {
+                            return Err(self.runtime_error(&msg!(
+                                "builtin.expects_one_or_two_arguments",
+                                "read_file"
+                            )));
                         }
                         let file_name = match self.execute_node(&args[0])? {
                             Value::String(v) => v,
-                            _ => return Err("read_file expects a string as the file name".into()),
+                            _ => {
+                                return Err(self.runtime_error(&msg!(
+                                    "builtin.expects_file_name",
+                                    "read_file"
+                                )))
+                            }
                         };
-                        let mut file = File::open(file_name).unwrap();
-                        let mut contents = String::new();
-                        file.read_to_string(&mut contents).unwrap();
+                        let label = self.eval_encoding_arg(args.get(1), "read_file")?;
+                        let bytes = std::fs::read(&file_name).map_err(|e| {
+                            self.runtime_error(&msg!("builtin.file_error", file_name, e))
+                                .into_io()
+                        })?;
+                        let contents = encoding::decode(&bytes, label.as_deref()).map_err(|e| {
+                            self.runtime_error(&msg!("builtin.file_error", file_name, e))
+                                .into_io()
+                        })?;
                         return Ok(Value::String(contents));
                     }
 
                     "write_file" => {
-                        if args.len() != 2 {
-                            return Err("write_file expects exactly two arguments".into());
+                        if args.len() != 2 && args.len() != 3 {
+                            return Err(self.runtime_error(&msg!(
+                                "builtin.expects_two_or_three_arguments",
+                                "write_file"
+                            )));
                         }
                         let file_name = match self.execute_node(&args[0])? {
                             Value::String(v) => v,
-                            _ => return Err("write_file expects a string as the file name".into()),
+                            _ => {
+                                return Err(self.runtime_error(&msg!(
+                                    "builtin.expects_file_name",
+                                    "write_file"
+                                )))
+                            }
                         };
                         let content = match self.execute_node(&args[1])? {
                             Value::String(v) => v,
@@ -994,21 +1595,32 @@ impl Decoder {
                                 .into_iter()
                                 .map(|v| {
                                     if let Value::String(s) = v {
-                                        Ok::<String, String>(s)
+                                        Ok::<String, ScriptError>(s)
                                     } else {
-                                        Err("write_file expects an array of strings as the content"
-                                            .into())
+                                        Err(self.runtime_error(&msg!(
+                                            "builtin.expects_string_array_content",
+                                            "write_file"
+                                        )))
                                     }
                                 })
-                                .collect::<Result<Vec<String>, String>>()?
+                                .collect::<Result<Vec<String>, ScriptError>>()?
                                 .join("\n"),
-                            _ => return Err(
-                                "write_file expects a string or an array of strings as the content"
-                                    .into(),
-                            ),
+                            _ => {
+                                return Err(self.runtime_error(&msg!(
+                                    "builtin.expects_string_content",
+                                    "write_file"
+                                )))
+                            }
                         };
-                        let mut file = File::create(file_name).unwrap();
-                        file.write_all(content.as_bytes()).unwrap();
+                        let label = self.eval_encoding_arg(args.get(2), "write_file")?;
+                        let bytes = encoding::encode(&content, label.as_deref())
+                            .map_err(|e| self.runtime_error(&e))?;
+                        File::create(&file_name)
+                            .and_then(|mut file| file.write_all(&bytes))
+                            .map_err(|e| {
+                                self.runtime_error(&msg!("builtin.file_error", file_name, e))
+                                    .into_io()
+                            })?;
 
                         return Ok(Value::Null);
                     }
@@ -1016,21 +1628,14 @@ impl Decoder {
                     "print" => {
                         let format = match self.execute_node(&args[0])? {
                             Value::String(v) => v,
-                            _ => return Err("print expects a string as the format".into()),
+                            _ => return Err(self.runtime_error(&msg!("builtin.print_format"))),
                         };
                         let args = self.execute_node(&args[1])?;
 
                         match args {
                             Value::Array(arr) => {
-                                let mut formatted_args: Vec<String> = arr
-                                    .iter()
-                                    .map(|arg| match arg {
-                                        Value::String(s) => s.clone(),
-                                        Value::Number(n) => n.to_string(),
-                                        Value::Bool(b) => b.to_string(),
-                                        _ => format!("{:?}", arg),
-                                    })
-                                    .collect();
+                                let mut formatted_args: Vec<String> =
+                                    arr.iter().map(display_value).collect();
 
                                 let mut formatted_string = format.clone();
                                 for arg in arr {
@@ -1050,28 +1655,29 @@ impl Decoder {
                                 }
                                 println!("{}", formatted_string);
                             }
-                            _ => return Err("print expects an array of arguments".into()),
+                            _ => return Err(self.runtime_error(&msg!("builtin.print_arguments"))),
                         }
                         return Ok(Value::Null);
                     }
 
                     "println" => {
-                        for arg in args {
-                            let value = self.execute_node(arg)?;
-                            print!("{}", value);
+                        for value in &evaluated_args {
+                            print!("{}", display_value(value));
                         }
                         println!();
                         return Ok(Value::Null);
                     }
                     "exit" => {
                         if args.len() != 1 {
-                            return Err("exit expects exactly one argument".into());
+                            return Err(
+                                self.runtime_error(&msg!("builtin.expects_one_argument", "exit"))
+                            );
                         }
                         let status = match self.execute_node(&args[0])? {
-                            Value::Number(n) => {
-                                n.as_i64().ok_or("exit expects a positive integer")?
-                            }
-                            _ => return Err("exit expects a number as the status".into()),
+                            Value::Number(n) => n.as_i64().ok_or_else(|| {
+                                self.runtime_error(&msg!("builtin.exit_positive"))
+                            })?,
+                            _ => return Err(self.runtime_error(&msg!("builtin.exit_status"))),
                         };
                         std::process::exit(status.try_into().unwrap());
 
@@ -1079,7 +1685,9 @@ impl Decoder {
                     }
                     "args" => {
                         if !args.is_empty() {
-                            return Err("args expects no arguments".into());
+                            return Err(
+                                self.runtime_error(&msg!("builtin.expects_no_arguments", "args"))
+                            );
                         }
                         let args: Vec<String> = std::env::args().collect();
                         let value: Value =
@@ -1088,37 +1696,39 @@ impl Decoder {
                     }
                     "cmd" => {
                         if evaluated_args.len() < 1 {
-                            return Err("cmd expects at least one argument".into());
+                            return Err(self.runtime_error(&msg!(
+                                "builtin.expects_at_least_one_argument",
+                                "cmd"
+                            )));
                         }
                         let command = match &evaluated_args[0] {
                             Value::String(v) => v.clone(),
-                            _ => return Err("cmd expects the first argument to be a string".into()),
+                            _ => return Err(self.runtime_error(&msg!("builtin.cmd_command"))),
                         };
-                        let command_args =
-                            if evaluated_args.len() > 1 {
-                                match &evaluated_args[1] {
-                                    Value::Array(v) => v
-                                        .iter()
-                                        .filter_map(|item| {
-                                            if let Value::String(s) = item {
-                                                Some(s.clone())
-                                            } else {
-                                                None
-                                            }
-                                        })
-                                        .collect(),
-                                    _ => return Err(
-                                        "cmd expects the second argument to be an array of strings"
-                                            .into(),
-                                    ),
-                                }
-                            } else {
-                                Vec::new()
-                            };
-                        let output = Command::new(command)
+                        let command_args = if evaluated_args.len() > 1 {
+                            match &evaluated_args[1] {
+                                Value::Array(v) => v
+                                    .iter()
+                                    .filter_map(|item| {
+                                        if let Value::String(s) = item {
+                                            Some(s.clone())
+                                        } else {
+                                            None
+                                        }
+                                    })
+                                    .collect(),
+                                _ => return Err(self.runtime_error(&msg!("builtin.cmd_arguments"))),
+                            }
+                        } else {
+                            Vec::new()
+                        };
+                        let output = Command::new(&command)
                             .args(&command_args)
                             .output()
-                            .expect("外部コマンドの実行に失敗しました");
+                            .map_err(|e| {
+                                self.runtime_error(&msg!("builtin.cmd_failed", command, e))
+                                    .into_system()
+                            })?;
                         let stdout = String::from_utf8_lossy(&output.stdout).to_string();
                         let stderr = String::from_utf8_lossy(&output.stderr).to_string();
                         return Ok(Value::Array(vec![
@@ -1126,8 +1736,79 @@ impl Decoder {
                             Value::String(stderr),
                         ]));
                     }
+                    "gc" => {
+                        if !args.is_empty() {
+                            return Err(
+                                self.runtime_error(&msg!("builtin.expects_no_arguments", "gc"))
+                            );
+                        }
+                        let collected = self.collect_garbage();
+                        return Ok(serde_json::json!(collected));
+                    }
+                    "heap_stats" => {
+                        if !args.is_empty() {
+                            return Err(self.runtime_error(&msg!(
+                                "builtin.expects_no_arguments",
+                                "heap_stats"
+                            )));
+                        }
+                        return Ok(serde_json::json!(self.memory_mgr.stats));
+                    }
+                    "heap_used" => {
+                        if !args.is_empty() {
+                            return Err(self.runtime_error(&msg!(
+                                "builtin.expects_no_arguments",
+                                "heap_used"
+                            )));
+                        }
+                        return Ok(serde_json::json!(self.memory_mgr.stats.used_bytes));
+                    }
+                    "heap_blocks" => {
+                        if !args.is_empty() {
+                            return Err(self.runtime_error(&msg!(
+                                "builtin.expects_no_arguments",
+                                "heap_blocks"
+                            )));
+                        }
+                        return Ok(serde_json::json!(self.memory_mgr.stats.live_blocks));
+                    }
+                    "sizeof" => {
+                        if evaluated_args.len() != 1 {
+                            return Err(
+                                self.runtime_error(&msg!("builtin.expects_one_argument", "sizeof"))
+                            );
+                        }
+                        return Ok(serde_json::json!(evaluated_args[0].size()));
+                    }
+                    "ord" => {
+                        if evaluated_args.len() != 1 {
+                            return Err(
+                                self.runtime_error(&msg!("builtin.expects_one_argument", "ord"))
+                            );
+                        }
+                        let c = as_char(&evaluated_args[0])
+                            .ok_or_else(|| self.runtime_error(&msg!("builtin.ord_char")))?;
+                        return Ok(serde_json::json!(c as u32));
+                    }
+                    "chr" => {
+                        if evaluated_args.len() != 1 {
+                            return Err(
+                                self.runtime_error(&msg!("builtin.expects_one_argument", "chr"))
+                            );
+                        }
+                        let code = evaluated_args[0]
+                            .as_u64()
+                            .ok_or_else(|| self.runtime_error(&msg!("builtin.chr_integer")))?;
+                        let c = u32::try_from(code)
+                            .ok()
+                            .and_then(char::from_u32)
+                            .ok_or_else(|| {
+                                self.runtime_error(&msg!("builtin.chr_invalid", code))
+                            })?;
+                        return Ok(Value::String(c.to_string()));
+                    }
                     // 他のシステム関数の処理...
-                    _ => return Err(format!("Unknown function: {}", name)),
+                    _ => return Err(self.runtime_error(&msg!("builtin.unknown", name))),
                 }
             }
         }
@@ -1140,6 +1821,7 @@ impl Decoder {
                 .cloned()
                 .ok_or_else(|| {
                     compile_error!(
+                        code = "E0302",
                         "error",
                         self.current_node.clone().unwrap().1.line(),
                         self.current_node.clone().unwrap().1.column(),
@@ -1148,8 +1830,8 @@ impl Decoder {
                             .file_contents
                             .get(&self.current_node.clone().unwrap().0)
                             .unwrap(),
-                        "Function '{}' is not defined",
-                        func_name
+                        "{}",
+                        msg!("runtime.function_not_defined", func_name)
                     )
                 })?
         };
@@ -1162,24 +1844,40 @@ impl Decoder {
         let return_type = func_info["return_type"].clone();
 
         // スタックフレームをプッシュ
-        self.memory_mgr.push_stack_frame(func_name);
+        let caller_node = self.current_node.clone();
+        let (file_name, call_node) = caller_node.clone().unwrap();
+        self.memory_mgr
+            .push_stack_frame(
+                func_name,
+                (file_name.clone(), call_node.line(), call_node.column()),
+            )
+            .map_err(|e| self.runtime_error(&e))?;
+        self.enter_frame(func_name);
+        // 呼び出し元のローカルスコープは関数本体から見えないように退避
+        let caller_local_context = std::mem::take(&mut self.context.local_context);
+        self.context.scope_stack.push(caller_local_context);
 
         for (arg, value) in _args.as_array().unwrap().iter().zip(&evaluated_args) {
             let arg_name = arg["name"].as_str().unwrap();
             let arg_type = arg["type"].clone();
-            let index = self.memory_mgr.allocate(value.clone());
-            let block = MemoryBlock {
-                id: index,
-                value: Box::new(value.clone()),
+            let arg_is_mutable = arg["mutable"].as_bool().unwrap_or(false);
+            let index = match self.allocate(value.clone()) {
+                Ok(index) => index,
+                Err(e) => {
+                    self.context.local_context = self.context.scope_stack.pop().unwrap_or_default();
+                    self.memory_mgr.pop_stack_frame();
+                    self.call_trace.pop();
+                    return Err(e);
+                }
             };
-            self.memory_mgr.add_to_stack_frame(func_name, block);
+            self.memory_mgr.add_to_stack_frame(index);
             self.context.local_context.insert(
                 arg_name.to_string(),
                 Variable {
                     value: value.clone(),
                     data_type: arg_type.clone(),
                     address: index,
-                    is_mutable: false,
+                    is_mutable: arg_is_mutable,
                     size: 0,
                 },
             );
@@ -1195,10 +1893,25 @@ impl Decoder {
             .collect::<Vec<_>>();
 
         let new_vec: Vec<Box<Node>> = b.iter().map(|x| (*x).clone()).collect();
-        result = self.eval_block(&new_vec)?;
-
-        // スタックフレームをポップ
-        self.memory_mgr.pop_stack_frame(func_name);
+        // 関数本体は定義されたファイルの位置で評価する
+        let body_file = new_vec
+            .iter()
+            .find_map(|node| node_file(node))
+            .unwrap_or(file_name);
+        self.current_node = Some((body_file, call_node));
+        let block_result = self
+            .eval_block(&new_vec)
+            .map_err(|e| e.with_backtrace(self.script_backtrace()));
+
+        // エラー時も含めて必ずスタックフレームをポップ
+        self.call_trace.pop();
+        self.current_node = caller_node;
+        self.context.local_context = self.context.scope_stack.pop().unwrap_or_default();
+        let return_value = self.memory_mgr.pop_stack_frame();
+        result = match return_value {
+            Some(v) => v,
+            None => block_result?,
+        };
 
         info!(
             "CallFunction: name = {:?},args = {:?},return_value = {:?}",
@@ -1212,22 +1925,22 @@ impl Decoder {
     fn eval_function(
         &mut self,
         name: &String,
-        args: &Vec<(Box<Node>, String)>,
+        args: &Vec<(Box<Node>, String, bool)>,
         body: &Box<Node>,
         return_type: &Box<Node>,
         is_system: &bool,
-    ) -> R<Value, String> {
+    ) -> R<Value, ScriptError> {
         let func_name = name; // すでに String 型なのでそのまま使う
                               //   info!("{:?}", func_name.clone());
         if func_name == "main" || func_name == "Main" {
             self.entry_func.0 = true;
             self.entry_func.1 = func_name.clone();
         }
-        self.check_reserved_words(&func_name, RESERVED_WORDS)?;
 
         // 関数がすでに定義されているかチェック
         if self.context.global_context.contains_key(func_name.as_str()) {
             return Err(compile_error!(
+                code = "E0303",
                 "error",
                 self.current_node.clone().unwrap().1.line(),
                 self.current_node.clone().unwrap().1.column(),
@@ -1236,17 +1949,17 @@ impl Decoder {
                     .file_contents
                     .get(&self.current_node.clone().unwrap().0)
                     .unwrap(),
-                "Function '{}' is already defined",
-                func_name
+                "{}",
+                msg!("runtime.function_already_defined", func_name)
             ));
         }
 
         let mut arg_addresses = Vec::new();
 
-        let func_index = self.memory_mgr.allocate(func_name.clone());
-
-        for (i, (data_type, arg_name)) in args.iter().enumerate() {
-            arg_addresses.push(serde_json::json!({"name": arg_name.clone(),"type": data_type}));
+        for (i, (data_type, arg_name, is_mutable)) in args.iter().enumerate() {
+            arg_addresses.push(
+                serde_json::json!({"name": arg_name.clone(),"type": data_type,"mutable": is_mutable}),
+            );
         }
 
         // 関数の情報をシリアライズしてヒープに格納
@@ -1255,7 +1968,7 @@ impl Decoder {
             "body": body,
             "return_type": return_type,
         });
-        let func_info_index = self.memory_mgr.allocate(func_info.clone());
+        let func_info_index = self.allocate(func_info.clone())?;
 
         if *is_system {
             // 関数の情報をグローバルコンテキストに保存
@@ -1289,24 +2002,6 @@ impl Decoder {
         Ok(Value::Null)
     }
 
-    fn check_reserved_words(&self, input: &str, reserved_words: &[&str]) -> Result<Value, String> {
-        if reserved_words.contains(&input) {
-            return Err(compile_error!(
-                "error",
-                self.current_node.clone().unwrap().1.line(),
-                self.current_node.clone().unwrap().1.column(),
-                &self.current_node.clone().unwrap().0,
-                &self
-                    .file_contents
-                    .get(&self.current_node.clone().unwrap().0)
-                    .unwrap(),
-                "'{}' is a reserved word",
-                input
-            ));
-        } else {
-            Ok(Value::Null)
-        }
-    }
     fn eval_variable_declaration(
         &mut self,
         node: &Node,
@@ -1315,10 +2010,11 @@ impl Decoder {
         value: &Box<Node>,
         is_local: &bool,
         is_mutable: &bool,
-    ) -> R<Value, String> {
+    ) -> R<Value, ScriptError> {
         // ステートメントフラグのチェック
         if !node.is_statement() {
             return Err(compile_error!(
+                code = "E0307",
                 "error",
                 self.current_node.clone().unwrap().1.line(),
                 self.current_node.clone().unwrap().1.column(),
@@ -1327,26 +2023,15 @@ impl Decoder {
                     .file_contents
                     .get(&self.current_node.clone().unwrap().0)
                     .unwrap(),
-                "Variable declaration must be a statement"
+                "{}",
+                msg!("runtime.declaration_not_statement")
             ));
         }
 
-        //info!("is_reference: {:?}", is_reference);
         let name = match var_name.value() {
             NodeValue::Variable(_, v, _, _) => v,
             _ => String::new(),
         };
-        // Variable(Box<Node>, String,bool, bool),// 変数(型名,変数名,可変性フラグ,参照型フラグ)
-        let value_is_mutable = match &value.clone().value() {
-            NodeValue::Variable(_, _, v, _) => v.clone(),
-            _ => false,
-        };
-        let value_is_reference = match &value.clone().value() {
-            NodeValue::Variable(_, _, _, v) => v.clone(),
-            _ => false,
-        };
-
-        self.check_reserved_words(&name, RESERVED_WORDS)?;
 
         let mut v_type = Value::Null;
         let v_value;
@@ -1362,6 +2047,7 @@ impl Decoder {
 
             if context.contains_key(&name) {
                 return Err(compile_error!(
+                    code = "E0303",
                     "error",
                     self.current_node.clone().unwrap().1.line(),
                     self.current_node.clone().unwrap().1.column(),
@@ -1370,13 +2056,14 @@ impl Decoder {
                         .file_contents
                         .get(&self.current_node.clone().unwrap().0)
                         .unwrap(),
-                    "Variable '{}' is already defined",
-                    name
+                    "{}",
+                    msg!("runtime.variable_already_defined", name)
                 ));
             }
 
             let v = match data_type.value {
                 NodeValue::Variable(_, ref v, _, _) => v.clone(),
+                NodeValue::DataType(DataType::String(ref v)) => v.clone(),
                 _ => String::new(),
             };
             v_value = {
@@ -1384,81 +2071,32 @@ impl Decoder {
                 //            self.check_type(&_value, v_type.as_str().unwrap_or(""))?
                 _value.clone()
             };
+            self.check_char_type(&name, &v, &v_value)?;
             v_type = Value::String(v.into());
         }
 
-        if value_is_reference {
-            // 参照型の場合、右辺の変数名を取り出してアドレスを取得して直接変更
-            address = {
-                let context = if *is_local {
-                    &mut self.context.local_context
-                } else {
-                    &mut self.context.global_context
-                };
-
-                match value.value() {
-                    NodeValue::Variable(_, v, _, _) => {
-                        if let Some(variable) = context.get(&v) {
-                            variable.address
-                        } else {
-                            return Err(compile_error!(
-                                "error",
-                                self.current_node.clone().unwrap().1.line(),
-                                self.current_node.clone().unwrap().1.column(),
-                                &self.current_node.clone().unwrap().0,
-                                &self
-                                    .file_contents
-                                    .get(&self.current_node.clone().unwrap().0)
-                                    .unwrap(),
-                                "Variable '{}' not found in context",
-                                v
-                            ));
-                        }
-                    }
-                    _ => {
-                        let _address = self.memory_mgr.allocate(v_value.clone());
-                        _address
-                    }
-                }
-            };
-
-            let context = if *is_local {
-                &mut self.context.local_context
-            } else {
-                &mut self.context.global_context
-            };
-
-            context.insert(
-                name.clone(),
-                Variable {
-                    value: v_value.clone(),
-                    data_type: v_type.clone(),
-                    address,
-                    is_mutable: *is_mutable,
-                    size: v_value.size(),
-                },
-            );
+        address = self.allocate(v_value.clone())?;
+        if *is_local {
+            self.memory_mgr.add_to_stack_frame(address);
+        }
+        let context = if *is_local {
+            &mut self.context.local_context
         } else {
-            address = self.memory_mgr.allocate(v_value.clone());
-            let context = if *is_local {
-                &mut self.context.local_context
-            } else {
-                &mut self.context.global_context
-            };
+            &mut self.context.global_context
+        };
 
-            context.insert(
-                name.clone(),
-                Variable {
-                    value: v_value.clone(),
-                    data_type: v_type.clone(),
-                    address,
-                    is_mutable: *is_mutable,
-                    size: v_value.size(),
-                },
-            );
-        }
+        context.insert(
+            name.clone(),
+            Variable {
+                value: v_value.clone(),
+                data_type: v_type.clone(),
+                address,
+                is_mutable: *is_mutable,
+                size: v_value.size(),
+            },
+        );
 
-        info!("VariableDeclaration: name = {:?}, data_type = {:?}, value = {:?}, address = {:?} is_mutable: {} is_local: {} value_is_mutable: {:?} value_is_reference: {:?}", name, v_type, v_value, address,is_mutable,is_local,value_is_mutable,value_is_reference);
+        info!("VariableDeclaration: name = {:?}, data_type = {:?}, value = {:?}, address = {:?} is_mutable: {} is_local: {}", name, v_type, v_value, address,is_mutable,is_local);
         let line = self.current_node.clone().unwrap().1.line();
         let column = self.current_node.clone().unwrap().1.column();
         self.context
@@ -1471,13 +2109,14 @@ impl Decoder {
         &mut self,
         _type_name: &Box<Node>,
         _type: &Box<Node>,
-    ) -> R<Value, String> {
+    ) -> R<Value, ScriptError> {
         let name = match _type_name.value() {
             NodeValue::Variable(_, v, _, _) => v,
             _ => String::new(),
         };
         if self.context.type_context.contains_key(&name) {
             return Err(compile_error!(
+                code = "E0303",
                 "error",
                 self.current_node.clone().unwrap().1.line(),
                 self.current_node.clone().unwrap().1.column(),
@@ -1486,8 +2125,8 @@ impl Decoder {
                     .file_contents
                     .get(&self.current_node.clone().unwrap().0)
                     .unwrap(),
-                "type '{}' is already defined",
-                name
+                "{}",
+                msg!("runtime.type_already_defined", name)
             ));
         }
         let v_type = match _type.value() {
@@ -1506,7 +2145,7 @@ impl Decoder {
         );
         Ok(Value::String(name.into()))
     }
-    fn eval_variable(&mut self, name: &String) -> R<Value, String> {
+    fn eval_variable(&mut self, name: &String) -> R<Value, ScriptError> {
         let line = self.current_node.clone().unwrap().1.line();
         let column = self.current_node.clone().unwrap().1.column();
         self.context
@@ -1554,8 +2193,12 @@ impl Decoder {
             Ok(Value::Null)
         }
     }
-    fn eval_return(&mut self, ret: &Box<Node>) -> R<Value, String> {
+    fn eval_return(&mut self, ret: &Box<Node>) -> R<Value, ScriptError> {
         let ret = self.execute_node(&ret)?;
+        // 現在のフレームの戻り値アドレスに書き込む
+        self.memory_mgr
+            .set_return_value(ret.clone())
+            .map_err(|e| self.runtime_error(&e))?;
         info!("Return: {:?}", ret);
         Ok(ret)
     }
@@ -1563,11 +2206,11 @@ impl Decoder {
     fn eval_callback_function(
         &mut self,
         name: &String,
-        args: &Vec<(Box<Node>, String)>,
+        args: &Vec<(Box<Node>, String, bool)>,
         body: &Box<Node>,
         return_type: &Box<Node>,
         is_system: &bool,
-    ) -> R<Value, String> {
+    ) -> R<Value, ScriptError> {
         let func_name = name; // すでに String 型なのでそのまま使う
         if func_name == "main" || func_name == "Main" {
             self.entry_func.0 = true;
@@ -1576,6 +2219,7 @@ impl Decoder {
         // 関数がすでに定義されているかチェック
         if self.context.global_context.contains_key(func_name.as_str()) {
             return Err(compile_error!(
+                code = "E0303",
                 "error",
                 self.current_node.clone().unwrap().1.line(),
                 self.current_node.clone().unwrap().1.column(),
@@ -1584,17 +2228,17 @@ impl Decoder {
                     .file_contents
                     .get(&self.current_node.clone().unwrap().0)
                     .unwrap(),
-                "Function '{}' is already defined",
-                func_name
+                "{}",
+                msg!("runtime.function_already_defined", func_name)
             ));
         }
 
         let mut arg_addresses = Vec::new();
 
-        let func_index = self.memory_mgr.allocate(func_name.clone());
-
-        for (i, (data_type, arg_name)) in args.iter().enumerate() {
-            arg_addresses.push(serde_json::json!({"name": arg_name.clone(),"type": data_type}));
+        for (i, (data_type, arg_name, is_mutable)) in args.iter().enumerate() {
+            arg_addresses.push(
+                serde_json::json!({"name": arg_name.clone(),"type": data_type,"mutable": is_mutable}),
+            );
         }
 
         // 関数の情報をシリアライズしてヒープに格納
@@ -1603,7 +2247,7 @@ impl Decoder {
             "body": body,
             "return_type": return_type,
         });
-        let func_info_index = self.memory_mgr.allocate(func_info.clone());
+        let func_info_index = self.allocate(func_info.clone())?;
 
         if *is_system {
             // 関数の情報をグローバルコンテキストに保存
@@ -1637,7 +2281,7 @@ impl Decoder {
         Ok(Value::Null)
     }
 
-    fn eval_binary_increment(&mut self, lhs: &Box<Node>) -> R<Value, String> {
+    fn eval_binary_increment(&mut self, lhs: &Box<Node>) -> R<Value, ScriptError> {
         let left_value = match self.execute_node(&lhs)? {
             Value::Number(v) => v,
             _ => serde_json::Number::from(-1),
@@ -1656,13 +2300,14 @@ impl Decoder {
         if let Some(variable) = variable_data {
             let result = left_value.as_i64().unwrap() + 1;
             self.memory_mgr
-                .update_value(variable.address.clone(), result);
+                .update_value(variable.address.clone(), Value::Number(result.into()))
+                .map_err(|e| self.runtime_error(&e))?;
             Ok(Value::Number(result.into()))
         } else {
             Ok(Value::Null)
         }
     }
-    fn eval_binary_decrement(&mut self, lhs: &Box<Node>) -> R<Value, String> {
+    fn eval_binary_decrement(&mut self, lhs: &Box<Node>) -> R<Value, ScriptError> {
         let left_value = match self.execute_node(&lhs)? {
             Value::Number(v) => v,
             _ => serde_json::Number::from(-1),
@@ -1681,30 +2326,35 @@ impl Decoder {
         if let Some(variable) = variable_data {
             let result = left_value.as_i64().unwrap() - 1;
             self.memory_mgr
-                .update_value(variable.address.clone(), result);
+                .update_value(variable.address.clone(), Value::Number(result.into()))
+                .map_err(|e| self.runtime_error(&e))?;
             Ok(Value::Number(result.into()))
         } else {
             Ok(Value::Null)
         }
     }
 
-    fn eval_binary_bit(&mut self, node: &Node) -> Result<Value, String> {
+    fn eval_binary_bit(&mut self, node: &Node) -> Result<Value, ScriptError> {
         if let NodeValue::Operator(Operator::BitAnd(left, right))
         | NodeValue::Operator(Operator::BitOr(left, right))
         | NodeValue::Operator(Operator::BitXor(left, right))
         | NodeValue::Operator(Operator::ShiftLeft(left, right))
         | NodeValue::Operator(Operator::ShiftRight(left, right)) = &node.value
         {
-            let left_value = self.execute_node(left)?;
-            let right_value = self.execute_node(right)?;
+            let left_value = self.eval_operand(left)?;
+            let right_value = self.eval_operand(right)?;
             match (&node.value, left_value, right_value) {
                 (
                     NodeValue::Operator(Operator::BitAnd(_, _)),
                     Value::Number(l),
                     Value::Number(r),
                 ) => {
-                    let l_i64 = l.as_i64().ok_or("Failed to convert left number to i64")?;
-                    let r_i64 = r.as_i64().ok_or("Failed to convert right number to i64")?;
+                    let l_i64 = l
+                        .as_i64()
+                        .ok_or_else(|| self.runtime_error(&msg!("runtime.left_not_i64")))?;
+                    let r_i64 = r
+                        .as_i64()
+                        .ok_or_else(|| self.runtime_error(&msg!("runtime.right_not_i64")))?;
                     Ok(Value::Number((l_i64 & r_i64).into()))
                 }
                 (
@@ -1712,8 +2362,12 @@ impl Decoder {
                     Value::Number(l),
                     Value::Number(r),
                 ) => {
-                    let l_i64 = l.as_i64().ok_or("Failed to convert left number to i64")?;
-                    let r_i64 = r.as_i64().ok_or("Failed to convert right number to i64")?;
+                    let l_i64 = l
+                        .as_i64()
+                        .ok_or_else(|| self.runtime_error(&msg!("runtime.left_not_i64")))?;
+                    let r_i64 = r
+                        .as_i64()
+                        .ok_or_else(|| self.runtime_error(&msg!("runtime.right_not_i64")))?;
                     Ok(Value::Number((l_i64 | r_i64).into()))
                 }
                 (
@@ -1721,8 +2375,12 @@ impl Decoder {
                     Value::Number(l),
                     Value::Number(r),
                 ) => {
-                    let l_i64 = l.as_i64().ok_or("Failed to convert left number to i64")?;
-                    let r_i64 = r.as_i64().ok_or("Failed to convert right number to i64")?;
+                    let l_i64 = l
+                        .as_i64()
+                        .ok_or_else(|| self.runtime_error(&msg!("runtime.left_not_i64")))?;
+                    let r_i64 = r
+                        .as_i64()
+                        .ok_or_else(|| self.runtime_error(&msg!("runtime.right_not_i64")))?;
                     Ok(Value::Number((l_i64 ^ r_i64).into()))
                 }
                 (
@@ -1730,8 +2388,12 @@ impl Decoder {
                     Value::Number(l),
                     Value::Number(r),
                 ) => {
-                    let l_i64 = l.as_i64().ok_or("Failed to convert left number to i64")?;
-                    let r_i64 = r.as_i64().ok_or("Failed to convert right number to i64")?;
+                    let l_i64 = l
+                        .as_i64()
+                        .ok_or_else(|| self.runtime_error(&msg!("runtime.left_not_i64")))?;
+                    let r_i64 = r
+                        .as_i64()
+                        .ok_or_else(|| self.runtime_error(&msg!("runtime.right_not_i64")))?;
                     Ok(Value::Number((l_i64 << r_i64).into()))
                 }
                 (
@@ -1739,18 +2401,22 @@ impl Decoder {
                     Value::Number(l),
                     Value::Number(r),
                 ) => {
-                    let l_i64 = l.as_i64().ok_or("Failed to convert left number to i64")?;
-                    let r_i64 = r.as_i64().ok_or("Failed to convert right number to i64")?;
+                    let l_i64 = l
+                        .as_i64()
+                        .ok_or_else(|| self.runtime_error(&msg!("runtime.left_not_i64")))?;
+                    let r_i64 = r
+                        .as_i64()
+                        .ok_or_else(|| self.runtime_error(&msg!("runtime.right_not_i64")))?;
                     Ok(Value::Number((l_i64 >> r_i64).into()))
                 }
-                _ => Err("Unsupported operation or mismatched types in condition".to_string()),
+                _ => Err(self.runtime_error(&msg!("runtime.unsupported_condition"))),
             }
         } else {
-            Err("Unsupported node value".to_string())
+            Err(self.runtime_error(&msg!("runtime.unsupported_node")))
         }
     }
 
-    fn eval_binary_condition(&mut self, node: &Node) -> Result<Value, String> {
+    fn eval_binary_condition(&mut self, node: &Node) -> Result<Value, ScriptError> {
         if let NodeValue::Operator(Operator::Eq(left, right))
         | NodeValue::Operator(Operator::Ne(left, right))
         | NodeValue::Operator(Operator::Lt(left, right))
@@ -1758,53 +2424,90 @@ impl Decoder {
         | NodeValue::Operator(Operator::Le(left, right))
         | NodeValue::Operator(Operator::Ge(left, right)) = &node.value
         {
-            let left_value = self.execute_node(left)?;
-            let right_value = self.execute_node(right)?;
+            let left_value = self.eval_operand(left)?;
+            let right_value = self.eval_operand(right)?;
             match (&node.value, left_value, right_value) {
                 (NodeValue::Operator(Operator::Eq(_, _)), Value::Number(l), Value::Number(r)) => {
-                    let l_f64 = l.as_f64().ok_or("Failed to convert left number to f64")?;
-                    let r_f64 = r.as_f64().ok_or("Failed to convert right number to f64")?;
+                    let l_f64 = l
+                        .as_f64()
+                        .ok_or_else(|| self.runtime_error(&msg!("runtime.left_not_f64")))?;
+                    let r_f64 = r
+                        .as_f64()
+                        .ok_or_else(|| self.runtime_error(&msg!("runtime.right_not_f64")))?;
                     Ok(Value::Bool(l_f64 == r_f64))
                 }
                 (NodeValue::Operator(Operator::Eq(_, _)), Value::String(l), Value::String(r)) => {
                     Ok(Value::Bool(l == r))
                 }
                 (NodeValue::Operator(Operator::Ne(_, _)), Value::Number(l), Value::Number(r)) => {
-                    let l_f64 = l.as_f64().ok_or("Failed to convert left number to f64")?;
-                    let r_f64 = r.as_f64().ok_or("Failed to convert right number to f64")?;
+                    let l_f64 = l
+                        .as_f64()
+                        .ok_or_else(|| self.runtime_error(&msg!("runtime.left_not_f64")))?;
+                    let r_f64 = r
+                        .as_f64()
+                        .ok_or_else(|| self.runtime_error(&msg!("runtime.right_not_f64")))?;
                     Ok(Value::Bool(l_f64 != r_f64))
                 }
                 (NodeValue::Operator(Operator::Ne(_, _)), Value::String(l), Value::String(r)) => {
                     Ok(Value::Bool(l != r))
                 }
                 (NodeValue::Operator(Operator::Lt(_, _)), Value::Number(l), Value::Number(r)) => {
-                    let l_f64 = l.as_f64().ok_or("Failed to convert left number to f64")?;
-                    let r_f64 = r.as_f64().ok_or("Failed to convert right number to f64")?;
+                    let l_f64 = l
+                        .as_f64()
+                        .ok_or_else(|| self.runtime_error(&msg!("runtime.left_not_f64")))?;
+                    let r_f64 = r
+                        .as_f64()
+                        .ok_or_else(|| self.runtime_error(&msg!("runtime.right_not_f64")))?;
                     Ok(Value::Bool(l_f64 < r_f64))
                 }
                 (NodeValue::Operator(Operator::Gt(_, _)), Value::Number(l), Value::Number(r)) => {
-                    let l_f64 = l.as_f64().ok_or("Failed to convert left number to f64")?;
-                    let r_f64 = r.as_f64().ok_or("Failed to convert right number to f64")?;
+                    let l_f64 = l
+                        .as_f64()
+                        .ok_or_else(|| self.runtime_error(&msg!("runtime.left_not_f64")))?;
+                    let r_f64 = r
+                        .as_f64()
+                        .ok_or_else(|| self.runtime_error(&msg!("runtime.right_not_f64")))?;
                     Ok(Value::Bool(l_f64 > r_f64))
                 }
                 (NodeValue::Operator(Operator::Le(_, _)), Value::Number(l), Value::Number(r)) => {
-                    let l_f64 = l.as_f64().ok_or("Failed to convert left number to f64")?;
-                    let r_f64 = r.as_f64().ok_or("Failed to convert right number to f64")?;
+                    let l_f64 = l
+                        .as_f64()
+                        .ok_or_else(|| self.runtime_error(&msg!("runtime.left_not_f64")))?;
+                    let r_f64 = r
+                        .as_f64()
+                        .ok_or_else(|| self.runtime_error(&msg!("runtime.right_not_f64")))?;
                     Ok(Value::Bool(l_f64 <= r_f64))
                 }
                 (NodeValue::Operator(Operator::Ge(_, _)), Value::Number(l), Value::Number(r)) => {
-                    let l_f64 = l.as_f64().ok_or("Failed to convert left number to f64")?;
-                    let r_f64 = r.as_f64().ok_or("Failed to convert right number to f64")?;
+                    let l_f64 = l
+                        .as_f64()
+                        .ok_or_else(|| self.runtime_error(&msg!("runtime.left_not_f64")))?;
+                    let r_f64 = r
+                        .as_f64()
+                        .ok_or_else(|| self.runtime_error(&msg!("runtime.right_not_f64")))?;
                     Ok(Value::Bool(l_f64 >= r_f64))
                 }
-                _ => Err("Unsupported operation or mismatched types in condition".to_string()),
+                // 文字(列)同士はコードポイント順で比較する
+                (NodeValue::Operator(Operator::Lt(_, _)), Value::String(l), Value::String(r)) => {
+                    Ok(Value::Bool(l < r))
+                }
+                (NodeValue::Operator(Operator::Gt(_, _)), Value::String(l), Value::String(r)) => {
+                    Ok(Value::Bool(l > r))
+                }
+                (NodeValue::Operator(Operator::Le(_, _)), Value::String(l), Value::String(r)) => {
+                    Ok(Value::Bool(l <= r))
+                }
+                (NodeValue::Operator(Operator::Ge(_, _)), Value::String(l), Value::String(r)) => {
+                    Ok(Value::Bool(l >= r))
+                }
+                _ => Err(self.runtime_error(&msg!("runtime.unsupported_condition"))),
             }
         } else {
-            Err("Unsupported node value".to_string())
+            Err(self.runtime_error(&msg!("runtime.unsupported_node")))
         }
     }
 
-    fn eval_binary_op(&mut self, node: &Node) -> Result<Value, String> {
+    fn eval_binary_op(&mut self, node: &Node) -> Result<Value, ScriptError> {
         if let NodeValue::Operator(Operator::Add(lhs, rhs))
         | NodeValue::Operator(Operator::Sub(lhs, rhs))
         | NodeValue::Operator(Operator::Mul(lhs, rhs))
@@ -1814,8 +2517,8 @@ impl Decoder {
         | NodeValue::Operator(Operator::MulAssign(lhs, rhs))
         | NodeValue::Operator(Operator::DivAssign(lhs, rhs)) = &node.value
         {
-            let left_value = self.execute_node(lhs)?;
-            let right_value = self.execute_node(rhs)?;
+            let left_value = self.eval_operand(lhs)?;
+            let right_value = self.eval_operand(rhs)?;
             match (&node.value, left_value.clone(), right_value.clone()) {
                 (NodeValue::Operator(Operator::Add(_, _)), Value::Number(l), Value::Number(r)) => {
                     if l.is_i64() && r.is_i64() {
@@ -1864,6 +2567,7 @@ impl Decoder {
                 (NodeValue::Operator(Operator::Div(_, _)), Value::Number(l), Value::Number(r)) => {
                     if r.as_f64().unwrap() == 0.0 {
                         return Err(compile_error!(
+                            code = "E0306",
                             "error",
                             self.current_node.clone().unwrap().1.line,
                             self.current_node.clone().unwrap().1.column,
@@ -1872,9 +2576,12 @@ impl Decoder {
                                 .file_contents
                                 .get(&self.current_node.clone().unwrap().0)
                                 .unwrap(),
-                            "Division by zero: {:?} / {:?}",
-                            left_value.clone(),
-                            right_value.clone()
+                            "{}",
+                            msg!(
+                                "runtime.division_by_zero",
+                                format!("{:?}", left_value),
+                                format!("{:?}", right_value)
+                            )
                         ));
                     }
                     if l.is_i64() && r.is_i64() {
@@ -1920,6 +2627,7 @@ impl Decoder {
                 ) => {
                     if r.as_f64().unwrap() == 0.0 {
                         return Err(compile_error!(
+                            code = "E0306",
                             "error",
                             self.current_node.clone().unwrap().1.line,
                             self.current_node.clone().unwrap().1.column,
@@ -1928,20 +2636,21 @@ impl Decoder {
                                 .file_contents
                                 .get(&self.current_node.clone().unwrap().0)
                                 .unwrap(),
-                            "Division by zero: {:?} / {:?}",
-                            left_value.clone(),
-                            right_value.clone()
+                            "{}",
+                            msg!(
+                                "runtime.division_by_zero",
+                                format!("{:?}", left_value),
+                                format!("{:?}", right_value)
+                            )
                         ));
                     }
                     let result = l.as_f64().unwrap() / r.as_f64().unwrap();
                     Ok(Value::Number(serde_json::Number::from_f64(result).unwrap()))
                 }
-                _ => {
-                    Err("Unsupported operation or mismatched types in binary operation".to_string())
-                }
+                _ => Err(self.runtime_error(&msg!("runtime.unsupported_binary"))),
             }
         } else {
-            Err("Unsupported node value".to_string())
+            Err(self.runtime_error(&msg!("runtime.unsupported_node")))
         }
     }
 
@@ -1949,7 +2658,7 @@ impl Decoder {
         &mut self,
         condition: &Box<Node>,
         body: &Box<Node>,
-    ) -> Result<Value, String> {
+    ) -> Result<Value, ScriptError> {
         let condition_result = self.execute_node(&condition)?;
         let mut result = Value::Null;
 
@@ -1972,10 +2681,13 @@ impl Decoder {
         Ok(result)
     }
 
-    fn eval_loop_statement(&mut self, body: &Box<Node>) -> Result<Value, String> {
+    fn eval_loop_statement(&mut self, body: &Box<Node>) -> Result<Value, ScriptError> {
         let mut result = Value::Null;
         loop {
             result = self.execute_node(&body)?;
+            if self.memory_mgr.is_returning() {
+                break;
+            }
         }
         Ok(result)
     }
@@ -1984,7 +2696,7 @@ impl Decoder {
         &mut self,
         condition: &Box<Node>,
         body: &Box<Node>,
-    ) -> Result<Value, String> {
+    ) -> Result<Value, ScriptError> {
         let mut result = Value::Null;
         loop {
             let condition_value = self.execute_node(&condition)?;
@@ -1992,6 +2704,10 @@ impl Decoder {
                 if value {
                     match self.execute_node(&body) {
                         Ok(val) => {
+                            if self.memory_mgr.is_returning() {
+                                result = val;
+                                break;
+                            }
                             if val == Value::String("break".to_string()) {
                                 break;
                             } else if val == Value::String("continue".to_string()) {
@@ -2006,7 +2722,7 @@ impl Decoder {
                     break;
                 }
             } else {
-                return Err("Condition must evaluate to a boolean".to_string());
+                return Err(self.runtime_error(&msg!("runtime.condition_not_bool")));
             }
         }
         Ok(result)
@@ -2017,15 +2733,19 @@ impl Decoder {
         value: &Box<Node>,
         iterator: &Box<Node>,
         body: &Box<Node>,
-    ) -> Result<Value, String> {
+    ) -> Result<Value, ScriptError> {
         let mut result = Value::Null;
 
         // イテレータの評価
-        let iter_value = self.execute_node(iterator)?;
+        let iter_value = match self.eval_operand(iterator)? {
+            // 文字列は1文字ずつ走査する
+            Value::String(s) => Value::Array(s.chars().map(|c| Value::String(c.into())).collect()),
+            v => v,
+        };
         if let Value::Array(elements) = iter_value {
             for element in elements {
                 // ループ変数に値を設定し、メモリを確保
-                let element_address = self.memory_mgr.allocate(element.clone());
+                let element_address = self.allocate(element.clone())?;
                 let variable = Variable {
                     data_type: Value::String("void".to_string()), // 型推論を仮定
                     value: element.clone(),
@@ -2038,10 +2758,15 @@ impl Decoder {
                     _ => String::new(),
                 };
                 self.context.local_context.insert(var.clone(), variable);
+                self.memory_mgr.add_to_stack_frame(element_address);
 
                 // ループボディの評価
                 match self.execute_node(body) {
                     Ok(val) => {
+                        if self.memory_mgr.is_returning() {
+                            result = val;
+                            break;
+                        }
                         if val == Value::String("break".to_string()) {
                             break;
                         } else if val == Value::String("continue".to_string()) {
@@ -2055,6 +2780,7 @@ impl Decoder {
             }
         } else {
             return Err(compile_error!(
+                code = "E0308",
                 "error",
                 self.current_node.clone().unwrap().1.line,
                 self.current_node.clone().unwrap().1.column,
@@ -2063,14 +2789,15 @@ impl Decoder {
                     .file_contents
                     .get(&self.current_node.clone().unwrap().0)
                     .unwrap(),
-                "The iterator is not an array",
+                "{}",
+                msg!("runtime.not_iterable")
             ));
         }
 
         Ok(result)
     }
 
-    fn eval_primitive_type(&mut self, node: &Node) -> Result<Value, String> {
+    fn eval_primitive_type(&mut self, node: &Node) -> Result<Value, ScriptError> {
         match &node.value {
             NodeValue::DataType(DataType::Int(number)) => Ok(Value::Number((*number).into())),
             NodeValue::DataType(DataType::Float(number)) => {
@@ -2078,6 +2805,13 @@ impl Decoder {
                 Ok(Value::Number(n.into()))
             }
             NodeValue::DataType(DataType::String(s)) => Ok(Value::String(s.clone())),
+            NodeValue::DataType(DataType::Char(c)) => Ok(Value::String(c.to_string())),
+            NodeValue::DataType(DataType::UInt(number)) => Ok(Value::Number((*number).into())),
+            // 型接尾辞は構文解析で範囲を検査済みなので値だけを評価する
+            NodeValue::DataType(DataType::Typed(value, _)) => self.eval_primitive_type(&Node {
+                value: NodeValue::DataType(*value.clone()),
+                ..Node::default()
+            }),
             NodeValue::DataType(DataType::Bool(b)) => Ok(Value::Bool(*b)),
             NodeValue::Declaration(Declaration::Array(data_type, values)) => {
                 self.eval_array(&data_type, &values)
@@ -2089,16 +2823,13 @@ impl Decoder {
         &mut self,
         name: &String,
         members: &Vec<Box<Node>>,
-    ) -> Result<Value, String> {
+    ) -> Result<Value, ScriptError> {
         // グローバルコンテキストへのアクセス
         let context = &mut self.context.global_context;
 
         // 既に構造体が定義されているかチェック
         if !context.contains_key(name) {
-            return Err(format!(
-                "Struct '{}' is not defined. Please define the struct before implementing.",
-                name
-            ));
+            return Err(self.runtime_error(&msg!("runtime.struct_not_defined", name)));
         }
 
         // 既存の構造体を取得
@@ -2108,10 +2839,7 @@ impl Decoder {
         let mut structs_map: HashMap<String, Value> = match &struct_var.value {
             Value::Object(map) => map.clone().into_iter().collect(), // 変換処理
             _ => {
-                return Err(format!(
-                    "The value associated with '{}' is not a HashMap.",
-                    name
-                ));
+                return Err(self.runtime_error(&msg!("runtime.not_a_hashmap", name)));
             }
         };
 
@@ -2164,7 +2892,7 @@ impl Decoder {
         &mut self,
         name: &String,
         members: &Vec<Box<Node>>,
-    ) -> Result<Value, String> {
+    ) -> Result<Value, ScriptError> {
         // 一時的にcontextの借用を解除
         let context = //if *is_local {
       //      &mut self.context.local_context
@@ -2174,6 +2902,7 @@ impl Decoder {
 
         if context.contains_key(&name.clone()) {
             return Err(compile_error!(
+                code = "E0303",
                 "error",
                 self.current_node.clone().unwrap().1.line(),
                 self.current_node.clone().unwrap().1.column(),
@@ -2182,8 +2911,8 @@ impl Decoder {
                     .file_contents
                     .get(&self.current_node.clone().unwrap().0)
                     .unwrap(),
-                "Struct '{}' is already defined",
-                name
+                "{}",
+                msg!("runtime.struct_already_defined", name)
             ));
         }
 
@@ -2207,6 +2936,9 @@ impl Decoder {
                             DataType::Int(_) => "int".to_string(),
                             DataType::Float(_) => "float".to_string(),
                             DataType::String(v) => v.clone(),
+                            DataType::Char(_) => "char".to_string(),
+                            DataType::UInt(_) => "int".to_string(),
+                            DataType::Typed(_, v) => v.clone(),
                             DataType::Bool(_) => "bool".to_string(),
                             DataType::Unit(_) => "unit".to_string(),
                         }
@@ -2248,7 +2980,7 @@ impl Decoder {
     }
 
     // ノードを評価
-    fn execute_node(&mut self, node: &Node) -> R<Value, String> {
+    fn execute_node(&mut self, node: &Node) -> R<Value, ScriptError> {
         let original_node = self.current_node.clone();
         self.current_node = Some((
             self.current_node.as_ref().unwrap().0.clone(),
@@ -2324,6 +3056,9 @@ impl Decoder {
             NodeValue::DataType(DataType::Int(_))
             | NodeValue::DataType(DataType::Float(_))
             | NodeValue::DataType(DataType::String(_))
+            | NodeValue::DataType(DataType::Char(_))
+            | NodeValue::DataType(DataType::UInt(_))
+            | NodeValue::DataType(DataType::Typed(_, _))
             | NodeValue::DataType(DataType::Bool(_))
             | NodeValue::Declaration(Declaration::Array(_, _)) => {
                 result = self.eval_primitive_type(&node.clone())?;
@@ -2383,9 +3118,19 @@ impl Decoder {
             NodeValue::Declaration(Declaration::Type(type_name, _type)) => {
                 result = self.eval_type_declaration(type_name, _type)?;
             }
+            NodeValue::Variable(_, name, is_mutable, true) => {
+                result = self.eval_reference(name, *is_mutable)?;
+            }
             NodeValue::Variable(_, name, _, _) => {
                 result = self.eval_variable(name)?;
             }
+            NodeValue::Interpolation(parts) => {
+                result = self.eval_interpolation(parts)?;
+            }
+            NodeValue::Dereference(target) => {
+                let reference = self.execute_node(target)?;
+                result = self.eval_dereference(&reference)?;
+            }
             NodeValue::ControlFlow(ControlFlow::Return(ret)) => {
                 result = self.eval_return(ret)?;
             }
@@ -2415,8 +3160,8 @@ impl Decoder {
                         .file_contents
                         .get(&self.current_node.clone().unwrap().0)
                         .unwrap(),
-                    "Unknown node value: {:?}",
-                    node.value
+                    "{}",
+                    msg!("runtime.unknown_node", format!("{:?}", node.value))
                 ));
             }
         }
@@ -2424,3 +3169,226 @@ impl Decoder {
         Ok(result)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn recursive_calls_return_to_their_caller() {
+        let mut decoder = Decoder::new();
+        decoder
+            .eval_text(
+                "test.sc",
+                "fn fact(n) {\n    if n <= 1 {\n        return 1;\n    }\n    return n * fact(n - 1);\n}",
+            )
+            .unwrap();
+        let value = decoder.eval_text("test.sc", "fact(5)").unwrap();
+        assert_eq!(value, json!(120));
+        assert!(decoder.memory_mgr.call_stack.is_empty());
+    }
+
+    #[test]
+    fn loops_do_not_grow_the_heap() {
+        let mut decoder = Decoder::new();
+        decoder
+            .eval_text(
+                "test.sc",
+                "let mut total = 0;\nfor i in 0..3000 {\n    let items = [i, i + 1];\n    total = total + i;\n}",
+            )
+            .unwrap();
+        decoder.collect_garbage();
+        let live_blocks = decoder.memory_mgr.stats.live_blocks;
+        assert!(live_blocks < 100, "live blocks: {}", live_blocks);
+        assert!(decoder.memory_mgr.stats.total_frees > 3000);
+        assert_eq!(
+            decoder.eval_text("test.sc", "total").unwrap(),
+            json!(4501500)
+        );
+    }
+
+    #[test]
+    fn runs_the_first_matching_if_branch() {
+        let mut decoder = Decoder::new();
+        decoder
+            .eval_text(
+                "test.sc",
+                "fn pick(n) {\n    let mut branch = 0;\n    if n == 1 {\n        branch = 1;\n    } else if n == 2 {\n        branch = 2;\n    } else {\n        branch = 3;\n    }\n    return branch;\n}",
+            )
+            .unwrap();
+        for (n, branch) in [(1, 1), (2, 2), (5, 3)] {
+            assert_eq!(
+                decoder
+                    .eval_text("test.sc", &format!("pick({})", n))
+                    .unwrap(),
+                json!(branch)
+            );
+        }
+    }
+
+    #[test]
+    fn array_literals_are_allocated_once() {
+        let mut decoder = Decoder::new();
+        decoder.eval_text("test.sc", "let a = 1;").unwrap();
+        let before = decoder.memory_mgr.stats.clone();
+        decoder
+            .eval_text("test.sc", "let items = [1, 2, 3];")
+            .unwrap();
+        let after = &decoder.memory_mgr.stats;
+        assert_eq!(after.total_allocations - before.total_allocations, 1);
+        assert_eq!(after.live_blocks - before.live_blocks, 1);
+        assert_eq!(
+            after.used_bytes - before.used_bytes,
+            Value::Array(vec![json!(1), json!(2), json!(3)]).size()
+        );
+    }
+
+    #[test]
+    fn scripts_fail_beyond_the_heap_limit() {
+        let mut decoder = Decoder::new().heap_limit(256);
+        let source = format!("let text = \"{}\";", "x".repeat(300));
+        let error = decoder.eval_text("test.sc", &source).unwrap_err();
+        // 表示言語によらずメッセージの数値より前の部分で確かめる
+        let out_of_memory = msg!("runtime.out_of_memory", 0, 0, 0);
+        let prefix = out_of_memory.split('0').next().unwrap();
+        assert!(error
+            .diagnostics()
+            .iter()
+            .any(|diagnostic| diagnostic.message.starts_with(prefix)));
+
+        let mut decoder = Decoder::new();
+        let used = decoder.eval_text("test.sc", "@heap_used()").unwrap();
+        let blocks = decoder.eval_text("test.sc", "@heap_blocks()").unwrap();
+        assert!(used.as_u64().is_some());
+        assert!(blocks.as_u64().is_some());
+    }
+
+    #[test]
+    fn references_write_through_to_the_referent() {
+        let mut decoder = Decoder::new();
+        decoder
+            .eval_text(
+                "test.sc",
+                "fn inc(r) {\n    *r = *r + 1;\n}\nlet mut x = 10;\nlet r = &mut x;\n*r = 20;\ninc(&mut x);\nlet mut a = [1, 2, 3];\nlet ra = &mut a;\nra[1] = 42;",
+            )
+            .unwrap();
+        assert_eq!(decoder.eval_text("test.sc", "x").unwrap(), json!(21));
+        assert_eq!(
+            decoder.eval_text("test.sc", "a").unwrap(),
+            json!([1, 42, 3])
+        );
+        let value = decoder.eval_text("test.sc", "let s = &x;\n*s * 2").unwrap();
+        assert_eq!(value, json!(42));
+    }
+
+    #[test]
+    fn references_report_invalid_uses() {
+        let message = |source: &str| {
+            let error = Decoder::new().eval_text("test.sc", source).unwrap_err();
+            error.diagnostics()[0].message.clone()
+        };
+        assert_eq!(
+            message("let x = 1;\n*x"),
+            msg!("runtime.deref_non_reference", 1)
+        );
+        assert_eq!(
+            message("fn make() {\n    let y = 1;\n    return &y;\n}\nlet r = make();\n*r"),
+            msg!("runtime.dangling_reference", "y")
+        );
+    }
+
+    #[test]
+    fn interpolated_strings_format_each_value() {
+        let mut decoder = Decoder::new();
+        let value = decoder
+            .eval_text(
+                "test.sc",
+                "let name = \"tanu\";\nlet count = 2;\nlet items = [1, 2];\nf\"{name} has {count + 1} of {items}\" + \"${name}!\"",
+            )
+            .unwrap();
+        assert_eq!(value, json!("tanu has 3 of [1, 2]tanu!"));
+
+        // 不正な埋め込み式は文字列の中の位置を指す
+        let error = decoder
+            .eval_text("test.sc", "let s = f\"value: {count count}\";")
+            .unwrap_err();
+        let diagnostic = &error.diagnostics()[0];
+        assert_eq!(diagnostic.code.as_deref(), Some("E0006"));
+        assert_eq!(
+            (diagnostic.labels[0].line, diagnostic.labels[0].column),
+            (1, 25)
+        );
+    }
+
+    #[test]
+    fn chars_compare_and_convert_to_code_points() {
+        let mut decoder = Decoder::new();
+        decoder
+            .eval_text(
+                "test.sc",
+                "let c: char = 'a';\nlet cs: [char] = ['x', '\\n'];",
+            )
+            .unwrap();
+        assert_eq!(decoder.eval_text("test.sc", "@ord(c)").unwrap(), json!(97));
+        assert_eq!(
+            decoder.eval_text("test.sc", "@chr(26085)").unwrap(),
+            json!("日")
+        );
+        // 比較は条件の中でだけ書ける
+        decoder
+            .eval_text(
+                "test.sc",
+                "let mut order = 0;\nif c < 'b' {\n    order = 1;\n}\nif c == 'a' {\n    order = order + 2;\n}",
+            )
+            .unwrap();
+        assert_eq!(decoder.eval_text("test.sc", "order").unwrap(), json!(3));
+        assert_eq!(
+            decoder.eval_text("test.sc", "cs").unwrap(),
+            json!(["x", "\n"])
+        );
+    }
+
+    #[test]
+    fn runtime_errors_carry_the_script_backtrace() {
+        colored::control::set_override(false);
+        let mut decoder = Decoder::new();
+        let error = decoder
+            .eval_text(
+                "test.sc",
+                "fn inner(n) {\n    return n / 0;\n}\nfn outer(n) {\n    return inner(n + 1);\n}\nouter(1);",
+            )
+            .unwrap_err();
+        let frames: Vec<_> = error
+            .backtrace()
+            .iter()
+            .map(|frame| {
+                (
+                    frame.function.as_str(),
+                    frame.file.as_str(),
+                    frame.line,
+                    frame.column,
+                )
+            })
+            .collect();
+        assert_eq!(
+            frames,
+            [
+                ("inner", "test.sc", 2, 17),
+                ("outer", "test.sc", 5, 24),
+                ("<top-level>", "test.sc", 7, 9),
+            ]
+        );
+        // 診断のあとに呼び出し履歴が続く
+        let text = error.to_string();
+        let trace = text.find(&msg!("runtime.stack_backtrace")).unwrap();
+        assert!(text[..trace].contains(&error.report().rendered));
+        assert!(text[trace..].contains(&format!(
+            "   1: {}",
+            msg!("runtime.frame", "outer", "test.sc", 5, 24)
+        )));
+        // 失敗した呼び出しの履歴は次の評価に残らない
+        let error = decoder.eval_text("test.sc", "inner(1)").unwrap_err();
+        assert_eq!(error.backtrace().len(), 2);
+    }
+}
@@ -0,0 +1,733 @@
+use super::checker::{primary, secondary, site, variable_name, Site};
+use crate::error::{span_position, CompilerError, Diagnostic, ScriptError};
+use crate::lexer::tokenizer::{Lexer, Token};
+use crate::msg;
+use crate::parser::syntax::{Node, Parser};
+use crate::types::*;
+use indexmap::IndexMap;
+use std::collections::{HashMap, HashSet};
+
+// 検査の規則(// tanucc-allow(規則名) で無効にできる)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Rule {
+    Unused,            // 使われない変数・引数・関数
+    Shadowing,         // グローバル変数を隠す宣言
+    Unreachable,       // return/break/continue の後の文
+    UnusedMut,         // 書き換えられない let mut
+    SelfAssign,        // 自分自身への代入
+    ConstantCondition, // 常に同じ値になる条件
+}
+
+// 規則の表(名前,規則,診断コード)
+pub static RULES: &[(&str, Rule, &str)] = &[
+    ("unused", Rule::Unused, "W0001"),
+    ("shadowing", Rule::Shadowing, "W0002"),
+    ("unreachable", Rule::Unreachable, "W0003"),
+    ("unused_mut", Rule::UnusedMut, "W0004"),
+    ("self_assign", Rule::SelfAssign, "W0005"),
+    ("constant_condition", Rule::ConstantCondition, "W0006"),
+];
+
+// 知らない規則名を指定した場合の診断コード
+const UNKNOWN_RULE_CODE: &str = "W0007";
+
+impl Rule {
+    pub fn from_name(name: &str) -> Option<Self> {
+        RULES
+            .iter()
+            .find(|(rule_name, _, _)| *rule_name == name)
+            .map(|(_, rule, _)| *rule)
+    }
+    pub fn name(self) -> &'static str {
+        RULES
+            .iter()
+            .find(|(_, rule, _)| *rule == self)
+            .map_or("", |(name, _, _)| name)
+    }
+    pub fn code(self) -> &'static str {
+        RULES
+            .iter()
+            .find(|(_, rule, _)| *rule == self)
+            .map_or("", |(_, _, code)| code)
+    }
+}
+
+// 束縛の種類
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BindingKind {
+    Variable,
+    Parameter,
+    LoopVariable,
+}
+
+// 束縛の情報(実行時の used_context と同じく宣言位置と参照の有無を持つ)
+#[derive(Debug, Clone)]
+struct Binding {
+    kind: BindingKind,
+    is_mutable: bool, // mut 付きで宣言したかどうか
+    site: Site,       // 宣言位置
+    used: bool,       // 値が読まれたかどうか
+    mutated: bool,    // 書き換えられたかどうか
+}
+
+// スコープごとの束縛(宣言順)
+#[derive(Debug, Default)]
+struct Scope {
+    bindings: IndexMap<String, Binding>,
+}
+
+// 宣言した関数
+#[derive(Debug, Clone)]
+struct Function {
+    site: Site,
+    is_callback: bool, // コールバックは外から呼ばれるので未使用とはみなさない
+}
+
+// 後でまとめて検査する関数本体
+struct PendingFunction {
+    name: String,
+    args: Vec<(Box<Node>, String, bool)>,
+    body: Node,
+    is_callback: bool,
+}
+
+// tanucc-allow(...) で無効にした規則(行ごと、ファイル全体)
+#[derive(Debug, Default)]
+struct Allowances {
+    lines: HashMap<usize, HashSet<Rule>>,
+    file: HashSet<Rule>,
+}
+
+impl Allowances {
+    fn allows(&self, rule: Rule, line: usize) -> bool {
+        self.file.contains(&rule)
+            || self
+                .lines
+                .get(&line)
+                .map_or(false, |rules| rules.contains(&rule))
+    }
+}
+
+// スクリプトを実行せずに、使われない宣言や疑わしいコードを警告する
+pub struct Linter {
+    scopes: Vec<Scope>,                    // スコープのスタック(先頭がグローバル)
+    functions: IndexMap<String, Function>, // 宣言した関数
+    called: HashSet<String>,               // 自分以外から呼び出された(参照された)関数名
+    current_function: Option<String>,      // 検査中の関数名
+    pending: Vec<PendingFunction>,         // トップレベルの後で検査する関数本体
+    allowances: Allowances,
+    warnings: Vec<(usize, usize, Diagnostic)>, // 警告とその位置(行数,列数)
+}
+
+impl Linter {
+    fn new(allowances: Allowances) -> Self {
+        Linter {
+            scopes: vec![Scope::default()],
+            functions: IndexMap::new(),
+            called: HashSet::new(),
+            current_function: None,
+            pending: Vec::new(),
+            allowances,
+            warnings: Vec::new(),
+        }
+    }
+
+    // ファイルを字句解析・構文解析して検査し、警告があればまとめて1つのエラーとして返す
+    pub fn lint(file_name: &str, source: &str) -> Result<(), ScriptError> {
+        let tokens = Lexer::from_tokenize(file_name, source.to_string())?;
+        let node = Parser::from_parse(&tokens, file_name, source.to_string())?;
+        let (allowances, unknown_rules) = collect_allowances(&tokens);
+        let mut linter = Linter::new(allowances);
+        for (name, line, column) in unknown_rules {
+            linter.warnings.push((
+                line,
+                column,
+                Diagnostic::new("warning", &msg!("lint.unknown_rule", name))
+                    .code(UNKNOWN_RULE_CODE)
+                    .primary(line, column, 2, "")
+                    .help(&msg!("lint.unknown_rule.help", rule_names())),
+            ));
+        }
+
+        let statements: Vec<Node> = node.iter().cloned().collect();
+        linter.lint_statements(&statements);
+        // 関数本体はグローバル変数がすべて宣言された後(main-entryと同じ時点)で検査する
+        while !linter.pending.is_empty() {
+            let function = linter.pending.remove(0);
+            linter.lint_function(function);
+        }
+        linter.close_scope();
+        linter.report_unused_functions();
+
+        if linter.warnings.is_empty() {
+            return Ok(());
+        }
+        // ソース上の順に並べる
+        linter
+            .warnings
+            .sort_by_key(|(line, column, _)| (*line, *column));
+        let mut errors = CompilerError::new();
+        for (_, _, warning) in linter.warnings {
+            errors.push(warning);
+        }
+        Err(errors.into_error(file_name, source))
+    }
+
+    fn lint_statements(&mut self, statements: &[Node]) {
+        // 直前の return/break/continue(以降の文には到達しない)
+        let mut terminator: Option<(Site, &'static str)> = None;
+        let mut reported = false;
+        for statement in statements {
+            if matches!(
+                statement.value,
+                NodeValue::EndStatement
+                    | NodeValue::Null
+                    | NodeValue::SingleComment(..)
+                    | NodeValue::MultiComment(..)
+            ) {
+                continue;
+            }
+            // 同じブロックでは到達しない最初の文だけ指摘する
+            if let (Some((at, keyword)), false) = (terminator, reported) {
+                let here = site(statement);
+                let diagnostic = Diagnostic::new("warning", &msg!("lint.unreachable"));
+                let diagnostic = primary(diagnostic, here, &msg!("lint.unreachable.label"));
+                let diagnostic = secondary(
+                    diagnostic,
+                    at,
+                    here,
+                    &msg!("lint.unreachable.cause", keyword),
+                );
+                self.report(Rule::Unreachable, here, diagnostic);
+                reported = true;
+            }
+            self.lint_node(statement);
+            if terminator.is_none() {
+                terminator = match statement.value {
+                    NodeValue::ControlFlow(ControlFlow::Return(_)) => {
+                        Some((site(statement), "return"))
+                    }
+                    NodeValue::ControlFlow(ControlFlow::Break) => Some((site(statement), "break")),
+                    NodeValue::ControlFlow(ControlFlow::Continue) => {
+                        Some((site(statement), "continue"))
+                    }
+                    _ => None,
+                };
+            }
+        }
+    }
+
+    fn lint_node(&mut self, node: &Node) {
+        match &node.value {
+            NodeValue::Declaration(Declaration::Variable(var_name, _, value, _, is_mutable)) => {
+                self.lint_node(value);
+                if let Some(name) = variable_name(var_name) {
+                    self.declare(&name, BindingKind::Variable, *is_mutable, site(var_name));
+                }
+            }
+            NodeValue::Declaration(Declaration::Function(name, args, body, _, _))
+            | NodeValue::Declaration(Declaration::CallBackFunction(name, args, body, _, _)) => {
+                let is_callback = matches!(
+                    node.value,
+                    NodeValue::Declaration(Declaration::CallBackFunction(..))
+                );
+                self.functions.insert(
+                    name.clone(),
+                    Function {
+                        site: site(node),
+                        is_callback,
+                    },
+                );
+                self.pending.push(PendingFunction {
+                    name: name.clone(),
+                    args: args.clone(),
+                    body: (**body).clone(),
+                    is_callback,
+                });
+            }
+            // 構造体とその実装は検査しない
+            NodeValue::Declaration(Declaration::Struct(..))
+            | NodeValue::Declaration(Declaration::Impl(..)) => {}
+            NodeValue::Assign(target, value, index) => self.lint_assign(node, target, value, index),
+            NodeValue::Operator(Operator::Increment(target))
+            | NodeValue::Operator(Operator::Decrement(target)) => self.lint_write(target),
+            NodeValue::Operator(Operator::AddAssign(target, value))
+            | NodeValue::Operator(Operator::SubAssign(target, value))
+            | NodeValue::Operator(Operator::MulAssign(target, value))
+            | NodeValue::Operator(Operator::DivAssign(target, value))
+            | NodeValue::Operator(Operator::BitAndAssign(target, value))
+            | NodeValue::Operator(Operator::BitOrAssign(target, value))
+            | NodeValue::Operator(Operator::BitXorAssign(target, value))
+            | NodeValue::Operator(Operator::ShiftLeftAssign(target, value))
+            | NodeValue::Operator(Operator::ShiftRightAssign(target, value)) => {
+                self.lint_node(value);
+                self.lint_write(target);
+            }
+            NodeValue::Variable(_, name, is_mutable, is_reference) => {
+                self.read(name, *is_reference && *is_mutable);
+            }
+            NodeValue::Call(name, args, is_system) => {
+                if !is_system && self.current_function.as_deref() != Some(name.as_str()) {
+                    self.called.insert(name.clone());
+                }
+                for arg in args {
+                    self.lint_node(arg);
+                }
+            }
+            NodeValue::Block(block) => {
+                let statements: Vec<Node> = block.iter().flat_map(|b| b.iter()).cloned().collect();
+                self.scopes.push(Scope::default());
+                self.lint_statements(&statements);
+                self.close_scope();
+            }
+            NodeValue::ControlFlow(ControlFlow::For(variable, iterator, body)) => {
+                self.lint_node(iterator);
+                self.scopes.push(Scope::default());
+                if let Some(name) = variable_name(variable) {
+                    self.declare(&name, BindingKind::LoopVariable, false, site(variable));
+                }
+                self.lint_node(body);
+                self.close_scope();
+            }
+            NodeValue::ControlFlow(ControlFlow::If(condition, _))
+            | NodeValue::ControlFlow(ControlFlow::ElseIf(condition, _)) => {
+                self.lint_condition(condition, false);
+                for child in node.children() {
+                    self.lint_node(child);
+                }
+            }
+            NodeValue::ControlFlow(ControlFlow::While(condition, _)) => {
+                self.lint_condition(condition, true);
+                for child in node.children() {
+                    self.lint_node(child);
+                }
+            }
+            _ => {
+                for child in node.children() {
+                    self.lint_node(child);
+                }
+            }
+        }
+    }
+
+    // 関数本体は引数のスコープから検査する(呼び出し元のローカル変数は見えない)
+    fn lint_function(&mut self, function: PendingFunction) {
+        let outer_scopes = self.scopes.split_off(1);
+        let outer_function = self.current_function.replace(function.name);
+
+        self.scopes.push(Scope::default());
+        for (data_type, name, is_mutable) in &function.args {
+            self.declare(name, BindingKind::Parameter, *is_mutable, site(data_type));
+            // コールバックの引数は呼び出し側が決めるので使わなくてもよい
+            if function.is_callback {
+                if let Some(binding) = self.lookup_mut(name) {
+                    binding.used = true;
+                }
+            }
+        }
+        self.lint_node(&function.body);
+        self.close_scope();
+
+        self.scopes.truncate(1);
+        self.scopes.extend(outer_scopes);
+        self.current_function = outer_function;
+    }
+
+    fn lint_assign(&mut self, node: &Node, target: &Node, value: &Node, index: &Node) {
+        self.lint_node(index);
+        match &target.value {
+            NodeValue::Variable(_, name, _, _) => {
+                let is_self_assign = matches!(index.value, NodeValue::Null)
+                    && matches!(&value.value, NodeValue::Variable(_, v, _, false) if v == name);
+                if is_self_assign {
+                    let at = site(node);
+                    let diagnostic = Diagnostic::new("warning", &msg!("lint.self_assign", name));
+                    let diagnostic = primary(diagnostic, at, &msg!("lint.self_assign.label"));
+                    self.report(
+                        Rule::SelfAssign,
+                        at,
+                        diagnostic.help(&msg!("lint.self_assign.help")),
+                    );
+                }
+                self.lint_node(value);
+                self.lint_write(target);
+            }
+            // *参照 = 値 は参照を読んで参照先に書き込む
+            _ => {
+                self.lint_node(value);
+                self.lint_node(target);
+            }
+        }
+    }
+
+    // 変数への書き込み(代入,++,--,複合代入)
+    fn lint_write(&mut self, target: &Node) {
+        match variable_name(target) {
+            Some(name) => {
+                if let Some(binding) = self.lookup_mut(&name) {
+                    binding.mutated = true;
+                }
+            }
+            None => self.lint_node(target),
+        }
+    }
+
+    // 変数の読み込み(&mut での借用は書き換えとしても扱う)
+    fn read(&mut self, name: &str, mutates: bool) {
+        match self.lookup_mut(name) {
+            Some(binding) => {
+                binding.used = true;
+                binding.mutated |= mutates;
+            }
+            // 関数名を値として渡した場合(コールバックなど)も呼び出しとみなす
+            None => {
+                if self.current_function.as_deref() != Some(name) {
+                    self.called.insert(name.to_string());
+                }
+            }
+        }
+    }
+
+    // 条件式が定数なら警告する
+    fn lint_condition(&mut self, condition: &Node, is_loop: bool) {
+        let Some(value) = constant_bool(condition) else {
+            return;
+        };
+        let at = site(condition);
+        let diagnostic = Diagnostic::new("warning", &msg!("lint.constant_condition", value));
+        let diagnostic = primary(diagnostic, at, &msg!("lint.constant_condition.label"));
+        let diagnostic = if is_loop && value {
+            diagnostic.help(&msg!("lint.constant_condition.loop"))
+        } else {
+            diagnostic
+        };
+        self.report(Rule::ConstantCondition, at, diagnostic);
+    }
+
+    fn declare(&mut self, name: &str, kind: BindingKind, is_mutable: bool, at: Site) {
+        // 関数やブロックの中でグローバル変数と同じ名前を宣言すると、そのスコープでは隠れる
+        let is_local = self.scopes.len() > 1;
+        let declared_locally = self.scopes[1..]
+            .iter()
+            .any(|scope| scope.bindings.contains_key(name));
+        if is_local && !declared_locally && !name.starts_with('_') {
+            if let Some(global) = self.scopes[0].bindings.get(name).cloned() {
+                let diagnostic = Diagnostic::new("warning", &msg!("lint.shadowing", name));
+                let diagnostic = primary(diagnostic, at, &msg!("lint.shadowing.label", name));
+                let diagnostic = secondary(
+                    diagnostic,
+                    global.site,
+                    at,
+                    &msg!("lint.shadowing.global", name),
+                );
+                self.report(Rule::Shadowing, at, diagnostic);
+            }
+        }
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.bindings.insert(
+                name.to_string(),
+                Binding {
+                    kind,
+                    is_mutable,
+                    site: at,
+                    used: false,
+                    mutated: false,
+                },
+            );
+        }
+    }
+
+    fn lookup_mut(&mut self, name: &str) -> Option<&mut Binding> {
+        self.scopes
+            .iter_mut()
+            .rev()
+            .find_map(|scope| scope.bindings.get_mut(name))
+    }
+
+    // スコープを抜けるときに、使われなかった束縛と書き換えられなかった let mut を警告する
+    fn close_scope(&mut self) {
+        let Some(scope) = self.scopes.pop() else {
+            return;
+        };
+        for (name, binding) in scope.bindings {
+            // _ で始まる名前は意図して使わないもの
+            if name.starts_with('_') {
+                continue;
+            }
+            if !binding.used {
+                let message = match binding.kind {
+                    BindingKind::Parameter => msg!("lint.unused_parameter", name),
+                    BindingKind::Variable | BindingKind::LoopVariable => {
+                        msg!("lint.unused_variable", name)
+                    }
+                };
+                let diagnostic = Diagnostic::new("warning", &message);
+                let diagnostic = primary(diagnostic, binding.site, &msg!("lint.unused.label"));
+                self.report(
+                    Rule::Unused,
+                    binding.site,
+                    diagnostic.help(&msg!("lint.unused.help", name)),
+                );
+            } else if binding.is_mutable && !binding.mutated {
+                let diagnostic = Diagnostic::new("warning", &msg!("lint.unused_mut", name));
+                let diagnostic = primary(diagnostic, binding.site, &msg!("lint.unused_mut.label"));
+                self.report(
+                    Rule::UnusedMut,
+                    binding.site,
+                    diagnostic.help(&msg!("lint.unused_mut.help")),
+                );
+            }
+        }
+    }
+
+    // 一度も呼び出されなかった関数を警告する(エントリーポイントとコールバックは除く)
+    fn report_unused_functions(&mut self) {
+        let functions: Vec<(String, Function)> = self
+            .functions
+            .iter()
+            .map(|(name, function)| (name.clone(), function.clone()))
+            .collect();
+        for (name, function) in functions {
+            let is_entry = name == "main" || name == "Main";
+            if is_entry
+                || function.is_callback
+                || name.starts_with('_')
+                || self.called.contains(&name)
+            {
+                continue;
+            }
+            let diagnostic = Diagnostic::new("warning", &msg!("lint.unused_function", name));
+            let diagnostic = primary(diagnostic, function.site, &msg!("lint.unused.label"));
+            self.report(
+                Rule::Unused,
+                function.site,
+                diagnostic.help(&msg!("lint.unused.help", name)),
+            );
+        }
+    }
+
+    // 無効にされていなければ警告を1件記録する
+    // 位置はノードの終わりではなく範囲の始まりで判断する
+    fn report(&mut self, rule: Rule, at: Site, diagnostic: Diagnostic) {
+        let (line, column) = span_position(at.span)
+            .map(|(line, column, _)| (line, column))
+            .unwrap_or((at.line, at.column));
+        if self.allowances.allows(rule, line) {
+            return;
+        }
+        let diagnostic = diagnostic
+            .code(rule.code())
+            .note(&msg!("lint.allow_hint", rule.name()));
+        self.warnings.push((line, column, diagnostic));
+    }
+}
+
+// コメントの tanucc-allow(規則,...) と tanucc-allow-file(規則,...) を集める
+// 行末のコメントはその行に、単独の行のコメントは次のコードの行に効く
+fn collect_allowances(tokens: &[Token]) -> (Allowances, Vec<(String, usize, usize)>) {
+    let mut allowances = Allowances::default();
+    let mut unknown_rules = Vec::new();
+    let mut last_code_line = 0;
+    for (i, token) in tokens.iter().enumerate() {
+        let TokenType::SingleComment(text, (line, column)) = token.token_type() else {
+            if !matches!(token.token_type(), TokenType::MultiComment(..)) {
+                last_code_line = token.line();
+            }
+            continue;
+        };
+        let text = text.trim();
+        let (is_file, names) = match (
+            text.strip_prefix("tanucc-allow-file("),
+            text.strip_prefix("tanucc-allow("),
+        ) {
+            (Some(rest), _) => (true, rest),
+            (None, Some(rest)) => (false, rest),
+            (None, None) => continue,
+        };
+        let Some(names) = names.strip_suffix(')') else {
+            continue;
+        };
+        let mut rules = HashSet::new();
+        for name in names
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+        {
+            match (name, Rule::from_name(name)) {
+                ("all", _) => rules.extend(RULES.iter().map(|(_, rule, _)| *rule)),
+                (_, Some(rule)) => {
+                    rules.insert(rule);
+                }
+                (_, None) => unknown_rules.push((name.to_string(), line, column)),
+            }
+        }
+        if is_file {
+            allowances.file.extend(rules);
+            continue;
+        }
+        let target_line = if last_code_line == line {
+            line
+        } else {
+            tokens[i + 1..]
+                .iter()
+                .find(|next| {
+                    !matches!(
+                        next.token_type(),
+                        TokenType::SingleComment(..) | TokenType::MultiComment(..)
+                    )
+                })
+                .map_or(line, |next| next.line())
+        };
+        allowances
+            .lines
+            .entry(target_line)
+            .or_default()
+            .extend(rules);
+    }
+    (allowances, unknown_rules)
+}
+
+fn rule_names() -> String {
+    RULES
+        .iter()
+        .map(|(name, _, _)| *name)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+// リテラルだけからなる条件式の値
+fn constant_bool(node: &Node) -> Option<bool> {
+    match &node.value {
+        NodeValue::DataType(DataType::Bool(value)) => Some(*value),
+        NodeValue::Operator(Operator::And(a, b)) => Some(constant_bool(a)? && constant_bool(b)?),
+        NodeValue::Operator(Operator::Or(a, b)) => Some(constant_bool(a)? || constant_bool(b)?),
+        NodeValue::Operator(Operator::Eq(a, b)) => compare(a, b).map(|o| o.is_eq()),
+        NodeValue::Operator(Operator::Ne(a, b)) => compare(a, b).map(|o| o.is_ne()),
+        NodeValue::Operator(Operator::Lt(a, b)) => compare(a, b).map(|o| o.is_lt()),
+        NodeValue::Operator(Operator::Gt(a, b)) => compare(a, b).map(|o| o.is_gt()),
+        NodeValue::Operator(Operator::Le(a, b)) => compare(a, b).map(|o| o.is_le()),
+        NodeValue::Operator(Operator::Ge(a, b)) => compare(a, b).map(|o| o.is_ge()),
+        _ => None,
+    }
+}
+
+fn compare(a: &Node, b: &Node) -> Option<std::cmp::Ordering> {
+    constant_number(a)?.partial_cmp(&constant_number(b)?)
+}
+
+fn constant_number(node: &Node) -> Option<f64> {
+    fn number(data_type: &DataType) -> Option<f64> {
+        match data_type {
+            DataType::Int(v) => Some(*v as f64),
+            DataType::UInt(v) => Some(*v as f64),
+            DataType::Float(v) => Some(*v),
+            DataType::Typed(v, _) => number(v),
+            _ => None,
+        }
+    }
+    match &node.value {
+        NodeValue::DataType(data_type) => number(data_type),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 警告の診断コード(ソース上の順)
+    fn codes(source: &str) -> Vec<String> {
+        match Linter::lint("linter_test.sc", source) {
+            Ok(()) => Vec::new(),
+            Err(e) => e
+                .diagnostics()
+                .iter()
+                .filter_map(|diagnostic| diagnostic.code.clone())
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn reports_unused_bindings() {
+        assert_eq!(codes("fn main() {\n    let total = 1;\n}\n"), ["W0001"]);
+        assert_eq!(
+            codes("fn helper(n) {\n    return 1;\n}\nfn main() {\n    @println(helper(1));\n}\n"),
+            ["W0001"]
+        );
+        assert_eq!(codes("fn unused() {}\nfn main() {}\n"), ["W0001"]);
+        assert_eq!(
+            codes("fn main() {\n    let _total = 1;\n}\n"),
+            Vec::<String>::new()
+        );
+    }
+
+    #[test]
+    fn reports_shadowed_globals() {
+        assert_eq!(
+            codes("let count = 0;\n@println(count);\nfn main() {\n    let count = 1;\n    @println(count);\n}\n"),
+            ["W0002"]
+        );
+    }
+
+    #[test]
+    fn reports_unreachable_statements() {
+        assert_eq!(
+            codes("fn main() {\n    return 1;\n    @println(\"done\");\n}\n"),
+            ["W0003"]
+        );
+    }
+
+    #[test]
+    fn reports_needless_mut() {
+        assert_eq!(
+            codes("fn main() {\n    let mut a = 1;\n    @println(a);\n}\n"),
+            ["W0004"]
+        );
+        assert_eq!(
+            codes("fn main() {\n    let mut a = 1;\n    a += 1;\n    @println(a);\n}\n"),
+            Vec::<String>::new()
+        );
+    }
+
+    #[test]
+    fn reports_self_assignment() {
+        assert_eq!(
+            codes("fn main() {\n    let mut a = 1;\n    a = a;\n    @println(a);\n}\n"),
+            ["W0005"]
+        );
+    }
+
+    #[test]
+    fn reports_constant_conditions() {
+        assert_eq!(
+            codes("fn main() {\n    if true {\n        @println(1);\n    }\n}\n"),
+            ["W0006"]
+        );
+    }
+
+    #[test]
+    fn allow_comments_silence_rules() {
+        assert_eq!(
+            codes("fn main() {\n    // tanucc-allow(unused)\n    let total = 1;\n}\n"),
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            codes(
+                "fn main() {\n    let total = 1; // tanucc-allow(unused)\n    let other = 2;\n}\n"
+            ),
+            ["W0001"]
+        );
+        assert_eq!(
+            codes("// tanucc-allow-file(all)\nfn main() {\n    let mut a = 1;\n    a = a;\n}\n"),
+            Vec::<String>::new()
+        );
+    }
+
+    #[test]
+    fn reports_unknown_rules() {
+        assert_eq!(
+            codes("fn main() {\n    // tanucc-allow(unsued)\n    let total = 1;\n}\n"),
+            ["W0007", "W0001"]
+        );
+    }
+}
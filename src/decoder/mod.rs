@@ -1,2 +1,6 @@
 #[cfg(any(feature = "full", feature = "decoder"))]
+pub mod checker;
+#[cfg(any(feature = "full", feature = "decoder"))]
 pub mod interpreter;
+#[cfg(any(feature = "full", feature = "decoder"))]
+pub mod linter;
@@ -0,0 +1,170 @@
+use crate::error::ScriptError;
+use crate::msg;
+use anyhow::Result as R;
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8};
+use std::path::Path;
+
+// 文字コードの名前(shift_jis,utf-8,utf-16le など)から文字コードを得る
+pub fn lookup(label: &str) -> R<&'static Encoding, String> {
+    Encoding::for_label(label.trim().as_bytes()).ok_or_else(|| msg!("encoding.unknown", label))
+}
+
+// スクリプトを読み込んでUTF-8の文字列にする
+// 文字コードは BOM > ファイル先頭の @encoding "..." > 指定された既定値 > UTF-8 の順に決める
+pub fn read_source(path: impl AsRef<Path>, default: Option<&str>) -> R<String, ScriptError> {
+    let path = path.as_ref();
+    let bytes = std::fs::read(path).map_err(|e| ScriptError::io(path.display(), e))?;
+    let label = source_label(&bytes, default);
+    decode(&bytes, label.as_deref()).map_err(|e| ScriptError::io(path.display(), e))
+}
+
+// スクリプトの文字コードの名前(BOM > @encoding "..." > 既定値、どれもなければUTF-8なのでNone)
+// 整形したスクリプトを元の文字コードで書き戻すためにも使う
+pub fn source_label(bytes: &[u8], default: Option<&str>) -> Option<String> {
+    Encoding::for_bom(bytes)
+        .map(|(encoding, _)| encoding.name().to_string())
+        .or_else(|| find_pragma(bytes))
+        .or(default.map(str::to_string))
+}
+
+// バイト列をUTF-8の文字列に変換する(BOMがあれば指定より優先し、BOM自体は取り除く)
+pub fn decode(bytes: &[u8], label: Option<&str>) -> R<String, String> {
+    let encoding = match label {
+        Some(label) => lookup(label)?,
+        None => UTF_8,
+    };
+    let (text, used, had_errors) = encoding.decode(bytes);
+    if had_errors {
+        return Err(match label {
+            Some(_) => msg!("encoding.invalid_bytes", used.name()),
+            None => msg!("encoding.invalid_bytes.hint", used.name()),
+        });
+    }
+    Ok(text.into_owned())
+}
+
+// UTF-8の文字列を指定の文字コードのバイト列に変換する(UTF-16はBOM付きで書き出す)
+pub fn encode(text: &str, label: Option<&str>) -> R<Vec<u8>, String> {
+    let encoding = match label {
+        Some(label) => lookup(label)?,
+        None => return Ok(text.as_bytes().to_vec()),
+    };
+    // encoding_rsはUTF-16への変換をしないので自前で行う
+    if encoding == UTF_16LE || encoding == UTF_16BE {
+        let little = encoding == UTF_16LE;
+        return Ok(std::iter::once(0xFEFF)
+            .chain(text.encode_utf16())
+            .flat_map(|unit| {
+                if little {
+                    unit.to_le_bytes()
+                } else {
+                    unit.to_be_bytes()
+                }
+            })
+            .collect());
+    }
+    let (bytes, used, had_errors) = encoding.encode(text);
+    if had_errors {
+        return Err(msg!("encoding.unrepresentable", used.name()));
+    }
+    Ok(bytes.into_owned())
+}
+
+// ファイル先頭(空白とコメントを除いた最初のコード)の @encoding "..." を探す
+// 文字コードが決まる前なので、ASCII互換のバイト列として読む
+fn find_pragma(bytes: &[u8]) -> Option<String> {
+    let mut rest = bytes;
+    loop {
+        rest = rest.trim_ascii_start();
+        if let Some(comment) = rest.strip_prefix(b"//") {
+            let end = comment
+                .iter()
+                .position(|&b| b == b'\n')
+                .map_or(comment.len(), |i| i + 1);
+            rest = &comment[end..];
+        } else if let Some(comment) = rest.strip_prefix(b"/*") {
+            let end = comment.windows(2).position(|pair| pair == b"*/")?;
+            rest = &comment[end + 2..];
+        } else {
+            break;
+        }
+    }
+    let rest = rest.strip_prefix(b"@encoding")?.trim_ascii_start();
+    let rest = rest.strip_prefix(b"\"")?;
+    let end = rest.iter().position(|&b| b == b'"')?;
+    String::from_utf8(rest[..end].to_vec()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chooses_bom_then_pragma_then_default() {
+        let pragma = b"@encoding \"shift_jis\"\nlet a = 1;";
+        let mut utf16 = vec![0xFF, 0xFE];
+        utf16.extend_from_slice(b"@\0");
+        assert_eq!(
+            source_label(&utf16, Some("euc-jp")).as_deref(),
+            Some("UTF-16LE")
+        );
+        let mut utf8 = b"\xEF\xBB\xBF".to_vec();
+        utf8.extend_from_slice(pragma);
+        assert_eq!(
+            source_label(&utf8, Some("euc-jp")).as_deref(),
+            Some("UTF-8")
+        );
+        assert_eq!(
+            source_label(pragma, Some("euc-jp")).as_deref(),
+            Some("shift_jis")
+        );
+        assert_eq!(
+            source_label(b"let a = 1;", Some("euc-jp")).as_deref(),
+            Some("euc-jp")
+        );
+        assert_eq!(source_label(b"let a = 1;", None), None);
+    }
+
+    #[test]
+    fn finds_the_pragma_only_before_other_code() {
+        assert_eq!(
+            find_pragma(b"// \xE5\x85\x88\xE9\xA0\xAD\n/* a\n   b */\r\n  @encoding  \"sjis\"\r\n")
+                .as_deref(),
+            Some("sjis")
+        );
+        assert_eq!(find_pragma(b"let a = 1;\n@encoding \"sjis\"\n"), None);
+        assert_eq!(find_pragma(b"/* unclosed\n@encoding \"sjis\"\n"), None);
+        assert_eq!(find_pragma(b"@encoding sjis\n"), None);
+    }
+
+    #[test]
+    fn decodes_and_encodes_in_the_chosen_encoding() {
+        let text = "let 名前 = \"たぬき\";";
+        let sjis = encode(text, Some("shift_jis")).unwrap();
+        assert_ne!(sjis, text.as_bytes());
+        assert_eq!(decode(&sjis, Some("shift_jis")).unwrap(), text);
+        // UTF-16 は BOM 付きで書き出し、読むときは BOM を取り除く
+        let utf16 = encode(text, Some("utf-16le")).unwrap();
+        assert_eq!(&utf16[..2], [0xFF, 0xFE]);
+        let label = source_label(&utf16, None);
+        assert_eq!(decode(&utf16, label.as_deref()).unwrap(), text);
+        assert_eq!(encode(text, None).unwrap(), text.as_bytes());
+    }
+
+    #[test]
+    fn reports_undecodable_sources() {
+        let sjis = encode("たぬき", Some("shift_jis")).unwrap();
+        assert_eq!(
+            decode(&sjis, None).unwrap_err(),
+            msg!("encoding.invalid_bytes.hint", "UTF-8")
+        );
+        assert_eq!(
+            lookup("no-such").unwrap_err(),
+            msg!("encoding.unknown", "no-such")
+        );
+        assert_eq!(
+            encode("😀", Some("shift_jis")).unwrap_err(),
+            msg!("encoding.unrepresentable", "Shift_JIS")
+        );
+    }
+}
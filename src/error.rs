@@ -1,24 +1,140 @@
-use anyhow::{Context, Result};
+use crate::msg;
+use crate::source_map::{with_source_map, Span};
 use colored::*;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
 use std::fmt;
-use thiserror::Error;
-use unicode_segmentation::UnicodeSegmentation;
-use unicode_width::UnicodeWidthStr;
+use unicode_width::UnicodeWidthChar;
+
+const TAB_WIDTH: usize = 4; // ソース表示でのタブの幅
 
 pub struct CompilerError {
     messages: Vec<ErrorMessage>,
 }
 
-struct ErrorMessage {
-    level: String,
-    lines: Vec<(usize, usize)>,
-    message: String,
-    children: Vec<ChildMessage>,
+// 構造化した診断(表示用の文字列にする前の情報)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ErrorMessage {
+    pub level: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+    pub message: String,
+    pub file: String, // 表示したときのファイル名(組み立て中は空)
+    pub labels: Vec<Label>,
+    pub children: Vec<ChildMessage>,
+}
+
+// ソース上で指摘する範囲(行・列は1始まり、幅は文字数)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Label {
+    pub line: usize,
+    pub column: usize,
+    pub width: usize,
+    #[serde(rename = "label")]
+    pub message: String,
+    pub primary: bool, // 主な指摘(^)か、補足の指摘(-)か
 }
 
-struct ChildMessage {
-    level: String,
-    message: String,
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChildMessage {
+    pub level: String,
+    pub message: String,
+}
+
+// 1件の診断を組み立てる
+pub struct Diagnostic {
+    message: ErrorMessage,
+}
+
+impl Diagnostic {
+    pub fn new(level: &str, message: &str) -> Self {
+        Diagnostic {
+            message: ErrorMessage {
+                level: level.to_string(),
+                code: None,
+                message: message.to_string(),
+                file: String::new(),
+                labels: Vec::new(),
+                children: Vec::new(),
+            },
+        }
+    }
+    // 診断コード(--explainで説明を表示できる)
+    pub fn code(mut self, code: &str) -> Self {
+        self.message.code = Some(code.to_string());
+        self
+    }
+    // 主な指摘の位置と幅
+    pub fn primary(self, line: usize, column: usize, width: usize, label: &str) -> Self {
+        self.label(line, column, width, label, true)
+    }
+    // 補足の指摘の位置と幅
+    pub fn secondary(self, line: usize, column: usize, width: usize, label: &str) -> Self {
+        self.label(line, column, width, label, false)
+    }
+    // ソースマップ上の範囲を主な指摘にする(範囲がなければ行・列の1文字を指摘する)
+    pub fn primary_span(self, span: Span, line: usize, column: usize, label: &str) -> Self {
+        let (line, column, width) = span_position(span).unwrap_or((line, column, 1));
+        self.primary(line, column, width, label)
+    }
+    // ソースマップ上の範囲を補足の指摘にする
+    pub fn secondary_span(self, span: Span, line: usize, column: usize, label: &str) -> Self {
+        let (line, column, width) = span_position(span).unwrap_or((line, column, 1));
+        self.secondary(line, column, width, label)
+    }
+    pub fn note(self, message: &str) -> Self {
+        self.child("note", message)
+    }
+    pub fn help(self, message: &str) -> Self {
+        self.child("help", message)
+    }
+    // 単独のエラーにする
+    pub fn into_error(self, file: &str, source_code: &str) -> ScriptError {
+        let mut error = CompilerError::new();
+        error.push(self);
+        error.into_error(file, source_code)
+    }
+
+    fn label(
+        mut self,
+        line: usize,
+        column: usize,
+        width: usize,
+        label: &str,
+        primary: bool,
+    ) -> Self {
+        self.message.labels.push(Label {
+            line,
+            column,
+            width,
+            message: label.to_string(),
+            primary,
+        });
+        self
+    }
+    fn child(mut self, level: &str, message: &str) -> Self {
+        self.message.children.push(ChildMessage {
+            level: level.to_string(),
+            message: message.to_string(),
+        });
+        self
+    }
+}
+
+// 範囲の開始位置(行,列)と幅(複数行にまたがる場合は開始行の末尾まで)
+pub(crate) fn span_position(span: Span) -> Option<(usize, usize, usize)> {
+    if span.is_dummy() {
+        return None;
+    }
+    let file = with_source_map(|map| map.get(span.file_id))?;
+    let start = file.line_column(span.start_byte);
+    let end = file.line_column(span.end_byte);
+    let width = if end.line == start.line {
+        end.column.saturating_sub(start.column)
+    } else {
+        usize::MAX
+    };
+    Some((start.line, start.column, width))
 }
 
 impl CompilerError {
@@ -28,22 +144,21 @@ impl CompilerError {
         }
     }
 
+    pub fn push(&mut self, diagnostic: Diagnostic) {
+        self.messages.push(diagnostic.message);
+    }
+
     pub fn add_group_message(&mut self, level: &str, lines: Vec<(usize, usize)>, message: &str) {
-        self.messages.push(ErrorMessage {
-            level: level.to_string(),
-            lines,
-            message: message.to_string(),
-            children: Vec::new(),
-        });
+        let labels = lines
+            .iter()
+            .fold(Diagnostic::new(level, message), |d, &(line, column)| {
+                d.primary(line, column, 1, "")
+            });
+        self.push(labels);
     }
 
     pub fn add_message(&mut self, level: &str, line: usize, column: usize, message: &str) {
-        self.messages.push(ErrorMessage {
-            level: level.to_string(),
-            lines: vec![(line, column)],
-            message: message.to_string(),
-            children: Vec::new(),
-        });
+        self.push(Diagnostic::new(level, message).primary(line, column, 1, ""));
     }
 
     pub fn add_child_message(&mut self, index: usize, level: &str, message: &str) {
@@ -55,52 +170,535 @@ impl CompilerError {
         }
     }
 
-    pub fn format_error_string(&self, file: &str, source_code: &str) -> String {
-        let mut result = String::new();
-        for msg in &self.messages {
-            let color = match msg.level.as_str() {
-                "warning" => "warning".yellow().bold(),
-                "error" => "error".red().bold(),
-                "note" => "note".blue().bold(),
-                _ => "info".normal(),
-            };
+    // 最後に追加した診断に診断コードを付ける
+    pub fn set_code(&mut self, code: &str) {
+        if let Some(msg) = self.messages.last_mut() {
+            msg.code = Some(code.to_string());
+        }
+    }
+
+    // 最後に追加した診断の指摘の幅(文字数)を設定する
+    pub fn set_width(&mut self, width: usize) {
+        if let Some(label) = self
+            .messages
+            .last_mut()
+            .and_then(|msg| msg.labels.first_mut())
+        {
+            label.width = width;
+        }
+    }
+
+    // 診断をエラーにする(種類は最初の診断コードで決まり、コードがなければ実行時エラー、Wで始まればlintの警告)
+    pub fn into_error(self, file: &str, source_code: &str) -> ScriptError {
+        let report = self.report(file, source_code);
+        let code = self.messages.first().and_then(|msg| msg.code.as_deref());
+        match code.map(|code| code.get(..3).unwrap_or_default()) {
+            Some("E00") => ScriptError::Lex(report),
+            Some("E01") => ScriptError::Parse(report),
+            Some("E02") => ScriptError::Type(report),
+            Some("W00") => ScriptError::Lint(report),
+            _ => ScriptError::Runtime(report),
+        }
+    }
+
+    fn report(&self, file: &str, source_code: &str) -> Report {
+        let source_lines: Vec<&str> = source_code.lines().collect();
+        let diagnostics: Vec<ErrorMessage> = self
+            .messages
+            .iter()
+            .map(|msg| resolve(msg, file, &source_lines))
+            .collect();
+        Report {
+            rendered: diagnostics
+                .iter()
+                .map(|msg| render(msg, &source_lines))
+                .collect(),
+            diagnostics,
+            backtrace: Vec::new(),
+        }
+    }
+}
+
+// スクリプトの呼び出し履歴の1段(関数名と、その関数を呼び出した位置)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Frame {
+    pub function: String,
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+// エラーの中身(表示用の文字列、構造化した診断、スクリプトの呼び出し履歴)
+#[derive(Debug, Clone, Default)]
+pub struct Report {
+    pub rendered: String,
+    pub diagnostics: Vec<ErrorMessage>,
+    pub backtrace: Vec<Frame>,
+}
+
+// 診断のあとに呼び出し履歴を表示する
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.rendered)?;
+        if !self.backtrace.is_empty() {
+            writeln!(f, "{}", msg!("runtime.stack_backtrace").bold())?;
+            for (i, frame) in self.backtrace.iter().enumerate() {
+                writeln!(f, "{:>4}: {}", i, frame)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            msg!(
+                "runtime.frame",
+                self.function,
+                self.file,
+                self.line,
+                self.column
+            )
+        )
+    }
+}
+
+// 字句解析から実行までのエラー
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum ScriptError {
+    #[error("{0}")]
+    Lex(Report),
+    #[error("{0}")]
+    Parse(Report),
+    #[error("{0}")]
+    Type(Report),
+    #[error("{0}")]
+    Runtime(Report),
+    #[error("{0}")]
+    Io(Report),
+    #[error("{0}")]
+    System(Report), // 外部コマンドなどOSの機能の呼び出しの失敗
+    #[error("{0}")]
+    Lint(Report), // lint の警告
+}
+
+impl ScriptError {
+    // ソースの位置を持たない入出力のエラー
+    pub fn io(path: impl fmt::Display, message: impl fmt::Display) -> Self {
+        let path = path.to_string();
+        let message = format!("{}: {}", path, message);
+        ScriptError::Io(Report {
+            rendered: message.clone(),
+            diagnostics: vec![plain_diagnostic(&message, &path)],
+            backtrace: Vec::new(),
+        })
+    }
+
+    // エラーの種類の名前
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ScriptError::Lex(_) => "lex",
+            ScriptError::Parse(_) => "parse",
+            ScriptError::Type(_) => "type",
+            ScriptError::Runtime(_) => "runtime",
+            ScriptError::Io(_) => "io",
+            ScriptError::System(_) => "system",
+            ScriptError::Lint(_) => "lint",
+        }
+    }
+
+    pub fn report(&self) -> &Report {
+        match self {
+            ScriptError::Lex(report)
+            | ScriptError::Parse(report)
+            | ScriptError::Type(report)
+            | ScriptError::Runtime(report)
+            | ScriptError::Io(report)
+            | ScriptError::System(report)
+            | ScriptError::Lint(report) => report,
+        }
+    }
+
+    fn report_mut(&mut self) -> &mut Report {
+        match self {
+            ScriptError::Lex(report)
+            | ScriptError::Parse(report)
+            | ScriptError::Type(report)
+            | ScriptError::Runtime(report)
+            | ScriptError::Io(report)
+            | ScriptError::System(report)
+            | ScriptError::Lint(report) => report,
+        }
+    }
+
+    // 構造化した診断
+    pub fn diagnostics(&self) -> &[ErrorMessage] {
+        &self.report().diagnostics
+    }
+
+    // 最初の診断の主な指摘
+    pub fn primary_label(&self) -> Option<&Label> {
+        let diagnostic = self.diagnostics().first()?;
+        diagnostic
+            .labels
+            .iter()
+            .find(|label| label.primary)
+            .or(diagnostic.labels.first())
+    }
+
+    pub fn backtrace(&self) -> &[Frame] {
+        &self.report().backtrace
+    }
+
+    // 呼び出し履歴を設定する(すでに設定されていれば、より深い位置の履歴を残す)
+    pub fn with_backtrace(mut self, backtrace: Vec<Frame>) -> Self {
+        let report = self.report_mut();
+        if report.backtrace.is_empty() {
+            report.backtrace = backtrace;
+        }
+        self
+    }
+
+    // 実行時エラーを入出力の失敗として扱う(位置の情報はそのまま残る)
+    pub fn into_io(self) -> Self {
+        match self {
+            ScriptError::Runtime(report) => ScriptError::Io(report),
+            other => other,
+        }
+    }
+
+    // 実行時エラーを外部コマンドなどの呼び出しの失敗として扱う
+    pub fn into_system(self) -> Self {
+        match self {
+            ScriptError::Runtime(report) => ScriptError::System(report),
+            other => other,
+        }
+    }
+
+    // 複数のエラーを最初のエラーの種類にまとめる
+    pub fn merge(errors: Vec<ScriptError>) -> Option<ScriptError> {
+        let mut errors = errors.into_iter();
+        let mut first = errors.next()?;
+        for error in errors {
+            let report = first.report_mut();
+            report.rendered.push_str(&error.report().rendered);
+            report
+                .diagnostics
+                .extend(error.diagnostics().iter().cloned());
+        }
+        Some(first)
+    }
+}
+
+// ファイル名を記録し、指摘の位置と幅をソースの行に収める
+fn resolve(msg: &ErrorMessage, file: &str, source_lines: &[&str]) -> ErrorMessage {
+    let labels = msg
+        .labels
+        .iter()
+        .map(|label| clamp_to_source(label, source_lines))
+        .collect();
+    ErrorMessage {
+        file: file.to_string(),
+        labels,
+        ..msg.clone()
+    }
+}
+
+// 診断を表示用の文字列にする
+fn render(msg: &ErrorMessage, source_lines: &[&str]) -> String {
+    let mut result = String::new();
+    let level = match msg.code {
+        Some(ref code) => format!("{}[{}]", msg.level, code),
+        None => msg.level.clone(),
+    };
+    let color = match msg.level.as_str() {
+        "warning" => level.yellow().bold(),
+        "error" => level.red().bold(),
+        "note" => level.blue().bold(),
+        "help" => level.green().bold(),
+        _ => level.normal(),
+    };
+    result.push_str(&format!("{}: {}\n", color, msg.message.bold()));
+
+    // 位置の表示は主な指摘(なければ最初の指摘)を使う
+    let gutter = " ".repeat(gutter_width(&msg.labels));
+    if let Some(first) = msg.labels.iter().find(|l| l.primary).or(msg.labels.first()) {
+        result.push_str(&format!(
+            "{}{} {}:{}:{}\n",
+            gutter,
+            "-->".blue().bold(),
+            msg.file,
+            first.line,
+            first.column
+        ));
+    }
+    let bar = "|".blue().bold();
+    let mut labels: Vec<&Label> = msg
+        .labels
+        .iter()
+        .filter(|l| l.line >= 1 && l.line <= source_lines.len())
+        .collect();
+    labels.sort_by_key(|l| (l.line, !l.primary, l.column));
+    if !labels.is_empty() {
+        result.push_str(&format!("{} {}\n", gutter, bar));
+    }
+    let mut previous_line = 0;
+    for label in labels {
+        let source_line = source_lines[label.line - 1];
+        if label.line != previous_line {
             result.push_str(&format!(
-                "{}: {}\n  {} {}:{}:{}\n",
-                color,
-                msg.message,
-                "-->".blue().bold(),
-                file,
-                msg.lines[0].0,
-                msg.lines[0].1
+                "{} {} {}\n",
+                format!("{:>width$}", label.line, width = gutter.len())
+                    .blue()
+                    .bold(),
+                bar,
+                source_line.replace('\t', &" ".repeat(TAB_WIDTH))
             ));
-            for &(line, column) in &msg.lines {
-                if let Some(source_line) = source_code.lines().nth(line - 1) {
-                    result.push_str(&format!(
-                        "{} {}   {}  \n",
-                        line.to_string().blue().bold(),
-                        "|".blue().bold(),
-                        source_line
-                    ));
-                    result.push_str(&format!(
-                        "   {} {}  {}  \n",
-                        "|".blue().bold(),
-                        " ".repeat(column - 1),
-                        "^".repeat(1).red().bold()
-                    ));
-                }
+            previous_line = label.line;
+        }
+        let (offset, width) = underline_range(source_line, label);
+        let marker = if label.primary { "^" } else { "-" }.repeat(width);
+        let marker = format!("{} {}", marker, label.message);
+        let marker = if label.primary {
+            marker.trim_end().red().bold()
+        } else {
+            marker.trim_end().blue().bold()
+        };
+        result.push_str(&format!(
+            "{} {} {}{}\n",
+            gutter,
+            bar,
+            " ".repeat(offset),
+            marker
+        ));
+    }
+    for child in &msg.children {
+        let child_color = match child.level.as_str() {
+            "note" => "note".blue().bold(),
+            "help" => "help".green().bold(),
+            _ => "info".normal(),
+        };
+        result.push_str(&format!(
+            "{} {} {}: {}\n",
+            gutter,
+            "=".blue().bold(),
+            child_color,
+            child.message
+        ));
+    }
+    result
+}
+
+// 位置を持たない(ソースを指せない)エラーを診断にする
+pub fn plain_diagnostic(message: &str, file: &str) -> ErrorMessage {
+    let mut diagnostic = Diagnostic::new("error", message.trim_end()).message;
+    diagnostic.file = file.to_string();
+    diagnostic
+}
+
+// JSONで出力する診断(実行時エラーにはスクリプトの呼び出し履歴を付ける)
+#[derive(Serialize)]
+struct JsonDiagnostic<'a> {
+    #[serde(flatten)]
+    diagnostic: &'a ErrorMessage,
+    #[serde(skip_serializing_if = "<[Frame]>::is_empty")]
+    backtrace: &'a [Frame],
+}
+
+// エラーの各診断と、そのエラーの呼び出し履歴
+fn diagnostics_with_backtrace(
+    errors: &[ScriptError],
+) -> impl Iterator<Item = (&ErrorMessage, &[Frame])> {
+    errors.iter().flat_map(|error| {
+        error
+            .diagnostics()
+            .iter()
+            .map(move |diagnostic| (diagnostic, error.backtrace()))
+    })
+}
+
+// 診断を1件1行のJSONにする
+pub fn to_json_lines(errors: &[ScriptError]) -> String {
+    diagnostics_with_backtrace(errors)
+        .map(|(diagnostic, backtrace)| {
+            serde_json::to_string(&JsonDiagnostic {
+                diagnostic,
+                backtrace,
+            })
+            .unwrap_or_default()
+                + "\n"
+        })
+        .collect()
+}
+
+// 診断をSARIF 2.1.0のログにする(列は文字数で数え、呼び出し履歴は stacks にする)
+pub fn to_sarif(errors: &[ScriptError]) -> Value {
+    let mut codes: Vec<&str> = diagnostics_with_backtrace(errors)
+        .filter_map(|(d, _)| d.code.as_deref())
+        .collect();
+    codes.sort();
+    codes.dedup();
+    let rules: Vec<Value> = codes
+        .iter()
+        .map(|code| match explain(code) {
+            Some(entry) => json!({
+                "id": entry.code,
+                "shortDescription": { "text": entry.summary },
+                "fullDescription": { "text": entry.explanation },
+            }),
+            None => json!({ "id": code }),
+        })
+        .collect();
+    let location = |file: &str, label: &Label| {
+        json!({
+            "physicalLocation": {
+                "artifactLocation": { "uri": file },
+                "region": {
+                    "startLine": label.line,
+                    "startColumn": label.column,
+                    "endColumn": label.column + label.width,
+                },
+            },
+            "message": { "text": label.message },
+        })
+    };
+    let frame = |frame: &Frame| {
+        json!({
+            "location": {
+                "physicalLocation": {
+                    "artifactLocation": { "uri": frame.file },
+                    "region": { "startLine": frame.line, "startColumn": frame.column },
+                },
+                "logicalLocations": [{ "name": frame.function, "kind": "function" }],
+            },
+        })
+    };
+    let results: Vec<Value> = diagnostics_with_backtrace(errors)
+        .map(|(d, backtrace)| {
+            let text = std::iter::once(d.message.clone())
+                .chain(d.children.iter().map(|c| format!("{}: {}", c.level, c.message)))
+                .collect::<Vec<_>>()
+                .join("\n");
+            let mut result = json!({
+                "level": match d.level.as_str() {
+                    "error" => "error",
+                    "warning" => "warning",
+                    _ => "note",
+                },
+                "message": { "text": text },
+                "locations": d.labels.iter().filter(|l| l.primary).map(|l| location(&d.file, l)).collect::<Vec<_>>(),
+                "relatedLocations": d.labels.iter().filter(|l| !l.primary).map(|l| location(&d.file, l)).collect::<Vec<_>>(),
+            });
+            if let Some(code) = &d.code {
+                result["ruleId"] = json!(code);
             }
-            for child in &msg.children {
-                let child_color = match child.level.as_str() {
-                    "note" => "note".blue().bold(),
-                    _ => "info".normal(),
-                };
-                result.push_str(&format!("  = {}: {}\n", child_color, child.message));
+            if !backtrace.is_empty() {
+                result["stacks"] = json!([{
+                    "message": { "text": msg!("runtime.stack_backtrace") },
+                    "frames": backtrace.iter().map(frame).collect::<Vec<_>>(),
+                }]);
             }
+            result
+        })
+        .collect();
+    json!({
+        "version": "2.1.0",
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": env!("CARGO_PKG_NAME"),
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "rules": rules,
+                },
+            },
+            "columnKind": "unicodeCodePoints",
+            "results": results,
+        }],
+    })
+}
+
+// 指摘をソースの行に収める
+// ファイル末尾(最終行の改行の後)は最終行の末尾に、列0は1にし、幅は行末までにする
+fn clamp_to_source(label: &Label, source_lines: &[&str]) -> Label {
+    let (line, column) = match source_lines.last() {
+        Some(last) if label.line == source_lines.len() + 1 && label.column <= 1 => {
+            (source_lines.len(), last.chars().count() + 1)
         }
-        result
+        _ => (label.line, label.column.max(1)),
+    };
+    let line_end = line
+        .checked_sub(1)
+        .and_then(|i| source_lines.get(i))
+        .map_or(column, |text| text.chars().count() + 1);
+    Label {
+        line,
+        column,
+        width: label.width.min(line_end.saturating_sub(column)).max(1),
+        message: label.message.clone(),
+        primary: label.primary,
     }
 }
 
+// 行番号の表示に必要な幅
+fn gutter_width(labels: &[Label]) -> usize {
+    labels
+        .iter()
+        .map(|l| l.line.to_string().len())
+        .max()
+        .unwrap_or(1)
+}
+
+// 文字の表示上の幅(全角文字は2桁、タブは表示時に置き換える空白の数)
+fn display_width(c: char) -> usize {
+    match c {
+        '\t' => TAB_WIDTH,
+        _ => c.width().unwrap_or(0),
+    }
+}
+
+// 指摘する範囲の表示上の開始位置と幅(列と幅は文字数で数え、列0は1として扱う)
+fn underline_range(line: &str, label: &Label) -> (usize, usize) {
+    let start = label.column.max(1) - 1;
+    let offset: usize = line.chars().take(start).map(display_width).sum();
+    let width: usize = line
+        .chars()
+        .skip(start)
+        .take(label.width.max(1))
+        .map(display_width)
+        .sum();
+    (offset, width.max(1))
+}
+
+// 診断コードとその説明(文はメッセージカタログの explain.<コード>.summary / explanation)
+pub struct ErrorCode {
+    pub code: &'static str,
+    pub summary: String,
+    pub explanation: String,
+}
+
+pub static ERROR_CODES: &[&str] = &[
+    "E0001", "E0002", "E0003", "E0004", "E0005", "E0006", "E0101", "E0102", "E0103", "E0104",
+    "E0105", "E0201", "E0202", "E0203", "E0204", "E0205", "E0301", "E0302", "E0303", "E0304",
+    "E0305", "E0306", "E0307", "E0308", "W0001", "W0002", "W0003", "W0004", "W0005", "W0006",
+    "W0007",
+];
+
+// 診断コードの説明を探す(大文字小文字は区別しない)
+pub fn explain(code: &str) -> Option<ErrorCode> {
+    let code = ERROR_CODES
+        .iter()
+        .find(|entry| entry.eq_ignore_ascii_case(code.trim()))?;
+    Some(ErrorCode {
+        code,
+        summary: msg!(&format!("explain.{}.summary", code)),
+        explanation: msg!(&format!("explain.{}.explanation", code)),
+    })
+}
+
 #[macro_export]
 macro_rules! compile_error_with_children {
     ($level:expr, $file_name:expr, $src:expr, $line:expr, $column:expr, $message:expr, $($child_level:expr, $child_message:expr),*) => {
@@ -110,29 +708,316 @@ macro_rules! compile_error_with_children {
             $(
                 compiler_error.add_child_message(0, $child_level, $child_message);
             )*
-            compiler_error.format_error_string($file_name, $src)
+            compiler_error.into_error($file_name, $src)
         }
     };
 }
 
 #[macro_export]
 macro_rules! compile_error {
+    (code = $code:expr, width = $width:expr, $level:expr, $line:expr, $column:expr, $file_name:expr, $src:expr, $($arg:tt)*) => {
+        {
+            let mut error = crate::error::CompilerError::new();
+            error.add_message($level, $line, $column, &format!($($arg)*));
+            error.set_code($code);
+            error.set_width($width);
+            error.into_error($file_name, $src)
+        }
+    };
+    (code = $code:expr, $level:expr, $line:expr, $column:expr, $file_name:expr, $src:expr, $($arg:tt)*) => {
+        {
+            let mut error = crate::error::CompilerError::new();
+            error.add_message($level, $line, $column, &format!($($arg)*));
+            error.set_code($code);
+            error.into_error($file_name, $src)
+        }
+    };
     ($level:expr, $line:expr, $column:expr, $file_name:expr, $src:expr, $($arg:tt)*) => {
         {
             let mut error = crate::error::CompilerError::new();
             error.add_message($level, $line, $column, &format!($($arg)*));
-            error.format_error_string($file_name, $src)
+            error.into_error($file_name, $src)
         }
     };
 }
 
 #[macro_export]
 macro_rules! compile_group_error {
+    (code = $code:expr, $level:expr, $file_name:expr, $src:expr, $message:expr, $($line:expr, $column:expr),*) => {
+        {
+            let mut compiler_error = crate::error::CompilerError::new();
+            compiler_error.add_group_message($level, vec![$(($line, $column)),*], $message);
+            compiler_error.set_code($code);
+            compiler_error.into_error($file_name, $src)
+        }
+    };
     ($level:expr, $file_name:expr, $src:expr, $message:expr, $($line:expr, $column:expr),*) => {
         {
             let mut compiler_error = crate::error::CompilerError::new();
             compiler_error.add_group_message($level, vec![$(($line, $column)),*], $message);
-            compiler_error.format_error_string($file_name, $src)
+            compiler_error.into_error($file_name, $src)
         }
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 色を付けずに表示した各行
+    fn rendered(diagnostic: Diagnostic, source: &str) -> Vec<String> {
+        colored::control::set_override(false);
+        let error = diagnostic.into_error("test.sc", source);
+        error
+            .report()
+            .rendered
+            .lines()
+            .map(str::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn underlines_ranges_under_wide_characters() {
+        let source = "let 名前 = \"値\" + 1;\n";
+        let diagnostic = Diagnostic::new("error", "mismatched types")
+            .code("E0301")
+            .primary(1, 10, 3, "string")
+            .secondary(1, 5, 2, "declared here")
+            .help("convert the value");
+        assert_eq!(
+            rendered(diagnostic, source),
+            [
+                "error[E0301]: mismatched types",
+                " --> test.sc:1:10",
+                "  |",
+                "1 | let 名前 = \"値\" + 1;",
+                "  |            ^^^^ string",
+                "  |     ---- declared here",
+                "  = help: convert the value",
+            ]
+        );
+    }
+
+    #[test]
+    fn expands_tabs_and_clamps_positions() {
+        let source = "\tlet a = 1;\nlet b";
+        // 列0は1に、幅は行末までに収める
+        let diagnostic = Diagnostic::new("error", "tab").primary(1, 0, 100, "");
+        assert_eq!(
+            rendered(diagnostic, source),
+            [
+                "error: tab",
+                " --> test.sc:1:1",
+                "  |",
+                "1 |     let a = 1;",
+                "  | ^^^^^^^^^^^^^^",
+            ]
+        );
+        // ファイル末尾は最終行の末尾を指す
+        let diagnostic = Diagnostic::new("error", "eof").primary(3, 1, 1, "here");
+        assert_eq!(
+            rendered(diagnostic, source),
+            [
+                "error: eof",
+                " --> test.sc:2:6",
+                "  |",
+                "2 | let b",
+                "  |      ^ here",
+            ]
+        );
+    }
+
+    #[test]
+    fn widens_the_gutter_for_long_line_numbers() {
+        let source = "a\n".repeat(9) + "let x = 1;\n";
+        let diagnostic = Diagnostic::new("warning", "unused")
+            .primary(10, 5, 1, "")
+            .secondary(9, 1, 1, "");
+        assert_eq!(
+            rendered(diagnostic, &source),
+            [
+                "warning: unused",
+                "  --> test.sc:10:5",
+                "   |",
+                " 9 | a",
+                "   | -",
+                "10 | let x = 1;",
+                "   |     ^",
+            ]
+        );
+    }
+
+    #[test]
+    fn converts_diagnostics_to_json_and_sarif() {
+        let error = Diagnostic::new("error", "broken")
+            .code("E0101")
+            .primary(1, 5, 2, "here")
+            .note("details")
+            .into_error("test.sc", "let ab = 1;");
+        let json: Value =
+            serde_json::from_str(to_json_lines(std::slice::from_ref(&error)).trim_end()).unwrap();
+        assert_eq!(json["code"], "E0101");
+        assert_eq!(json["file"], "test.sc");
+        assert_eq!(json["labels"][0]["label"], "here");
+
+        assert!(json.get("backtrace").is_none());
+
+        let sarif = to_sarif(&[error]);
+        let run = &sarif["runs"][0];
+        assert_eq!(run["tool"]["driver"]["rules"][0]["id"], "E0101");
+        assert_eq!(
+            run["tool"]["driver"]["rules"][0]["shortDescription"]["text"],
+            msg!("explain.E0101.summary")
+        );
+        let result = &run["results"][0];
+        assert_eq!(result["ruleId"], "E0101");
+        assert!(result.get("stacks").is_none());
+        assert_eq!(result["message"]["text"], "broken\nnote: details");
+        let region = &result["locations"][0]["physicalLocation"]["region"];
+        assert_eq!(
+            (
+                region["startLine"].clone(),
+                region["startColumn"].clone(),
+                region["endColumn"].clone()
+            ),
+            (json!(1), json!(5), json!(7))
+        );
+    }
+
+    fn frame(function: &str, line: usize) -> Frame {
+        Frame {
+            function: function.to_string(),
+            file: "test.sc".to_string(),
+            line,
+            column: 1,
+        }
+    }
+
+    #[test]
+    fn includes_the_backtrace_in_json_and_sarif() {
+        let error = Diagnostic::new("error", "failed")
+            .primary(2, 3, 1, "")
+            .into_error("test.sc", "fn f() {\n  1 / 0;\n}")
+            .with_backtrace(vec![frame("f", 2), frame("<top-level>", 4)]);
+        let json: Value =
+            serde_json::from_str(to_json_lines(std::slice::from_ref(&error)).trim_end()).unwrap();
+        assert_eq!(json["message"], "failed");
+        assert_eq!(json["backtrace"][0]["function"], "f");
+        assert_eq!(json["backtrace"][1]["line"], 4);
+
+        let sarif = to_sarif(&[error]);
+        let frames = &sarif["runs"][0]["results"][0]["stacks"][0]["frames"];
+        let location = &frames[1]["location"];
+        assert_eq!(location["logicalLocations"][0]["name"], "<top-level>");
+        assert_eq!(
+            location["physicalLocation"]["region"],
+            json!({ "startLine": 4, "startColumn": 1 })
+        );
+    }
+
+    #[test]
+    fn classifies_errors_by_code_prefix() {
+        let kind = |code: Option<&str>| {
+            let diagnostic = Diagnostic::new("error", "message");
+            let diagnostic = match code {
+                Some(code) => diagnostic.code(code),
+                None => diagnostic,
+            };
+            diagnostic.into_error("test.sc", "").kind()
+        };
+        assert_eq!(kind(Some("E0001")), "lex");
+        assert_eq!(kind(Some("E0101")), "parse");
+        assert_eq!(kind(Some("E0201")), "type");
+        assert_eq!(kind(Some("W0001")), "lint");
+        assert_eq!(kind(Some("E0301")), "runtime");
+        assert_eq!(kind(None), "runtime");
+        assert_eq!(ScriptError::io("a.sc", "not found").kind(), "io");
+    }
+
+    #[test]
+    fn merges_errors_into_the_first_kind() {
+        assert!(ScriptError::merge(Vec::new()).is_none());
+        let first = Diagnostic::new("error", "first")
+            .code("E0101")
+            .into_error("test.sc", "");
+        let second = Diagnostic::new("error", "second")
+            .code("E0001")
+            .into_error("test.sc", "");
+        let merged = ScriptError::merge(vec![first.clone(), second.clone()]).unwrap();
+        assert_eq!(merged.kind(), "parse");
+        let messages: Vec<_> = merged
+            .diagnostics()
+            .iter()
+            .map(|msg| msg.message.as_str())
+            .collect();
+        assert_eq!(messages, ["first", "second"]);
+        assert_eq!(
+            merged.report().rendered,
+            first.report().rendered.clone() + &second.report().rendered
+        );
+    }
+
+    #[test]
+    fn io_errors_have_a_diagnostic_without_location() {
+        let error = ScriptError::io("a.sc", "not found");
+        assert_eq!(error.to_string(), "a.sc: not found");
+        let diagnostic = &error.diagnostics()[0];
+        assert_eq!(diagnostic.message, "a.sc: not found");
+        assert_eq!(diagnostic.file, "a.sc");
+        assert!(error.primary_label().is_none());
+    }
+
+    #[test]
+    fn keeps_the_deepest_backtrace() {
+        let error = Diagnostic::new("error", "failed")
+            .into_error("test.sc", "")
+            .with_backtrace(vec![frame("inner", 2), frame("outer", 5)])
+            .with_backtrace(vec![frame("outer", 5)]);
+        let functions: Vec<_> = error
+            .backtrace()
+            .iter()
+            .map(|frame| frame.function.as_str())
+            .collect();
+        assert_eq!(functions, ["inner", "outer"]);
+    }
+
+    #[test]
+    fn reclassifies_only_runtime_errors() {
+        let runtime = || {
+            Diagnostic::new("error", "failed")
+                .primary(1, 1, 1, "")
+                .into_error("test.sc", "a")
+        };
+        let io = runtime().into_io();
+        assert_eq!(io.kind(), "io");
+        // 位置の情報はそのまま残る
+        assert_eq!(io.primary_label().map(|label| label.line), Some(1));
+        assert_eq!(runtime().into_system().kind(), "system");
+        let parse = Diagnostic::new("error", "failed")
+            .code("E0101")
+            .into_error("test.sc", "");
+        assert_eq!(parse.clone().into_io().kind(), "parse");
+        assert_eq!(parse.into_system().kind(), "parse");
+    }
+
+    #[test]
+    fn finds_the_primary_label_of_the_first_diagnostic() {
+        let source = "let a = b;";
+        let error = Diagnostic::new("error", "first")
+            .secondary(1, 1, 3, "secondary")
+            .primary(1, 9, 1, "primary")
+            .into_error("test.sc", source);
+        assert_eq!(
+            error.primary_label().map(|label| label.message.as_str()),
+            Some("primary")
+        );
+        // 主な指摘がなければ最初の指摘
+        let error = Diagnostic::new("error", "first")
+            .secondary(1, 5, 1, "only")
+            .into_error("test.sc", source);
+        assert_eq!(
+            error.primary_label().map(|label| label.message.as_str()),
+            Some("only")
+        );
+    }
+}
@@ -0,0 +1,235 @@
+use crate::error::{ErrorMessage, Frame, ScriptError};
+use crate::msg;
+use anyhow::Result as R;
+use chrono::{Local, SecondsFormat};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+pub const LOG_DIR: &str = "./script-log"; // エラーログの出力先(script-analysisと同じ階層)
+const LOG_FILE: &str = "error.log";
+const MAX_LOG_SIZE: u64 = 1024 * 1024; // これを超えたらローテーションする(バイト)
+const MAX_ROTATED_FILES: usize = 5; // 残しておく古いログの数(error.log.1 .. error.log.5)
+
+// エラーログの1件(1行のJSONとして追記する)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogEntry {
+    pub timestamp: String,
+    pub script: String,
+    pub kind: String,
+    pub diagnostics: Vec<ErrorMessage>,
+    #[serde(default)]
+    pub backtrace: Vec<Frame>,
+}
+
+impl LogEntry {
+    pub fn new(script: &str, error: &ScriptError) -> Self {
+        LogEntry {
+            timestamp: Local::now().to_rfc3339_opts(SecondsFormat::Secs, false),
+            script: script.to_string(),
+            kind: error.kind().to_string(),
+            diagnostics: error.diagnostics().to_vec(),
+            backtrace: error.backtrace().to_vec(),
+        }
+    }
+
+    // 一覧に表示する1行(最初の診断とその位置)
+    pub fn summary(&self) -> String {
+        let Some(first) = self.diagnostics.first() else {
+            return String::new();
+        };
+        match first.labels.iter().find(|label| label.primary) {
+            Some(label) => format!(
+                "{} ({}:{}:{})",
+                first.message, first.file, label.line, label.column
+            ),
+            None => first.message.clone(),
+        }
+    }
+}
+
+// エラーをログに追記する(大きくなったログは先にローテーションする)
+pub fn append(script: &str, error: &ScriptError) -> R<(), ScriptError> {
+    let dir = Path::new(LOG_DIR);
+    fs::create_dir_all(dir).map_err(|e| ScriptError::io(LOG_DIR, e))?;
+    let path = dir.join(LOG_FILE);
+    rotate(&path)?;
+    let line = serde_json::to_string(&LogEntry::new(script, error))
+        .map_err(|e| ScriptError::io(path.display(), e))?;
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .and_then(|mut file| writeln!(file, "{}", line))
+        .map_err(|e| ScriptError::io(path.display(), e))
+}
+
+// error.log が上限を超えていれば error.log.1 にずらし、古いものから順に番号を繰り上げる
+fn rotate(path: &Path) -> R<(), ScriptError> {
+    let size = fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+    if size < MAX_LOG_SIZE {
+        return Ok(());
+    }
+    let oldest = rotated_path(path, MAX_ROTATED_FILES);
+    if oldest.exists() {
+        fs::remove_file(&oldest).map_err(|e| ScriptError::io(oldest.display(), e))?;
+    }
+    for n in (1..MAX_ROTATED_FILES).rev() {
+        let from = rotated_path(path, n);
+        if from.exists() {
+            fs::rename(&from, rotated_path(path, n + 1))
+                .map_err(|e| ScriptError::io(from.display(), e))?;
+        }
+    }
+    fs::rename(path, rotated_path(path, 1)).map_err(|e| ScriptError::io(path.display(), e))
+}
+
+fn rotated_path(path: &Path, n: usize) -> PathBuf {
+    PathBuf::from(format!("{}.{}", path.display(), n))
+}
+
+// 新しい順にエラーログを読む(ローテーションした古いログも含む)
+pub fn recent(limit: usize) -> R<Vec<LogEntry>, ScriptError> {
+    let path = Path::new(LOG_DIR).join(LOG_FILE);
+    let files = std::iter::once(path.clone())
+        .chain((1..=MAX_ROTATED_FILES).map(|n| rotated_path(&path, n)));
+    let mut entries = Vec::new();
+    for file in files {
+        if entries.len() >= limit {
+            break;
+        }
+        let content = match fs::read_to_string(&file) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(ScriptError::io(file.display(), e)),
+        };
+        // 壊れた行は読み飛ばす
+        entries.extend(
+            content
+                .lines()
+                .rev()
+                .filter_map(|line| serde_json::from_str::<LogEntry>(line).ok()),
+        );
+    }
+    entries.truncate(limit);
+    Ok(entries)
+}
+
+// 最近のエラーの一覧(1が最新)
+pub fn format_list(entries: &[LogEntry]) -> String {
+    if entries.is_empty() {
+        return msg!("log.empty", LOG_DIR) + "\n";
+    }
+    entries
+        .iter()
+        .enumerate()
+        .map(|(i, entry)| {
+            format!(
+                "{:>3}  {}  {:<7}  {}  {}\n",
+                i + 1,
+                entry.timestamp,
+                entry.kind,
+                entry.script,
+                entry.summary()
+            )
+        })
+        .collect()
+}
+
+// 1件のエラーの詳細(診断、指摘の位置、呼び出し履歴)
+pub fn format_entry(entry: &LogEntry) -> String {
+    let mut result = msg!(
+        "log.entry_header",
+        entry.kind,
+        entry.script,
+        entry.timestamp
+    ) + "\n";
+    for diagnostic in &entry.diagnostics {
+        let level = match diagnostic.code {
+            Some(ref code) => format!("{}[{}]", diagnostic.level, code),
+            None => diagnostic.level.clone(),
+        };
+        result.push_str(&format!("{}: {}\n", level, diagnostic.message));
+        for label in &diagnostic.labels {
+            let marker = if label.primary { "-->" } else { "   " };
+            result.push_str(&format!(
+                " {} {}:{}:{}",
+                marker, diagnostic.file, label.line, label.column
+            ));
+            if !label.message.is_empty() {
+                result.push_str(&format!(" {}", label.message));
+            }
+            result.push('\n');
+        }
+        for child in &diagnostic.children {
+            result.push_str(&format!("  = {}: {}\n", child.level, child.message));
+        }
+    }
+    if !entry.backtrace.is_empty() {
+        result.push_str(&(msg!("runtime.stack_backtrace") + "\n"));
+        for (i, frame) in entry.backtrace.iter().enumerate() {
+            result.push_str(&format!("{:>4}: {}\n", i, frame));
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Diagnostic;
+
+    #[test]
+    fn rotates_large_logs_keeping_a_fixed_number() {
+        let dir = std::env::temp_dir().join(format!("tanucc-error-log-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(LOG_FILE);
+        // 上限未満ならそのまま追記を続ける
+        fs::write(&path, vec![b'x'; MAX_LOG_SIZE as usize - 1]).unwrap();
+        rotate(&path).unwrap();
+        assert!(path.exists());
+        assert!(!rotated_path(&path, 1).exists());
+        // 上限に達するたびに番号を繰り上げ、最も古いものを捨てる
+        for n in 0..=MAX_ROTATED_FILES {
+            let mut content = vec![b'x'; MAX_LOG_SIZE as usize];
+            content[0] = b'0' + n as u8;
+            fs::write(&path, content).unwrap();
+            rotate(&path).unwrap();
+            assert!(!path.exists());
+        }
+        for n in 1..=MAX_ROTATED_FILES {
+            let content = fs::read(rotated_path(&path, n)).unwrap();
+            assert_eq!(content[0], b'0' + (MAX_ROTATED_FILES + 1 - n) as u8);
+        }
+        assert!(!rotated_path(&path, MAX_ROTATED_FILES + 1).exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn entries_round_trip_through_json() {
+        let error = Diagnostic::new("error", "broken")
+            .code("E0101")
+            .primary(1, 5, 2, "here")
+            .into_error("test.sc", "let ab = 1;")
+            .with_backtrace(vec![Frame {
+                function: "<top-level>".to_string(),
+                file: "test.sc".to_string(),
+                line: 1,
+                column: 5,
+            }]);
+        let entry = LogEntry::new("test.sc", &error);
+        let line = serde_json::to_string(&entry).unwrap();
+        let read: LogEntry = serde_json::from_str(&line).unwrap();
+        assert_eq!(read.kind, "parse");
+        assert_eq!(read.summary(), "broken (test.sc:1:5)");
+        assert_eq!(format_entry(&read), format_entry(&entry));
+        assert!(format_entry(&read).contains(" --> test.sc:1:5 here\n"));
+        // 呼び出し履歴のない古い行も読める
+        let old = line.replace(",\"backtrace\":", ",\"ignored\":");
+        assert!(serde_json::from_str::<LogEntry>(&old)
+            .unwrap()
+            .backtrace
+            .is_empty());
+    }
+}
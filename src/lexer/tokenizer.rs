@@ -1,33 +1,152 @@
 use crate::compile_error;
-use crate::error::CompilerError;
-use crate::types::TokenType;
+use crate::error::{CompilerError, ScriptError};
+use crate::msg;
+use crate::source_map::{with_source_map, SourceFile, Span};
+use crate::types::{Keyword, TokenType};
 use anyhow::{anyhow, Context, Result as R};
 use log::{error, info, warn};
 use property_rs::Property;
 use serde::Serialize;
-use std::str::FromStr;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+// 数値リテラルの型接尾辞
+pub static NUMBER_SUFFIXES: &[&str] = &[
+    "i8", "i16", "i32", "i64", "u8", "u16", "u32", "u64", "f32", "f64",
+];
+
+// 演算子・記号の表(同じ文字で始まるものは長いものを先に置く)
+static OPERATORS: &[(&str, TokenType)] = &[
+    ("<<=", TokenType::ShiftLeftAssign),
+    (">>=", TokenType::ShiftRightAssign),
+    ("==", TokenType::Eq),
+    ("!=", TokenType::Ne),
+    ("<<", TokenType::ShiftLeft),
+    ("<=", TokenType::Le),
+    (">>", TokenType::ShiftRight),
+    (">=", TokenType::Ge),
+    ("&&", TokenType::And),
+    ("&=", TokenType::BitAndAssign),
+    ("||", TokenType::Or),
+    ("|=", TokenType::BitOrAssign),
+    ("^=", TokenType::BitXorAssign),
+    ("++", TokenType::Increment),
+    ("+=", TokenType::AddAssign),
+    ("--", TokenType::Decrement),
+    ("-=", TokenType::SubAssign),
+    ("->", TokenType::RightArrow),
+    ("*=", TokenType::MulAssign),
+    ("/=", TokenType::DivAssign),
+    ("::", TokenType::ScopeResolution),
+    ("!", TokenType::BitOr),
+    ("<", TokenType::Lt),
+    (">", TokenType::Gt),
+    ("&", TokenType::BitAnd),
+    ("|", TokenType::BitOr),
+    ("^", TokenType::BitXor),
+    ("~", TokenType::BitNot),
+    ("+", TokenType::Add),
+    ("-", TokenType::Sub),
+    ("*", TokenType::Mul),
+    ("/", TokenType::Div),
+    (":", TokenType::Colon),
+    ("=", TokenType::Equals),
+    ("(", TokenType::LeftParen),
+    (")", TokenType::RightParen),
+    ("{", TokenType::LeftCurlyBrace),
+    ("}", TokenType::RightCurlyBrace),
+    ("[", TokenType::LeftSquareBrace),
+    ("]", TokenType::RightSquareBrace),
+    (",", TokenType::Conma),
+    ("@", TokenType::AtSign),
+    (";", TokenType::Semi),
+];
+
+// 文字列リテラル内の埋め込み式の書き方
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Embed {
+    None,   // 埋め込み式なし('...')
+    Dollar, // "...${式}..."
+    Brace,  // f"...{式}..."
+}
+
+// 入力をバイト位置で読み進めるカーソル(字句の切り出しは入力を借用する)
+struct Cursor<'s> {
+    input: &'s str,
+    pos: usize,    // 入力の先頭からのバイト位置
+    offset: usize, // 入力の先頭のソースファイル上のバイト位置
+}
+
+impl<'s> Cursor<'s> {
+    fn new(input: &'s str, offset: usize) -> Self {
+        Cursor {
+            input,
+            pos: 0,
+            offset,
+        }
+    }
+    // 現在位置の文字(ASCIIはデコードせずに返す)
+    fn peek(&self) -> Option<char> {
+        match *self.input.as_bytes().get(self.pos)? {
+            byte if byte.is_ascii() => Some(byte as char),
+            _ => self.input[self.pos..].chars().next(),
+        }
+    }
+    // nバイト先のバイト(入力の末尾より先は0)
+    fn byte_at(&self, n: usize) -> u8 {
+        self.input
+            .as_bytes()
+            .get(self.pos + n)
+            .copied()
+            .unwrap_or(0)
+    }
+    fn rest(&self) -> &'s str {
+        &self.input[self.pos..]
+    }
+    // startから現在位置までの字句
+    fn slice(&self, start: usize) -> &'s str {
+        &self.input[start..self.pos]
+    }
+    // 入力上の位置をソースファイル上のバイト位置に変換する
+    fn source_pos(&self, pos: usize) -> usize {
+        self.offset + pos
+    }
+}
 
 #[derive(Debug, Property, Clone, Serialize)]
 pub struct Token {
-    #[property(get)]
-    token_value: String,
+    token_value: Arc<str>, // 字句解析器の共有表にある綴り
     #[property(get)]
     token_type: TokenType,
     #[property(get)]
     line: usize,
     #[property(get)]
     column: usize,
+    #[property(get)]
+    span: Span,
 }
 
 impl Token {
-    fn new(token_value: String, token_type: TokenType, line: usize, column: usize) -> Self {
+    fn new(token_value: Arc<str>, token_type: TokenType, line: usize, column: usize) -> Self {
         Token {
             token_value,
             token_type,
             line,
             column,
+            span: Span::default(),
         }
     }
+    fn with_span(mut self, span: Span) -> Self {
+        self.span = span;
+        self
+    }
+    pub fn token_value(&self) -> String {
+        self.token_value.to_string()
+    }
+    // 綴りを複製せずに参照する
+    pub fn text(&self) -> &str {
+        &self.token_value
+    }
 }
 
 #[derive(Debug, Property, Clone)]
@@ -46,6 +165,9 @@ pub struct Lexer {
     eof_line: usize,
     #[property(get)]
     eof_column: usize,
+    #[property(get)]
+    diagnostics: Vec<ScriptError>, // 字句のエラーの診断
+    symbols: HashSet<Arc<str>>, // トークンの綴りの共有表(同じ綴りは1つの領域を使い回す)
 }
 
 impl Lexer {
@@ -58,6 +180,8 @@ impl Lexer {
             column: 1,
             eof_line: 1,
             eof_column: 1,
+            diagnostics: Vec::new(),
+            symbols: HashSet::new(),
         }
     }
     pub fn new_with_value_vec(input_content_vec: Vec<String>) -> Self {
@@ -69,6 +193,8 @@ impl Lexer {
             column: 1,
             eof_line: 1,
             eof_column: 1,
+            diagnostics: Vec::new(),
+            symbols: HashSet::new(),
         }
     }
 
@@ -81,782 +207,1379 @@ impl Lexer {
             column: 1,
             eof_line: 1,
             eof_column: 1,
+            diagnostics: Vec::new(),
+            symbols: HashSet::new(),
         }
     }
 
-    fn is_symbol(&self, c: char) -> bool {
-        matches!(
-            c,
-            '(' | ')' | ',' | '=' | ';' | '@' | '{' | '}' | '<' | '>' | ':' | '[' | ']'
-        )
+    // 綴りを共有表から取り出す(初めての綴りだけ確保する)
+    fn intern(&mut self, text: &str) -> Arc<str> {
+        if let Some(symbol) = self.symbols.get(text) {
+            return symbol.clone();
+        }
+        let symbol: Arc<str> = Arc::from(text);
+        self.symbols.insert(symbol.clone());
+        symbol
     }
-    fn tokenize_string(&mut self, input_content: &String) -> R<Vec<Token>, String> {
-        let mut tokens: Vec<Token> = Vec::new();
-        let mut chars = input_content.chars().peekable();
 
-        while let Some(&c) = chars.peek() {
-            if c.is_whitespace() {
-                if c == '\r' {
-                    chars.next();
-                    continue;
+    // 文字列リテラルの値(エスケープを含む場合だけ解釈した文字列を新しく確保する)
+    fn string_value(&mut self, raw: &str, line: usize, column: usize) -> R<Arc<str>, ScriptError> {
+        if raw.contains('\\') {
+            Ok(Arc::from(self.unescape(raw, line, column)?))
+        } else {
+            Ok(self.intern(raw))
+        }
+    }
+
+    // 文字を1つ読み進めて行数・列数を更新
+    fn advance(&mut self, cursor: &mut Cursor) -> Option<char> {
+        let c = cursor.peek()?;
+        cursor.pos += c.len_utf8();
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else if c != '\r' {
+            self.column += 1;
+        }
+        Some(c)
+    }
+
+    // 閉じ引用符までの本文をエスケープを解釈せずに読み取り、(本文,本文の開始行,開始列)を返す
+    // 埋め込み式の中の引用符では閉じない
+    fn read_quoted<'s>(
+        &mut self,
+        cursor: &mut Cursor<'s>,
+        quote: char,
+        embed: Embed,
+        start_line: usize,
+        start_column: usize,
+    ) -> R<(&'s str, usize, usize), ScriptError> {
+        let (line, column) = (self.line, self.column);
+        let start = cursor.pos;
+        let mut depth = 0; // 埋め込み式の波括弧の深さ
+        let mut embed_start = (line, column); // 直近の埋め込み式の開始位置
+        loop {
+            let end = cursor.pos;
+            let Some(c) = self.advance(cursor) else {
+                break;
+            };
+            if depth == 0 && c == quote {
+                return Ok((&cursor.input[start..end], line, column));
+            }
+            match c {
+                '\\' => {
+                    // エスケープされた文字では閉じない
+                    self.advance(cursor);
                 }
-                if c == '\n' {
-                    self.line += 1;
-                    self.column = 1;
-                } else {
-                    self.column += 1;
+                '"' | '\'' if depth > 0 => {
+                    // 埋め込み式の中の文字列リテラル
+                    let inner_embed = if c == '"' { Embed::Dollar } else { Embed::None };
+                    // 閉じられない場合は埋め込み式の閉じ忘れとして報告する
+                    if self
+                        .read_quoted(cursor, c, inner_embed, start_line, start_column)
+                        .is_err()
+                    {
+                        break;
+                    }
                 }
-                chars.next();
-                continue;
-            }
-
-            let start_line = self.line();
-            let start_column = self.column();
-            if c.is_digit(10) || c == '0' {
-                let mut number = String::new();
-                let mut base = 10;
-                let mut has_decimal_point = false;
-                let mut decimal_point_count = 0;
-
-                if c == '0' {
-                    chars.next();
-                    self.column += 1;
-                    if let Some(&next_char) = chars.peek() {
-                        match next_char {
-                            'b' | 'B' => {
-                                base = 2;
-                                chars.next();
-                                self.column += 1;
-                            }
-                            'o' | 'O' => {
-                                base = 8;
-                                chars.next();
-                                self.column += 1;
-                            }
-                            'x' | 'X' => {
-                                base = 16;
-                                chars.next();
-                                self.column += 1;
-                            }
-                            _ => {
-                                number.push('0');
-                            }
-                        }
+                '{' if depth > 0 => depth += 1,
+                '}' if depth > 0 => depth -= 1,
+                '$' if embed == Embed::Dollar && depth == 0 && cursor.byte_at(0) == b'{' => {
+                    embed_start = (self.line, self.column - 1);
+                    self.advance(cursor);
+                    depth = 1;
+                }
+                '{' if embed == Embed::Brace => {
+                    if cursor.byte_at(0) == b'{' {
+                        self.advance(cursor);
                     } else {
-                        number.push('0');
+                        embed_start = (self.line, self.column - 1);
+                        depth = 1;
                     }
                 }
+                _ => {}
+            }
+        }
+        if depth > 0 {
+            return Err(compile_error!(
+                code = "E0006",
+                "error",
+                embed_start.0,
+                embed_start.1,
+                &self.input_path,
+                &self.input_content,
+                "{}",
+                msg!("lex.unclosed_embed")
+            ));
+        }
+        let message = if quote == '"' {
+            msg!("lex.unclosed_double_quote")
+        } else {
+            msg!("lex.unclosed_single_quote")
+        };
+        Err(compile_error!(
+            code = "E0002",
+            width = usize::MAX, // 行末まで
+            "error",
+            start_line,
+            start_column,
+            &self.input_path,
+            &self.input_content,
+            "{}",
+            message
+        ))
+    }
 
-                while let Some(&c) = chars.peek() {
-                    if c.is_digit(base) {
-                        number.push(c);
-                        chars.next();
-                        self.column += 1;
-                    } else if c == '.' && base == 10 {
-                        decimal_point_count += 1;
-                        if decimal_point_count == 1 {
-                            number.push(c);
-                            chars.next();
-                            self.column += 1;
-                            has_decimal_point = true;
-                        } else if decimal_point_count == 2 {
-                            if !number.is_empty() {
-                                let value = if has_decimal_point {
-                                    f64::from_str(&number).unwrap()
-                                } else {
-                                    i64::from_str_radix(&number, base).unwrap() as f64
-                                };
-                                tokens.push(Token::new(
-                                    value.to_string(),
-                                    TokenType::Number,
-                                    start_line,
-                                    start_column,
-                                ));
-                                number.clear();
-                            }
-                            tokens.push(Token::new(
-                                "..".to_string(),
-                                TokenType::Range,
-                                start_line,
-                                start_column,
-                            ));
-                            chars.next();
-                            self.column += 1;
-                            break;
-                        } else {
-                            break;
-                        }
-                    } else {
-                        break;
-                    }
+    // 数値リテラルを読み取る(区切りの _、0b/0o/0x、小数点、指数、型接尾辞に対応)
+    // トークンの値は10進に正規化した数値に接尾辞を付けたもの(例: 0xffu8 → "255u8")
+    // startは符号を含むリテラルの開始位置で、直後が .. の場合は範囲演算子のトークンも追加する
+    fn read_number(
+        &mut self,
+        cursor: &mut Cursor,
+        tokens: &mut Vec<Token>,
+        source: &SourceFile,
+        start: usize,
+        (start_line, start_column): (usize, usize),
+    ) -> R<(), ScriptError> {
+        let is_negative = cursor.input.as_bytes()[start] == b'-';
+        let mut base = 10;
+        let mut is_float = false;
+
+        if cursor.byte_at(0) == b'0' {
+            base = match cursor.byte_at(1) {
+                b'b' | b'B' => 2,
+                b'o' | b'O' => 8,
+                b'x' | b'X' => 16,
+                _ => 10,
+            };
+            if base != 10 {
+                self.advance(cursor);
+                self.advance(cursor);
+            }
+        }
+
+        let digits_start = cursor.pos;
+        if self.read_digits(cursor, base) == 0 {
+            return Err(compile_error!(
+                code = "E0004",
+                width = cursor.slice(start).chars().count(),
+                "error",
+                start_line,
+                start_column,
+                &self.input_path,
+                &self.input_content,
+                "{}",
+                msg!("lex.missing_base_digits")
+            ));
+        }
+
+        if base == 10 && cursor.byte_at(0) == b'.' {
+            match cursor.rest()[1..].chars().next() {
+                // 1..5 の範囲演算子
+                Some('.') => {}
+                // 1.foo のようなメンバーアクセスでは小数点として扱わない
+                Some(c) if c.is_alphabetic() || c == '_' => {}
+                _ => {
+                    self.advance(cursor);
+                    self.read_digits(cursor, 10);
+                    is_float = true;
                 }
+            }
+        }
 
-                if decimal_point_count < 2 && !number.is_empty() {
-                    let value = if has_decimal_point {
-                        f64::from_str(&number).unwrap()
-                    } else {
-                        i64::from_str_radix(&number, base).unwrap() as f64
-                    };
-                    tokens.push(Token::new(
-                        value.to_string(),
-                        TokenType::Number,
-                        start_line,
-                        start_column,
-                    ));
+        if base == 10 && matches!(cursor.byte_at(0), b'e' | b'E') {
+            let sign_len = matches!(cursor.byte_at(1), b'+' | b'-') as usize;
+            if cursor.byte_at(1 + sign_len).is_ascii_digit() {
+                for _ in 0..=sign_len {
+                    self.advance(cursor);
                 }
-            } else if c.is_alphanumeric() || c == '_' {
-                let mut ident = String::new();
-                while let Some(&c) = chars.peek() {
-                    if c.is_alphanumeric() || c == '_' {
-                        ident.push(c);
-                        chars.next();
-                        self.column += 1;
-                    } else {
-                        break;
+                self.read_digits(cursor, 10);
+                is_float = true;
+            }
+        }
+
+        // 型接尾辞
+        let (suffix_line, suffix_column) = (self.line, self.column);
+        let suffix_start = cursor.pos;
+        while let Some(c) = cursor.peek() {
+            if !(c.is_alphanumeric() || c == '_') {
+                break;
+            }
+            self.advance(cursor);
+        }
+        let suffix = cursor.slice(suffix_start);
+        let suffix_error = if suffix.is_empty() {
+            None
+        } else if suffix.starts_with(|c: char| c.is_ascii_digit()) {
+            Some(msg!(
+                "lex.invalid_digit",
+                suffix.chars().next().unwrap_or_default(),
+                base
+            ))
+        } else if !NUMBER_SUFFIXES.contains(&suffix) {
+            Some(msg!("lex.invalid_suffix", suffix))
+        } else if is_float && !suffix.starts_with('f') {
+            Some(msg!("lex.integer_suffix_on_float", suffix))
+        } else if base != 10 && suffix.starts_with('f') {
+            Some(msg!("lex.float_suffix_on_base", suffix, base))
+        } else {
+            None
+        };
+        if let Some(message) = suffix_error {
+            return Err(compile_error!(
+                code = "E0004",
+                width = suffix.chars().count(),
+                "error",
+                suffix_line,
+                suffix_column,
+                &self.input_path,
+                &self.input_content,
+                "{}",
+                message
+            ));
+        }
+
+        let literal = &cursor.input[digits_start..suffix_start];
+        let token_value = if base == 10 && !literal.contains('_') {
+            // 10進で区切りがなければソースの綴りのまま使う
+            self.intern(cursor.slice(start))
+        } else {
+            let mut number: String = literal.chars().filter(|&c| c != '_').collect();
+            // 2進・8進・16進は10進に変換する(範囲の検査は構文解析で型に応じて行う)
+            if base != 10 {
+                number = match u128::from_str_radix(&number, base) {
+                    Ok(value) => value.to_string(),
+                    Err(_) => {
+                        return Err(compile_error!(
+                            code = "E0004",
+                            width = cursor.slice(start).chars().count(),
+                            "error",
+                            start_line,
+                            start_column,
+                            &self.input_path,
+                            &self.input_content,
+                            "{}",
+                            msg!("lex.integer_too_large")
+                        ))
                     }
+                };
+            }
+            let sign = if is_negative { "-" } else { "" };
+            self.intern(&format!("{}{}{}", sign, number, suffix))
+        };
+        tokens.push(
+            Token::new(token_value, TokenType::Number, start_line, start_column).with_span(
+                Span::new(
+                    source.id,
+                    cursor.source_pos(start),
+                    cursor.source_pos(cursor.pos),
+                ),
+            ),
+        );
+
+        if cursor.byte_at(0) == b'.' && cursor.byte_at(1) == b'.' {
+            let (line, column, range_start) = (self.line, self.column, cursor.pos);
+            self.advance(cursor);
+            self.advance(cursor);
+            let token_value = self.intern("..");
+            tokens.push(
+                Token::new(token_value, TokenType::Range, line, column).with_span(Span::new(
+                    source.id,
+                    cursor.source_pos(range_start),
+                    cursor.source_pos(cursor.pos),
+                )),
+            );
+        }
+        Ok(())
+    }
+
+    // 指定の基数の数字を読み取り、読んだ数字の数を返す(区切りの _ は読み飛ばす)
+    fn read_digits(&mut self, cursor: &mut Cursor, base: u32) -> usize {
+        let mut count = 0;
+        while let Some(c) = cursor.peek() {
+            if c.is_digit(base) {
+                count += 1;
+            } else if c != '_' {
+                break;
+            }
+            self.advance(cursor);
+        }
+        count
+    }
+
+    // r"..." / r#"..."# の開始かどうか
+    fn is_raw_string_start(&self, cursor: &Cursor) -> bool {
+        let hashes = cursor.rest()[1..]
+            .bytes()
+            .take_while(|&b| b == b'#')
+            .count();
+        cursor.byte_at(1 + hashes) == b'"'
+    }
+
+    // 生文字列: エスケープを解釈せず、開始と同じ数の # が続く " で閉じる
+    fn read_raw_string(
+        &mut self,
+        cursor: &mut Cursor,
+        start_line: usize,
+        start_column: usize,
+    ) -> R<Arc<str>, ScriptError> {
+        let mut hashes = 0;
+        while cursor.byte_at(0) == b'#' {
+            self.advance(cursor);
+            hashes += 1;
+        }
+        self.advance(cursor); // 開始のクォートをスキップ
+        let body_start = cursor.pos;
+        loop {
+            let end = cursor.pos;
+            let Some(c) = self.advance(cursor) else {
+                break;
+            };
+            if c == '"' && (0..hashes).all(|i| cursor.byte_at(i) == b'#') {
+                for _ in 0..hashes {
+                    self.advance(cursor);
                 }
-                tokens.push(Token::new(
-                    ident,
-                    TokenType::Ident,
-                    start_line,
-                    start_column,
-                ));
-            } else if c == '\'' {
-                let mut string = String::new();
-                chars.next(); // 開始のクォートをスキップ
-                self.column += 1;
-                let mut closed = false;
-                while let Some(c) = chars.next() {
+                // 改行のCRは値に含めない
+                let body = &cursor.input[body_start..end];
+                return Ok(if body.contains('\r') {
+                    Arc::from(body.replace('\r', ""))
+                } else {
+                    self.intern(body)
+                });
+            }
+        }
+        Err(compile_error!(
+            code = "E0002",
+            width = usize::MAX, // 行末まで
+            "error",
+            start_line,
+            start_column,
+            &self.input_path,
+            &self.input_content,
+            "{}",
+            msg!("lex.unclosed_raw_string", "#".repeat(hashes))
+        ))
+    }
+
+    // """...""" の複数行文字列: 共通のインデントを取り除いてからエスケープを解釈する
+    fn read_triple_quoted(
+        &mut self,
+        cursor: &mut Cursor,
+        start_line: usize,
+        start_column: usize,
+    ) -> R<String, ScriptError> {
+        // (行の開始行,開始列,行の内容)
+        let mut lines: Vec<(usize, usize, String)> = vec![(self.line, self.column, String::new())];
+        let mut closed = false;
+        while let Some(c) = self.advance(cursor) {
+            if c == '"' && cursor.byte_at(0) == b'"' && cursor.byte_at(1) == b'"' {
+                self.advance(cursor);
+                self.advance(cursor);
+                closed = true;
+                break;
+            }
+            match c {
+                '\n' => lines.push((self.line, self.column, String::new())),
+                '\r' => {}
+                _ => {
+                    let current = &mut lines.last_mut().unwrap().2;
+                    current.push(c);
                     if c == '\\' {
-                        // エスケープシーケンスの処理
-                        if let Some(next_char) = chars.next() {
-                            string.push(next_char);
-                            self.column += 2; // エスケープ文字と次の文字をスキップ
-                            continue;
+                        if let Some(next_char) = cursor.peek() {
+                            if next_char != '\n' {
+                                current.push(next_char);
+                                self.advance(cursor);
+                            }
                         }
-                    } else if c == '\'' {
-                        self.column += 1;
-                        closed = true;
-                        break;
                     }
-                    string.push(c);
-                    self.column += 1;
                 }
-                if !closed {
-                    return Err(compile_error!(
-                        "error",
-                        start_line,
-                        start_column,
-                        &self.input_path.clone(),
-                        &self.input_content.clone(),
-                        "Single quote not closed",
-                    ));
+            }
+        }
+        if !closed {
+            return Err(compile_error!(
+                code = "E0002",
+                width = usize::MAX, // 行末まで
+                "error",
+                start_line,
+                start_column,
+                &self.input_path,
+                &self.input_content,
+                "{}",
+                msg!("lex.unclosed_triple_quote")
+            ));
+        }
+
+        // 開始直後の改行と、閉じる """ だけの行は内容に含めない
+        if lines.len() > 1 && lines[0].2.trim().is_empty() {
+            lines.remove(0);
+        }
+        if lines.len() > 1 && lines.last().unwrap().2.trim().is_empty() {
+            lines.pop();
+        }
+        let indent = lines
+            .iter()
+            .filter(|(_, _, text)| !text.trim().is_empty())
+            .map(|(_, _, text)| text.chars().take_while(|c| *c == ' ' || *c == '\t').count())
+            .min()
+            .unwrap_or(0);
+
+        let mut result = Vec::new();
+        for (line, column, text) in &lines {
+            let strip = text
+                .chars()
+                .take(indent)
+                .take_while(|c| *c == ' ' || *c == '\t')
+                .count();
+            let body: String = text.chars().skip(strip).collect();
+            result.push(self.unescape(&body, *line, column + strip)?);
+        }
+        Ok(result.join("\n"))
+    }
+
+    // 補間文字列をリテラル部分と埋め込み式のトークン列に分割する
+    // "...${式}..." は ${ で、f"...{式}..." は { で式を始める(f文字列では {{ と }} で波括弧自体を書く)
+    // raw_startは本文の、startは f や開始のクォートを含むリテラル全体のソースファイル上のバイト位置
+    fn tokenize_interpolation(
+        &mut self,
+        tokens: &mut Vec<Token>,
+        raw: &str,
+        source: &SourceFile,
+        (line, column, raw_start): (usize, usize, usize),
+        is_format: bool,
+        (start_line, start_column, start): (usize, usize, usize),
+    ) -> R<(), ScriptError> {
+        // 本文の各文字と位置(文字,行数,列数,本文の先頭からのバイト位置)
+        let mut positioned: Vec<(char, usize, usize, usize)> = Vec::new();
+        let (mut l, mut c) = (line, column);
+        for (byte, ch) in raw.char_indices() {
+            positioned.push((ch, l, c, byte));
+            if ch == '\n' {
+                l += 1;
+                c = 1;
+            } else if ch != '\r' {
+                c += 1;
+            }
+        }
+        let end_position = (l, c);
+        let position_at = |i: usize| {
+            positioned
+                .get(i)
+                .map(|&(_, l, c, _)| (l, c))
+                .unwrap_or(end_position)
+        };
+        let byte_at = |i: usize| raw_start + positioned.get(i).map_or(raw.len(), |p| p.3);
+        let span_between =
+            |from: usize, to: usize| Span::new(source.id, byte_at(from), byte_at(to));
+
+        let token_value = self.intern("");
+        tokens.push(
+            Token::new(
+                token_value.clone(),
+                TokenType::TemplateStart,
+                start_line,
+                start_column,
+            )
+            .with_span(Span::new(source.id, start, raw_start)),
+        );
+        let mut literal = String::new();
+        let mut literal_start = 0;
+        let mut i = 0;
+        while i < positioned.len() {
+            let (ch, ch_line, ch_column, _) = positioned[i];
+            let next = positioned.get(i + 1).map(|&(ch, _, _, _)| ch);
+            if ch == '\\' {
+                literal.push(ch);
+                if let Some(next) = next {
+                    literal.push(next);
                 }
-                tokens.push(Token::new(
-                    string,
-                    TokenType::SingleQuote,
-                    start_line,
-                    start_column,
+                i += 2;
+                continue;
+            }
+            if is_format && (ch == '{' && next == Some('{') || ch == '}' && next == Some('}')) {
+                literal.push(ch);
+                i += 2;
+                continue;
+            }
+            if is_format && ch == '}' {
+                return Err(compile_error!(
+                    code = "E0006",
+                    "error",
+                    ch_line,
+                    ch_column,
+                    &self.input_path,
+                    &self.input_content,
+                    "{}",
+                    msg!("lex.unmatched_brace")
                 ));
-            } else if c == '\"' {
-                let mut string = String::new();
-                chars.next(); // 開始のクォートをスキップ
-                self.column += 1;
-                let mut closed = false;
-                while let Some(c) = chars.next() {
-                    if c == '\\' {
-                        // エスケープシーケンスの処理
-                        if let Some(next_char) = chars.next() {
-                            string.push(next_char);
-                            self.column += 2; // エスケープ文字と次の文字をスキップ
-                            continue;
+            }
+            let opener_len = if is_format && ch == '{' {
+                1
+            } else if !is_format && ch == '$' && next == Some('{') {
+                2
+            } else {
+                0
+            };
+            if opener_len == 0 {
+                literal.push(ch);
+                i += 1;
+                continue;
+            }
+
+            // ここまでのリテラル部分
+            if !literal.is_empty() {
+                let (text_line, text_column) = position_at(literal_start);
+                let text = self.string_value(&literal, text_line, text_column)?;
+                tokens.push(
+                    Token::new(text, TokenType::TemplateText, text_line, text_column)
+                        .with_span(span_between(literal_start, i)),
+                );
+                literal.clear();
+            }
+
+            // 対応する閉じ波括弧を探す(式の中の文字列や入れ子の波括弧は読み飛ばす)
+            let expr_start = i + opener_len;
+            let mut depth = 1;
+            let mut quote: Option<char> = None;
+            let mut j = expr_start;
+            while j < positioned.len() {
+                let inner = positioned[j].0;
+                match quote {
+                    Some(q) => {
+                        if inner == '\\' {
+                            j += 1;
+                        } else if inner == q {
+                            quote = None;
                         }
-                    } else if c == '\"' {
-                        self.column += 1;
-                        closed = true;
-                        break;
                     }
-                    string.push(c);
-                    self.column += 1;
+                    None => match inner {
+                        '"' | '\'' => quote = Some(inner),
+                        '{' => depth += 1,
+                        '}' => {
+                            depth -= 1;
+                            if depth == 0 {
+                                break;
+                            }
+                        }
+                        _ => {}
+                    },
+                }
+                j += 1;
+            }
+            if j >= positioned.len() {
+                return Err(compile_error!(
+                    code = "E0006",
+                    "error",
+                    ch_line,
+                    ch_column,
+                    &self.input_path,
+                    &self.input_content,
+                    "{}",
+                    msg!("lex.unclosed_embed")
+                ));
+            }
+            let expr_text = &raw[positioned[expr_start].3..positioned[j].3];
+            if expr_text.trim().is_empty() {
+                return Err(compile_error!(
+                    code = "E0006",
+                    "error",
+                    ch_line,
+                    ch_column,
+                    &self.input_path,
+                    &self.input_content,
+                    "{}",
+                    msg!("lex.empty_embed")
+                ));
+            }
+
+            tokens.push(
+                Token::new(
+                    token_value.clone(),
+                    TokenType::TemplateExprStart,
+                    ch_line,
+                    ch_column,
+                )
+                .with_span(span_between(i, expr_start)),
+            );
+            // 埋め込み式は元のソース上の位置のまま同じ字句解析器でトークン化する
+            let saved = (self.line, self.column, self.eof_line, self.eof_column);
+            (self.line, self.column) = position_at(expr_start);
+            self.tokenize_source(tokens, expr_text, source, byte_at(expr_start));
+            (self.line, self.column, self.eof_line, self.eof_column) = saved;
+            let (end_line, end_column) = position_at(j);
+            tokens.push(
+                Token::new(
+                    token_value.clone(),
+                    TokenType::TemplateExprEnd,
+                    end_line,
+                    end_column,
+                )
+                .with_span(span_between(j, j + 1)),
+            );
+
+            i = j + 1;
+            literal_start = i;
+        }
+        if !literal.is_empty() {
+            let (text_line, text_column) = position_at(literal_start);
+            let text = self.string_value(&literal, text_line, text_column)?;
+            tokens.push(
+                Token::new(text, TokenType::TemplateText, text_line, text_column)
+                    .with_span(span_between(literal_start, positioned.len())),
+            );
+        }
+        // 閉じのクォートまでがリテラルの終わり
+        let raw_end = raw_start + raw.len();
+        tokens.push(
+            Token::new(
+                token_value,
+                TokenType::TemplateEnd,
+                end_position.0,
+                end_position.1,
+            )
+            .with_span(Span::new(source.id, raw_end, raw_end + 1)),
+        );
+        Ok(())
+    }
+
+    // エスケープシーケンスを解釈する(line,columnはrawの先頭文字の位置)
+    fn unescape(&self, raw: &str, line: usize, column: usize) -> R<String, ScriptError> {
+        let mut string = String::new();
+        let (mut line, mut column) = (line, column);
+        let mut chars = raw.chars().peekable();
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                string.push(c);
+                if c == '\n' {
+                    line += 1;
+                    column = 1;
+                } else if c != '\r' {
+                    column += 1;
                 }
-                if !closed {
+                continue;
+            }
+            // エラーはバックスラッシュの位置を指す
+            let escape_column = column;
+            let next_char = match chars.next() {
+                Some(next_char) => next_char,
+                None => {
                     return Err(compile_error!(
+                        code = "E0003",
                         "error",
-                        start_line,
-                        start_column,
-                        &self.input_path.clone(),
-                        &self.input_content.clone(),
-                        "Double quote not closed",
+                        line,
+                        escape_column,
+                        &self.input_path,
+                        &self.input_content,
+                        "{}",
+                        msg!("lex.incomplete_escape")
                     ));
                 }
-                tokens.push(Token::new(
-                    string,
-                    TokenType::DoubleQuote,
-                    start_line,
-                    start_column,
-                ));
-            } else if c == '/' {
-                chars.next();
-                self.column += 1;
-
-                if let Some(&next_char) = chars.peek() {
-                    if next_char == '=' {
-                        tokens.push(Token::new(
-                            "/=".to_string(),
-                            TokenType::DivAssign,
-                            start_line,
-                            start_column,
-                        ));
-                        self.column += 1;
-                        chars.next();
-                    } else if next_char == '/' {
-                        chars.next();
-                        self.column += 1;
-                        let mut comment = String::new();
-                        while let Some(&c) = chars.peek() {
-                            if c == '\r' {
-                                chars.next();
-                                continue;
-                            }
-                            if c == '\n' {
-                                break;
-                            }
-                            comment.push(c);
-                            chars.next();
-                            self.column += 1;
+            };
+            column += 2;
+            match next_char {
+                'n' => string.push('\n'),
+                't' => string.push('\t'),
+                'r' => string.push('\r'),
+                '0' => string.push('\0'),
+                '\\' => string.push('\\'),
+                '"' => string.push('"'),
+                '\'' => string.push('\''),
+                '$' => string.push('$'),
+                'x' => {
+                    let digits: String = chars.by_ref().take(2).collect();
+                    column += digits.chars().count();
+                    match u8::from_str_radix(&digits, 16) {
+                        Ok(value) if digits.len() == 2 && value <= 0x7f => {
+                            string.push(value as char)
                         }
-                        tokens.push(Token::new(
-                            comment.clone(),
-                            TokenType::SingleComment(comment, (start_line, start_column)),
-                            start_line,
-                            start_column,
-                        ));
-                    } else if next_char == '*' {
-                        chars.next(); // '*' をスキップ
-                        self.column += 1;
-                        let mut comment = String::new();
-                        let mut lines = Vec::new();
-                        let mut closed = false;
-                        while let Some(c) = chars.next() {
-                            if c == '*' {
-                                if let Some(&next_char) = chars.peek() {
-                                    if next_char == '/' {
-                                        chars.next(); // '/' をスキップ
-                                        self.column += 1;
-                                        closed = true;
-                                        break;
-                                    }
-                                }
-                            }
-                            if c == '\n' {
-                                self.line += 1;
-                                self.column = 1;
-                                lines.push(comment.clone());
-                                comment.clear();
-                            } else if c == '\t' {
-                                comment.push_str("    "); // タブを4つのスペースに変換
-                                self.column += 4;
-                            } else {
-                                comment.push(c);
-                                self.column += 1;
-                            }
-                        }
-                        if !closed {
+                        _ => {
                             return Err(compile_error!(
+                                code = "E0003",
+                                width = column - escape_column,
                                 "error",
-                                start_line,
-                                start_column,
-                                &self.input_path.clone(),
-                                &self.input_content.clone(),
-                                "Multi-line comment not closed",
+                                line,
+                                escape_column,
+                                &self.input_path,
+                                &self.input_content,
+                                "{}",
+                                msg!("lex.invalid_hex_escape", digits)
                             ));
                         }
-
-                        if !comment.is_empty() {
-                            lines.push(comment);
-                        }
-                        tokens.push(Token::new(
-                            lines.join("\n"),
-                            TokenType::MultiComment(lines, (start_line, start_column)),
-                            start_line,
-                            start_column,
-                        ));
-                    } else {
-                        tokens.push(Token::new(
-                            "/".to_string(),
-                            TokenType::Div,
-                            start_line,
-                            start_column,
-                        ));
                     }
-                } else {
-                    tokens.push(Token::new(
-                        "/".to_string(),
-                        TokenType::Div,
-                        start_line,
-                        start_column,
-                    ));
                 }
-            } else if c == '=' {
-                chars.next();
-                self.column += 1;
-                if let Some(&next_char) = chars.peek() {
-                    if next_char == '=' {
-                        tokens.push(Token::new(
-                            "==".to_string(),
-                            TokenType::Eq,
-                            start_line,
-                            start_column,
-                        ));
-                        self.column += 1;
-                        chars.next();
-                    } else {
-                        tokens.push(Token::new(
-                            "=".to_string(),
-                            TokenType::Equals,
-                            start_line,
-                            start_column,
-                        ));
-                    }
-                }
-            } else if c == '!' {
-                chars.next();
-                self.column += 1;
-                if let Some(&next_char) = chars.peek() {
-                    if next_char == '=' {
-                        tokens.push(Token::new(
-                            "!=".to_string(),
-                            TokenType::Ne,
-                            start_line,
-                            start_column,
-                        ));
-                        self.column += 1;
+                'u' => {
+                    let mut digits = String::new();
+                    let mut closed = false;
+                    if chars.peek() == Some(&'{') {
                         chars.next();
-                    } else {
-                        tokens.push(Token::new(
-                            "!".to_string(),
-                            TokenType::BitOr,
-                            start_line,
-                            start_column,
-                        ));
-                    }
-                }
-            } else if c == '<' {
-                chars.next();
-                self.column += 1;
-                if let Some(&next_char) = chars.peek() {
-                    if next_char == '<' {
-                        chars.next();
-                        self.column += 1;
-                        if let Some(&next_char) = chars.peek() {
-                            if next_char == '=' {
-                                tokens.push(Token::new(
-                                    "<<=".to_string(),
-                                    TokenType::ShiftLeftAssign,
-                                    start_line,
-                                    start_column,
-                                ));
-                                self.column += 1;
-                                chars.next();
-                            } else {
-                                tokens.push(Token::new(
-                                    "<<".to_string(),
-                                    TokenType::ShiftLeft,
-                                    start_line,
-                                    start_column,
-                                ));
+                        column += 1;
+                        while let Some(c) = chars.next() {
+                            column += 1;
+                            if c == '}' {
+                                closed = true;
+                                break;
                             }
-                        } else {
-                            tokens.push(Token::new(
-                                "<<".to_string(),
-                                TokenType::ShiftLeft,
-                                start_line,
-                                start_column,
-                            ));
+                            digits.push(c);
                         }
-                    } else if next_char == '=' {
-                        tokens.push(Token::new(
-                            "<=".to_string(),
-                            TokenType::Le,
-                            start_line,
-                            start_column,
-                        ));
-                        self.column += 1;
-                        chars.next();
-                    } else {
-                        tokens.push(Token::new(
-                            "<".to_string(),
-                            TokenType::Lt,
-                            start_line,
-                            start_column,
-                        ));
                     }
-                }
-            } else if c == '>' {
-                chars.next();
-                self.column += 1;
-                if let Some(&next_char) = chars.peek() {
-                    if next_char == '>' {
-                        chars.next();
-                        self.column += 1;
-                        if let Some(&next_char) = chars.peek() {
-                            if next_char == '=' {
-                                tokens.push(Token::new(
-                                    ">>=".to_string(),
-                                    TokenType::ShiftRightAssign,
-                                    start_line,
-                                    start_column,
-                                ));
-                                self.column += 1;
-                                chars.next();
-                            } else {
-                                tokens.push(Token::new(
-                                    ">>".to_string(),
-                                    TokenType::ShiftRight,
-                                    start_line,
-                                    start_column,
-                                ));
-                            }
-                        } else {
-                            tokens.push(Token::new(
-                                ">>".to_string(),
-                                TokenType::ShiftRight,
-                                start_line,
-                                start_column,
+                    let scalar = u32::from_str_radix(&digits, 16)
+                        .ok()
+                        .filter(|_| closed && (1..=6).contains(&digits.len()))
+                        .and_then(char::from_u32);
+                    match scalar {
+                        Some(c) => string.push(c),
+                        None => {
+                            return Err(compile_error!(
+                                code = "E0003",
+                                width = column - escape_column,
+                                "error",
+                                line,
+                                escape_column,
+                                &self.input_path,
+                                &self.input_content,
+                                "{}",
+                                msg!("lex.invalid_unicode_escape", digits)
                             ));
                         }
-                    } else if next_char == '=' {
-                        tokens.push(Token::new(
-                            ">=".to_string(),
-                            TokenType::Ge,
-                            start_line,
-                            start_column,
-                        ));
-                        self.column += 1;
-                        chars.next();
-                    } else {
-                        tokens.push(Token::new(
-                            ">".to_string(),
-                            TokenType::Gt,
-                            start_line,
-                            start_column,
-                        ));
                     }
                 }
-            } else if c == '&' {
-                chars.next();
-                self.column += 1;
-                if let Some(&next_char) = chars.peek() {
-                    if next_char == '&' {
-                        tokens.push(Token::new(
-                            "&&".to_string(),
-                            TokenType::And,
-                            start_line,
-                            start_column,
-                        ));
-                        self.column += 1;
-                        chars.next();
-                    } else if next_char == '=' {
-                        tokens.push(Token::new(
-                            "&=".to_string(),
-                            TokenType::BitAndAssign,
-                            start_line,
-                            start_column,
-                        ));
-                        self.column += 1;
-                        chars.next();
-                    } else {
-                        tokens.push(Token::new(
-                            "&".to_string(),
-                            TokenType::BitAnd,
-                            start_line,
-                            start_column,
-                        ));
-                    }
-                } else {
-                    tokens.push(Token::new(
-                        "&".to_string(),
-                        TokenType::BitAnd,
-                        start_line,
-                        start_column,
+                _ => {
+                    return Err(compile_error!(
+                        code = "E0003",
+                        width = 2,
+                        "error",
+                        line,
+                        escape_column,
+                        &self.input_path,
+                        &self.input_content,
+                        "{}",
+                        msg!("lex.unknown_escape", next_char)
                     ));
                 }
-            } else if c == '|' {
-                chars.next();
-                self.column += 1;
-                if let Some(&next_char) = chars.peek() {
-                    if next_char == '|' {
-                        tokens.push(Token::new(
-                            "||".to_string(),
-                            TokenType::Or,
-                            start_line,
-                            start_column,
-                        ));
-                        self.column += 1;
-                        chars.next();
-                    }
-                    if next_char == '=' {
-                        tokens.push(Token::new(
-                            "|=".to_string(),
-                            TokenType::BitOrAssign,
-                            start_line,
-                            start_column,
-                        ));
-                        self.column += 1;
-                        chars.next();
-                    } else {
-                        tokens.push(Token::new(
-                            "|".to_string(),
-                            TokenType::BitOr,
-                            start_line,
-                            start_column,
-                        ));
-                    }
-                }
-            } else if c == '^' {
-                chars.next();
-                self.column += 1;
-                if let Some(&next_char) = chars.peek() {
-                    if next_char == '=' {
-                        tokens.push(Token::new(
-                            "^=".to_string(),
-                            TokenType::BitXorAssign,
-                            start_line,
-                            start_column,
-                        ));
-                        self.column += 1;
-                        chars.next();
-                    } else {
-                        tokens.push(Token::new(
-                            "^".to_string(),
-                            TokenType::BitXor,
-                            start_line,
-                            start_column,
-                        ));
-                    }
-                }
-            } else if c == '~' {
-                chars.next();
-                self.column += 1;
+            }
+        }
+        Ok(string)
+    }
+
+    // 入力をトークン化してtokensに追加する(offsetは入力の先頭のソースファイル上のバイト位置)
+    // 字句のエラーはdiagnosticsに記録し、Errorトークンを置いて最後まで読み進める
+    fn tokenize_source(
+        &mut self,
+        tokens: &mut Vec<Token>,
+        input: &str,
+        source: &SourceFile,
+        offset: usize,
+    ) {
+        let mut cursor = Cursor::new(input, offset);
+        let mut unspanned = tokens.len(); // 範囲が未設定のトークンの先頭
+        let mut token_start = offset;
+
+        while let Some(c) = cursor.peek() {
+            // 前の字句を読み終えた位置がそのトークンの終端になる
+            close_spans(
+                source,
+                &mut tokens[unspanned..],
+                token_start,
+                cursor.source_pos(cursor.pos),
+            );
+            unspanned = tokens.len();
+            if c.is_whitespace() {
+                self.advance(&mut cursor);
+                continue;
+            }
+
+            let start = cursor.pos;
+            token_start = cursor.source_pos(start);
+            let start_line = self.line();
+            let start_column = self.column();
+            if let Err(message) = self.read_token(&mut cursor, tokens, source, c) {
+                // 読めなかった字句はErrorトークンにして、続きから読み直す
+                tokens.truncate(unspanned);
+                self.recover(&mut cursor, start, (start_line, start_column));
+                self.diagnostics.push(message);
+                let token_value = self.intern(cursor.slice(start));
                 tokens.push(Token::new(
-                    "~".to_string(),
-                    TokenType::BitNot,
+                    token_value,
+                    TokenType::Error,
                     start_line,
                     start_column,
                 ));
-            } else if c == '+' {
-                chars.next();
-                self.column += 1;
-                if let Some(&next_char) = chars.peek() {
-                    if next_char == '+' {
-                        tokens.push(Token::new(
-                            "++".to_string(),
-                            TokenType::Increment,
-                            start_line,
-                            start_column,
-                        ));
-                        self.column += 1;
-                        chars.next();
-                    } else if next_char == '=' {
-                        tokens.push(Token::new(
-                            "+=".to_string(),
-                            TokenType::AddAssign,
-                            start_line,
-                            start_column,
-                        ));
-                        self.column += 1;
-                        chars.next();
-                    } else {
-                        tokens.push(Token::new(
-                            "+".to_string(),
-                            TokenType::Add,
-                            start_line,
-                            start_column,
-                        ));
-                    }
-                }
-            } else if c == '-' {
-                chars.next();
-                self.column += 1;
-                if let Some(&next_char) = chars.peek() {
-                    if next_char.is_digit(10) {
-                        let mut number = String::new();
-
-                        let mut has_decimal_point = false;
-
-                        number.push('-');
-                        while let Some(&c) = chars.peek() {
-                            if c.is_digit(10) {
-                                number.push(c);
-                                chars.next();
-                                self.column += 1;
-                            } else if c == '.' && !has_decimal_point {
-                                number.push(c);
-                                chars.next();
-                                self.column += 1;
-                                has_decimal_point = true;
-                            } else {
-                                break;
-                            }
-                        }
-                        tokens.push(Token::new(
-                            number,
-                            TokenType::Number,
-                            start_line,
-                            start_column,
-                        ));
-                        continue;
-                    }
-                    if next_char == '-' {
-                        tokens.push(Token::new(
-                            "--".to_string(),
-                            TokenType::Decrement,
-                            start_line,
-                            start_column,
-                        ));
-                        self.column += 1;
-                        chars.next();
-                    } else if next_char == '=' {
-                        tokens.push(Token::new(
-                            "-=".to_string(),
-                            TokenType::SubAssign,
-                            start_line,
-                            start_column,
-                        ));
-                        self.column += 1;
-                        chars.next();
-                    } else if next_char == '>' {
-                        tokens.push(Token::new(
-                            "->".to_string(),
-                            TokenType::RightArrow,
-                            start_line,
-                            start_column,
-                        ));
-                        self.column += 1;
-                        chars.next();
-                    } else {
-                        tokens.push(Token::new(
-                            "-".to_string(),
-                            TokenType::Sub,
-                            start_line,
-                            start_column,
-                        ));
-                    }
-                }
-            } else if c == '*' {
-                chars.next();
-                self.column += 1;
-                if let Some(&next_char) = chars.peek() {
-                    if next_char == '=' {
-                        tokens.push(Token::new(
-                            "*=".to_string(),
-                            TokenType::MulAssign,
-                            start_line,
-                            start_column,
-                        ));
-                        self.column += 1;
-                        chars.next();
-                    } else {
-                        tokens.push(Token::new(
-                            "*".to_string(),
-                            TokenType::Mul,
-                            start_line,
-                            start_column,
-                        ));
-                    }
-                }
-            } else if c == ':' {
-                chars.next();
-                self.column += 1;
-                if let Some(&next_char) = chars.peek() {
-                    if next_char == ':' {
-                        tokens.push(Token::new(
-                            "::".to_string(),
-                            TokenType::ScopeResolution,
-                            start_line,
-                            start_column,
-                        ));
-                        self.column += 1;
-                        chars.next();
-                    } else {
-                        tokens.push(Token::new(
-                            ":".to_string(),
-                            TokenType::Colon,
-                            start_line,
-                            start_column,
-                        ));
-                    }
+            }
+            self.eof_line = start_line;
+            self.eof_column = start_column;
+        }
+        close_spans(
+            source,
+            &mut tokens[unspanned..],
+            token_start,
+            cursor.source_pos(cursor.pos),
+        );
+    }
+
+    // 字句を1つ読み取ってtokensに追加する
+    fn read_token(
+        &mut self,
+        cursor: &mut Cursor,
+        tokens: &mut Vec<Token>,
+        source: &SourceFile,
+        c: char,
+    ) -> R<(), ScriptError> {
+        let start = cursor.pos;
+        let token_start = cursor.source_pos(start);
+        let start_line = self.line();
+        let start_column = self.column();
+        if c.is_ascii_digit() || c == '-' && cursor.byte_at(1).is_ascii_digit() {
+            if c == '-' {
+                self.advance(cursor);
+            }
+            self.read_number(cursor, tokens, source, start, (start_line, start_column))?;
+        } else if c == 'r' && self.is_raw_string_start(cursor) {
+            self.advance(cursor); // 'r' をスキップ
+            let string = self.read_raw_string(cursor, start_line, start_column)?;
+            tokens.push(Token::new(
+                string,
+                TokenType::DoubleQuote,
+                start_line,
+                start_column,
+            ));
+        } else if c == 'f' && cursor.byte_at(1) == b'"' {
+            self.advance(cursor); // 'f' をスキップ
+            self.advance(cursor); // 開始のクォートをスキップ
+            let raw_start = cursor.pos;
+            let (raw, line, column) =
+                self.read_quoted(cursor, '"', Embed::Brace, start_line, start_column)?;
+            self.tokenize_interpolation(
+                tokens,
+                raw,
+                source,
+                (line, column, cursor.source_pos(raw_start)),
+                true,
+                (start_line, start_column, token_start),
+            )?;
+        } else if c.is_alphanumeric() || c == '_' {
+            while let Some(c) = cursor.peek() {
+                if !(c.is_alphanumeric() || c == '_') {
+                    break;
                 }
-            } else if self.is_symbol(c) {
-                let token_type = match c {
-                    '(' => TokenType::LeftParen,
-                    ')' => TokenType::RightParen,
-                    '{' => TokenType::LeftCurlyBrace,
-                    '}' => TokenType::RightCurlyBrace,
-                    '[' => TokenType::LeftSquareBrace,
-                    ']' => TokenType::RightSquareBrace,
-                    ',' => TokenType::Conma,
-                    '=' => TokenType::Equals,
-                    '@' => TokenType::AtSign,
-                    ';' => TokenType::Semi,
-                    _ => {
-                        return Err(compile_error!(
-                            "error",
-                            start_line,
-                            start_column,
-                            &self.input_path.clone(),
-                            &self.input_content.clone(),
-                            "Unexpected symbol"
-                        ));
-                    }
-                };
-                tokens.push(Token::new(
-                    c.to_string(),
-                    token_type,
+                self.advance(cursor);
+            }
+            let ident = cursor.slice(start);
+            // キーワードの表にある語はキーワードのトークンにする
+            let token_type = match Keyword::from_word(ident) {
+                Some(keyword) => TokenType::Keyword(keyword),
+                None => TokenType::Ident,
+            };
+            let token_value = self.intern(ident);
+            tokens.push(Token::new(
+                token_value,
+                token_type,
+                start_line,
+                start_column,
+            ));
+        } else if c == '\'' {
+            self.advance(cursor); // 開始のクォートをスキップ
+            let (raw, line, column) =
+                self.read_quoted(cursor, '\'', Embed::None, start_line, start_column)?;
+            let string = self.string_value(raw, line, column)?;
+            // 文字リテラルはUnicodeスカラー値ちょうど1つ
+            let count = string.chars().count();
+            if count != 1 {
+                return Err(compile_error!(
+                    code = "E0005",
+                    width = raw.chars().count() + 2,
+                    "error",
                     start_line,
                     start_column,
+                    &self.input_path,
+                    &self.input_content,
+                    "{}",
+                    msg!("lex.char_literal_length", count)
                 ));
-                chars.next();
-                self.column += 1;
+            }
+            tokens.push(Token::new(
+                string,
+                TokenType::SingleQuote,
+                start_line,
+                start_column,
+            ));
+        } else if c == '"' {
+            let string = if cursor.byte_at(1) == b'"' && cursor.byte_at(2) == b'"' {
+                for _ in 0..3 {
+                    self.advance(cursor); // 開始の """ をスキップ
+                }
+                Arc::from(self.read_triple_quoted(cursor, start_line, start_column)?)
             } else {
+                self.advance(cursor); // 開始のクォートをスキップ
+                let raw_start = cursor.pos;
+                let (raw, line, column) =
+                    self.read_quoted(cursor, '"', Embed::Dollar, start_line, start_column)?;
+                if raw.contains("${") {
+                    self.tokenize_interpolation(
+                        tokens,
+                        raw,
+                        source,
+                        (line, column, cursor.source_pos(raw_start)),
+                        false,
+                        (start_line, start_column, token_start),
+                    )?;
+                    return Ok(());
+                }
+                self.string_value(raw, line, column)?
+            };
+            tokens.push(Token::new(
+                string,
+                TokenType::DoubleQuote,
+                start_line,
+                start_column,
+            ));
+        } else if c == '/' && cursor.byte_at(1) == b'/' {
+            // /// はドキュメントコメント(//// 以上は通常のコメント)
+            let is_doc = cursor.byte_at(2) == b'/' && cursor.byte_at(3) != b'/';
+            self.advance(cursor);
+            self.advance(cursor);
+            if is_doc {
+                self.advance(cursor);
+            }
+            let body_start = cursor.pos;
+            while let Some(c) = cursor.peek() {
+                if c == '\n' {
+                    break;
+                }
+                self.advance(cursor);
+            }
+            let comment = cursor.slice(body_start).replace('\r', "");
+            let token_type = if is_doc {
+                let text = comment.strip_prefix(' ').unwrap_or(&comment).trim_end();
+                TokenType::DocComment(text.to_string())
+            } else {
+                TokenType::SingleComment(comment.clone(), (start_line, start_column))
+            };
+            let token_value = self.intern(&comment);
+            tokens.push(Token::new(
+                token_value,
+                token_type,
+                start_line,
+                start_column,
+            ));
+        } else if c == '/' && cursor.byte_at(1) == b'*' {
+            // /** */ はドキュメントコメント(/**/ と /*** 以上は通常のコメント)
+            let is_doc = cursor.byte_at(2) == b'*' && !matches!(cursor.byte_at(3), b'*' | b'/');
+            self.advance(cursor);
+            self.advance(cursor);
+            if is_doc {
+                self.advance(cursor);
+            }
+            let mut comment = String::new();
+            let mut lines = Vec::new();
+            let mut closed = false;
+            while let Some(c) = self.advance(cursor) {
+                if c == '*' && cursor.byte_at(0) == b'/' {
+                    self.advance(cursor); // '/' をスキップ
+                    closed = true;
+                    break;
+                }
+                match c {
+                    '\n' => lines.push(std::mem::take(&mut comment)),
+                    '\r' => {}
+                    '\t' => comment.push_str("    "), // タブを4つのスペースに変換
+                    _ => comment.push(c),
+                }
+            }
+            if !closed {
                 return Err(compile_error!(
+                    code = "E0002",
+                    width = usize::MAX, // 行末まで
                     "error",
                     start_line,
                     start_column,
-                    &self.input_path.clone(),
-                    &self.input_content.clone(),
-                    "Unable to process input_content"
+                    &self.input_path,
+                    &self.input_content,
+                    "{}",
+                    msg!("lex.unclosed_comment")
                 ));
             }
-            self.eof_line = start_line;
-            self.eof_column = start_column;
+
+            if !comment.is_empty() {
+                lines.push(comment);
+            }
+            let token_value = self.intern(&lines.join("\n"));
+            let token_type = if is_doc {
+                TokenType::DocComment(block_doc_text(&lines))
+            } else {
+                TokenType::MultiComment(lines, (start_line, start_column))
+            };
+            tokens.push(Token::new(
+                token_value,
+                token_type,
+                start_line,
+                start_column,
+            ));
+        } else if let Some((text, token_type)) = OPERATORS
+            .iter()
+            .find(|(text, _)| cursor.rest().starts_with(text))
+        {
+            for _ in 0..text.len() {
+                self.advance(cursor);
+            }
+            let token_value = self.intern(text);
+            tokens.push(Token::new(
+                token_value,
+                token_type.clone(),
+                start_line,
+                start_column,
+            ));
+        } else {
+            return Err(compile_error!(
+                code = "E0001",
+                "error",
+                start_line,
+                start_column,
+                &self.input_path,
+                &self.input_content,
+                "{}",
+                msg!("lex.unexpected_char", c)
+            ));
         }
+        Ok(())
+    }
 
-        Ok(tokens)
+    // エラーになった字句の後から読み直せるように読み取り位置を調整する
+    fn recover(&mut self, cursor: &mut Cursor, start: usize, (line, column): (usize, usize)) {
+        let lexeme = &cursor.input[start..];
+        let is_line_literal = lexeme.starts_with('\'')
+            || lexeme.starts_with("f\"")
+            || lexeme.starts_with('"') && !lexeme.starts_with("\"\"\"");
+        if cursor.pos == start {
+            // 読めない文字は1文字だけ読み飛ばす
+            self.advance(cursor);
+        } else if lexeme.starts_with(|c: char| c.is_ascii_digit() || c == '-') {
+            // 数値リテラルに続く英数字もまとめて1つの字句にする
+            while let Some(c) = cursor.peek() {
+                if !(c.is_alphanumeric() || c == '_') {
+                    break;
+                }
+                self.advance(cursor);
+            }
+        } else if is_line_literal && cursor.pos == cursor.input.len() {
+            // 閉じられていない文字列は、開始した行の終わりから読み直す
+            let end = start + lexeme.find('\n').unwrap_or(lexeme.len());
+            cursor.pos = end;
+            self.line = line;
+            self.column = column
+                + cursor.input[start..end]
+                    .chars()
+                    .filter(|&c| c != '\r')
+                    .count();
+        }
     }
-    pub fn from_tokenize(input_path: &str, input_content: String) -> R<Vec<Token>, String> {
+
+    pub fn from_tokenize(input_path: &str, input_content: String) -> R<Vec<Token>, ScriptError> {
         let mut lexer = Lexer::new();
         lexer.set_input_path(input_path.to_string());
         lexer.set_input_content(input_content);
         lexer.tokenize()
     }
-    pub fn tokenize(&mut self) -> R<Vec<Token>, String> {
+    // 字句のエラーがあればすべての診断をまとめてエラーとして返す
+    pub fn tokenize(&mut self) -> R<Vec<Token>, ScriptError> {
+        let tokens = self.tokenize_all();
+        match ScriptError::merge(self.diagnostics.clone()) {
+            Some(error) => Err(error),
+            None => Ok(tokens),
+        }
+    }
+    // 字句のエラーがあっても最後まで読み、Errorトークンを含むトークン列を返す
+    // 見つかった問題はdiagnosticsで参照できる
+    pub fn tokenize_all(&mut self) -> Vec<Token> {
         let mut all_tokens: Vec<Token> = Vec::new();
+        let source = with_source_map(|map| map.add_file(&self.input_path, &self.input_content));
+        self.diagnostics.clear();
 
         if self.input_content_vec.is_empty() {
-            all_tokens.extend(self.tokenize_string(&self.input_content.clone())?);
+            self.tokenize_source(&mut all_tokens, &source.content, &source, 0);
         } else {
-            for input_content in &self.input_content_vec.clone() {
-                all_tokens.extend(self.tokenize_string(input_content)?);
+            // 入力の一覧は複製せずに一時的に取り出して読む
+            let contents = std::mem::take(&mut self.input_content_vec);
+            for content in &contents {
+                self.tokenize_source(&mut all_tokens, content, &source, 0);
             }
+            self.input_content_vec = contents;
+        }
+        let end_byte = source.content.len();
+        let token_value = self.intern("");
+        all_tokens.push(
+            Token::new(
+                token_value,
+                TokenType::Eof,
+                self.eof_line(),
+                self.eof_column(),
+            )
+            .with_span(Span::new(source.id, end_byte, end_byte)),
+        );
+
+        all_tokens
+    }
+}
+
+// /** */ のドキュメントコメントの本文を整える
+// 各行の先頭の空白と * を1つ、続く空白を1つ取り除き、前後の空行は含めない
+fn block_doc_text(lines: &[String]) -> String {
+    let lines: Vec<&str> = lines
+        .iter()
+        .map(|line| {
+            let line = line.trim_start();
+            let line = line.strip_prefix('*').unwrap_or(line);
+            line.strip_prefix(' ').unwrap_or(line).trim_end()
+        })
+        .collect();
+    let first = lines.iter().position(|line| !line.is_empty());
+    let last = lines.iter().rposition(|line| !line.is_empty());
+    match (first, last) {
+        (Some(first), Some(last)) => lines[first..=last].join("\n"),
+        _ => String::new(),
+    }
+}
+
+// 範囲が未設定のトークンに、字句の開始位置から終了位置までの範囲を設定する
+fn close_spans(source: &SourceFile, tokens: &mut [Token], start: usize, end: usize) {
+    for token in tokens.iter_mut().filter(|token| token.span.is_dummy()) {
+        token.span = Span::new(source.id, start, end);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 字句(Eof を除く)と診断コード
+    fn lex(source: &str) -> (Vec<(TokenType, String)>, Vec<String>) {
+        let mut lexer = Lexer::new_with_value("test.sc", source.to_string());
+        let tokens = lexer
+            .tokenize_all()
+            .iter()
+            .filter(|token| token.token_type() != TokenType::Eof)
+            .map(|token| (token.token_type(), token.token_value()))
+            .collect();
+        let codes = lexer
+            .diagnostics()
+            .iter()
+            .flat_map(|error| error.diagnostics())
+            .filter_map(|diagnostic| diagnostic.code.clone())
+            .collect();
+        (tokens, codes)
+    }
+
+    fn string(value: &str) -> (TokenType, String) {
+        (TokenType::DoubleQuote, value.to_string())
+    }
+
+    #[test]
+    fn reads_every_keyword_as_a_keyword_token() {
+        for (spelling, keyword) in crate::types::KEYWORDS {
+            let (tokens, codes) = lex(spelling);
+            assert_eq!(
+                tokens,
+                [(TokenType::Keyword(*keyword), spelling.to_string())],
+                "{}",
+                spelling
+            );
+            assert!(codes.is_empty());
         }
-        all_tokens.push(Token::new(
-            String::from(""),
-            TokenType::Eof,
-            self.eof_line(),
-            self.eof_column(),
-        ));
+        // キーワードで始まるだけの語や大文字を含む語は識別子
+        let (tokens, _) = lex("lets value mutable_ If _v");
+        assert!(tokens
+            .iter()
+            .all(|(token_type, _)| *token_type == TokenType::Ident));
+        assert_eq!(tokens.len(), 5);
+    }
+
+    #[test]
+    fn unescapes_string_literals() {
+        let (tokens, codes) = lex(r#""a\n\t\"q\"\\ \u{1F600} \x41 \$ \0 \r \'""#);
+        assert_eq!(tokens, [string("a\n\t\"q\"\\ \u{1F600} A $ \0 \r '")]);
+        assert!(codes.is_empty());
+    }
+
+    #[test]
+    fn reports_invalid_escapes() {
+        let (tokens, codes) = lex(r#""\q" "\x80" "\u{D800}" "\u{110000}""#);
+        assert!(tokens
+            .iter()
+            .all(|(token_type, _)| *token_type == TokenType::Error));
+        assert_eq!(codes, ["E0003", "E0003", "E0003", "E0003"]);
+    }
+
+    #[test]
+    fn keeps_raw_strings_as_written() {
+        let (tokens, codes) = lex(r##"r"C:\dir\n" r#"say "hi""#"##);
+        assert_eq!(tokens, [string(r"C:\dir\n"), string(r#"say "hi""#)]);
+        assert!(codes.is_empty());
+        let (_, codes) = lex(r##"r#"unclosed""##);
+        assert_eq!(codes, ["E0002"]);
+    }
 
-        Ok(all_tokens)
+    #[test]
+    fn reads_multi_line_strings() {
+        let (tokens, codes) = lex("\"\"\"line1\n  \"line2\"\\t\"\"\"");
+        assert_eq!(tokens, [string("line1\n  \"line2\"\t")]);
+        assert!(codes.is_empty());
+        let (_, codes) = lex("\"\"\"line1\nline2");
+        assert_eq!(codes, ["E0002"]);
+    }
+
+    fn template(parts: &[(TokenType, &str)]) -> Vec<(TokenType, String)> {
+        parts
+            .iter()
+            .map(|(token_type, text)| (token_type.clone(), text.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn splits_interpolated_strings() {
+        let (tokens, codes) = lex(r#""hi ${name}, ${a + 1}!""#);
+        assert_eq!(
+            tokens,
+            template(&[
+                (TokenType::TemplateStart, ""),
+                (TokenType::TemplateText, "hi "),
+                (TokenType::TemplateExprStart, ""),
+                (TokenType::Ident, "name"),
+                (TokenType::TemplateExprEnd, ""),
+                (TokenType::TemplateText, ", "),
+                (TokenType::TemplateExprStart, ""),
+                (TokenType::Ident, "a"),
+                (TokenType::Add, "+"),
+                (TokenType::Number, "1"),
+                (TokenType::TemplateExprEnd, ""),
+                (TokenType::TemplateText, "!"),
+                (TokenType::TemplateEnd, ""),
+            ])
+        );
+        assert!(codes.is_empty());
+    }
+
+    #[test]
+    fn splits_format_strings() {
+        let (tokens, codes) = lex(r#"f"x={x} {{y}}""#);
+        assert_eq!(
+            tokens,
+            template(&[
+                (TokenType::TemplateStart, ""),
+                (TokenType::TemplateText, "x="),
+                (TokenType::TemplateExprStart, ""),
+                (TokenType::Ident, "x"),
+                (TokenType::TemplateExprEnd, ""),
+                (TokenType::TemplateText, " {y}"),
+                (TokenType::TemplateEnd, ""),
+            ])
+        );
+        assert!(codes.is_empty());
+        // 埋め込み式のない文字列はそのまま文字列になる
+        assert_eq!(lex(r#""cost: $5""#).0, [string("cost: $5")]);
+    }
+
+    #[test]
+    fn reads_char_literals() {
+        let (tokens, codes) = lex(r"'a' '\n' '\u{3042}' 'あ' '😀'");
+        let chars: Vec<_> = ["a", "\n", "あ", "あ", "😀"]
+            .iter()
+            .map(|value| (TokenType::SingleQuote, value.to_string()))
+            .collect();
+        assert_eq!(tokens, chars);
+        assert!(codes.is_empty());
+    }
+
+    #[test]
+    fn reports_char_literals_without_exactly_one_char() {
+        let (tokens, codes) = lex("'ab' ''");
+        assert!(tokens
+            .iter()
+            .all(|(token_type, _)| *token_type == TokenType::Error));
+        assert_eq!(codes, ["E0005", "E0005"]);
+    }
+
+    #[test]
+    fn reads_doc_comments() {
+        let (tokens, codes) =
+            lex("/// 説明\n//// 区切り\n// 普通\n/** ブロック\n * 2行目\n */\n/* 普通 */");
+        let kinds: Vec<_> = tokens
+            .into_iter()
+            .map(|(token_type, _)| match token_type {
+                TokenType::DocComment(text) => format!("doc {}", text),
+                TokenType::SingleComment(..) => "comment".to_string(),
+                TokenType::MultiComment(..) => "block".to_string(),
+                token_type => format!("{:?}", token_type),
+            })
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                "doc 説明",
+                "comment",
+                "comment",
+                "doc ブロック\n2行目",
+                "block"
+            ]
+        );
+        assert!(codes.is_empty());
+    }
+
+    #[test]
+    fn reports_invalid_interpolations() {
+        for source in [r#""${}""#, r#"f"{a""#, r#"f"a}""#] {
+            assert_eq!(lex(source).1, ["E0006"], "for {}", source);
+        }
+    }
+}
+
+#[cfg(test)]
+mod bench {
+    use super::Lexer;
+    use std::time::Instant;
+
+    // 字句解析の速度計測用に数MBのスクリプトを生成する
+    fn generate_script(target_bytes: usize) -> String {
+        let unit = r#"// 計測用の関数
+fn calc_{n}(mut a: i64, b: i64) -> i64 {
+    let values = [1, 2_000, 0xff, 3.5e2, 10u8];
+    let name = "tanuki_{n}";
+    let escaped = "line\n\t\"quoted\" \u{1F600}";
+    let text = "${name} has ${a + b} items";
+    /* 複数行の
+       コメント */
+    for value in values {
+        if a >= b && value != 0 { a += value * 2; } else { a -= 1; }
+    }
+    return a << 1;
+}
+"#;
+        let mut script = String::with_capacity(target_bytes + unit.len());
+        let mut n = 0;
+        while script.len() < target_bytes {
+            script.push_str(&unit.replace("{n}", &n.to_string()));
+            n += 1;
+        }
+        script
+    }
+
+    // cargo test --release lexer_throughput -- --ignored --nocapture
+    #[test]
+    #[ignore]
+    fn lexer_throughput() {
+        let script = generate_script(8 * 1024 * 1024);
+        let mb = script.len() as f64 / (1024.0 * 1024.0);
+        let mut best = f64::MAX;
+        let mut count = 0;
+        for _ in 0..5 {
+            let start = Instant::now();
+            let tokens = Lexer::from_tokenize("bench.sc", script.clone()).unwrap();
+            best = best.min(start.elapsed().as_secs_f64());
+            count = tokens.len();
+        }
+        println!(
+            "lexer: {:.1} MB, {} tokens, best {:.3} s, {:.1} MB/s",
+            mb,
+            count,
+            best,
+            mb / best
+        );
     }
 }
@@ -0,0 +1,16 @@
+pub mod context;
+pub mod decoder;
+pub mod encoding;
+pub mod error;
+pub mod error_log;
+pub mod lexer;
+#[cfg(any(feature = "full", feature = "decoder"))]
+pub mod lsp;
+pub mod memory_mgr;
+pub mod messages;
+pub mod parser;
+#[cfg(any(feature = "full", feature = "decoder"))]
+pub mod repl;
+pub mod source_map;
+pub mod traits;
+pub mod types;
@@ -0,0 +1,837 @@
+use crate::decoder::checker::Checker;
+use crate::decoder::linter::Linter;
+use crate::error::{ErrorMessage, ScriptError};
+use crate::lexer::tokenizer::{Lexer, Token};
+use crate::msg;
+use crate::parser::syntax::{Node, Parser};
+use crate::source_map::{with_source_map, SourceFile, Span};
+use crate::types::*;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+// 宣言の種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolKind {
+    Function,
+    Callback,
+    Method,
+    Struct,
+    Field,
+    TypeAlias,
+    Variable,
+    Parameter,
+    LoopVariable,
+}
+
+// 宣言(範囲はいずれもファイル上のバイト位置の(開始,終了))
+#[derive(Debug, Clone)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    pub name_range: (usize, usize), // 名前の範囲
+    pub range: (usize, usize),      // 宣言全体の範囲
+    pub scope: usize,               // 宣言したスコープ
+    pub owner: Option<String>,      // フィールド・メソッドの構造体名、引数の関数名
+    pub type_name: Option<String>,  // 宣言した型(なければ初期値から推定した型)
+    pub is_inferred: bool,          // 型を初期値から推定したかどうか
+    pub params: Vec<String>,        // 関数の引数の表記
+    pub is_mutable: bool,
+    pub doc: Option<String>,
+}
+
+// 宣言が有効な範囲(0番はファイル全体、関数本体やブロックはその内側)
+#[derive(Debug, Clone)]
+pub struct Scope {
+    pub range: (usize, usize),
+    pub parent: Option<usize>,
+}
+
+// 名前の参照先
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Target {
+    Symbol(usize), // 同じファイルのローカルな宣言
+    Global,        // トップレベルの宣言(インクルードしたファイルも含めて名前で探す)
+    Builtin,       // 組み込み関数
+}
+
+// 名前の参照
+#[derive(Debug, Clone)]
+pub struct Reference {
+    pub name: String,
+    pub range: (usize, usize),
+    pub target: Target,
+}
+
+// @include で読み込むファイル
+#[derive(Debug, Clone)]
+pub struct Include {
+    pub file_name: String,
+    pub range: (usize, usize),
+}
+
+// 位置にある名前
+#[derive(Debug, Clone, Copy)]
+pub enum Occurrence<'a> {
+    Declaration(usize),
+    Reference(&'a Reference),
+    Include(&'a Include),
+}
+
+// 1つのファイルを実行せずに解析した結果
+pub struct Analysis {
+    pub file: Arc<SourceFile>,
+    pub parsed: bool, // 構文木が得られたかどうか(構文エラーがあっても回復できた部分の宣言は持つ)
+    pub symbols: Vec<Symbol>,
+    pub scopes: Vec<Scope>,
+    pub references: Vec<Reference>,
+    pub includes: Vec<Include>,
+    pub diagnostics: Vec<ErrorMessage>, // 字句・構文のエラー、借用検査のエラー、lint の警告
+}
+
+impl Analysis {
+    pub fn new(file_name: &str, text: &str) -> Self {
+        // 編集のたびに解析し直すので、ソースマップには最新の内容だけを残す
+        let file = with_source_map(|map| map.replace_file(file_name, text));
+        let mut analysis = Analysis {
+            file,
+            parsed: false,
+            symbols: Vec::new(),
+            scopes: vec![Scope {
+                range: (0, text.len()),
+                parent: None,
+            }],
+            references: Vec::new(),
+            includes: Vec::new(),
+            diagnostics: Vec::new(),
+        };
+
+        let mut lexer = Lexer::new_with_value(file_name, text.to_string());
+        let tokens = lexer.tokenize_all();
+        // 字句・構文のエラーはファイル全体からまとめて報告する
+        let (node, parse_errors) = Parser::new(&tokens, file_name, text.to_string()).parse_all();
+        let errors = lexer
+            .diagnostics()
+            .into_iter()
+            .chain(parse_errors)
+            .collect();
+        let error = ScriptError::merge(errors);
+        let Some(node) = node else {
+            analysis.diagnostics = error.map(|e| e.diagnostics().to_vec()).unwrap_or_default();
+            return analysis;
+        };
+        analysis.parsed = true;
+        match error {
+            // 構文エラーがあっても、回復して読めた部分の宣言と参照は集める
+            // (借用検査と lint は不完全な構文木では誤った指摘をするので行わない)
+            Some(error) => analysis.diagnostics = error.diagnostics().to_vec(),
+            None => {
+                let checked = [
+                    Checker::check(&node, file_name, text),
+                    Linter::lint(file_name, text),
+                ];
+                analysis.diagnostics = checked
+                    .iter()
+                    .filter_map(|result| result.as_ref().err())
+                    .flat_map(|e| e.diagnostics().iter().cloned())
+                    .collect();
+            }
+        }
+        Collector::new(&tokens, &mut analysis).collect(&node);
+        analysis
+    }
+
+    pub fn text(&self) -> &str {
+        &self.file.content
+    }
+
+    // 位置にある宣言の名前・参照・インクルード(重なる場合は狭い方)
+    pub fn occurrence_at(&self, offset: usize) -> Option<Occurrence<'_>> {
+        let contains = |range: (usize, usize)| range.0 <= offset && offset <= range.1;
+        let width = |range: (usize, usize)| range.1 - range.0;
+        let declarations = self
+            .symbols
+            .iter()
+            .enumerate()
+            .filter(|(_, symbol)| contains(symbol.name_range))
+            .map(|(i, symbol)| (width(symbol.name_range), Occurrence::Declaration(i)));
+        let references = self
+            .references
+            .iter()
+            .filter(|reference| contains(reference.range))
+            .map(|reference| (width(reference.range), Occurrence::Reference(reference)));
+        let includes = self
+            .includes
+            .iter()
+            .filter(|include| contains(include.range))
+            .map(|include| (width(include.range), Occurrence::Include(include)));
+        declarations
+            .chain(references)
+            .chain(includes)
+            .min_by_key(|(width, _)| *width)
+            .map(|(_, occurrence)| occurrence)
+    }
+
+    // トップレベルで宣言した名前(フィールドとメソッドは名前だけでは参照できない)
+    pub fn globals(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.symbols.len())
+            .filter(|&i| self.symbols[i].scope == 0 && is_named(&self.symbols[i]))
+    }
+
+    pub fn find_global(&self, name: &str) -> Option<usize> {
+        self.globals().find(|&i| self.symbols[i].name == name)
+    }
+
+    // 位置から参照できる宣言(内側のスコープのものを優先し、同じ名前は1つにまとめる)
+    pub fn visible_symbols(&self, offset: usize) -> Vec<usize> {
+        let mut chain = Vec::new();
+        let mut scope = self.innermost_scope(offset);
+        while let Some(index) = scope {
+            chain.push(index);
+            scope = self.scopes[index].parent;
+        }
+        let mut visible: Vec<usize> = Vec::new();
+        for scope in chain {
+            let mut found: Vec<usize> = (0..self.symbols.len())
+                .filter(|&i| {
+                    let symbol = &self.symbols[i];
+                    // ローカル変数は宣言より後でだけ見える
+                    symbol.scope == scope
+                        && is_named(symbol)
+                        && (scope == 0 || symbol.name_range.1 <= offset)
+                })
+                .collect();
+            found.reverse();
+            for i in found {
+                if !visible
+                    .iter()
+                    .any(|&j| self.symbols[j].name == self.symbols[i].name)
+                {
+                    visible.push(i);
+                }
+            }
+        }
+        visible
+    }
+
+    fn innermost_scope(&self, offset: usize) -> Option<usize> {
+        self.scopes
+            .iter()
+            .enumerate()
+            .rev() // 同じ範囲なら後から入った(内側の)スコープ
+            .filter(|(_, scope)| scope.range.0 <= offset && offset <= scope.range.1)
+            .min_by_key(|(_, scope)| scope.range.1 - scope.range.0)
+            .map(|(i, _)| i)
+    }
+
+    // ホバーで表示する宣言の表記
+    pub fn signature(&self, index: usize) -> String {
+        let symbol = &self.symbols[index];
+        let typed = |name: &str| match &symbol.type_name {
+            Some(type_name) => format!("{}: {}", name, type_name),
+            None => name.to_string(),
+        };
+        let returns = match &symbol.type_name {
+            Some(type_name) => format!(" -> {}", type_name),
+            None => String::new(),
+        };
+        let mutable = if symbol.is_mutable { "mut " } else { "" };
+        match symbol.kind {
+            SymbolKind::Function => {
+                format!(
+                    "fn {}({}){}",
+                    symbol.name,
+                    symbol.params.join(", "),
+                    returns
+                )
+            }
+            SymbolKind::Callback => format!(
+                "callback {}({}){}",
+                symbol.name,
+                symbol.params.join(", "),
+                returns
+            ),
+            SymbolKind::Method => format!(
+                "fn {}::{}({}){}",
+                symbol.owner.as_deref().unwrap_or_default(),
+                symbol.name,
+                symbol.params.join(", "),
+                returns
+            ),
+            SymbolKind::Struct => {
+                let fields: Vec<String> = self
+                    .fields(&symbol.name)
+                    .map(|field| format!("    {},\n", self.signature(field)))
+                    .collect();
+                format!("struct {} {{\n{}}}", symbol.name, fields.concat())
+            }
+            SymbolKind::Field => typed(&symbol.name),
+            SymbolKind::TypeAlias => match &symbol.type_name {
+                Some(type_name) => format!("type {} = {}", symbol.name, type_name),
+                None => format!("type {}", symbol.name),
+            },
+            SymbolKind::Variable => format!("let {}{}", mutable, typed(&symbol.name)),
+            SymbolKind::Parameter | SymbolKind::LoopVariable => {
+                format!("{}{}", mutable, typed(&symbol.name))
+            }
+        }
+    }
+
+    // 宣言の説明(引数やフィールドの持ち主、型を推定したこと)
+    pub fn description(&self, index: usize) -> Option<String> {
+        let symbol = &self.symbols[index];
+        let owner = symbol.owner.as_deref().unwrap_or_default();
+        match symbol.kind {
+            SymbolKind::Parameter => Some(msg!("lsp.hover.parameter", owner)),
+            SymbolKind::Field => Some(msg!("lsp.hover.field", owner)),
+            SymbolKind::Method => Some(msg!("lsp.hover.method", owner)),
+            SymbolKind::LoopVariable => Some(msg!("lsp.hover.loop_variable")),
+            SymbolKind::Variable if symbol.is_inferred => Some(msg!("lsp.hover.inferred")),
+            _ => None,
+        }
+    }
+
+    // 構造体のフィールド(宣言順)
+    pub fn fields<'a>(&'a self, owner: &'a str) -> impl Iterator<Item = usize> + 'a {
+        (0..self.symbols.len()).filter(move |&i| {
+            self.symbols[i].kind == SymbolKind::Field
+                && self.symbols[i].owner.as_deref() == Some(owner)
+        })
+    }
+}
+
+// 名前だけで参照できる宣言かどうか
+fn is_named(symbol: &Symbol) -> bool {
+    !matches!(symbol.kind, SymbolKind::Field | SymbolKind::Method)
+}
+
+// 構文木をたどって宣言・スコープ・参照を集める
+struct Collector<'a> {
+    tokens: Vec<&'a Token>, // コメントを除いたトークン
+    analysis: &'a mut Analysis,
+    stack: Vec<(usize, HashMap<String, usize>)>, // 参照を解決するスコープ(内側が後ろ)
+    return_types: HashMap<String, String>,       // トップレベルの関数の戻り値の型
+}
+
+impl<'a> Collector<'a> {
+    fn new(tokens: &'a [Token], analysis: &'a mut Analysis) -> Self {
+        let tokens = tokens
+            .iter()
+            .filter(|token| {
+                !matches!(
+                    token.token_type(),
+                    TokenType::SingleComment(..)
+                        | TokenType::MultiComment(..)
+                        | TokenType::DocComment(_)
+                )
+            })
+            .collect();
+        Collector {
+            tokens,
+            analysis,
+            stack: vec![(0, HashMap::new())],
+            return_types: HashMap::new(),
+        }
+    }
+
+    fn collect(mut self, node: &Node) {
+        // 関数は宣言より前から呼び出せるので、戻り値の型を先に集める
+        for statement in node.iter() {
+            if let NodeValue::Declaration(Declaration::Function(name, _, _, return_type, _)) =
+                &statement.value
+            {
+                if let Some(type_name) = declared_type(return_type) {
+                    self.return_types.insert(name.clone(), type_name);
+                }
+            }
+        }
+        self.walk_statements(node);
+    }
+
+    fn walk_statements(&mut self, node: &Node) {
+        for statement in node.iter() {
+            self.walk(statement);
+        }
+    }
+
+    fn walk(&mut self, node: &Node) {
+        match &node.value {
+            NodeValue::Declaration(Declaration::Variable(
+                var_name,
+                data_type,
+                value,
+                _,
+                is_mutable,
+            )) => {
+                self.walk(value);
+                let NodeValue::Variable(_, name, _, _) = &var_name.value else {
+                    return;
+                };
+                let declared = declared_type(data_type);
+                let inferred = declared.is_none().then(|| self.infer(value)).flatten();
+                let symbol = self.symbol(name, SymbolKind::Variable, node, node.span);
+                self.declare(Symbol {
+                    is_inferred: inferred.is_some(),
+                    type_name: declared.or(inferred),
+                    is_mutable: *is_mutable,
+                    ..symbol
+                });
+            }
+            NodeValue::Declaration(Declaration::Function(name, args, body, return_type, _)) => {
+                self.walk_function(node, name, args, body, return_type, None)
+            }
+            NodeValue::Declaration(Declaration::CallBackFunction(
+                name,
+                args,
+                body,
+                return_type,
+                _,
+            )) => self.walk_function(node, name, args, body, return_type, None),
+            NodeValue::Declaration(Declaration::Struct(name, members)) => {
+                let symbol = self.symbol(name, SymbolKind::Struct, node, node.span);
+                self.declare(symbol);
+                for member in members {
+                    let NodeValue::Variable(data_type, field, _, _) = &member.value else {
+                        continue;
+                    };
+                    let symbol = self.symbol(field, SymbolKind::Field, member, member.span);
+                    self.declare(Symbol {
+                        owner: Some(name.clone()),
+                        type_name: declared_type(data_type),
+                        ..symbol
+                    });
+                }
+            }
+            NodeValue::Declaration(Declaration::Impl(name, members)) => {
+                self.reference(name, node.span, Target::Global);
+                for member in members {
+                    if let NodeValue::Declaration(Declaration::Function(
+                        method,
+                        args,
+                        body,
+                        return_type,
+                        _,
+                    )) = &member.value
+                    {
+                        self.walk_function(member, method, args, body, return_type, Some(name));
+                    }
+                }
+            }
+            NodeValue::Declaration(Declaration::Type(name, value)) => {
+                let name = match &name.value {
+                    NodeValue::Variable(_, name, _, _) => name,
+                    NodeValue::DataType(DataType::String(name)) => name,
+                    _ => return,
+                };
+                let symbol = self.symbol(name, SymbolKind::TypeAlias, node, node.span);
+                self.declare(Symbol {
+                    type_name: declared_type(value),
+                    ..symbol
+                });
+            }
+            NodeValue::Variable(_, name, _, _) => {
+                let target = self.resolve(name);
+                self.reference(name, node.span, target);
+            }
+            NodeValue::Call(name, args, is_system) => {
+                let target = if *is_system {
+                    Target::Builtin
+                } else {
+                    self.resolve(name)
+                };
+                self.reference(name, node.span, target);
+                for arg in args {
+                    self.walk(arg);
+                }
+            }
+            NodeValue::Block(block) => {
+                let range = self.block_range(node.span);
+                self.enter_scope(range);
+                for statement in block {
+                    self.walk_statements(statement);
+                }
+                self.stack.pop();
+            }
+            NodeValue::ControlFlow(ControlFlow::For(variable, iterator, body)) => {
+                self.walk(iterator);
+                self.enter_scope(range(node.span));
+                if let NodeValue::Variable(_, name, _, _) = &variable.value {
+                    let symbol = self.symbol(name, SymbolKind::LoopVariable, variable, node.span);
+                    let type_name = match &iterator.value {
+                        NodeValue::Operator(Operator::Range(start, _)) => self.infer(start),
+                        _ => None,
+                    };
+                    self.declare(Symbol {
+                        type_name,
+                        ..symbol
+                    });
+                }
+                self.walk(body);
+                self.stack.pop();
+            }
+            NodeValue::Include(file_name) => self.analysis.includes.push(Include {
+                file_name: file_name.clone(),
+                range: range(node.span),
+            }),
+            _ => {
+                for child in node.children() {
+                    self.walk(child);
+                }
+            }
+        }
+    }
+
+    fn walk_function(
+        &mut self,
+        node: &Node,
+        name: &str,
+        args: &[(Box<Node>, String, bool)],
+        body: &Node,
+        return_type: &Node,
+        owner: Option<&String>,
+    ) {
+        let kind = match (&node.value, owner) {
+            (_, Some(_)) => SymbolKind::Method,
+            (NodeValue::Declaration(Declaration::CallBackFunction(..)), _) => SymbolKind::Callback,
+            _ => SymbolKind::Function,
+        };
+        let function = self.symbol(name, kind, node, node.span);
+
+        // 引数は関数名より後に順に並んでいる
+        let mut cursor = function.name_range.1;
+        let mut params = Vec::new();
+        for (_, param, is_mutable) in args {
+            let found = self.find_token(param, cursor, node.span.end_byte);
+            let name_range = found.map_or(range(node.span), |i| token_range(self.tokens[i]));
+            cursor = name_range.1;
+            let type_name = found.and_then(|i| self.param_type(i));
+            params.push(Symbol {
+                name: param.clone(),
+                kind: SymbolKind::Parameter,
+                name_range,
+                range: name_range,
+                scope: 0,
+                owner: Some(name.to_string()),
+                type_name,
+                is_inferred: false,
+                params: Vec::new(),
+                is_mutable: *is_mutable,
+                doc: None,
+            });
+        }
+        self.declare(Symbol {
+            owner: owner.cloned(),
+            type_name: declared_type(return_type),
+            params: params.iter().map(param_signature).collect(),
+            ..function
+        });
+
+        // 関数本体からは呼び出し元のローカル変数は見えない(グローバルは名前で探す)
+        let outer = std::mem::take(&mut self.stack);
+        self.stack.push((0, HashMap::new()));
+        self.enter_scope(range(node.span));
+        for param in params {
+            self.declare(param);
+        }
+        self.walk(body);
+        self.stack = outer;
+    }
+
+    // 宣言の名前をトークンから探して宣言を作る(スコープは declare で決める)
+    fn symbol(&self, name: &str, kind: SymbolKind, node: &Node, within: Span) -> Symbol {
+        let name_range = self
+            .find_token(name, within.start_byte, within.end_byte)
+            .map_or(range(within), |i| token_range(self.tokens[i]));
+        Symbol {
+            name: name.to_string(),
+            kind,
+            name_range,
+            range: range(node.span),
+            scope: 0,
+            owner: None,
+            type_name: None,
+            is_inferred: false,
+            params: Vec::new(),
+            is_mutable: false,
+            doc: node.doc.clone(),
+        }
+    }
+
+    fn declare(&mut self, mut symbol: Symbol) -> usize {
+        let index = self.analysis.symbols.len();
+        let (scope, names) = self.stack.last_mut().expect("scope stack is never empty");
+        symbol.scope = *scope;
+        if is_named(&symbol) {
+            names.insert(symbol.name.clone(), index);
+        }
+        self.analysis.symbols.push(symbol);
+        index
+    }
+
+    fn enter_scope(&mut self, range: (usize, usize)) {
+        let parent = self.stack.last().map_or(0, |(scope, _)| *scope);
+        self.analysis.scopes.push(Scope {
+            range,
+            parent: Some(parent),
+        });
+        self.stack
+            .push((self.analysis.scopes.len() - 1, HashMap::new()));
+    }
+
+    fn resolve(&self, name: &str) -> Target {
+        // トップレベルの宣言は位置に関係なく名前で探す(関数は宣言より前から呼べる)
+        self.stack[1..]
+            .iter()
+            .rev()
+            .find_map(|(_, names)| names.get(name))
+            .map_or(Target::Global, |&index| Target::Symbol(index))
+    }
+
+    fn reference(&mut self, name: &str, within: Span, target: Target) {
+        let range = self
+            .find_token(name, within.start_byte, within.end_byte)
+            .map_or(range(within), |i| token_range(self.tokens[i]));
+        self.analysis.references.push(Reference {
+            name: name.to_string(),
+            range,
+            target,
+        });
+    }
+
+    // 範囲の中で最初に現れる、指定した綴りの識別子
+    fn find_token(&self, name: &str, start: usize, end: usize) -> Option<usize> {
+        let first = self
+            .tokens
+            .partition_point(|token| token.span().start_byte < start);
+        (first..self.tokens.len())
+            .take_while(|&i| self.tokens[i].span().end_byte <= end)
+            .find(|&i| {
+                self.tokens[i].token_type() == TokenType::Ident && self.tokens[i].text() == name
+            })
+    }
+
+    // 引数名に続く「: 型」の表記
+    fn param_type(&self, index: usize) -> Option<String> {
+        if self.tokens.get(index + 1)?.token_type() != TokenType::Colon {
+            return None;
+        }
+        let mut depth = 0usize;
+        let mut type_name = String::new();
+        for token in &self.tokens[index + 2..] {
+            match token.token_type() {
+                TokenType::Conma | TokenType::RightParen if depth == 0 => break,
+                TokenType::LeftSquareBrace | TokenType::LeftParen => depth += 1,
+                TokenType::RightSquareBrace | TokenType::RightParen => {
+                    depth = depth.saturating_sub(1)
+                }
+                _ => {}
+            }
+            // 語と語の間だけ空白を入れる(&mut i32, [i32])
+            let is_word = |c: char| c.is_alphanumeric() || c == '_';
+            if type_name.ends_with(is_word) && token.text().starts_with(is_word) {
+                type_name.push(' ');
+            }
+            type_name.push_str(token.text());
+        }
+        Some(type_name).filter(|type_name| !type_name.is_empty())
+    }
+
+    // ブロックの範囲を波括弧まで広げる(文のないところでもスコープに入るように)
+    fn block_range(&self, span: Span) -> (usize, usize) {
+        let first = self
+            .tokens
+            .partition_point(|token| token.span().start_byte < span.start_byte);
+        let start = self.tokens[..first]
+            .iter()
+            .rev()
+            .find(|token| token.token_type() == TokenType::LeftCurlyBrace)
+            .map_or(span.start_byte, |token| token.span().start_byte);
+        let last = self
+            .tokens
+            .partition_point(|token| token.span().start_byte < span.end_byte);
+        let end = self.tokens[last..]
+            .iter()
+            .find(|token| token.token_type() == TokenType::RightCurlyBrace)
+            .map_or(span.end_byte, |token| token.span().end_byte);
+        (start, end)
+    }
+
+    // 初期値から型を推定する(分からなければNone)
+    fn infer(&self, node: &Node) -> Option<String> {
+        match &node.value {
+            NodeValue::DataType(data_type) => data_type.literal_type(),
+            NodeValue::Interpolation(_) => Some("string".to_string()),
+            NodeValue::Declaration(Declaration::Array(_, values)) => values
+                .first()
+                .and_then(|value| self.infer(value))
+                .map(|type_name| format!("[{}]", type_name)),
+            NodeValue::Variable(_, name, is_mutable, is_reference) => {
+                let Target::Symbol(index) = self.resolve(name) else {
+                    return None;
+                };
+                let type_name = self.analysis.symbols[index].type_name.clone()?;
+                Some(match (is_reference, is_mutable) {
+                    (true, true) => format!("&mut {}", type_name),
+                    (true, false) => format!("&{}", type_name),
+                    _ => type_name,
+                })
+            }
+            NodeValue::Call(name, _, false) => self.return_types.get(name).cloned(),
+            NodeValue::Operator(operator) => match operator {
+                Operator::Eq(..)
+                | Operator::Ne(..)
+                | Operator::Lt(..)
+                | Operator::Gt(..)
+                | Operator::Le(..)
+                | Operator::Ge(..)
+                | Operator::And(..)
+                | Operator::Or(..) => Some("bool".to_string()),
+                Operator::Add(a, b)
+                | Operator::Sub(a, b)
+                | Operator::Mul(a, b)
+                | Operator::Div(a, b) => self.infer(a).or_else(|| self.infer(b)),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+// 引数の表記(mut 名前: 型)
+fn param_signature(param: &Symbol) -> String {
+    let mutable = if param.is_mutable { "mut " } else { "" };
+    match &param.type_name {
+        Some(type_name) => format!("{}{}: {}", mutable, param.name, type_name),
+        None => format!("{}{}", mutable, param.name),
+    }
+}
+
+fn range(span: Span) -> (usize, usize) {
+    (span.start_byte, span.end_byte)
+}
+
+fn token_range(token: &Token) -> (usize, usize) {
+    range(token.span())
+}
+
+// 宣言に書かれた型名
+fn declared_type(node: &Node) -> Option<String> {
+    match &node.value {
+        NodeValue::DataType(DataType::String(type_name)) if !type_name.is_empty() => {
+            Some(type_name.clone())
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "/// 足し算\nfn add(a: i32, mut b) -> i32 {\n    let c: i32 = a + b;\n    return c;\n}\nlet g = 1.5;\n@println(add(1, 2));\n";
+
+    fn symbol(analysis: &Analysis, name: &str) -> usize {
+        analysis
+            .symbols
+            .iter()
+            .position(|symbol| symbol.name == name)
+            .unwrap()
+    }
+
+    #[test]
+    fn collects_declarations() {
+        let analysis = Analysis::new("analysis_test.sc", SOURCE);
+        assert!(analysis.parsed);
+        let add = symbol(&analysis, "add");
+        assert_eq!(analysis.signature(add), "fn add(a: i32, mut b) -> i32");
+        assert_eq!(analysis.symbols[add].doc.as_deref(), Some("足し算"));
+        let g = symbol(&analysis, "g");
+        assert_eq!(analysis.symbols[g].type_name.as_deref(), Some("f32"));
+        assert!(analysis.symbols[g].is_inferred);
+    }
+
+    #[test]
+    fn infers_the_same_types_as_the_interpreter() {
+        let source =
+            "let a = 1;\nlet b = 5000000000;\nlet c = 1.5;\nlet d = \"s\";\nlet e = true;\n";
+        let analysis = Analysis::new("analysis_infer_test.sc", source);
+        let mut decoder = crate::decoder::interpreter::Decoder::new();
+        decoder.eval_text("analysis_infer_test.sc", source).unwrap();
+        for name in ["a", "b", "c", "d", "e"] {
+            let value = decoder.eval_text("analysis_infer_test.sc", name).unwrap();
+            assert_eq!(
+                analysis.symbols[symbol(&analysis, name)].type_name,
+                Some(decoder.infer_type(&value)),
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn resolves_references() {
+        let analysis = Analysis::new("analysis_test.sc", SOURCE);
+        let offset = SOURCE.find("return c").unwrap() + "return ".len();
+        let Some(Occurrence::Reference(reference)) = analysis.occurrence_at(offset) else {
+            panic!("no reference at {}", offset);
+        };
+        assert_eq!(reference.target, Target::Symbol(symbol(&analysis, "c")));
+        let offset = SOURCE.find("@println").unwrap() + 1;
+        let Some(Occurrence::Reference(reference)) = analysis.occurrence_at(offset) else {
+            panic!("no reference at {}", offset);
+        };
+        assert_eq!(reference.target, Target::Builtin);
+    }
+
+    #[test]
+    fn local_symbols_are_visible_only_inside_their_scope() {
+        let analysis = Analysis::new("analysis_test.sc", SOURCE);
+        let names = |offset: usize| -> Vec<String> {
+            analysis
+                .visible_symbols(offset)
+                .into_iter()
+                .map(|i| analysis.symbols[i].name.clone())
+                .collect()
+        };
+        let inside = names(SOURCE.find("return c").unwrap());
+        assert!(["a", "b", "c", "add", "g"]
+            .iter()
+            .all(|name| inside.iter().any(|n| n == name)));
+        let outside = names(SOURCE.find("@println").unwrap());
+        assert!(!outside.iter().any(|n| n == "c" || n == "a"));
+    }
+
+    #[test]
+    fn declarations_after_a_syntax_error_still_resolve() {
+        let source = "let x = 1 +;\nfn after(n) {\n    return n;\n}\n@println(after(1));\n";
+        let analysis = Analysis::new("analysis_recover_test.sc", source);
+        assert!(analysis.parsed);
+        assert!(analysis
+            .diagnostics
+            .iter()
+            .any(|diagnostic| diagnostic.code.as_deref() == Some("E0101")));
+        let after = symbol(&analysis, "after");
+        let offset = source.rfind("after").unwrap() + 1;
+        let Some(Occurrence::Reference(reference)) = analysis.occurrence_at(offset) else {
+            panic!("no reference at {}", offset);
+        };
+        assert_eq!(reference.target, Target::Global);
+        assert_eq!(analysis.find_global("after"), Some(after));
+        let offset = source.find("return n").unwrap() + "return ".len();
+        let Some(Occurrence::Reference(reference)) = analysis.occurrence_at(offset) else {
+            panic!("no reference at {}", offset);
+        };
+        assert_eq!(reference.target, Target::Symbol(symbol(&analysis, "n")));
+    }
+
+    #[test]
+    fn syntax_errors_are_reported_as_diagnostics() {
+        let analysis = Analysis::new("analysis_test.sc", "let x = 1 +;\n");
+        assert!(!analysis.diagnostics.is_empty());
+        // 構文エラーがある間は lint の警告を出さない
+        assert!(analysis.diagnostics.iter().all(|diagnostic| !diagnostic
+            .code
+            .as_deref()
+            .unwrap_or_default()
+            .starts_with('W')));
+    }
+}
@@ -0,0 +1,610 @@
+mod analysis;
+mod workspace;
+
+use crate::decoder::interpreter::BUILTINS;
+use crate::error::{ErrorMessage, Label};
+use crate::messages;
+use crate::msg;
+use crate::types::KEYWORDS;
+use analysis::{Analysis, Occurrence, SymbolKind, Target};
+use anyhow::Result as R;
+use log::info;
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response};
+use lsp_types::notification::{
+    DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument,
+    Notification as NotificationMethod, PublishDiagnostics,
+};
+use lsp_types::request::{
+    Completion, DocumentSymbolRequest, GotoDefinition, HoverRequest, References,
+    Request as RequestMethod,
+};
+use lsp_types::*;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use workspace::{Definition, Workspace};
+
+// ホバーで表示するコードブロックの言語名
+const LANGUAGE_ID: &str = "tanucc";
+
+// 標準入出力でエディタと通信する(exit の通知を受けるまで戻らない)
+pub fn run() -> R<(), String> {
+    let (connection, io_threads) = Connection::stdio();
+    serve(connection)?;
+    io_threads.join().map_err(|e| e.to_string())
+}
+
+// 初期化から shutdown までの要求に応える
+pub fn serve(connection: Connection) -> R<(), String> {
+    let capabilities = serde_json::to_value(capabilities()).map_err(|e| e.to_string())?;
+    let params = connection
+        .initialize(capabilities)
+        .map_err(|e| e.to_string())?;
+    let params: InitializeParams = serde_json::from_value(params).map_err(|e| e.to_string())?;
+    // エディタの表示言語があればメッセージもそれに合わせる
+    if let Some(locale) = params
+        .locale
+        .as_deref()
+        .and_then(messages::Locale::from_name)
+    {
+        messages::set_locale(locale);
+    }
+    let server = Server {
+        connection,
+        workspace: Workspace::new(workspace_root(&params)),
+    };
+    server.main_loop()
+}
+
+fn capabilities() -> ServerCapabilities {
+    ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        definition_provider: Some(OneOf::Left(true)),
+        references_provider: Some(OneOf::Left(true)),
+        document_symbol_provider: Some(OneOf::Left(true)),
+        completion_provider: Some(CompletionOptions {
+            trigger_characters: Some(vec!["@".to_string()]),
+            ..CompletionOptions::default()
+        }),
+        ..ServerCapabilities::default()
+    }
+}
+
+#[allow(deprecated)]
+fn workspace_root(params: &InitializeParams) -> Option<PathBuf> {
+    let uri = match &params.workspace_folders {
+        Some(folders) if !folders.is_empty() => &folders[0].uri,
+        _ => params.root_uri.as_ref()?,
+    };
+    uri.to_file_path().ok()
+}
+
+// 参照を探す対象
+enum Wanted {
+    Declaration(Definition),
+    Builtin(String),
+}
+
+struct Server {
+    connection: Connection,
+    workspace: Workspace,
+}
+
+impl Server {
+    fn main_loop(mut self) -> R<(), String> {
+        let receiver = self.connection.receiver.clone();
+        for message in &receiver {
+            match message {
+                Message::Request(request) => {
+                    if self
+                        .connection
+                        .handle_shutdown(&request)
+                        .map_err(|e| e.to_string())?
+                    {
+                        return Ok(());
+                    }
+                    let response = self.handle_request(request);
+                    self.send(Message::Response(response))?;
+                }
+                Message::Notification(notification) => self.handle_notification(notification)?,
+                Message::Response(_) => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn send(&self, message: Message) -> R<(), String> {
+        self.connection
+            .sender
+            .send(message)
+            .map_err(|e| e.to_string())
+    }
+
+    fn handle_request(&mut self, request: Request) -> Response {
+        info!("lsp request: {}", request.method);
+        let id = request.id.clone();
+        let result = match request.method.as_str() {
+            HoverRequest::METHOD => params(&request).map(|p| to_json(self.hover(p))),
+            GotoDefinition::METHOD => params(&request).map(|p| to_json(self.definition(p))),
+            References::METHOD => params(&request).map(|p| to_json(self.references(p))),
+            DocumentSymbolRequest::METHOD => {
+                params(&request).map(|p| to_json(self.document_symbols(p)))
+            }
+            Completion::METHOD => params(&request).map(|p| to_json(self.completion(p))),
+            method => {
+                return Response::new_err(
+                    id,
+                    ErrorCode::MethodNotFound as i32,
+                    msg!("lsp.unknown_request", method),
+                )
+            }
+        };
+        match result {
+            Ok(value) => Response::new_ok(id, value),
+            Err(e) => Response::new_err(id, ErrorCode::InvalidParams as i32, e),
+        }
+    }
+
+    fn handle_notification(&mut self, notification: Notification) -> R<(), String> {
+        match notification.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let p: DidOpenTextDocumentParams = notification_params(&notification)?;
+                let document = p.text_document;
+                if let Some(path) = to_path(&document.uri) {
+                    self.workspace.open(path.clone(), document.text);
+                    self.publish(&document.uri, &path, Some(document.version))?;
+                }
+            }
+            DidChangeTextDocument::METHOD => {
+                // 変更は常にファイル全体で受け取る(TextDocumentSyncKind::FULL)
+                let p: DidChangeTextDocumentParams = notification_params(&notification)?;
+                let document = p.text_document;
+                if let (Some(path), Some(change)) =
+                    (to_path(&document.uri), p.content_changes.into_iter().last())
+                {
+                    self.workspace.open(path.clone(), change.text);
+                    self.publish(&document.uri, &path, Some(document.version))?;
+                }
+            }
+            DidCloseTextDocument::METHOD => {
+                let p: DidCloseTextDocumentParams = notification_params(&notification)?;
+                if let Some(path) = to_path(&p.text_document.uri) {
+                    self.workspace.close(&path);
+                }
+                self.notify::<PublishDiagnostics>(PublishDiagnosticsParams::new(
+                    p.text_document.uri,
+                    Vec::new(),
+                    None,
+                ))?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn notify<N: NotificationMethod>(&self, params: N::Params) -> R<(), String> {
+        self.send(Message::Notification(Notification::new(
+            N::METHOD.to_string(),
+            params,
+        )))
+    }
+
+    // ファイルを解析して診断を送る
+    fn publish(&mut self, uri: &Url, path: &Path, version: Option<i32>) -> R<(), String> {
+        let Some(analysis) = self.workspace.analysis(path) else {
+            return Ok(());
+        };
+        let diagnostics = analysis
+            .diagnostics
+            .iter()
+            .map(|message| to_diagnostic(&analysis, uri, message))
+            .collect();
+        self.notify::<PublishDiagnostics>(PublishDiagnosticsParams::new(
+            uri.clone(),
+            diagnostics,
+            version,
+        ))
+    }
+
+    // 位置のファイルとその解析結果(構文エラーのある間は位置がずれるので使わない)
+    fn locate(
+        &mut self,
+        position: &TextDocumentPositionParams,
+    ) -> Option<(PathBuf, Arc<Analysis>, usize)> {
+        let path = to_path(&position.text_document.uri)?;
+        let analysis = self.workspace.analysis(&path).filter(|a| a.parsed)?;
+        let offset = to_offset(&analysis, position.position);
+        Some((path, analysis, offset))
+    }
+
+    fn hover(&mut self, params: HoverParams) -> Option<Hover> {
+        let (path, analysis, offset) = self.locate(&params.text_document_position_params)?;
+        let (range, contents) = match analysis.occurrence_at(offset)? {
+            Occurrence::Declaration(index) => (
+                analysis.symbols[index].name_range,
+                describe(&analysis, index),
+            ),
+            Occurrence::Reference(reference) => {
+                let contents = match reference.target {
+                    Target::Builtin => describe_builtin(&reference.name)?,
+                    target => {
+                        let definition = self.workspace.resolve(&path, &reference.name, target)?;
+                        let defined_in = self.workspace.parsed_analysis(&definition.path)?;
+                        describe(&defined_in, definition.index)
+                    }
+                };
+                (reference.range, contents)
+            }
+            Occurrence::Include(include) => {
+                let file = self.workspace.resolve_include(&path, &include.file_name)?;
+                (include.range, msg!("lsp.hover.include", file.display()))
+            }
+        };
+        Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: contents,
+            }),
+            range: Some(to_range(&analysis, range)),
+        })
+    }
+
+    fn definition(&mut self, params: GotoDefinitionParams) -> Option<GotoDefinitionResponse> {
+        let (path, analysis, offset) = self.locate(&params.text_document_position_params)?;
+        let location = match analysis.occurrence_at(offset)? {
+            Occurrence::Declaration(index) => self.location(&Definition { path, index })?,
+            Occurrence::Reference(reference) => {
+                let definition =
+                    self.workspace
+                        .resolve(&path, &reference.name, reference.target)?;
+                self.location(&definition)?
+            }
+            // インクルードしたファイルの先頭に移動する
+            Occurrence::Include(include) => {
+                let file = self.workspace.resolve_include(&path, &include.file_name)?;
+                Location::new(Url::from_file_path(file).ok()?, Range::default())
+            }
+        };
+        Some(GotoDefinitionResponse::Scalar(location))
+    }
+
+    fn location(&mut self, definition: &Definition) -> Option<Location> {
+        let analysis = self.workspace.parsed_analysis(&definition.path)?;
+        let symbol = analysis.symbols.get(definition.index)?;
+        Some(Location::new(
+            Url::from_file_path(&definition.path).ok()?,
+            to_range(&analysis, symbol.name_range),
+        ))
+    }
+
+    fn references(&mut self, params: ReferenceParams) -> Option<Vec<Location>> {
+        let (path, analysis, offset) = self.locate(&params.text_document_position)?;
+        let wanted = match analysis.occurrence_at(offset)? {
+            Occurrence::Declaration(index) => Wanted::Declaration(Definition { path, index }),
+            Occurrence::Reference(reference) if reference.target == Target::Builtin => {
+                Wanted::Builtin(reference.name.clone())
+            }
+            Occurrence::Reference(reference) => Wanted::Declaration(self.workspace.resolve(
+                &path,
+                &reference.name,
+                reference.target,
+            )?),
+            Occurrence::Include(_) => return None,
+        };
+        // ローカルな宣言は同じファイルの中だけを探す
+        let (name, files) = match &wanted {
+            Wanted::Declaration(definition) => {
+                let defined_in = self.workspace.parsed_analysis(&definition.path)?;
+                let symbol = defined_in.symbols.get(definition.index)?;
+                let files = if symbol.scope == 0 {
+                    self.workspace.files()
+                } else {
+                    vec![definition.path.clone()]
+                };
+                (symbol.name.clone(), files)
+            }
+            Wanted::Builtin(name) => (name.clone(), self.workspace.files()),
+        };
+
+        let mut locations = Vec::new();
+        for file in files {
+            let (Some(analysis), Ok(uri)) = (
+                self.workspace.parsed_analysis(&file),
+                Url::from_file_path(&file),
+            ) else {
+                continue;
+            };
+            if let Wanted::Declaration(definition) = &wanted {
+                if params.context.include_declaration && definition.path == file {
+                    let symbol = &analysis.symbols[definition.index];
+                    locations.push(Location::new(
+                        uri.clone(),
+                        to_range(&analysis, symbol.name_range),
+                    ));
+                }
+            }
+            for reference in analysis.references.iter().filter(|r| r.name == name) {
+                let found = match (&wanted, reference.target) {
+                    (Wanted::Builtin(_), target) => target == Target::Builtin,
+                    (Wanted::Declaration(_), Target::Builtin) => false,
+                    (Wanted::Declaration(definition), target) => {
+                        self.workspace
+                            .resolve(&file, &reference.name, target)
+                            .as_ref()
+                            == Some(definition)
+                    }
+                };
+                if found {
+                    locations.push(Location::new(
+                        uri.clone(),
+                        to_range(&analysis, reference.range),
+                    ));
+                }
+            }
+        }
+        Some(locations)
+    }
+
+    fn document_symbols(&mut self, params: DocumentSymbolParams) -> Option<DocumentSymbolResponse> {
+        let path = to_path(&params.text_document.uri)?;
+        let analysis = self.workspace.parsed_analysis(&path)?;
+        // トップレベルの宣言(構造体はフィールドを子に持つ)
+        let symbols = (0..analysis.symbols.len())
+            .filter(|&i| {
+                let symbol = &analysis.symbols[i];
+                symbol.scope == 0 && symbol.kind != SymbolKind::Field
+            })
+            .map(|i| {
+                let symbol = &analysis.symbols[i];
+                let children = (symbol.kind == SymbolKind::Struct).then(|| {
+                    analysis
+                        .fields(&symbol.name)
+                        .map(|field| document_symbol(&analysis, field, None))
+                        .collect()
+                });
+                document_symbol(&analysis, i, children)
+            })
+            .collect();
+        Some(DocumentSymbolResponse::Nested(symbols))
+    }
+
+    fn completion(&mut self, params: CompletionParams) -> Option<CompletionResponse> {
+        let position = params.text_document_position;
+        let path = to_path(&position.text_document.uri)?;
+        let current = self.workspace.analysis(&path)?;
+        let offset = to_offset(&current, position.position);
+
+        // 入力中の語の直前が @ なら組み込み関数とディレクティブだけを出す
+        let before = &current.text()[..offset];
+        let word_start = before
+            .trim_end_matches(|c: char| c.is_alphanumeric() || c == '_')
+            .len();
+        if before[..word_start].ends_with('@') {
+            let mut items: Vec<CompletionItem> = BUILTINS
+                .iter()
+                .map(|(name, params)| CompletionItem {
+                    label: name.to_string(),
+                    kind: Some(CompletionItemKind::FUNCTION),
+                    detail: Some(format!("@{}({})", name, params)),
+                    documentation: Some(Documentation::String(builtin_doc(name))),
+                    ..CompletionItem::default()
+                })
+                .collect();
+            items.push(keyword_item("include"));
+            return Some(CompletionResponse::Array(items));
+        }
+
+        let mut items: Vec<CompletionItem> = Vec::new();
+        // 構文エラーのある間は最後に成功した解析結果の名前を使う
+        if let Some(analysis) = self.workspace.parsed_analysis(&path) {
+            let offset = offset.min(analysis.text().len());
+            for index in analysis.visible_symbols(offset) {
+                push_symbol(&mut items, &analysis, index);
+            }
+        }
+        for file in self.workspace.included_files(&path).into_iter().skip(1) {
+            if let Some(analysis) = self.workspace.parsed_analysis(&file) {
+                for index in analysis.globals() {
+                    push_symbol(&mut items, &analysis, index);
+                }
+            }
+        }
+        // 1文字の別名(l, v)は補完しない
+        for (spelling, _) in KEYWORDS.iter().filter(|(spelling, _)| spelling.len() > 1) {
+            items.push(keyword_item(spelling));
+        }
+        Some(CompletionResponse::Array(items))
+    }
+}
+
+fn params<P: DeserializeOwned>(request: &Request) -> R<P, String> {
+    serde_json::from_value(request.params.clone())
+        .map_err(|e| msg!("lsp.invalid_params", request.method, e))
+}
+
+fn notification_params<P: DeserializeOwned>(notification: &Notification) -> R<P, String> {
+    serde_json::from_value(notification.params.clone())
+        .map_err(|e| msg!("lsp.invalid_params", notification.method, e))
+}
+
+fn to_json(value: impl Serialize) -> Value {
+    serde_json::to_value(value).unwrap_or_default()
+}
+
+fn to_path(uri: &Url) -> Option<PathBuf> {
+    uri.to_file_path().ok()
+}
+
+// バイト位置をLSPの位置(0始まりの行とUTF-16の列)にする
+fn to_position(analysis: &Analysis, byte: usize) -> Position {
+    let at = analysis.file.line_column(byte);
+    Position::new((at.line - 1) as u32, (at.utf16_column - 1) as u32)
+}
+
+fn to_range(analysis: &Analysis, (start, end): (usize, usize)) -> Range {
+    Range::new(to_position(analysis, start), to_position(analysis, end))
+}
+
+fn to_offset(analysis: &Analysis, position: Position) -> usize {
+    analysis
+        .file
+        .utf16_byte_offset(position.line as usize + 1, position.character as usize + 1)
+}
+
+// 診断の指摘(行・列は文字数で数える)の範囲
+fn label_range(analysis: &Analysis, label: &Label) -> Range {
+    let line = label.line.max(1);
+    let column = label.column.max(1);
+    let start = analysis.file.byte_offset(line, column);
+    let end = analysis
+        .file
+        .byte_offset(line, column.saturating_add(label.width));
+    to_range(analysis, (start, end))
+}
+
+fn to_diagnostic(analysis: &Analysis, uri: &Url, message: &ErrorMessage) -> Diagnostic {
+    let primary = message
+        .labels
+        .iter()
+        .find(|label| label.primary)
+        .or(message.labels.first());
+    // note と help は本文に続けて表示する
+    let mut text = message.message.clone();
+    for child in &message.children {
+        text.push_str(&format!("\n{}: {}", child.level, child.message));
+    }
+    let related: Vec<DiagnosticRelatedInformation> = message
+        .labels
+        .iter()
+        .filter(|label| !label.primary && !label.message.is_empty())
+        .map(|label| DiagnosticRelatedInformation {
+            location: Location::new(uri.clone(), label_range(analysis, label)),
+            message: label.message.clone(),
+        })
+        .collect();
+    Diagnostic {
+        range: primary.map_or(Range::default(), |label| label_range(analysis, label)),
+        severity: Some(match message.level.as_str() {
+            "error" => DiagnosticSeverity::ERROR,
+            "warning" => DiagnosticSeverity::WARNING,
+            _ => DiagnosticSeverity::INFORMATION,
+        }),
+        code: message.code.clone().map(NumberOrString::String),
+        source: Some(LANGUAGE_ID.to_string()),
+        message: text,
+        related_information: (!related.is_empty()).then_some(related),
+        ..Diagnostic::default()
+    }
+}
+
+// 宣言の表記・説明・ドキュメントコメント
+fn describe(analysis: &Analysis, index: usize) -> String {
+    let mut text = format!("```{}\n{}\n```", LANGUAGE_ID, analysis.signature(index));
+    if let Some(description) = analysis.description(index) {
+        text.push_str(&format!("\n\n{}", description));
+    }
+    if let Some(doc) = &analysis.symbols[index].doc {
+        text.push_str(&format!("\n\n---\n\n{}", doc));
+    }
+    text
+}
+
+fn describe_builtin(name: &str) -> Option<String> {
+    let (_, params) = BUILTINS.iter().find(|(builtin, _)| *builtin == name)?;
+    Some(format!(
+        "```{}\n@{}({})\n```\n\n{}\n\n---\n\n{}",
+        LANGUAGE_ID,
+        name,
+        params,
+        msg!("lsp.hover.builtin"),
+        builtin_doc(name)
+    ))
+}
+
+fn builtin_doc(name: &str) -> String {
+    msg!(&format!("builtin.doc.{}", name))
+}
+
+fn keyword_item(keyword: &str) -> CompletionItem {
+    CompletionItem {
+        label: keyword.to_string(),
+        kind: Some(CompletionItemKind::KEYWORD),
+        ..CompletionItem::default()
+    }
+}
+
+// 宣言を補完候補に加える(同じ名前は内側のスコープのものだけ)
+fn push_symbol(items: &mut Vec<CompletionItem>, analysis: &Analysis, index: usize) {
+    let symbol = &analysis.symbols[index];
+    if items.iter().any(|item| item.label == symbol.name) {
+        return;
+    }
+    items.push(CompletionItem {
+        label: symbol.name.clone(),
+        kind: Some(completion_kind(symbol.kind)),
+        detail: Some(analysis.signature(index)),
+        documentation: symbol.doc.clone().map(|doc| {
+            Documentation::MarkupContent(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: doc,
+            })
+        }),
+        ..CompletionItem::default()
+    });
+}
+
+fn completion_kind(kind: SymbolKind) -> CompletionItemKind {
+    match kind {
+        SymbolKind::Function | SymbolKind::Callback => CompletionItemKind::FUNCTION,
+        SymbolKind::Method => CompletionItemKind::METHOD,
+        SymbolKind::Struct => CompletionItemKind::STRUCT,
+        SymbolKind::Field => CompletionItemKind::FIELD,
+        SymbolKind::TypeAlias => CompletionItemKind::TYPE_PARAMETER,
+        SymbolKind::Variable | SymbolKind::Parameter | SymbolKind::LoopVariable => {
+            CompletionItemKind::VARIABLE
+        }
+    }
+}
+
+#[allow(deprecated)]
+fn document_symbol(
+    analysis: &Analysis,
+    index: usize,
+    children: Option<Vec<DocumentSymbol>>,
+) -> DocumentSymbol {
+    let symbol = &analysis.symbols[index];
+    let (name, kind) = match symbol.kind {
+        SymbolKind::Function | SymbolKind::Callback => {
+            (symbol.name.clone(), lsp_types::SymbolKind::FUNCTION)
+        }
+        SymbolKind::Method => (
+            format!(
+                "{}::{}",
+                symbol.owner.as_deref().unwrap_or_default(),
+                symbol.name
+            ),
+            lsp_types::SymbolKind::METHOD,
+        ),
+        SymbolKind::Struct => (symbol.name.clone(), lsp_types::SymbolKind::STRUCT),
+        SymbolKind::Field => (symbol.name.clone(), lsp_types::SymbolKind::FIELD),
+        SymbolKind::TypeAlias => (symbol.name.clone(), lsp_types::SymbolKind::TYPE_PARAMETER),
+        SymbolKind::Variable | SymbolKind::Parameter | SymbolKind::LoopVariable => {
+            (symbol.name.clone(), lsp_types::SymbolKind::VARIABLE)
+        }
+    };
+    // 構造体はフィールドを子として持つので、表記は1行のものだけを出す
+    let detail = (symbol.kind != SymbolKind::Struct).then(|| analysis.signature(index));
+    DocumentSymbol {
+        name,
+        detail,
+        kind,
+        tags: None,
+        deprecated: None,
+        range: to_range(analysis, symbol.range),
+        selection_range: to_range(analysis, symbol.name_range),
+        children,
+    }
+}
@@ -0,0 +1,180 @@
+use super::analysis::{Analysis, Target};
+use crate::encoding;
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+
+// 参照を探すときに読むファイル数の上限
+const MAX_WORKSPACE_FILES: usize = 2000;
+
+// 宣言の位置(ファイルと名前の範囲)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Definition {
+    pub path: PathBuf,
+    pub index: usize, // ファイルの解析結果での宣言の番号
+}
+
+// エディタで開いているファイルと、解析結果のキャッシュ
+#[derive(Default)]
+pub struct Workspace {
+    root: Option<PathBuf>,
+    open: HashMap<PathBuf, String>, // 開いているファイルの編集中の内容
+    analyses: HashMap<PathBuf, Arc<Analysis>>, // 最後に解析した結果
+    parsed: HashMap<PathBuf, Arc<Analysis>>, // 最後に構文解析まで成功した結果
+}
+
+impl Workspace {
+    pub fn new(root: Option<PathBuf>) -> Self {
+        Workspace {
+            root,
+            ..Workspace::default()
+        }
+    }
+
+    pub fn open(&mut self, path: PathBuf, text: String) {
+        self.open.insert(path, text);
+    }
+
+    pub fn close(&mut self, path: &Path) {
+        self.open.remove(path);
+        self.analyses.remove(path);
+        self.parsed.remove(path);
+    }
+
+    // ファイルの内容(開いていれば編集中の内容、なければディスク上の内容)
+    fn text(&self, path: &Path) -> Option<String> {
+        match self.open.get(path) {
+            Some(text) => Some(text.clone()),
+            None => encoding::read_source(path, None).ok(),
+        }
+    }
+
+    // ファイルを解析する(内容が変わっていなければ前回の結果を使う)
+    pub fn analysis(&mut self, path: &Path) -> Option<Arc<Analysis>> {
+        let text = self.text(path)?;
+        if let Some(analysis) = self.analyses.get(path) {
+            if analysis.text() == text {
+                return Some(analysis.clone());
+            }
+        }
+        let analysis = Arc::new(Analysis::new(&path.to_string_lossy(), &text));
+        if analysis.parsed {
+            self.parsed.insert(path.to_path_buf(), analysis.clone());
+        }
+        self.analyses.insert(path.to_path_buf(), analysis.clone());
+        Some(analysis)
+    }
+
+    // 宣言を持つ解析結果(編集中で構文木が得られなければ、最後に得られた結果)
+    pub fn parsed_analysis(&mut self, path: &Path) -> Option<Arc<Analysis>> {
+        let analysis = self.analysis(path)?;
+        if analysis.parsed {
+            return Some(analysis);
+        }
+        self.parsed.get(path).cloned()
+    }
+
+    // @include のファイル名を、読み込む側のファイルのディレクトリから上にたどって探す
+    // (実行時はスクリプトのディレクトリから探すので、サブディレクトリのファイルからも見つかるようにする)
+    pub fn resolve_include(&self, from: &Path, file_name: &str) -> Option<PathBuf> {
+        from.parent()?
+            .ancestors()
+            .map(|dir| normalize(&dir.join(file_name)))
+            .find(|path| self.open.contains_key(path) || path.is_file())
+    }
+
+    // ファイル自身と、そこからインクルードしているファイル(深さ優先で重複なし)
+    pub fn included_files(&mut self, path: &Path) -> Vec<PathBuf> {
+        let mut files = Vec::new();
+        let mut pending = vec![path.to_path_buf()];
+        while let Some(path) = pending.pop() {
+            if files.contains(&path) {
+                continue;
+            }
+            if let Some(analysis) = self.parsed_analysis(&path) {
+                let includes: Vec<PathBuf> = analysis
+                    .includes
+                    .iter()
+                    .filter_map(|include| self.resolve_include(&path, &include.file_name))
+                    .collect();
+                pending.extend(includes.into_iter().rev());
+            }
+            files.push(path);
+        }
+        files
+    }
+
+    // トップレベルの名前を、ファイル自身とインクルードしたファイルから探す
+    pub fn find_global(&mut self, path: &Path, name: &str) -> Option<Definition> {
+        self.included_files(path).into_iter().find_map(|file| {
+            let index = self.parsed_analysis(&file)?.find_global(name)?;
+            Some(Definition { path: file, index })
+        })
+    }
+
+    // 参照先の宣言(組み込み関数は None)
+    pub fn resolve(&mut self, path: &Path, name: &str, target: Target) -> Option<Definition> {
+        match target {
+            Target::Symbol(index) => Some(Definition {
+                path: path.to_path_buf(),
+                index,
+            }),
+            Target::Global => self.find_global(path, name),
+            Target::Builtin => None,
+        }
+    }
+
+    // 参照を探すファイル(開いているファイル、ワークスペースのスクリプト、それらがインクルードするファイル)
+    pub fn files(&mut self) -> Vec<PathBuf> {
+        let mut files: Vec<PathBuf> = self.open.keys().cloned().collect();
+        if let Some(root) = self.root.clone() {
+            collect_scripts(&root, &mut files);
+        }
+        let mut all = Vec::new();
+        for file in files {
+            for included in self.included_files(&file) {
+                if !all.contains(&included) {
+                    all.push(included);
+                }
+            }
+        }
+        all
+    }
+}
+
+// ディレクトリ以下のスクリプト(隠しディレクトリとビルド結果は除く)
+fn collect_scripts(dir: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        if files.len() >= MAX_WORKSPACE_FILES {
+            return;
+        }
+        let path = entry.path();
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if path.is_dir() {
+            if !name.starts_with('.') && name != "target" {
+                collect_scripts(&path, files);
+            }
+        } else if path.extension().is_some_and(|ext| ext == "sc") && !files.contains(&path) {
+            files.push(path);
+        }
+    }
+}
+
+// . と .. を取り除いたパス(同じファイルを同じキーで扱うため)
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
@@ -1,18 +1,9 @@
-mod context;
-mod decoder;
-mod error;
-mod lexer;
-mod memory_mgr;
-mod parser;
-mod traits;
-mod types;
-
 use anyhow::{anyhow, Context, Result as R};
 
 #[cfg(any(feature = "full", feature = "decoder"))]
 use decoder::interpreter::*;
 use env_logger;
-use error::CompilerError;
+use error::{CompilerError, ScriptError};
 #[cfg(any(feature = "full", feature = "lexer"))]
 use lexer::tokenizer::{Lexer, Token};
 use log::info;
@@ -29,34 +20,291 @@ use std::env;
 use std::fs;
 use std::fs::File;
 use std::io::Write;
-use std::io::{self, BufRead};
+use std::io::{self, BufRead, IsTerminal};
 use std::path::Path;
 use std::vec::Vec;
+use tanucc_script::msg;
+use tanucc_script::{decoder, encoding, error, error_log, lexer, messages, parser, types};
 use types::*;
 
+// ヒープサイズの指定を解釈する(K/M/G の接尾辞に対応)
+fn parse_heap_size(text: &str) -> R<usize, String> {
+    let text = text.trim();
+    let (digits, unit) = match text.chars().last() {
+        Some('K') | Some('k') => (&text[..text.len() - 1], 1024),
+        Some('M') | Some('m') => (&text[..text.len() - 1], 1024 * 1024),
+        Some('G') | Some('g') => (&text[..text.len() - 1], 1024 * 1024 * 1024),
+        _ => (text, 1),
+    };
+    digits
+        .parse::<usize>()
+        .ok()
+        .and_then(|n| n.checked_mul(unit))
+        .ok_or_else(|| msg!("cli.invalid_heap_size", text))
+}
+
+// 診断の出力形式
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ErrorFormat {
+    Text,  // 人が読むための表示
+    Json,  // 1件1行のJSON
+    Sarif, // SARIF 2.1.0
+}
+
+fn parse_error_format(text: &str) -> R<ErrorFormat, String> {
+    match text {
+        "text" => Ok(ErrorFormat::Text),
+        "json" => Ok(ErrorFormat::Json),
+        "sarif" => Ok(ErrorFormat::Sarif),
+        _ => Err(msg!("cli.invalid_error_format", text)),
+    }
+}
+
+// エラーを報告する(テキスト以外では構造化した診断を最後にまとめて出力するために集める)
+fn report_error(error: &ScriptError, format: ErrorFormat, diagnostics: &mut Vec<ScriptError>) {
+    match format {
+        ErrorFormat::Text => eprintln!("{}", error),
+        _ => diagnostics.push(error.clone()),
+    }
+}
+
+// 構造化した診断をまとめて出力する
+// (機械が読む出力なので、スクリプトの出力やログと混ざらないよう標準出力に書き、失敗は終了コードで知らせる)
+fn print_diagnostics(format: ErrorFormat, diagnostics: &[ScriptError]) -> R<(), String> {
+    match format {
+        ErrorFormat::Text => {}
+        ErrorFormat::Json => print!("{}", error::to_json_lines(diagnostics)),
+        ErrorFormat::Sarif => println!(
+            "{}",
+            to_string_pretty(&error::to_sarif(diagnostics)).map_err(|e| e.to_string())?
+        ),
+    }
+    Ok(())
+}
+
+// 実行以外のサブコマンド
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Command {
+    Lint, // 実行せずに警告だけを出す
+    Fmt,  // ソースを整形する
+    Repl, // 対話的に評価する
+}
+
 fn main() -> R<(), String> {
     env_logger::init();
-    let default_script_dir = std::path::Path::new("./script");
-    std::env::set_current_dir(&default_script_dir)
-        .expect("カレントディレクトリの設定に失敗しました");
-
     // コマンドライン引数を取得
     let args: Vec<String> = env::args().collect();
-    let file_name = if args.len() > 1 { &args[1] } else { "main.sc" };
+
+    // メッセージの言語(--lang > LC_ALL > LC_MESSAGES > LANG)
+    let lang = match args.iter().position(|arg| arg == "--lang") {
+        Some(i) => Some(
+            args.get(i + 1)
+                .ok_or_else(|| msg!("cli.requires_value", "--lang"))?
+                .as_str(),
+        ),
+        None => None,
+    };
+    messages::set_locale(messages::Locale::detect(lang)?);
+
+    // 診断コードの説明だけを表示する場合はスクリプトを読み込まない
+    if let Some(i) = args.iter().position(|arg| arg == "--explain") {
+        let code = args
+            .get(i + 1)
+            .ok_or_else(|| msg!("cli.explain_requires_code"))?;
+        let entry = error::explain(code).ok_or_else(|| msg!("cli.unknown_error_code", code))?;
+        println!("{}: {}\n\n{}", entry.code, entry.summary, entry.explanation);
+        return Ok(());
+    }
+
+    // 色付きの診断は標準エラー出力に表示するので、その出力先が端末のときだけ色を付ける
+    // (NO_COLOR が指定されていれば端末でも付けない)
+    let no_color = env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+    colored::control::set_override(io::stderr().is_terminal() && !no_color);
+
+    let default_script_dir = std::path::Path::new("./script");
+    std::env::set_current_dir(&default_script_dir).map_err(|e| {
+        msg!(
+            "cli.set_current_dir_failed",
+            default_script_dir.display(),
+            e
+        )
+    })?;
+
+    let mut file_name = "main.sc".to_string();
+    let mut heap_size: Option<usize> = None;
+    let mut heap_report = false;
+    let mut source_encoding: Option<String> = None;
+    let mut error_format = ErrorFormat::Text;
+    let mut list_errors: Option<usize> = None;
+    let mut show_error: Option<usize> = None;
+    let mut check = false;
+    // 最初の引数がサブコマンドならそれを使う
+    let command = match args.get(1).map(String::as_str) {
+        Some("lint") => Some(Command::Lint),
+        Some("fmt") => Some(Command::Fmt),
+        Some("repl") => Some(Command::Repl),
+        _ => None,
+    };
+    let mut rest = args
+        .iter()
+        .skip(if command.is_some() { 2 } else { 1 })
+        .peekable();
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "--heap-size" => {
+                let size = rest
+                    .next()
+                    .ok_or_else(|| msg!("cli.requires_value", "--heap-size"))?;
+                heap_size = Some(parse_heap_size(size)?);
+            }
+            "--heap-report" => heap_report = true,
+            "--check" => check = true,
+            "--lang" => {
+                // 言語は最初に設定済み
+                rest.next();
+            }
+            "--encoding" => {
+                let label = rest
+                    .next()
+                    .ok_or_else(|| msg!("cli.requires_value", "--encoding"))?;
+                encoding::lookup(label)?;
+                source_encoding = Some(label.clone());
+            }
+            "--error-format" => {
+                let format = rest
+                    .next()
+                    .ok_or_else(|| msg!("cli.requires_value", "--error-format"))?;
+                error_format = parse_error_format(format)?;
+            }
+            "--list-errors" => {
+                // 件数は省略可能(既定は10件)
+                let limit = match rest.peek().and_then(|n| n.parse::<usize>().ok()) {
+                    Some(limit) => {
+                        rest.next();
+                        limit
+                    }
+                    None => 10,
+                };
+                list_errors = Some(limit);
+            }
+            "--show-error" => {
+                let index = rest
+                    .next()
+                    .and_then(|n| n.parse::<usize>().ok())
+                    .filter(|&n| n >= 1)
+                    .ok_or_else(|| msg!("cli.show_error_requires_number"))?;
+                show_error = Some(index);
+            }
+            _ if arg.starts_with("--") => return Err(msg!("cli.unknown_option", arg)),
+            _ => file_name = arg.clone(),
+        }
+    }
+    let file_name = file_name.as_str();
+    let mut diagnostics = Vec::new();
+
+    // 記録したエラーログを表示する場合はスクリプトを実行しない
+    if let Some(limit) = list_errors {
+        let entries = error_log::recent(limit).map_err(|e| e.to_string())?;
+        print!("{}", error_log::format_list(&entries));
+        return Ok(());
+    }
+    if let Some(index) = show_error {
+        let entries = error_log::recent(index).map_err(|e| e.to_string())?;
+        let entry = entries
+            .get(index - 1)
+            .ok_or_else(|| msg!("log.not_recorded", index, error_log::LOG_DIR))?;
+        print!("{}", error_log::format_entry(entry));
+        return Ok(());
+    }
+
+    // lint はスクリプトを実行せず、警告をログにも残さない
+    #[cfg(any(feature = "full", feature = "decoder"))]
+    if command == Some(Command::Lint) {
+        let result = encoding::read_source(file_name, source_encoding.as_deref())
+            .and_then(|source| decoder::linter::Linter::lint(file_name, &source));
+        return match result {
+            Ok(()) => Ok(()),
+            Err(e) => {
+                report_error(&e, error_format, &mut diagnostics);
+                print_diagnostics(error_format, &diagnostics)?;
+                std::process::exit(1);
+            }
+        };
+    }
+
+    // fmt はスクリプトを整形して元の文字コードで書き戻す(--check では差分を表示するだけ)
+    #[cfg(any(feature = "full", feature = "parser"))]
+    if command == Some(Command::Fmt) {
+        let bytes = fs::read(file_name).map_err(|e| ScriptError::io(file_name, e).to_string())?;
+        let label = encoding::source_label(&bytes, source_encoding.as_deref());
+        let result =
+            encoding::read_source(file_name, source_encoding.as_deref()).and_then(|source| {
+                parser::formatter::format_source(file_name, &source)
+                    .map(|formatted| (source, formatted))
+            });
+        let (source, formatted) = match result {
+            Ok(v) => v,
+            Err(e) => {
+                report_error(&e, error_format, &mut diagnostics);
+                print_diagnostics(error_format, &diagnostics)?;
+                std::process::exit(1);
+            }
+        };
+        if formatted == source {
+            return Ok(());
+        }
+        if check {
+            print!(
+                "{}",
+                parser::formatter::diff(file_name, &source, &formatted)
+            );
+            eprintln!("{}", msg!("fmt.not_formatted", file_name));
+            std::process::exit(1);
+        }
+        let bytes = encoding::encode(&formatted, label.as_deref())?;
+        fs::write(file_name, bytes).map_err(|e| ScriptError::io(file_name, e).to_string())?;
+        return Ok(());
+    }
+
+    // repl は入力ごとに評価する(ドキュメントやASTのファイルは生成しない)
+    #[cfg(any(feature = "full", feature = "decoder"))]
+    if command == Some(Command::Repl) {
+        let mut decoder = Decoder::new();
+        if let Some(size) = heap_size {
+            decoder = decoder.heap_limit(size);
+        }
+        return tanucc_script::repl::run(decoder, source_encoding.as_deref());
+    }
 
     /*デコード*/
     #[cfg(any(feature = "full", feature = "decoder"))]
-    let mut decoder = match Decoder::load_script(file_name) {
-        Ok(v) => v,
-        Err(e) => {
-            eprintln!("{}", e);
-            Decoder::new()
+    let mut load_error = None;
+    #[cfg(any(feature = "full", feature = "decoder"))]
+    let mut decoder =
+        match Decoder::load_script_with_encoding(file_name, source_encoding.as_deref()) {
+            Ok(v) => v,
+            Err(e) => {
+                report_error(&e, error_format, &mut diagnostics);
+                load_error = Some(e);
+                Decoder::new()
+            }
         }
+        .generate_doc(true)
+        .generate_ast_file(true)
+        .generate_error_log_file(true)
+        .measured_decode_time(true)
+        .report_heap_usage(heap_report);
+    #[cfg(any(feature = "full", feature = "decoder"))]
+    if let Some(size) = heap_size {
+        decoder = decoder.heap_limit(size);
     }
-    .generate_doc(true)
-    .generate_ast_file(true)
-    .generate_error_log_file(true)
-    .measured_decode_time(true);
+    // 読み込みで失敗したエラー(字句・構文)もログに残す
+    #[cfg(any(feature = "full", feature = "decoder"))]
+    if let Some(e) = &load_error {
+        decoder.log_error(file_name, e);
+    }
+    #[cfg(any(feature = "full", feature = "decoder"))]
+    let mut failed = false;
     #[cfg(any(feature = "full", feature = "decoder"))]
     match decoder.decode() {
         Ok(v) => {
@@ -64,7 +312,16 @@ fn main() -> R<(), String> {
             //info!("ast_maps: {:?}", decoder.ast_map());
             info!("decode total-time: {:?}", decoder.decode_time())
         }
-        Err(e) => eprintln!("{}", e),
+        Err(e) => {
+            report_error(&e, error_format, &mut diagnostics);
+            failed = true;
+        }
+    }
+    print_diagnostics(error_format, &diagnostics)?;
+    // 読み込みか実行に失敗した場合は lint や fmt と同じく終了コード1で終える
+    #[cfg(any(feature = "full", feature = "decoder"))]
+    if failed || load_error.is_some() {
+        std::process::exit(1);
     }
     /*
     /*テスト用*/
@@ -1,12 +1,60 @@
+use crate::msg;
+use crate::traits::Size;
+use serde::Serialize;
+use serde_json::Value;
 use std::any::Any;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use uuid::Uuid;
+
+// 自動GCを走らせるまでの確保回数
+const DEFAULT_GC_THRESHOLD: usize = 1024;
+// 参照値を表すオブジェクトのキー
+const REFERENCE_KEY: &str = "__ref__";
+
+// 参照値(参照先アドレス,可変性,参照先の変数名)をValueとして表現する
+pub fn new_reference(address: Uuid, is_mutable: bool, target: &str) -> Value {
+    serde_json::json!({
+        REFERENCE_KEY: address.to_string(),
+        "mutable": is_mutable,
+        "target": target,
+    })
+}
+
+// Valueが参照値であれば(参照先アドレス,可変性,参照先の変数名)を返す
+pub fn as_reference(value: &Value) -> Option<(Uuid, bool, String)> {
+    let object = value.as_object()?;
+    let address = Uuid::parse_str(object.get(REFERENCE_KEY)?.as_str()?).ok()?;
+    let is_mutable = object
+        .get("mutable")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+    let target = object
+        .get("target")
+        .and_then(|v| v.as_str())
+        .unwrap_or_default()
+        .to_string();
+    Some((address, is_mutable, target))
+}
+
+// 値の中に含まれる参照値の参照先アドレスをすべて集める
+fn collect_references(value: &Value, out: &mut Vec<Uuid>) {
+    if let Some((address, _, _)) = as_reference(value) {
+        out.push(address);
+        return;
+    }
+    match value {
+        Value::Array(array) => array.iter().for_each(|v| collect_references(v, out)),
+        Value::Object(object) => object.values().for_each(|v| collect_references(v, out)),
+        _ => {}
+    }
+}
 impl Clone for MemoryBlock {
     fn clone(&self) -> Self {
         // クローン処理。今回はidのみクローンし、valueはクローン不可のため新たに初期化
         MemoryBlock {
             id: self.id,
             value: Box::new(()), // クローンできないためデフォルトの空の値を持たせる
+            size: self.size,
         }
     }
 }
@@ -18,6 +66,7 @@ impl MemoryBlock {
             Some(MemoryBlock {
                 id: self.id,
                 value: Box::new(cloned_value.clone()) as Box<dyn Any>,
+                size: self.size,
             })
         } else {
             None // クローンできない場合はNoneを返す
@@ -28,61 +77,202 @@ impl MemoryBlock {
 pub struct MemoryBlock {
     pub id: Uuid,
     pub value: Box<dyn Any>,
+    pub size: usize, // 確保時に計上したバイト数
 }
 
 // メモリの管理
 #[derive(Debug, Clone)]
 pub struct MemoryManager {
     pub heap: HashMap<Uuid, MemoryBlock>, // ヒープ(アドレス,値)
-    pub free_list: Vec<Uuid>,
-    pub stack_frames: HashMap<String, StackFrame>, // 関数名ごとのスタックフレーム
+    pub call_stack: Vec<StackFrame>,      // コールスタック(末尾が現在実行中のフレーム)
+    pub heap_size: usize,                 // ヒープの上限(バイト)
+    pub stats: HeapStats,                 // ヒープの統計情報
+    pub gc_threshold: usize,              // 自動GCを走らせるまでの確保回数
+    allocations_since_gc: usize,          // 前回のGCからの確保回数
+}
+
+// ヒープの統計情報
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct HeapStats {
+    pub live_blocks: usize,       // 現在確保中のブロック数
+    pub used_bytes: usize,        // 現在使用中のバイト数
+    pub peak_bytes: usize,        // 使用バイト数の最大値
+    pub total_allocations: usize, // 累計の確保回数
+    pub total_frees: usize,       // 累計の解放回数
+    pub collections: usize,       // GCの実行回数
+    pub last_collected: usize,    // 直近のGCで回収したブロック数
 }
 #[derive(Debug, Clone)]
 pub struct StackFrame {
-    pub blocks: Vec<MemoryBlock>, // スタックフレーム内のメモリブロック
+    pub func_name: String,                 // 関数名
+    pub return_address: Uuid,              // 戻り値の格納先アドレス
+    pub locals: Vec<Uuid>,                 // フレーム内で確保したローカル変数のアドレス
+    pub call_site: (String, usize, usize), // 呼び出し位置(ファイル名,行数,列数)
+    pub is_returned: bool,                 // return文が実行されたかどうか
 }
 impl MemoryManager {
     pub fn new(heap_size: usize) -> Self {
         MemoryManager {
             heap: HashMap::new(),
-            free_list: Vec::new(),
-            stack_frames: HashMap::new(),
+            call_stack: Vec::new(),
+            heap_size,
+            stats: HeapStats::default(),
+            gc_threshold: DEFAULT_GC_THRESHOLD,
+            allocations_since_gc: 0,
         }
     }
-    pub fn push_stack_frame(&mut self, func_name: &str) {
-        self.stack_frames
-            .insert(func_name.to_string(), StackFrame { blocks: Vec::new() });
+    // 関数呼び出し時に新しいフレームを積む
+    pub fn push_stack_frame(
+        &mut self,
+        func_name: &str,
+        call_site: (String, usize, usize),
+    ) -> Result<(), String> {
+        let return_address = self.allocate(Value::Null)?;
+        self.call_stack.push(StackFrame {
+            func_name: func_name.to_string(),
+            return_address,
+            locals: Vec::new(),
+            call_site,
+            is_returned: false,
+        });
+        Ok(())
     }
 
-    pub fn pop_stack_frame(&mut self, func_name: &str) {
-        self.stack_frames.remove(func_name);
+    // 現在のフレームを降ろしてローカル変数を解放し、return文の値があれば返す
+    pub fn pop_stack_frame(&mut self) -> Option<Value> {
+        let frame = self.call_stack.pop()?;
+        let return_value = if frame.is_returned {
+            self.get_value::<Value>(frame.return_address).cloned()
+        } else {
+            None
+        };
+        for id in frame.locals {
+            self.deallocate(id);
+        }
+        self.deallocate(frame.return_address);
+        return_value
     }
 
-    pub fn add_to_stack_frame(&mut self, func_name: &str, block: MemoryBlock) {
-        if let Some(frame) = self.stack_frames.get_mut(func_name) {
-            frame.blocks.push(block);
+    // 現在のフレームにローカル変数のアドレスを登録
+    pub fn add_to_stack_frame(&mut self, id: Uuid) {
+        if let Some(frame) = self.call_stack.last_mut() {
+            frame.locals.push(id);
         }
     }
-    // 指定の型の値を確保してUUIDのアドレスを返す
-    pub fn allocate<T: 'static + Any>(&mut self, value: T) -> Uuid {
-        let id = if let Some(free_id) = self.free_list.pop() {
-            // 解放済みのブロックがあれば再利用
-            free_id
-        } else {
-            Uuid::new_v4() // 新しいUUIDを生成
+
+    // 現在のフレームに戻り値を書き込む(フレームがなければfalse)
+    pub fn set_return_value(&mut self, value: Value) -> Result<bool, String> {
+        let return_address = match self.call_stack.last_mut() {
+            Some(frame) => {
+                frame.is_returned = true;
+                frame.return_address
+            }
+            None => return Ok(false),
         };
+        self.update_value(return_address, value)
+    }
+
+    // 現在のフレームでreturn文が実行済みかどうか
+    pub fn is_returning(&self) -> bool {
+        self.call_stack
+            .last()
+            .map(|frame| frame.is_returned)
+            .unwrap_or(false)
+    }
+    // 指定の型の値を確保してUUIDのアドレスを返す(上限を超える場合はエラー)
+    // 解放済みのアドレスは再利用しない(ダングリング参照を検出できるようにするため)
+    pub fn allocate<T: 'static + Any + Size>(&mut self, value: T) -> Result<Uuid, String> {
+        let size = value.size();
+        self.check_heap_limit(size)?;
+        let id = Uuid::new_v4();
         let block = MemoryBlock {
             id,
             value: Box::new(value),
+            size,
         };
         self.heap.insert(id, block);
-        id // 割り当てたメモリのIDを返す
+        self.stats.total_allocations += 1;
+        self.stats.live_blocks = self.heap.len();
+        self.add_used_bytes(size);
+        self.allocations_since_gc += 1;
+        Ok(id) // 割り当てたメモリのIDを返す
     }
     // 指定アドレス(UUID)のメモリを開放
     pub fn deallocate(&mut self, id: Uuid) {
-        if self.heap.remove(&id).is_some() {
-            self.free_list.push(id); // 解放されたメモリブロックをフリーリストに追加
+        if let Some(block) = self.heap.remove(&id) {
+            self.stats.total_frees += 1;
+            self.stats.live_blocks = self.heap.len();
+            self.stats.used_bytes -= block.size;
+        }
+    }
+
+    // 追加でsizeバイト確保できるか確認
+    fn check_heap_limit(&self, size: usize) -> Result<(), String> {
+        if self.stats.used_bytes + size > self.heap_size {
+            return Err(msg!(
+                "runtime.out_of_memory",
+                size,
+                self.stats.used_bytes,
+                self.heap_size
+            ));
         }
+        Ok(())
+    }
+
+    fn add_used_bytes(&mut self, size: usize) {
+        self.stats.used_bytes += size;
+        self.stats.peak_bytes = self.stats.peak_bytes.max(self.stats.used_bytes);
+    }
+
+    // 前回のGCから閾値以上確保していれば自動GCを走らせる
+    pub fn should_collect(&self) -> bool {
+        self.allocations_since_gc >= self.gc_threshold
+    }
+
+    // マーク&スイープ: ルートとコールスタックから到達できないブロックを解放し、回収数を返す
+    pub fn collect_garbage(&mut self, roots: &HashSet<Uuid>) -> usize {
+        // マーク(フレームのローカル変数はスコープ側のルートで生存判定する)
+        let mut marked: HashSet<Uuid> = roots.clone();
+        for frame in &self.call_stack {
+            marked.insert(frame.return_address);
+        }
+        // 生存しているブロックが持つ参照値の参照先もたどってマーク
+        let mut pending: Vec<Uuid> = marked.iter().copied().collect();
+        while let Some(id) = pending.pop() {
+            let mut referenced = Vec::new();
+            if let Some(value) = self.get_value::<Value>(id) {
+                collect_references(value, &mut referenced);
+            }
+            for address in referenced {
+                if marked.insert(address) {
+                    pending.push(address);
+                }
+            }
+        }
+
+        // スイープ
+        let garbage: Vec<Uuid> = self
+            .heap
+            .keys()
+            .filter(|id| !marked.contains(id))
+            .copied()
+            .collect();
+        for id in &garbage {
+            self.deallocate(*id);
+        }
+        // 回収済みのアドレスをフレームから外す
+        for frame in self.call_stack.iter_mut() {
+            frame.locals.retain(|id| marked.contains(id));
+        }
+
+        self.allocations_since_gc = 0;
+        self.stats.collections += 1;
+        self.stats.last_collected = garbage.len();
+        garbage.len()
+    }
+    // 指定のアドレスが確保済みかどうか
+    pub fn is_allocated(&self, id: Uuid) -> bool {
+        self.heap.contains_key(&id)
     }
     // 指定のアドレスの値を返す
     pub fn get_value<T: 'static + Any>(&self, id: Uuid) -> Option<&T> {
@@ -90,13 +280,158 @@ impl MemoryManager {
             .get(&id)
             .and_then(|block| block.value.downcast_ref::<T>()) // IDから値を取得
     }
-    // 指定のアドレスの値を更新
-    pub fn update_value<T: 'static + Any>(&mut self, id: Uuid, new_value: T) -> bool {
+    // 指定のアドレスの値を更新(サイズが増えて上限を超える場合はエラー)
+    pub fn update_value<T: 'static + Any + Size>(
+        &mut self,
+        id: Uuid,
+        new_value: T,
+    ) -> Result<bool, String> {
+        let old_size = match self.heap.get(&id) {
+            Some(block) => block.size,
+            None => return Ok(false), // 指定されたIDが見つからなかった場合
+        };
+        let new_size = new_value.size();
+        if new_size > old_size {
+            self.check_heap_limit(new_size - old_size)?;
+        }
         if let Some(block) = self.heap.get_mut(&id) {
             block.value = Box::new(new_value); // 新しい値で更新
-            true
-        } else {
-            false // 指定されたIDが見つからなかった場合
+            block.size = new_size;
+        }
+        self.stats.used_bytes -= old_size;
+        self.add_used_bytes(new_size);
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn call_site(line: usize) -> (String, usize, usize) {
+        ("test.sc".to_string(), line, 1)
+    }
+
+    #[test]
+    fn recursive_calls_keep_the_caller_frame() {
+        let mut memory = MemoryManager::new(1024 * 1024);
+        memory.push_stack_frame("fib", call_site(1)).unwrap();
+        let caller_local = memory.allocate(json!(1)).unwrap();
+        memory.add_to_stack_frame(caller_local);
+        memory.push_stack_frame("fib", call_site(2)).unwrap();
+        let callee_local = memory.allocate(json!(2)).unwrap();
+        memory.add_to_stack_frame(callee_local);
+        assert_eq!(memory.call_stack.len(), 2);
+
+        assert!(memory.set_return_value(json!(3)).unwrap());
+        assert!(memory.is_returning());
+        assert_eq!(memory.pop_stack_frame(), Some(json!(3)));
+        // 降ろしたフレームのローカル変数だけが解放される
+        assert!(!memory.is_allocated(callee_local));
+        assert!(memory.is_allocated(caller_local));
+        assert_eq!(memory.call_stack.len(), 1);
+        assert_eq!(memory.call_stack[0].call_site, call_site(1));
+        assert!(!memory.is_returning());
+
+        // return文がなければ戻り値はない
+        assert_eq!(memory.pop_stack_frame(), None);
+        assert!(!memory.is_allocated(caller_local));
+        assert_eq!(memory.stats.live_blocks, 0);
+        assert_eq!(memory.pop_stack_frame(), None);
+    }
+
+    #[test]
+    fn return_value_requires_a_frame() {
+        let mut memory = MemoryManager::new(1024 * 1024);
+        assert!(!memory.set_return_value(json!(1)).unwrap());
+        assert!(!memory.is_returning());
+    }
+
+    #[test]
+    fn garbage_collection_keeps_blocks_reachable_from_roots() {
+        let mut memory = MemoryManager::new(1024 * 1024);
+        let target = memory.allocate(json!([1, 2, 3])).unwrap();
+        // ルートの値が持つ参照値、さらにその参照先もたどる
+        let middle = memory
+            .allocate(json!([new_reference(target, false, "target")]))
+            .unwrap();
+        let root = memory
+            .allocate(new_reference(middle, true, "middle"))
+            .unwrap();
+        let unreachable = memory.allocate(json!("garbage")).unwrap();
+        memory.push_stack_frame("f", call_site(1)).unwrap();
+        let return_address = memory.call_stack[0].return_address;
+
+        let collected = memory.collect_garbage(&HashSet::from([root]));
+        assert_eq!(collected, 1);
+        assert!(!memory.is_allocated(unreachable));
+        for id in [root, middle, target, return_address] {
+            assert!(memory.is_allocated(id));
         }
+        assert_eq!(memory.stats.collections, 1);
+        assert_eq!(memory.stats.last_collected, 1);
+        assert_eq!(memory.stats.live_blocks, 4);
+    }
+
+    #[test]
+    fn garbage_collection_drops_collected_locals_from_frames() {
+        let mut memory = MemoryManager::new(1024 * 1024);
+        memory.push_stack_frame("f", call_site(1)).unwrap();
+        let kept = memory.allocate(json!(1)).unwrap();
+        let dropped = memory.allocate(json!(2)).unwrap();
+        memory.add_to_stack_frame(kept);
+        memory.add_to_stack_frame(dropped);
+        assert_eq!(memory.collect_garbage(&HashSet::from([kept])), 1);
+        assert_eq!(memory.call_stack[0].locals, [kept]);
+    }
+
+    #[test]
+    fn garbage_collection_runs_after_the_threshold() {
+        let mut memory = MemoryManager::new(1024 * 1024);
+        memory.gc_threshold = 2;
+        memory.allocate(json!(1)).unwrap();
+        assert!(!memory.should_collect());
+        memory.allocate(json!(2)).unwrap();
+        assert!(memory.should_collect());
+        memory.collect_garbage(&HashSet::new());
+        assert!(!memory.should_collect());
+        assert_eq!(memory.stats.used_bytes, 0);
+    }
+
+    #[test]
+    fn allocation_fails_beyond_the_heap_limit() {
+        let mut memory = MemoryManager::new(16);
+        let id = memory.allocate(json!(1)).unwrap();
+        memory.allocate(json!(2)).unwrap();
+        assert_eq!(memory.stats.used_bytes, 16);
+        let error = memory.allocate(json!(3)).unwrap_err();
+        assert_eq!(error, msg!("runtime.out_of_memory", 8, 16, 16));
+        assert_eq!(memory.stats.total_allocations, 2);
+
+        // 値が大きくなる更新も上限を超えれば失敗し、元の値が残る
+        assert!(memory.update_value(id, json!("longer than eight")).is_err());
+        assert_eq!(memory.get_value::<Value>(id), Some(&json!(1)));
+        // 小さくなる更新は上限に関係なく成功する
+        assert!(memory.update_value(id, Value::Null).unwrap());
+        assert_eq!(memory.stats.used_bytes, 8);
+        assert_eq!(memory.stats.peak_bytes, 16);
+    }
+
+    #[test]
+    fn statistics_follow_allocations_and_frees() {
+        let mut memory = MemoryManager::new(1024);
+        let first = memory.allocate(json!("abc")).unwrap();
+        let second = memory.allocate(json!([1, 2])).unwrap();
+        let peak = memory.stats.used_bytes;
+        assert_eq!(peak, json!("abc").size() + json!([1, 2]).size());
+        memory.deallocate(first);
+        memory.deallocate(first);
+        assert_eq!(memory.stats.live_blocks, 1);
+        assert_eq!(memory.stats.used_bytes, json!([1, 2]).size());
+        assert_eq!(memory.stats.total_allocations, 2);
+        assert_eq!(memory.stats.total_frees, 1);
+        assert_eq!(memory.stats.peak_bytes, peak);
+        assert!(memory.is_allocated(second));
     }
 }
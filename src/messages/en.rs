@@ -0,0 +1,313 @@
+// 英語のメッセージ(引数は {0} {1} ... で埋め込む)
+pub const MESSAGES: &[(&str, &str)] = &[
+    // 字句解析
+    ("lex.unclosed_embed", "Unclosed embedded expression in string interpolation"),
+    ("lex.empty_embed", "Empty expression in string interpolation"),
+    ("lex.unmatched_brace", "Unmatched '}' in format string (use '}}' for a literal brace)"),
+    ("lex.unclosed_double_quote", "Double quote not closed"),
+    ("lex.unclosed_single_quote", "Single quote not closed"),
+    ("lex.unclosed_raw_string", "Raw string not closed (expected '\"{0}')"),
+    ("lex.unclosed_triple_quote", "Triple-quoted string not closed"),
+    ("lex.unclosed_comment", "Multi-line comment not closed"),
+    ("lex.missing_base_digits", "Missing digits after the base prefix of a number literal"),
+    ("lex.invalid_digit", "Invalid digit '{0}' in a base-{1} literal"),
+    ("lex.invalid_suffix", "Invalid suffix '{0}' for number literal"),
+    ("lex.integer_suffix_on_float", "Integer suffix '{0}' is not allowed on a float literal"),
+    ("lex.float_suffix_on_base", "Float suffix '{0}' is not allowed on a base-{1} literal"),
+    ("lex.integer_too_large", "Integer literal is too large"),
+    ("lex.incomplete_escape", "Incomplete escape sequence at end of string"),
+    ("lex.invalid_hex_escape", "Invalid escape '\\x{0}': expected two hex digits in the range 00-7F"),
+    ("lex.invalid_unicode_escape", "Invalid unicode escape '\\u{{0}}': expected 1-6 hex digits forming a valid Unicode scalar value"),
+    ("lex.unknown_escape", "Unknown escape sequence '\\{0}' (use a raw string r\"...\" for literal backslashes)"),
+    ("lex.char_literal_length", "Character literal must contain exactly one character, found {0} (use double quotes for strings)"),
+    ("lex.unexpected_char", "Unexpected character '{0}'"),
+    // 構文解析
+    ("parse.keyword_as_name", "Expected {0} name, found keyword '{1}'"),
+    ("parse.keyword_as_name.label", "keywords cannot be used as identifiers"),
+    ("parse.keyword_as_name.help", "choose a different name, such as '{0}_'"),
+    ("parse.expected_name", "Expected {0} name, found {1}"),
+    ("parse.expected_name.label", "expected {0} name"),
+    ("parse.unexpected_token", "Unexpected token: {0}"),
+    ("parse.literal_out_of_range", "Literal '{0}' is out of range for type '{1}'"),
+    ("parse.literal_out_of_range.label", "does not fit in '{0}'"),
+    ("parse.literal_out_of_range.range", "the range of '{0}' is {1}..={2}"),
+    ("parse.literal_out_of_range.wider", "the range of '{0}' is {1}..={2}; use a wider type such as 'i64'"),
+    ("parse.unclosed_double_quote", "Unexpected end of input_content, no closing DoubleQuote found: {0}"),
+    ("parse.keyword_as_expression", "Expected an expression, found keyword '{0}'"),
+    ("parse.expected_expression", "Expected an expression, found {0}"),
+    ("parse.expected_expression.label", "expected an expression"),
+    ("parse.expected_close_paren", "Expected ')', found {0}"),
+    ("parse.expected_close_paren.label", "expected ')'"),
+    ("parse.unclosed_delimiter.label", "unclosed delimiter"),
+    ("parse.invalid_token", "Invalid token '{0}'"),
+    ("parse.invalid_token.label", "invalid token"),
+    ("parse.malformed_interpolation", "Malformed expression in string interpolation: unexpected {0}"),
+    ("parse.unexpected_interpolation_token", "Unexpected token in string interpolation: {0}"),
+    ("parse.invalid_argument_name", "Invalid argument name"),
+    ("parse.expected_in", "Expected 'in' after the loop variable, found {0}"),
+    ("parse.expected_in.label", "expected 'in'"),
+    ("parse.expected_in.help", "write the loop as 'for {0} in 0..10 { ... }'"),
+    ("parse.unclosed_block", "Unexpected end of input, no closing curly brace found"),
+    ("parse.unclosed_block.label", "expected '}'"),
+    ("parse.unclosed_block_at", "no closing curly brace in block: {0}"),
+    ("parse.expected_type_equals", "Expected '=' in type declaration, found {0}"),
+    ("parse.expected_type_equals.label", "expected '='"),
+    ("parse.expected_encoding_name", "Expected an encoding name string after @encoding, found '{0}'"),
+    ("parse.expected_encoding_name.label", "expected a string"),
+    ("parse.expected_encoding_name.help", "write the encoding name in double quotes: @encoding \"shift_jis\""),
+    ("parse.misplaced_encoding_pragma", "@encoding must come before any other code in the script"),
+    ("parse.misplaced_encoding_pragma.label", "the encoding is only read from the top of the file"),
+    ("parse.misplaced_encoding_pragma.help", "move @encoding \"...\" to the first line; only comments may precede it"),
+    ("parse.expected_impl_brace", "Expected '{' after impl {0}, found {1}"),
+    ("parse.expected_impl_brace.label", "expected '{'"),
+    ("parse.no_statements", "No statements found"),
+    ("parse.failed_statement", "Failed to parse statement"),
+    ("parse.token.end_of_input", "end of input"),
+    ("parse.token.embed_end", "'}' (end of the embedded expression)"),
+    ("parse.token.template_end", "the end of the interpolated string"),
+    ("parse.name.function", "function"),
+    ("parse.name.parameter", "parameter"),
+    ("parse.name.loop_variable", "loop variable"),
+    ("parse.name.type", "type"),
+    ("parse.name.variable", "variable"),
+    ("parse.name.struct", "struct"),
+    // 可変性と借用の検査
+    ("check.borrow_immutable_as_mutable", "cannot borrow '{0}' as mutable, as it is not declared as mutable"),
+    ("check.borrow_immutable_as_mutable.label", "cannot borrow as mutable"),
+    ("check.declared_without_mut", "'{0}' is declared here without 'mut'"),
+    ("check.mutable_borrow_twice", "cannot borrow '{0}' as mutable more than once at a time"),
+    ("check.shared_while_mutable", "cannot borrow '{0}' as immutable because it is also borrowed as mutable"),
+    ("check.mutable_while_shared", "cannot borrow '{0}' as mutable because it is also borrowed as immutable"),
+    ("check.second_borrow.label", "second borrow occurs here"),
+    ("check.first_borrow.label", "first borrow occurs here"),
+    ("check.first_borrow_used_by", "first borrow is later used by '{0}'"),
+    ("check.assign_immutable_parameter", "cannot assign to immutable parameter '{0}'"),
+    ("check.assign_twice", "cannot assign twice to immutable variable '{0}'"),
+    ("check.assign.label", "cannot assign"),
+    ("check.assign_borrowed", "cannot assign to '{0}' because it is borrowed"),
+    ("check.assign_borrowed.label", "assignment occurs here"),
+    ("check.borrowed_here", "'{0}' is borrowed here"),
+    ("check.borrow_used_by", "the borrow is later used by '{0}'"),
+    ("check.assign_through_shared", "cannot assign through '{0}', which is behind a shared reference"),
+    ("check.assign_through_shared.label", "cannot write through a '&' reference"),
+    ("check.holds_shared_reference", "'{0}' holds a '&' reference"),
+    ("check.assign_through_shared.help", "borrow with '&mut' to write through the reference"),
+    ("check.mutable_parameter_hint", "consider making the parameter mutable: 'mut {0}'"),
+    ("check.mutable_binding_hint", "consider making this binding mutable: 'let mut {0}'"),
+    // 実行時
+    ("runtime.mismatched_types", "mismatched types: '{0}' is declared as '{1}' but the value is {2}"),
+    ("runtime.variable_not_defined", "Variable '{0}' is not defined"),
+    ("runtime.variable_already_defined", "Variable '{0}' is already defined"),
+    ("runtime.variable_not_mutable", "Variable '{0}' is not mutable"),
+    ("runtime.function_not_defined", "Function '{0}' is not defined"),
+    ("runtime.function_already_defined", "Function '{0}' is already defined"),
+    ("runtime.type_already_defined", "type '{0}' is already defined"),
+    ("runtime.struct_already_defined", "Struct '{0}' is already defined"),
+    ("runtime.struct_not_defined", "Struct '{0}' is not defined. Please define the struct before implementing."),
+    ("runtime.not_a_hashmap", "The value associated with '{0}' is not a HashMap."),
+    ("runtime.assign_not_statement", "Variable Assign must be a statement"),
+    ("runtime.declaration_not_statement", "Variable declaration must be a statement"),
+    ("runtime.borrow_immutable_as_mutable", "cannot borrow immutable variable '{0}' as mutable"),
+    ("runtime.deref_non_reference", "cannot dereference a value that is not a reference: {0}"),
+    ("runtime.assign_through_non_reference", "cannot assign through a value that is not a reference: {0}"),
+    ("runtime.assign_through_shared", "cannot assign through '&{0}': it is a shared reference (use '&mut {0}')"),
+    ("runtime.dangling_reference", "dangling reference: '{0}' does not live long enough (it was dropped when its scope ended)"),
+    ("runtime.index_out_of_bounds", "Index out of bounds"),
+    ("runtime.index_not_number", "Index is not a number"),
+    ("runtime.index_non_array", "Cannot index into a non-array value"),
+    ("runtime.division_by_zero", "Division by zero: {0} / {1}"),
+    ("runtime.not_iterable", "The iterator is not an array or a string"),
+    ("runtime.condition_not_bool", "Condition must evaluate to a boolean"),
+    ("runtime.unsupported_condition", "Unsupported operation or mismatched types in condition"),
+    ("runtime.unsupported_binary", "Unsupported operation or mismatched types in binary operation"),
+    ("runtime.unsupported_node", "Unsupported node value"),
+    ("runtime.unknown_node", "Unknown node value: {0}"),
+    ("runtime.left_not_i64", "Failed to convert left number to i64"),
+    ("runtime.right_not_i64", "Failed to convert right number to i64"),
+    ("runtime.left_not_f64", "Failed to convert left number to f64"),
+    ("runtime.right_not_f64", "Failed to convert right number to f64"),
+    ("runtime.out_of_memory", "out of memory: failed to allocate {0} bytes ({1} of {2} bytes in use)"),
+    ("runtime.stack_backtrace", "stack backtrace:"),
+    ("runtime.frame", "{0} at {1}:{2}:{3}"),
+    ("heap.report.title", "heap report:"),
+    ("heap.report.limit", "  limit            : {0} bytes"),
+    ("heap.report.peak", "  peak usage       : {0} bytes"),
+    ("heap.report.in_use", "  in use at exit   : {0} bytes in {1} blocks"),
+    ("heap.report.unreachable", "  unreachable      : {0} blocks ({1} bytes)"),
+    ("heap.report.reachable", "  still reachable  : {0} bytes in {1} blocks"),
+    ("heap.report.total", "  total            : {0} allocs, {1} frees, {2} collections"),
+    // 組み込み関数
+    ("builtin.unknown", "Unknown function: {0}"),
+    ("builtin.expects_no_arguments", "{0} expects no arguments"),
+    ("builtin.expects_one_argument", "{0} expects exactly one argument"),
+    ("builtin.expects_two_arguments", "{0} expects exactly two arguments"),
+    ("builtin.expects_one_or_two_arguments", "{0} expects one or two arguments"),
+    ("builtin.expects_two_or_three_arguments", "{0} expects two or three arguments"),
+    ("builtin.expects_at_least_one_argument", "{0} expects at least one argument"),
+    ("builtin.expects_file_name", "{0} expects a string as the file name"),
+    ("builtin.expects_encoding", "{0} expects a string as the encoding"),
+    ("builtin.expects_string_array_content", "{0} expects an array of strings as the content"),
+    ("builtin.expects_string_content", "{0} expects a string or an array of strings as the content"),
+    ("builtin.print_format", "print expects a string as the format"),
+    ("builtin.print_arguments", "print expects an array of arguments"),
+    ("builtin.exit_status", "exit expects a number as the status"),
+    ("builtin.exit_positive", "exit expects a positive integer"),
+    ("builtin.cmd_command", "cmd expects the first argument to be a string"),
+    ("builtin.cmd_arguments", "cmd expects the second argument to be an array of strings"),
+    ("builtin.cmd_failed", "failed to run '{0}': {1}"),
+    ("builtin.ord_char", "ord expects a char"),
+    ("builtin.chr_integer", "chr expects a non-negative integer"),
+    ("builtin.chr_invalid", "chr: {0} is not a valid Unicode scalar value"),
+    ("builtin.file_error", "{0}: {1}"),
+    ("builtin.doc.list_files", "Returns the paths of the files in a directory"),
+    ("builtin.doc.play_music", "Plays a music file and waits until it finishes"),
+    ("builtin.doc.str", "Converts a value to its display string"),
+    ("builtin.doc.show_msg_box", "Shows a message box and returns the button that was pressed"),
+    ("builtin.doc.write_at_file", "Inserts text into a file at the given character position"),
+    ("builtin.doc.open_recent", "Returns the recently used files"),
+    ("builtin.doc.sleep", "Waits for the given number of seconds"),
+    ("builtin.doc.read_file", "Reads a file as a string (the encoding is optional)"),
+    ("builtin.doc.write_file", "Writes a string or an array of strings to a file (the encoding is optional)"),
+    ("builtin.doc.print", "Prints a format string with '{}' replaced by the values"),
+    ("builtin.doc.println", "Prints the values followed by a newline"),
+    ("builtin.doc.exit", "Exits the script with the given status"),
+    ("builtin.doc.args", "Returns the command-line arguments"),
+    ("builtin.doc.cmd", "Runs an external command and returns its output"),
+    ("builtin.doc.gc", "Runs the garbage collector"),
+    ("builtin.doc.heap_stats", "Returns statistics of the heap"),
+    ("builtin.doc.heap_used", "Returns the number of bytes in use on the heap"),
+    ("builtin.doc.heap_blocks", "Returns the number of blocks allocated on the heap"),
+    ("builtin.doc.sizeof", "Returns the size of a value in bytes"),
+    ("builtin.doc.ord", "Returns the code point of a character"),
+    ("builtin.doc.chr", "Returns the character for a code point"),
+    // 文字コード
+    ("encoding.unknown", "unknown encoding: {0}"),
+    ("encoding.invalid_bytes", "invalid {0} byte sequence"),
+    ("encoding.invalid_bytes.hint", "invalid {0} byte sequence (specify the encoding with @encoding \"shift_jis\" or --encoding)"),
+    ("encoding.unrepresentable", "text cannot be represented in {0}"),
+    // エラーログ
+    ("log.write_failed", "warning: failed to write the error log: {0}"),
+    ("log.empty", "no errors recorded in {0}"),
+    ("log.not_recorded", "error #{0} is not recorded in {1}"),
+    ("log.entry_header", "{0} error in {1} at {2}"),
+    // lint
+    ("lint.unused_variable", "unused variable: '{0}'"),
+    ("lint.unused_parameter", "unused parameter: '{0}'"),
+    ("lint.unused_function", "function '{0}' is never called"),
+    ("lint.unused.label", "never used"),
+    ("lint.unused.help", "if this is intentional, prefix it with an underscore: '_{0}'"),
+    ("lint.shadowing", "'{0}' shadows a global variable"),
+    ("lint.shadowing.label", "this '{0}' hides the global one"),
+    ("lint.shadowing.global", "the global '{0}' is declared here"),
+    ("lint.unreachable", "unreachable statement"),
+    ("lint.unreachable.label", "unreachable"),
+    ("lint.unreachable.cause", "any code following this '{0}' is unreachable"),
+    ("lint.unused_mut", "variable '{0}' does not need to be mutable"),
+    ("lint.unused_mut.label", "never mutated"),
+    ("lint.unused_mut.help", "remove 'mut'"),
+    ("lint.self_assign", "'{0}' is assigned to itself"),
+    ("lint.self_assign.label", "self-assignment has no effect"),
+    ("lint.self_assign.help", "remove this statement or assign the intended value"),
+    ("lint.constant_condition", "this condition is always {0}"),
+    ("lint.constant_condition.label", "constant condition"),
+    ("lint.constant_condition.loop", "use 'loop { ... }' for an infinite loop"),
+    ("lint.unknown_rule", "unknown lint rule '{0}'"),
+    ("lint.unknown_rule.help", "known rules: {0}, all"),
+    ("lint.allow_hint", "silence this warning with '// tanucc-allow({0})'"),
+    // 整形
+    ("fmt.changed_meaning", "formatting would change the meaning of the script; the file was left unchanged"),
+    ("fmt.diff_header", "Diff in {0} at line {1}:"),
+    ("fmt.not_formatted", "{0} is not formatted; run 'tanucc-script fmt {0}' to fix it"),
+    // コマンドライン
+    ("cli.explain_requires_code", "--explain requires an error code (e.g. E0101)"),
+    ("cli.unknown_error_code", "unknown error code: {0}"),
+    ("cli.requires_value", "{0} requires a value"),
+    ("cli.show_error_requires_number", "--show-error requires a number (1 is the latest)"),
+    ("cli.unknown_option", "unknown option: {0}"),
+    ("cli.invalid_heap_size", "invalid heap size: {0}"),
+    ("cli.invalid_error_format", "invalid error format: {0} (expected text, json or sarif)"),
+    ("cli.invalid_locale", "unknown language: {0} (expected ja or en)"),
+    ("cli.set_current_dir_failed", "failed to change the current directory to {0}: {1}"),
+    // 言語サーバー
+    ("lsp.unknown_request", "unknown request: {0}"),
+    ("lsp.invalid_params", "invalid parameters for {0}: {1}"),
+    ("lsp.hover.parameter", "parameter of '{0}'"),
+    ("lsp.hover.field", "field of '{0}'"),
+    ("lsp.hover.method", "method of '{0}'"),
+    ("lsp.hover.loop_variable", "loop variable"),
+    ("lsp.hover.inferred", "type inferred from the initial value"),
+    ("lsp.hover.builtin", "builtin function"),
+    ("lsp.hover.include", "includes {0}"),
+    // 診断コードの説明(--explain, SARIF の rules)
+    ("explain.E0001.summary", "unexpected character"),
+    ("explain.E0001.explanation", "The lexer found a character that does not start any token.\n\n    let a = 1 ` 2;\n\nRemove the character, or put it inside a string literal."),
+    ("explain.E0002.summary", "unterminated literal or comment"),
+    ("explain.E0002.explanation", "A string, character literal, raw string or block comment reaches the end of the line or file without its closing delimiter.\n\n    let s = \"abc;\n\nAdd the closing quote (or `*/`). Use a triple-quoted string \"\"\"...\"\"\" for text spanning several lines."),
+    ("explain.E0003.summary", "invalid escape sequence"),
+    ("explain.E0003.explanation", "Only \\n, \\r, \\t, \\0, \\\\, \\', \\\", \\$, \\xNN (00-7F) and \\u{...} escapes are recognized in string and character literals.\n\n    let p = \"C:\\dir\";\n\nDouble the backslash (\"C:\\\\dir\") or write a raw string r\"C:\\dir\"."),
+    ("explain.E0004.summary", "invalid number literal"),
+    ("explain.E0004.explanation", "A number literal has no digits after its base prefix, contains digits outside its base, or does not fit in 64 bits.\n\n    let a = 0x;\n\nWrite at least one digit after 0x, 0o or 0b and keep the value within range."),
+    ("explain.E0005.summary", "invalid character literal"),
+    ("explain.E0005.explanation", "A character literal in single quotes must contain exactly one character.\n\n    let c = 'ab';\n\nUse double quotes for strings: \"ab\"."),
+    ("explain.E0006.summary", "malformed string interpolation"),
+    ("explain.E0006.explanation", "An embedded expression in an interpolated string (f\"{...}\" or \"${...}\") is empty, not closed, or a lone `}` appears in a format string.\n\n    let s = f\"{a\";\n\nClose the expression with `}`, and write `{{` / `}}` for literal braces in format strings."),
+    ("explain.E0101.summary", "unexpected token"),
+    ("explain.E0101.explanation", "The parser found a token that cannot appear at this position.\n\n    let a = );\n\nCheck for a missing operand, operator or separator before the highlighted token."),
+    ("explain.E0102.summary", "expected an identifier"),
+    ("explain.E0102.explanation", "A name was expected (for a variable, function, parameter, struct or loop variable) but a keyword or another token was found.\n\n    let fn = 1;\n\nKeywords cannot be used as names; choose a different name."),
+    ("explain.E0103.summary", "unclosed delimiter"),
+    ("explain.E0103.explanation", "A parenthesis or curly brace was opened but the matching closing delimiter was not found.\n\n    fn f() {\n        let a = (1 + 2;\n\nAdd the missing `)` or `}`."),
+    ("explain.E0104.summary", "literal out of range for its type"),
+    ("explain.E0104.explanation", "A number literal does not fit in the declared integer type.\n\n    let a: u8 = 300;\n\nUse a wider type such as i32 or i64, or a smaller value."),
+    ("explain.E0105.summary", "malformed declaration"),
+    ("explain.E0105.explanation", "A declaration or pragma is missing a required part, such as `=` in a type declaration, `in` in a for loop, or the encoding name after @encoding.\n\n    type Int \"i32\";\n\nAdd the missing part: type Int = \"i32\";"),
+    ("explain.E0201.summary", "assignment to an immutable binding"),
+    ("explain.E0201.explanation", "A variable or parameter declared without `mut` is assigned after its initialization.\n\n    let a = 1;\n    a = 2;\n\nDeclare it with `let mut a = 1;` (or `mut a` for parameters)."),
+    ("explain.E0202.summary", "conflicting borrows"),
+    ("explain.E0202.explanation", "A value may have either one mutable reference or any number of shared references at a time, while the references are still in use.\n\n    let mut a = 1;\n    let r = &mut a;\n    let s = &a;\n    @println(r);\n\nEnd the first borrow before taking the second, or use shared references only."),
+    ("explain.E0203.summary", "mutable borrow of an immutable binding"),
+    ("explain.E0203.explanation", "`&mut` was taken of a variable declared without `mut`.\n\n    let a = 1;\n    let r = &mut a;\n\nDeclare the variable with `let mut`."),
+    ("explain.E0204.summary", "assignment to a borrowed variable"),
+    ("explain.E0204.explanation", "A variable is assigned while a reference to it is still in use.\n\n    let mut a = 1;\n    let r = &a;\n    a = 2;\n    @println(r);\n\nFinish using the reference before assigning."),
+    ("explain.E0205.summary", "write through a shared reference"),
+    ("explain.E0205.explanation", "A value is modified through a `&` reference, which only allows reading.\n\n    let mut a = 1;\n    let r = &a;\n    *r = 2;\n\nBorrow with `&mut` to write through the reference."),
+    ("explain.E0301.summary", "undefined variable"),
+    ("explain.E0301.explanation", "A variable is used before it is declared, or outside the block that declares it.\n\n    @println(total);\n\nDeclare it with `let` first, and check the spelling."),
+    ("explain.E0302.summary", "undefined function"),
+    ("explain.E0302.explanation", "A function is called that is neither declared in the script nor a built-in.\n\n    prnt(1);\n\nCheck the spelling; built-in functions are called with `@`, for example @println(1)."),
+    ("explain.E0303.summary", "duplicate definition"),
+    ("explain.E0303.explanation", "A variable, function or struct with the same name is already defined in this scope.\n\n    fn f() {}\n    fn f() {}\n\nRename one of the definitions."),
+    ("explain.E0304.summary", "assignment to an immutable variable at runtime"),
+    ("explain.E0304.explanation", "An immutable variable was assigned while the script was running.\n\n    let a = 1;\n    a = 2;\n\nDeclare it with `let mut`."),
+    ("explain.E0305.summary", "invalid index"),
+    ("explain.E0305.explanation", "An array index is not a number or is outside the array.\n\n    let a = [1, 2];\n    @println(a[2]);\n\nIndexes start at 0 and must be less than the array length."),
+    ("explain.E0306.summary", "division by zero"),
+    ("explain.E0306.explanation", "The right operand of `/` or `/=` evaluated to zero.\n\nCheck the divisor before dividing."),
+    ("explain.E0307.summary", "declaration used as an expression"),
+    ("explain.E0307.explanation", "A variable declaration or assignment appears where a value is expected.\n\n    let a = (let b = 1);\n\nWrite the declaration as its own statement ending with `;`."),
+    ("explain.E0308.summary", "value is not iterable"),
+    ("explain.E0308.explanation", "A for loop iterates over a value that is neither an array, a string nor a range.\n\n    for x in 10 { }\n\nIterate over a range (0..10), an array or a string."),
+    ("explain.W0001.summary", "unused variable, parameter or function"),
+    ("explain.W0001.explanation", "A variable or parameter is never read, or a function is never called. Assigning to a variable does not count as using it.\n\n    let total = 1;\n\nRemove the declaration, or prefix the name with an underscore (_total) if it is intentionally unused. Silence with // tanucc-allow(unused)."),
+    ("explain.W0002.summary", "global variable shadowed"),
+    ("explain.W0002.explanation", "A variable, parameter or loop variable has the same name as a global variable, which becomes inaccessible in that scope.\n\n    let count = 0;\n    fn f() { let count = 1; }\n\nRename one of them. Silence with // tanucc-allow(shadowing)."),
+    ("explain.W0003.summary", "unreachable statement"),
+    ("explain.W0003.explanation", "A statement follows `return`, `break` or `continue` in the same block and is never executed.\n\n    return a;\n    @println(\"done\");\n\nRemove the statement or move it before the jump. Silence with // tanucc-allow(unreachable)."),
+    ("explain.W0004.summary", "variable does not need to be mutable"),
+    ("explain.W0004.explanation", "A variable is declared with `mut` but is never assigned, incremented or borrowed with `&mut`.\n\n    let mut a = 1;\n    @println(a);\n\nRemove `mut`. Silence with // tanucc-allow(unused_mut)."),
+    ("explain.W0005.summary", "self-assignment"),
+    ("explain.W0005.explanation", "A variable is assigned its own value, which has no effect.\n\n    a = a;\n\nRemove the statement, or assign the value that was intended. Silence with // tanucc-allow(self_assign)."),
+    ("explain.W0006.summary", "constant condition"),
+    ("explain.W0006.explanation", "An if or while condition consists only of literals, so it always has the same value.\n\n    while true { }\n\nUse `loop { }` for an infinite loop, or remove the dead branch. Silence with // tanucc-allow(constant_condition)."),
+    ("explain.W0007.summary", "unknown lint rule"),
+    ("explain.W0007.explanation", "A tanucc-allow comment names a rule that does not exist, so nothing is silenced.\n\n    // tanucc-allow(unsued)\n\nUse one of: unused, shadowing, unreachable, unused_mut, self_assign, constant_condition or all."),
+    // REPL
+    ("repl.banner", "tanucc-script REPL (:help lists the commands, Ctrl-D exits)"),
+    ("repl.help", ":type <value>  show the type of a variable or literal without evaluating code\n:ast <code>    show the syntax tree without evaluating it\n:vars          list the defined variables, functions and structs\n:load <file>   evaluate a script file\n:help          show this help\n:quit          exit the REPL"),
+    ("repl.unknown_command", "unknown command: {0} (:help lists the commands)"),
+    ("repl.requires_argument", "{0} requires an argument"),
+    ("repl.no_variables", "no variables are defined"),
+    ("repl.type_requires_value", ":type only accepts a variable name or a literal, so that nothing is evaluated: {0}"),
+    ("repl.editor_failed", "line editing failed: {0}"),
+    ("repl.history_failed", "failed to save the history to {0}: {1}"),
+];
@@ -0,0 +1,313 @@
+// 日本語のメッセージ(引数は {0} {1} ... で埋め込む)
+pub const MESSAGES: &[(&str, &str)] = &[
+    // 字句解析
+    ("lex.unclosed_embed", "文字列補間の埋め込み式が閉じられていません"),
+    ("lex.empty_embed", "文字列補間の埋め込み式が空です"),
+    ("lex.unmatched_brace", "書式文字列に対応しない '}' があります('}' そのものは '}}' と書きます)"),
+    ("lex.unclosed_double_quote", "ダブルクォートが閉じられていません"),
+    ("lex.unclosed_single_quote", "シングルクォートが閉じられていません"),
+    ("lex.unclosed_raw_string", "生文字列が閉じられていません('\"{0}' が必要です)"),
+    ("lex.unclosed_triple_quote", "三重引用符の文字列が閉じられていません"),
+    ("lex.unclosed_comment", "複数行コメントが閉じられていません"),
+    ("lex.missing_base_digits", "数値リテラルの基数の接頭辞のあとに数字がありません"),
+    ("lex.invalid_digit", "{1}進数のリテラルに使えない数字 '{0}' があります"),
+    ("lex.invalid_suffix", "数値リテラルの接尾辞 '{0}' は不正です"),
+    ("lex.integer_suffix_on_float", "浮動小数点数のリテラルに整数の接尾辞 '{0}' は付けられません"),
+    ("lex.float_suffix_on_base", "{1}進数のリテラルに浮動小数点数の接尾辞 '{0}' は付けられません"),
+    ("lex.integer_too_large", "整数リテラルが大きすぎます"),
+    ("lex.incomplete_escape", "文字列の末尾のエスケープシーケンスが不完全です"),
+    ("lex.invalid_hex_escape", "エスケープ '\\x{0}' は不正です: 00-7Fの範囲の16進数2桁が必要です"),
+    ("lex.invalid_unicode_escape", "Unicodeエスケープ '\\u{{0}}' は不正です: 有効なUnicodeスカラー値を表す1-6桁の16進数が必要です"),
+    ("lex.unknown_escape", "不明なエスケープシーケンス '\\{0}' です(バックスラッシュをそのまま書くには生文字列 r\"...\" を使います)"),
+    ("lex.char_literal_length", "文字リテラルには1文字だけを書きますが、{0}文字あります(文字列にはダブルクォートを使います)"),
+    ("lex.unexpected_char", "予期しない文字 '{0}' があります"),
+    // 構文解析
+    ("parse.keyword_as_name", "{0}の名前が必要ですが、キーワード '{1}' があります"),
+    ("parse.keyword_as_name.label", "キーワードは名前に使えません"),
+    ("parse.keyword_as_name.help", "'{0}_' のような別の名前にしてください"),
+    ("parse.expected_name", "{0}の名前が必要ですが、{1} があります"),
+    ("parse.expected_name.label", "{0}の名前が必要です"),
+    ("parse.unexpected_token", "予期しないトークンです: {0}"),
+    ("parse.literal_out_of_range", "リテラル '{0}' は型 '{1}' の範囲外です"),
+    ("parse.literal_out_of_range.label", "'{0}' に収まりません"),
+    ("parse.literal_out_of_range.range", "'{0}' の範囲は {1}..={2} です"),
+    ("parse.literal_out_of_range.wider", "'{0}' の範囲は {1}..={2} です。'i64' などのより広い型を使ってください"),
+    ("parse.unclosed_double_quote", "入力が終わりましたが、閉じるダブルクォートがありません: {0}"),
+    ("parse.keyword_as_expression", "式が必要ですが、キーワード '{0}' があります"),
+    ("parse.expected_expression", "式が必要ですが、{0} があります"),
+    ("parse.expected_expression.label", "式が必要です"),
+    ("parse.expected_close_paren", "')' が必要ですが、{0} があります"),
+    ("parse.expected_close_paren.label", "')' が必要です"),
+    ("parse.unclosed_delimiter.label", "閉じられていない括弧"),
+    ("parse.invalid_token", "不正なトークン '{0}' です"),
+    ("parse.invalid_token.label", "不正なトークン"),
+    ("parse.malformed_interpolation", "文字列補間の式が不正です: 予期しない {0}"),
+    ("parse.unexpected_interpolation_token", "文字列補間に予期しないトークンがあります: {0}"),
+    ("parse.invalid_argument_name", "引数名が不正です"),
+    ("parse.expected_in", "ループ変数のあとに 'in' が必要ですが、{0} があります"),
+    ("parse.expected_in.label", "'in' が必要です"),
+    ("parse.expected_in.help", "ループは 'for {0} in 0..10 { ... }' のように書きます"),
+    ("parse.unclosed_block", "入力が終わりましたが、閉じる波括弧がありません"),
+    ("parse.unclosed_block.label", "'}' が必要です"),
+    ("parse.unclosed_block_at", "ブロックに閉じる波括弧がありません: {0}"),
+    ("parse.expected_type_equals", "型の宣言には '=' が必要ですが、{0} があります"),
+    ("parse.expected_type_equals.label", "'=' が必要です"),
+    ("parse.expected_encoding_name", "@encoding のあとには文字コード名の文字列が必要ですが、'{0}' があります"),
+    ("parse.expected_encoding_name.label", "文字列が必要です"),
+    ("parse.expected_encoding_name.help", "文字コード名はダブルクォートで囲みます: @encoding \"shift_jis\""),
+    ("parse.misplaced_encoding_pragma", "@encoding はスクリプトのほかのコードより前に書く必要があります"),
+    ("parse.misplaced_encoding_pragma.label", "文字コードはファイルの先頭でだけ読み取られます"),
+    ("parse.misplaced_encoding_pragma.help", "@encoding \"...\" を先頭の行に移してください(前に書けるのはコメントだけです)"),
+    ("parse.expected_impl_brace", "impl {0} のあとには '{' が必要ですが、{1} があります"),
+    ("parse.expected_impl_brace.label", "'{' が必要です"),
+    ("parse.no_statements", "文がありません"),
+    ("parse.failed_statement", "文を解析できませんでした"),
+    ("parse.token.end_of_input", "入力の終わり"),
+    ("parse.token.embed_end", "'}'(埋め込み式の終わり)"),
+    ("parse.token.template_end", "補間文字列の終わり"),
+    ("parse.name.function", "関数"),
+    ("parse.name.parameter", "引数"),
+    ("parse.name.loop_variable", "ループ変数"),
+    ("parse.name.type", "型"),
+    ("parse.name.variable", "変数"),
+    ("parse.name.struct", "構造体"),
+    // 可変性と借用の検査
+    ("check.borrow_immutable_as_mutable", "'{0}' は mut で宣言されていないため、可変として借用できません"),
+    ("check.borrow_immutable_as_mutable.label", "可変として借用できません"),
+    ("check.declared_without_mut", "'{0}' はここで 'mut' なしで宣言されています"),
+    ("check.mutable_borrow_twice", "'{0}' を同時に2回以上可変として借用することはできません"),
+    ("check.shared_while_mutable", "'{0}' は可変として借用されているため、不変として借用できません"),
+    ("check.mutable_while_shared", "'{0}' は不変として借用されているため、可変として借用できません"),
+    ("check.second_borrow.label", "2つ目の借用はここです"),
+    ("check.first_borrow.label", "1つ目の借用はここです"),
+    ("check.first_borrow_used_by", "1つ目の借用はあとで '{0}' が使っています"),
+    ("check.assign_immutable_parameter", "不変の引数 '{0}' には代入できません"),
+    ("check.assign_twice", "不変の変数 '{0}' に2回代入することはできません"),
+    ("check.assign.label", "代入できません"),
+    ("check.assign_borrowed", "'{0}' は借用されているため代入できません"),
+    ("check.assign_borrowed.label", "ここで代入しています"),
+    ("check.borrowed_here", "'{0}' はここで借用されています"),
+    ("check.borrow_used_by", "借用はあとで '{0}' が使っています"),
+    ("check.assign_through_shared", "'{0}' は共有参照なので、それを通して代入できません"),
+    ("check.assign_through_shared.label", "'&' 参照を通して書き込めません"),
+    ("check.holds_shared_reference", "'{0}' は '&' 参照を保持しています"),
+    ("check.assign_through_shared.help", "参照を通して書き込むには '&mut' で借用してください"),
+    ("check.mutable_parameter_hint", "引数を可変にすることを検討してください: 'mut {0}'"),
+    ("check.mutable_binding_hint", "この変数を可変にすることを検討してください: 'let mut {0}'"),
+    // 実行時
+    ("runtime.mismatched_types", "型が一致しません: '{0}' は '{1}' として宣言されていますが、値は {2} です"),
+    ("runtime.variable_not_defined", "変数 '{0}' は定義されていません"),
+    ("runtime.variable_already_defined", "変数 '{0}' はすでに定義されています"),
+    ("runtime.variable_not_mutable", "変数 '{0}' は可変ではありません"),
+    ("runtime.function_not_defined", "関数 '{0}' は定義されていません"),
+    ("runtime.function_already_defined", "関数 '{0}' はすでに定義されています"),
+    ("runtime.type_already_defined", "型 '{0}' はすでに定義されています"),
+    ("runtime.struct_already_defined", "構造体 '{0}' はすでに定義されています"),
+    ("runtime.struct_not_defined", "構造体 '{0}' は定義されていません。implの前に構造体を定義してください。"),
+    ("runtime.not_a_hashmap", "'{0}' に対応する値はHashMapではありません。"),
+    ("runtime.assign_not_statement", "変数への代入は文として書く必要があります"),
+    ("runtime.declaration_not_statement", "変数の宣言は文として書く必要があります"),
+    ("runtime.borrow_immutable_as_mutable", "不変の変数 '{0}' を可変として借用することはできません"),
+    ("runtime.deref_non_reference", "参照ではない値は参照外しできません: {0}"),
+    ("runtime.assign_through_non_reference", "参照ではない値を通して代入することはできません: {0}"),
+    ("runtime.assign_through_shared", "'&{0}' は共有参照なので、それを通して代入できません('&mut {0}' を使ってください)"),
+    ("runtime.dangling_reference", "ダングリング参照: '{0}' は十分に長く生存しません(スコープの終わりで破棄されました)"),
+    ("runtime.index_out_of_bounds", "添字が範囲外です"),
+    ("runtime.index_not_number", "添字が数値ではありません"),
+    ("runtime.index_non_array", "配列ではない値に添字は使えません"),
+    ("runtime.division_by_zero", "0で除算しました: {0} / {1}"),
+    ("runtime.not_iterable", "繰り返しの対象が配列でも文字列でもありません"),
+    ("runtime.condition_not_bool", "条件は真偽値になる必要があります"),
+    ("runtime.unsupported_condition", "条件の演算に対応していないか、型が一致しません"),
+    ("runtime.unsupported_binary", "二項演算に対応していないか、型が一致しません"),
+    ("runtime.unsupported_node", "対応していないノードの値です"),
+    ("runtime.unknown_node", "不明なノードの値です: {0}"),
+    ("runtime.left_not_i64", "左辺の数値をi64に変換できませんでした"),
+    ("runtime.right_not_i64", "右辺の数値をi64に変換できませんでした"),
+    ("runtime.left_not_f64", "左辺の数値をf64に変換できませんでした"),
+    ("runtime.right_not_f64", "右辺の数値をf64に変換できませんでした"),
+    ("runtime.out_of_memory", "メモリが不足しています: {0}バイトを確保できませんでした({2}バイト中{1}バイト使用中)"),
+    ("runtime.stack_backtrace", "呼び出し履歴:"),
+    ("runtime.frame", "{0} ({1}:{2}:{3})"),
+    ("heap.report.title", "ヒープの使用状況:"),
+    ("heap.report.limit", "  上限             : {0} バイト"),
+    ("heap.report.peak", "  最大使用量       : {0} バイト"),
+    ("heap.report.in_use", "  終了時の使用量   : {1} ブロック, {0} バイト"),
+    ("heap.report.unreachable", "  到達不能         : {0} ブロック ({1} バイト)"),
+    ("heap.report.reachable", "  到達可能         : {1} ブロック, {0} バイト"),
+    ("heap.report.total", "  合計             : 確保 {0} 回, 解放 {1} 回, 回収 {2} 回"),
+    // 組み込み関数
+    ("builtin.unknown", "不明な関数です: {0}"),
+    ("builtin.expects_no_arguments", "{0} は引数を取りません"),
+    ("builtin.expects_one_argument", "{0} は引数を1つだけ取ります"),
+    ("builtin.expects_two_arguments", "{0} は引数を2つだけ取ります"),
+    ("builtin.expects_one_or_two_arguments", "{0} は引数を1つか2つ取ります"),
+    ("builtin.expects_two_or_three_arguments", "{0} は引数を2つか3つ取ります"),
+    ("builtin.expects_at_least_one_argument", "{0} は引数を1つ以上取ります"),
+    ("builtin.expects_file_name", "{0} のファイル名には文字列が必要です"),
+    ("builtin.expects_encoding", "{0} の文字コードには文字列が必要です"),
+    ("builtin.expects_string_array_content", "{0} の内容には文字列の配列が必要です"),
+    ("builtin.expects_string_content", "{0} の内容には文字列か文字列の配列が必要です"),
+    ("builtin.print_format", "print の書式には文字列が必要です"),
+    ("builtin.print_arguments", "print の引数には配列が必要です"),
+    ("builtin.exit_status", "exit の終了コードには数値が必要です"),
+    ("builtin.exit_positive", "exit の終了コードには正の整数が必要です"),
+    ("builtin.cmd_command", "cmd の1つ目の引数には文字列が必要です"),
+    ("builtin.cmd_arguments", "cmd の2つ目の引数には文字列の配列が必要です"),
+    ("builtin.cmd_failed", "外部コマンド '{0}' の実行に失敗しました: {1}"),
+    ("builtin.ord_char", "ord には文字が必要です"),
+    ("builtin.chr_integer", "chr には0以上の整数が必要です"),
+    ("builtin.chr_invalid", "chr: {0} は有効なUnicodeスカラー値ではありません"),
+    ("builtin.file_error", "{0}: {1}"),
+    ("builtin.doc.list_files", "ディレクトリにあるファイルのパスを返します"),
+    ("builtin.doc.play_music", "音楽ファイルを再生し、終わるまで待ちます"),
+    ("builtin.doc.str", "値を表示用の文字列に変換します"),
+    ("builtin.doc.show_msg_box", "メッセージボックスを表示し、押されたボタンを返します"),
+    ("builtin.doc.write_at_file", "ファイルの指定した文字位置に文字列を挿入します"),
+    ("builtin.doc.open_recent", "最近使用したファイルの一覧を返します"),
+    ("builtin.doc.sleep", "指定した秒数だけ待ちます"),
+    ("builtin.doc.read_file", "ファイルを文字列として読み込みます(文字コードは省略可能)"),
+    ("builtin.doc.write_file", "文字列または文字列の配列をファイルに書き込みます(文字コードは省略可能)"),
+    ("builtin.doc.print", "'{}' を値で置き換えた書式文字列を表示します"),
+    ("builtin.doc.println", "値を表示して改行します"),
+    ("builtin.doc.exit", "指定した終了コードでスクリプトを終了します"),
+    ("builtin.doc.args", "コマンドライン引数を返します"),
+    ("builtin.doc.cmd", "外部コマンドを実行し、その出力を返します"),
+    ("builtin.doc.gc", "ガベージコレクションを実行します"),
+    ("builtin.doc.heap_stats", "ヒープの統計情報を返します"),
+    ("builtin.doc.heap_used", "ヒープで使用中のバイト数を返します"),
+    ("builtin.doc.heap_blocks", "ヒープに確保されているブロック数を返します"),
+    ("builtin.doc.sizeof", "値の大きさをバイト数で返します"),
+    ("builtin.doc.ord", "文字のコードポイントを返します"),
+    ("builtin.doc.chr", "コードポイントに対応する文字を返します"),
+    // 文字コード
+    ("encoding.unknown", "不明な文字コードです: {0}"),
+    ("encoding.invalid_bytes", "{0} として不正なバイト列です"),
+    ("encoding.invalid_bytes.hint", "{0} として不正なバイト列です(@encoding \"shift_jis\" または --encoding で文字コードを指定してください)"),
+    ("encoding.unrepresentable", "{0} では表せない文字があります"),
+    // エラーログ
+    ("log.write_failed", "warning: エラーログを書き込めませんでした: {0}"),
+    ("log.empty", "{0} に記録されたエラーはありません"),
+    ("log.not_recorded", "{1} にエラー #{0} は記録されていません"),
+    ("log.entry_header", "{1} の{0}エラー({2})"),
+    // lint
+    ("lint.unused_variable", "変数 '{0}' は使われていません"),
+    ("lint.unused_parameter", "引数 '{0}' は使われていません"),
+    ("lint.unused_function", "関数 '{0}' は一度も呼び出されていません"),
+    ("lint.unused.label", "使われていません"),
+    ("lint.unused.help", "意図して使わない場合は名前の先頭に _ を付けてください: '_{0}'"),
+    ("lint.shadowing", "'{0}' はグローバル変数を隠しています"),
+    ("lint.shadowing.label", "この '{0}' がグローバル変数を隠します"),
+    ("lint.shadowing.global", "グローバル変数 '{0}' はここで宣言されています"),
+    ("lint.unreachable", "到達しない文です"),
+    ("lint.unreachable.label", "到達しません"),
+    ("lint.unreachable.cause", "この '{0}' より後のコードには到達しません"),
+    ("lint.unused_mut", "変数 '{0}' は可変にする必要がありません"),
+    ("lint.unused_mut.label", "書き換えられていません"),
+    ("lint.unused_mut.help", "'mut' を取り除いてください"),
+    ("lint.self_assign", "'{0}' を自分自身に代入しています"),
+    ("lint.self_assign.label", "自分自身への代入は何もしません"),
+    ("lint.self_assign.help", "この文を取り除くか、意図した値を代入してください"),
+    ("lint.constant_condition", "この条件は常に {0} です"),
+    ("lint.constant_condition.label", "定数の条件"),
+    ("lint.constant_condition.loop", "無限ループには 'loop { ... }' を使ってください"),
+    ("lint.unknown_rule", "不明な lint の規則 '{0}' です"),
+    ("lint.unknown_rule.help", "使える規則: {0}, all"),
+    ("lint.allow_hint", "'// tanucc-allow({0})' でこの警告を抑止できます"),
+    // 整形
+    ("fmt.changed_meaning", "整形するとスクリプトの意味が変わるため、ファイルは変更しませんでした"),
+    ("fmt.diff_header", "{0} の {1} 行目からの差分:"),
+    ("fmt.not_formatted", "{0} は整形されていません('tanucc-script fmt {0}' で整形できます)"),
+    // コマンドライン
+    ("cli.explain_requires_code", "--explain にはエラーコードを指定してください(例: E0101)"),
+    ("cli.unknown_error_code", "不明なエラーコードです: {0}"),
+    ("cli.requires_value", "{0} には値を指定してください"),
+    ("cli.show_error_requires_number", "--show-error には番号を指定してください(1が最新)"),
+    ("cli.unknown_option", "不明なオプションです: {0}"),
+    ("cli.invalid_heap_size", "ヒープサイズが不正です: {0}"),
+    ("cli.invalid_error_format", "出力形式が不正です: {0}(text、json、sarif のいずれかを指定してください)"),
+    ("cli.invalid_locale", "不明な言語です: {0}(ja か en を指定してください)"),
+    ("cli.set_current_dir_failed", "カレントディレクトリを {0} に設定できませんでした: {1}"),
+    // 言語サーバー
+    ("lsp.unknown_request", "不明なリクエストです: {0}"),
+    ("lsp.invalid_params", "{0} のパラメーターが正しくありません: {1}"),
+    ("lsp.hover.parameter", "'{0}' の引数"),
+    ("lsp.hover.field", "'{0}' のフィールド"),
+    ("lsp.hover.method", "'{0}' のメソッド"),
+    ("lsp.hover.loop_variable", "ループ変数"),
+    ("lsp.hover.inferred", "型は初期値から推定しています"),
+    ("lsp.hover.builtin", "組み込み関数"),
+    ("lsp.hover.include", "{0} を読み込みます"),
+    // 診断コードの説明(--explain, SARIF の rules)
+    ("explain.E0001.summary", "予期しない文字"),
+    ("explain.E0001.explanation", "どのトークンの始まりにもならない文字が見つかりました。\n\n    let a = 1 ` 2;\n\nその文字を取り除くか、文字列リテラルの中に入れてください。"),
+    ("explain.E0002.summary", "閉じられていないリテラルまたはコメント"),
+    ("explain.E0002.explanation", "文字列、文字リテラル、raw文字列またはブロックコメントが、閉じる記号のないまま行末またはファイルの終わりに達しています。\n\n    let s = \"abc;\n\n閉じる引用符(または `*/`)を追加してください。複数行にわたる文字列には三重引用符 \"\"\"...\"\"\" を使ってください。"),
+    ("explain.E0003.summary", "不正なエスケープシーケンス"),
+    ("explain.E0003.explanation", "文字列と文字リテラルで使えるエスケープは \\n, \\r, \\t, \\0, \\\\, \\', \\\", \\$, \\xNN (00-7F) と \\u{...} だけです。\n\n    let p = \"C:\\dir\";\n\nバックスラッシュを重ねる(\"C:\\\\dir\")か、raw文字列 r\"C:\\dir\" を使ってください。"),
+    ("explain.E0004.summary", "不正な数値リテラル"),
+    ("explain.E0004.explanation", "数値リテラルの基数の接頭辞の後に数字がないか、基数に合わない数字を含むか、64ビットに収まりません。\n\n    let a = 0x;\n\n0x, 0o, 0b の後に少なくとも1桁の数字を書き、値を範囲内に収めてください。"),
+    ("explain.E0005.summary", "不正な文字リテラル"),
+    ("explain.E0005.explanation", "シングルクォートの文字リテラルにはちょうど1文字だけを書きます。\n\n    let c = 'ab';\n\n文字列にはダブルクォートを使ってください: \"ab\"。"),
+    ("explain.E0006.summary", "不正な文字列補間"),
+    ("explain.E0006.explanation", "補間文字列(f\"{...}\" または \"${...}\")の埋め込み式が空か閉じられていないか、フォーマット文字列に単独の `}` があります。\n\n    let s = f\"{a\";\n\n式を `}` で閉じてください。フォーマット文字列で括弧そのものを書くには `{{` / `}}` を使います。"),
+    ("explain.E0101.summary", "予期しないトークン"),
+    ("explain.E0101.explanation", "この位置に書けないトークンが見つかりました。\n\n    let a = );\n\n指摘されたトークンの前に、オペランド、演算子または区切りが抜けていないか確認してください。"),
+    ("explain.E0102.summary", "識別子が必要です"),
+    ("explain.E0102.explanation", "名前(変数、関数、引数、構造体、ループ変数)を書く位置に、キーワードまたは別のトークンがあります。\n\n    let fn = 1;\n\nキーワードは名前に使えません。別の名前を付けてください。"),
+    ("explain.E0103.summary", "閉じられていない括弧"),
+    ("explain.E0103.explanation", "丸括弧または波括弧が開かれたまま、対応する閉じ括弧が見つかりません。\n\n    fn f() {\n        let a = (1 + 2;\n\n足りない `)` または `}` を追加してください。"),
+    ("explain.E0104.summary", "型の範囲外のリテラル"),
+    ("explain.E0104.explanation", "数値リテラルが宣言された整数型に収まりません。\n\n    let a: u8 = 300;\n\ni32 や i64 のような広い型を使うか、値を小さくしてください。"),
+    ("explain.E0105.summary", "不完全な宣言"),
+    ("explain.E0105.explanation", "宣言またはプラグマに必要な部分(型宣言の `=`、for 文の `in`、@encoding の後の文字コード名など)が抜けています。\n\n    type Int \"i32\";\n\n抜けている部分を追加してください: type Int = \"i32\";"),
+    ("explain.E0201.summary", "不変の変数への代入"),
+    ("explain.E0201.explanation", "`mut` なしで宣言した変数または引数に、初期化の後で代入しています。\n\n    let a = 1;\n    a = 2;\n\n`let mut a = 1;`(引数の場合は `mut a`)で宣言してください。"),
+    ("explain.E0202.summary", "借用の衝突"),
+    ("explain.E0202.explanation", "参照が使われている間、値を借用できるのは可変参照1つか、任意の数の共有参照のどちらかだけです。\n\n    let mut a = 1;\n    let r = &mut a;\n    let s = &a;\n    @println(r);\n\n最初の借用を使い終えてから次の借用をするか、共有参照だけを使ってください。"),
+    ("explain.E0203.summary", "不変の変数の可変借用"),
+    ("explain.E0203.explanation", "`mut` なしで宣言した変数に `&mut` を付けています。\n\n    let a = 1;\n    let r = &mut a;\n\n変数を `let mut` で宣言してください。"),
+    ("explain.E0204.summary", "借用中の変数への代入"),
+    ("explain.E0204.explanation", "変数への参照がまだ使われている間に、その変数に代入しています。\n\n    let mut a = 1;\n    let r = &a;\n    a = 2;\n    @println(r);\n\n参照を使い終えてから代入してください。"),
+    ("explain.E0205.summary", "共有参照を通した書き込み"),
+    ("explain.E0205.explanation", "読み取りしかできない `&` 参照を通して値を書き換えています。\n\n    let mut a = 1;\n    let r = &a;\n    *r = 2;\n\n参照を通して書き込むには `&mut` で借用してください。"),
+    ("explain.E0301.summary", "未定義の変数"),
+    ("explain.E0301.explanation", "宣言より前、または宣言したブロックの外で変数を使っています。\n\n    @println(total);\n\n先に `let` で宣言し、綴りも確認してください。"),
+    ("explain.E0302.summary", "未定義の関数"),
+    ("explain.E0302.explanation", "スクリプトで宣言されておらず、組み込み関数でもない関数を呼び出しています。\n\n    prnt(1);\n\n綴りを確認してください。組み込み関数は @println(1) のように `@` を付けて呼び出します。"),
+    ("explain.E0303.summary", "重複した定義"),
+    ("explain.E0303.explanation", "同じ名前の変数、関数または構造体がこのスコープですでに定義されています。\n\n    fn f() {}\n    fn f() {}\n\nどちらかの名前を変えてください。"),
+    ("explain.E0304.summary", "実行時の不変の変数への代入"),
+    ("explain.E0304.explanation", "スクリプトの実行中に不変の変数へ代入しました。\n\n    let a = 1;\n    a = 2;\n\n`let mut` で宣言してください。"),
+    ("explain.E0305.summary", "不正な添字"),
+    ("explain.E0305.explanation", "配列の添字が数値でないか、配列の範囲外です。\n\n    let a = [1, 2];\n    @println(a[2]);\n\n添字は0から始まり、配列の長さより小さくなければなりません。"),
+    ("explain.E0306.summary", "ゼロ除算"),
+    ("explain.E0306.explanation", "`/` または `/=` の右辺がゼロになりました。\n\n割る前に除数を確認してください。"),
+    ("explain.E0307.summary", "式として使われた宣言"),
+    ("explain.E0307.explanation", "値を書く位置に変数宣言または代入があります。\n\n    let a = (let b = 1);\n\n宣言は `;` で終わる独立した文として書いてください。"),
+    ("explain.E0308.summary", "反復できない値"),
+    ("explain.E0308.explanation", "for 文で、配列、文字列、範囲のどれでもない値を反復しようとしています。\n\n    for x in 10 { }\n\n範囲(0..10)、配列または文字列を反復してください。"),
+    ("explain.W0001.summary", "使われていない変数・引数・関数"),
+    ("explain.W0001.explanation", "変数や引数が一度も読まれていないか、関数が一度も呼び出されていません。変数への代入は使ったことになりません。\n\n    let total = 1;\n\n宣言を取り除くか、意図して使わない場合は名前の先頭に _ を付けてください(_total)。// tanucc-allow(unused) で抑止できます。"),
+    ("explain.W0002.summary", "グローバル変数の隠蔽"),
+    ("explain.W0002.explanation", "変数、引数またはループ変数がグローバル変数と同じ名前のため、そのスコープではグローバル変数を参照できません。\n\n    let count = 0;\n    fn f() { let count = 1; }\n\nどちらかの名前を変えてください。// tanucc-allow(shadowing) で抑止できます。"),
+    ("explain.W0003.summary", "到達しない文"),
+    ("explain.W0003.explanation", "同じブロックの `return`、`break` または `continue` の後にある文は実行されません。\n\n    return a;\n    @println(\"done\");\n\n文を取り除くか、ジャンプより前に移してください。// tanucc-allow(unreachable) で抑止できます。"),
+    ("explain.W0004.summary", "可変にする必要のない変数"),
+    ("explain.W0004.explanation", "`mut` で宣言した変数が、一度も代入、インクリメントまたは `&mut` での借用をされていません。\n\n    let mut a = 1;\n    @println(a);\n\n`mut` を取り除いてください。// tanucc-allow(unused_mut) で抑止できます。"),
+    ("explain.W0005.summary", "自分自身への代入"),
+    ("explain.W0005.explanation", "変数に自分自身の値を代入しており、何の効果もありません。\n\n    a = a;\n\n文を取り除くか、意図した値を代入してください。// tanucc-allow(self_assign) で抑止できます。"),
+    ("explain.W0006.summary", "定数の条件"),
+    ("explain.W0006.explanation", "if または while の条件がリテラルだけでできており、常に同じ値になります。\n\n    while true { }\n\n無限ループには `loop { }` を使うか、実行されない分岐を取り除いてください。// tanucc-allow(constant_condition) で抑止できます。"),
+    ("explain.W0007.summary", "不明な lint ルール"),
+    ("explain.W0007.explanation", "tanucc-allow コメントに存在しないルール名が書かれているため、何も抑止されません。\n\n    // tanucc-allow(unsued)\n\nunused, shadowing, unreachable, unused_mut, self_assign, constant_condition, all のいずれかを使ってください。"),
+    // REPL
+    ("repl.banner", "tanucc-script REPL(:help でコマンドの一覧、Ctrl-D で終了)"),
+    ("repl.help", ":type <値>         コードを評価せずに変数かリテラルの型を表示します\n:ast <コード>      評価せずに構文木を表示します\n:vars              定義済みの変数・関数・構造体を表示します\n:load <ファイル>   スクリプトファイルを評価します\n:help              このヘルプを表示します\n:quit              REPL を終了します"),
+    ("repl.unknown_command", "不明なコマンドです: {0}(:help でコマンドの一覧を表示します)"),
+    ("repl.requires_argument", "{0} には引数が必要です"),
+    ("repl.no_variables", "定義済みの変数はありません"),
+    ("repl.type_requires_value", ":type には何も評価しないように変数名かリテラルだけを指定できます: {0}"),
+    ("repl.editor_failed", "行の編集に失敗しました: {0}"),
+    ("repl.history_failed", "履歴を {0} に保存できませんでした: {1}"),
+];
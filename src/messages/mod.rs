@@ -0,0 +1,262 @@
+mod en;
+mod ja;
+
+use anyhow::Result as R;
+use std::fmt::Display;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+// 診断などのメッセージの言語
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Locale {
+    En,
+    Ja,
+}
+
+static LOCALE: AtomicU8 = AtomicU8::new(Locale::En as u8);
+
+impl Locale {
+    // "ja", "ja_JP.UTF-8", "en_US" などの言語名から選ぶ(C/POSIXは英語)
+    pub fn from_name(name: &str) -> Option<Self> {
+        let language = name
+            .split(['_', '-', '.', '@'])
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        match language.as_str() {
+            "ja" => Some(Locale::Ja),
+            "en" | "c" | "posix" => Some(Locale::En),
+            _ => None,
+        }
+    }
+
+    // コマンドラインの指定 > LC_ALL > LC_MESSAGES > LANG の順に決める(分からなければ英語)
+    pub fn detect(flag: Option<&str>) -> R<Self, String> {
+        if let Some(name) = flag {
+            return Locale::from_name(name)
+                .ok_or_else(|| message_in(Locale::En, "cli.invalid_locale", &[&name]));
+        }
+        let from_env = ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|key| std::env::var(key).ok())
+            .find(|value| !value.is_empty());
+        Ok(from_env
+            .and_then(|name| Locale::from_name(&name))
+            .unwrap_or(Locale::En))
+    }
+
+    fn catalog(self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Locale::En => en::MESSAGES,
+            Locale::Ja => ja::MESSAGES,
+        }
+    }
+}
+
+pub fn set_locale(locale: Locale) {
+    LOCALE.store(locale as u8, Ordering::Relaxed);
+}
+
+pub fn locale() -> Locale {
+    match LOCALE.load(Ordering::Relaxed) {
+        x if x == Locale::Ja as u8 => Locale::Ja,
+        _ => Locale::En,
+    }
+}
+
+// 現在の言語でメッセージを組み立てる
+pub fn message(id: &str, args: &[&dyn Display]) -> String {
+    message_in(locale(), id, args)
+}
+
+// 指定した言語でメッセージを組み立てる(見つからなければ英語、それもなければIDのまま)
+pub fn message_in(locale: Locale, id: &str, args: &[&dyn Display]) -> String {
+    let template = lookup(locale, id)
+        .or_else(|| lookup(Locale::En, id))
+        .unwrap_or(id);
+    fill(template, args)
+}
+
+fn lookup(locale: Locale, id: &str) -> Option<&'static str> {
+    locale
+        .catalog()
+        .iter()
+        .find(|(key, _)| *key == id)
+        .map(|(_, text)| *text)
+}
+
+// {0} {1} ... を引数に置き換える(それ以外の括弧はそのまま残す)
+fn fill(template: &str, args: &[&dyn Display]) -> String {
+    let mut result = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        result.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let digits = after.len() - after.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        let index = after[..digits].parse::<usize>().ok();
+        match (index, after[digits..].starts_with('}')) {
+            (Some(index), true) if index < args.len() => {
+                result.push_str(&args[index].to_string());
+                rest = &after[digits + 1..];
+            }
+            _ => {
+                result.push('{');
+                rest = after;
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+// メッセージカタログからメッセージを組み立てる
+#[macro_export]
+macro_rules! msg {
+    ($id:expr) => {
+        $crate::messages::message($id, &[])
+    };
+    ($id:expr, $($arg:expr),+ $(,)?) => {
+        $crate::messages::message($id, &[$(&$arg as &dyn std::fmt::Display),+])
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+    use std::path::Path;
+
+    fn ids(catalog: &[(&str, &str)]) -> BTreeSet<String> {
+        catalog.iter().map(|(id, _)| id.to_string()).collect()
+    }
+
+    // メッセージ中の {0} {1} ... の番号
+    fn placeholders(template: &str) -> BTreeSet<usize> {
+        let mut found = BTreeSet::new();
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            rest = &rest[start + 1..];
+            let digits = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+            if digits > 0 && rest[digits..].starts_with('}') {
+                found.insert(rest[..digits].parse().unwrap());
+            }
+        }
+        found
+    }
+
+    // ソース中の msg!("...") で使われているID
+    fn used_ids(dir: &Path, found: &mut BTreeSet<String>) {
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                used_ids(&path, found);
+            } else if path.extension().is_some_and(|ext| ext == "rs") {
+                let source = std::fs::read_to_string(&path).unwrap();
+                for part in source.split("msg!(").skip(1) {
+                    let part = part.trim_start();
+                    if let Some(rest) = part.strip_prefix('"') {
+                        let id: String = rest
+                            .chars()
+                            .take_while(|c| c.is_ascii_lowercase() || *c == '_' || *c == '.')
+                            .collect();
+                        // コメントや文字列中の msg!( は除く
+                        if rest[id.len()..].starts_with('"')
+                            && id.starts_with(|c: char| c.is_ascii_lowercase())
+                        {
+                            found.insert(id);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn every_id_exists_in_both_catalogs() {
+        let en = ids(en::MESSAGES);
+        let ja = ids(ja::MESSAGES);
+        let missing_in_ja: Vec<_> = en.difference(&ja).collect();
+        let missing_in_en: Vec<_> = ja.difference(&en).collect();
+        assert!(
+            missing_in_ja.is_empty(),
+            "missing in ja: {:?}",
+            missing_in_ja
+        );
+        assert!(
+            missing_in_en.is_empty(),
+            "missing in en: {:?}",
+            missing_in_en
+        );
+    }
+
+    #[test]
+    fn ids_are_unique() {
+        for catalog in [en::MESSAGES, ja::MESSAGES] {
+            assert_eq!(ids(catalog).len(), catalog.len());
+        }
+    }
+
+    #[test]
+    fn placeholders_match_between_catalogs() {
+        for (id, text) in en::MESSAGES {
+            let translated = lookup(Locale::Ja, id).unwrap();
+            assert_eq!(
+                placeholders(text),
+                placeholders(translated),
+                "placeholders differ for {}",
+                id
+            );
+        }
+    }
+
+    #[test]
+    fn every_used_id_is_defined() {
+        let mut used = BTreeSet::new();
+        used_ids(
+            &Path::new(env!("CARGO_MANIFEST_DIR")).join("src"),
+            &mut used,
+        );
+        let defined = ids(en::MESSAGES);
+        let undefined: Vec<_> = used.difference(&defined).collect();
+        assert!(!used.is_empty());
+        assert!(
+            undefined.is_empty(),
+            "undefined message ids: {:?}",
+            undefined
+        );
+    }
+
+    // 診断コードの説明は msg! に文字列リテラルで渡さないので別に確かめる
+    #[test]
+    fn every_error_code_has_an_explanation() {
+        let en = ids(en::MESSAGES);
+        for code in crate::error::ERROR_CODES {
+            for part in ["summary", "explanation"] {
+                let id = format!("explain.{}.{}", code, part);
+                assert!(en.contains(&id), "undefined message id: {}", id);
+            }
+        }
+    }
+
+    #[test]
+    fn fills_arguments_by_position() {
+        assert_eq!(
+            message_in(Locale::Ja, "log.not_recorded", &[&3, &"./script-log"]),
+            "./script-log にエラー #3 は記録されていません"
+        );
+        assert_eq!(
+            message_in(Locale::En, "lex.invalid_unicode_escape", &[&"110000"]),
+            "Invalid unicode escape '\\u{110000}': expected 1-6 hex digits forming a valid Unicode scalar value"
+        );
+        assert_eq!(message_in(Locale::En, "no.such.id", &[]), "no.such.id");
+    }
+
+    #[test]
+    fn detects_locale_from_names() {
+        assert_eq!(Locale::from_name("ja_JP.UTF-8"), Some(Locale::Ja));
+        assert_eq!(Locale::from_name("en_US.UTF-8"), Some(Locale::En));
+        assert_eq!(Locale::from_name("C"), Some(Locale::En));
+        assert_eq!(Locale::from_name("fr_FR"), None);
+        assert_eq!(Locale::detect(Some("ja")), Ok(Locale::Ja));
+        assert!(Locale::detect(Some("xx")).is_err());
+    }
+}
@@ -0,0 +1,641 @@
+use crate::error::ScriptError;
+use crate::lexer::tokenizer::{Lexer, Token};
+use crate::msg;
+use crate::parser::syntax::{Node, Parser};
+use crate::types::*;
+use anyhow::Result as R;
+use serde_json::Value;
+
+// 1段のインデント
+const INDENT: &str = "    ";
+
+// 整形の単位(トークン1つ、補間文字列は全体で1つ)
+#[derive(Debug, Clone)]
+struct Piece {
+    token_type: TokenType,
+    text: String,      // ソース上の綴り(改行はLFにそろえる)
+    start_line: usize, // ソース上の開始行
+    end_line: usize,   // ソース上の終了行
+}
+
+impl Piece {
+    fn is(&self, token_type: TokenType) -> bool {
+        self.token_type == token_type
+    }
+    fn is_comment(&self) -> bool {
+        matches!(
+            self.token_type,
+            TokenType::SingleComment(..) | TokenType::MultiComment(..) | TokenType::DocComment(_)
+        )
+    }
+    // 値の終わりになるトークン(直後の & * は二項演算子になる)
+    fn ends_value(&self) -> bool {
+        match &self.token_type {
+            TokenType::Ident
+            | TokenType::Number
+            | TokenType::DoubleQuote
+            | TokenType::SingleQuote
+            | TokenType::TemplateStart
+            | TokenType::RightParen
+            | TokenType::RightSquareBrace
+            | TokenType::RightCurlyBrace
+            | TokenType::Increment
+            | TokenType::Decrement => true,
+            TokenType::Keyword(keyword) => keyword.is_type(),
+            _ => false,
+        }
+    }
+    // 前後を空ける二項演算子と代入
+    fn is_binary_operator(&self) -> bool {
+        matches!(
+            self.token_type,
+            TokenType::Add
+                | TokenType::Sub
+                | TokenType::Mul
+                | TokenType::Div
+                | TokenType::AddAssign
+                | TokenType::SubAssign
+                | TokenType::MulAssign
+                | TokenType::DivAssign
+                | TokenType::Eq
+                | TokenType::Ne
+                | TokenType::Lt
+                | TokenType::Gt
+                | TokenType::Le
+                | TokenType::Ge
+                | TokenType::And
+                | TokenType::Or
+                | TokenType::BitAnd
+                | TokenType::BitOr
+                | TokenType::BitXor
+                | TokenType::ShiftLeft
+                | TokenType::ShiftRight
+                | TokenType::BitAndAssign
+                | TokenType::BitOrAssign
+                | TokenType::BitXorAssign
+                | TokenType::ShiftLeftAssign
+                | TokenType::ShiftRightAssign
+                | TokenType::Equals
+                | TokenType::RightArrow
+        )
+    }
+}
+
+// 開いている括弧の種類
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Bracket {
+    Block { is_item: bool }, // 文のブロック(トップレベルの fn/callback/impl の本体は項目)
+    Struct,                  // 構造体のフィールド(1行に1つ)
+    List { multiline: bool }, // () と [] (開き括弧の直後で改行していれば要素ごとに改行)
+}
+
+struct Formatter {
+    pieces: Vec<Piece>,
+    out: Vec<String>,
+    brackets: Vec<Bracket>,
+    item_closed: bool, // 直前にトップレベルの項目を閉じた
+    prefix: bool,      // 直前のトークンが前置の演算子(参照・参照外し・補数)
+}
+
+// スクリプトを整形する
+// 字句と構文を解析してから標準の配置で出力し直し、構文木が変わらないことを確かめる
+pub fn format_source(file_name: &str, source: &str) -> R<String, ScriptError> {
+    let tokens = Lexer::from_tokenize(file_name, source.to_string())?;
+    let original = Parser::from_parse(&tokens, file_name, source.to_string())?;
+
+    let formatted = Formatter::new(pieces(&tokens, source)).run();
+
+    let unchanged = Lexer::from_tokenize(file_name, formatted.clone())
+        .and_then(|tokens| Parser::from_parse(&tokens, file_name, formatted.clone()))
+        .is_ok_and(|node| shape(&node) == shape(&original));
+    if !unchanged {
+        return Err(ScriptError::io(file_name, msg!("fmt.changed_meaning")));
+    }
+    Ok(formatted)
+}
+
+// 整形前後の行の差分(--check で表示する)
+pub fn diff(file_name: &str, before: &str, after: &str) -> String {
+    let old: Vec<&str> = before.lines().collect();
+    let new: Vec<&str> = after.lines().collect();
+    // 最長共通部分列の長さの表
+    let mut table = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            table[i][j] = if old[i] == new[j] {
+                table[i + 1][j + 1] + 1
+            } else {
+                table[i + 1][j].max(table[i][j + 1])
+            };
+        }
+    }
+    let mut result = String::new();
+    let (mut i, mut j) = (0, 0);
+    let mut in_hunk = false;
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            i += 1;
+            j += 1;
+            in_hunk = false;
+            continue;
+        }
+        if !in_hunk {
+            result.push_str(&msg!("fmt.diff_header", file_name, i + 1));
+            result.push('\n');
+            in_hunk = true;
+        }
+        if i < old.len() && (j == new.len() || table[i + 1][j] >= table[i][j + 1]) {
+            result.push_str(&format!("-{}\n", old[i]));
+            i += 1;
+        } else {
+            result.push_str(&format!("+{}\n", new[j]));
+            j += 1;
+        }
+    }
+    result
+}
+
+// トークン列を整形の単位に分ける(補間文字列は中身ごと1つにまとめる)
+fn pieces(tokens: &[Token], source: &str) -> Vec<Piece> {
+    let line_of = |byte: usize| source[..byte.min(source.len())].matches('\n').count() + 1;
+    let mut pieces: Vec<Piece> = Vec::new();
+    let mut template: Option<(usize, usize)> = None; // (開始バイト,入れ子の深さ)
+    for token in tokens {
+        let span = token.span();
+        match (token.token_type(), template) {
+            (TokenType::Eof, _) => break,
+            (TokenType::TemplateStart, None) => template = Some((span.start_byte, 1)),
+            (TokenType::TemplateStart, Some((start, depth))) => template = Some((start, depth + 1)),
+            (TokenType::TemplateEnd, Some((start, 1))) => {
+                template = None;
+                pieces.push(Piece {
+                    token_type: TokenType::TemplateStart,
+                    text: source[start..span.end_byte].replace('\r', ""),
+                    start_line: line_of(start),
+                    end_line: line_of(span.end_byte),
+                });
+            }
+            (TokenType::TemplateEnd, Some((start, depth))) => template = Some((start, depth - 1)),
+            (_, Some(_)) => {}
+            (token_type, None) => {
+                let text = source[span.start_byte..span.end_byte].replace('\r', "");
+                pieces.push(Piece {
+                    token_type,
+                    text: text.trim_end().to_string(),
+                    start_line: line_of(span.start_byte),
+                    end_line: line_of(span.end_byte),
+                });
+            }
+        }
+    }
+    pieces
+}
+
+impl Formatter {
+    fn new(pieces: Vec<Piece>) -> Self {
+        Formatter {
+            pieces,
+            out: Vec::new(),
+            brackets: Vec::new(),
+            item_closed: false,
+            prefix: false,
+        }
+    }
+
+    fn run(mut self) -> String {
+        let mut last_code: Option<Piece> = None; // コメントを除いた直前のトークン
+        let mut previous: Option<Piece> = None;
+        for i in 0..self.pieces.len() {
+            let piece = self.pieces[i].clone();
+            let next = self.pieces.get(i + 1).cloned();
+
+            // 閉じ括弧は改行の判断より先に閉じる
+            let closing = match piece.token_type {
+                TokenType::RightCurlyBrace
+                | TokenType::RightParen
+                | TokenType::RightSquareBrace => self.brackets.pop(),
+                _ => None,
+            };
+
+            match &previous {
+                None => self.out.push(String::new()),
+                Some(previous) => {
+                    let last = last_code.as_ref();
+                    if self.breaks_before(previous, last, &piece, closing) {
+                        // 空行は1行までにそろえ、括弧の内側の最初と最後には置かない
+                        let blank = (piece.start_line > previous.end_line + 1 || self.item_closed)
+                            && !last.is_some_and(|last| {
+                                matches!(
+                                    last.token_type,
+                                    TokenType::LeftCurlyBrace
+                                        | TokenType::LeftParen
+                                        | TokenType::LeftSquareBrace
+                                )
+                            })
+                            && closing.is_none();
+                        if blank {
+                            self.out.push(String::new());
+                        }
+                        let continues = self.continues(last, &piece);
+                        let depth = self.depth() + continues as usize;
+                        self.out.push(INDENT.repeat(depth));
+                    } else if self.spaced(last, previous, &piece) {
+                        self.write(" ");
+                    }
+                }
+            }
+            self.write(&piece.text);
+            self.item_closed = false;
+
+            if piece.is_comment() {
+                previous = Some(piece);
+                continue;
+            }
+            self.update(i, next.as_ref(), closing);
+            self.prefix = matches!(
+                piece.token_type,
+                TokenType::BitAnd | TokenType::Mul | TokenType::BitNot
+            ) && !last_code.as_ref().is_some_and(Piece::ends_value);
+            last_code = Some(piece.clone());
+            previous = Some(piece);
+        }
+
+        let mut text: String = self
+            .out
+            .iter()
+            .map(|line| line.trim_end())
+            .collect::<Vec<_>>()
+            .join("\n");
+        text.push('\n');
+        text
+    }
+
+    fn write(&mut self, text: &str) {
+        if let Some(line) = self.out.last_mut() {
+            line.push_str(text);
+        }
+    }
+
+    // インデントの段数
+    fn depth(&self) -> usize {
+        self.brackets
+            .iter()
+            .filter(|bracket| !matches!(bracket, Bracket::List { multiline: false }))
+            .count()
+    }
+
+    // トークンを書いた後に括弧の状態を進める
+    fn update(&mut self, i: usize, next: Option<&Piece>, closing: Option<Bracket>) {
+        let piece = &self.pieces[i];
+        match piece.token_type {
+            TokenType::LeftCurlyBrace => {
+                let bracket = match self.introducer(i) {
+                    Some(Keyword::Struct) => Bracket::Struct,
+                    Some(Keyword::Fn | Keyword::Callback | Keyword::Impl) => Bracket::Block {
+                        is_item: self.brackets.is_empty(),
+                    },
+                    _ => Bracket::Block { is_item: false },
+                };
+                self.brackets.push(bracket);
+            }
+            TokenType::LeftParen | TokenType::LeftSquareBrace => {
+                let multiline = next.is_some_and(|next| {
+                    next.start_line > piece.end_line
+                        && !next.is(TokenType::RightParen)
+                        && !next.is(TokenType::RightSquareBrace)
+                });
+                self.brackets.push(Bracket::List { multiline });
+            }
+            TokenType::RightCurlyBrace => {
+                // トップレベルの関数・impl・構造体の後は1行空ける
+                let is_item = match closing {
+                    Some(Bracket::Block { is_item }) => is_item,
+                    Some(Bracket::Struct) => self.brackets.is_empty(),
+                    _ => false,
+                };
+                if is_item {
+                    self.item_closed = true;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // i番目の { を導くキーワード(同じ文の中でさかのぼって最初に見つかるもの)
+    fn introducer(&self, i: usize) -> Option<Keyword> {
+        let mut depth = 0;
+        for piece in self.pieces[..i].iter().rev() {
+            match &piece.token_type {
+                TokenType::RightParen | TokenType::RightSquareBrace => depth += 1,
+                TokenType::LeftParen | TokenType::LeftSquareBrace => depth -= 1,
+                TokenType::Semi | TokenType::LeftCurlyBrace | TokenType::RightCurlyBrace => {
+                    return None
+                }
+                TokenType::Keyword(
+                    keyword @ (Keyword::Fn
+                    | Keyword::Callback
+                    | Keyword::Impl
+                    | Keyword::Struct
+                    | Keyword::If
+                    | Keyword::Else
+                    | Keyword::While
+                    | Keyword::For
+                    | Keyword::Loop),
+                ) if depth == 0 => return Some(*keyword),
+                _ => {}
+            }
+        }
+        None
+    }
+
+    // 直前との間で改行するかどうか
+    fn breaks_before(
+        &self,
+        previous: &Piece,
+        last: Option<&Piece>,
+        piece: &Piece,
+        closing: Option<Bracket>,
+    ) -> bool {
+        // 行コメントの後は必ず改行し、行末のコメントは同じ行に残す
+        if matches!(
+            previous.token_type,
+            TokenType::SingleComment(..) | TokenType::DocComment(_)
+        ) {
+            return true;
+        }
+        if matches!(piece.token_type, TokenType::DocComment(_)) {
+            return true;
+        }
+        if piece.is_comment() {
+            return piece.start_line > previous.end_line;
+        }
+        if previous.is_comment() && piece.start_line > previous.end_line {
+            return true;
+        }
+        let Some(last) = last else {
+            return false;
+        };
+        let top = self.brackets.last().copied();
+
+        // 改行が必要な位置
+        match last.token_type {
+            TokenType::Semi => return true,
+            TokenType::LeftCurlyBrace => return !piece.is(TokenType::RightCurlyBrace),
+            TokenType::RightCurlyBrace => {
+                return !matches!(
+                    piece.token_type,
+                    TokenType::Keyword(Keyword::Else)
+                        | TokenType::Semi
+                        | TokenType::Conma
+                        | TokenType::RightParen
+                        | TokenType::RightSquareBrace
+                );
+            }
+            TokenType::LeftParen | TokenType::LeftSquareBrace
+                if top == Some(Bracket::List { multiline: true }) && closing.is_none() =>
+            {
+                return true;
+            }
+            TokenType::Conma
+                if matches!(
+                    top,
+                    Some(Bracket::Struct) | Some(Bracket::List { multiline: true })
+                ) =>
+            {
+                return true;
+            }
+            _ => {}
+        }
+        match piece.token_type {
+            TokenType::RightCurlyBrace => return true,
+            TokenType::RightParen | TokenType::RightSquareBrace => {
+                return closing == Some(Bracket::List { multiline: true });
+            }
+            // 続けて書く位置
+            TokenType::Semi | TokenType::Conma | TokenType::Keyword(Keyword::Else) => {
+                return false;
+            }
+            TokenType::LeftCurlyBrace if last.ends_value() => return false,
+            _ => {}
+        }
+        // それ以外はソースの改行を残す
+        piece.start_line > previous.end_line
+    }
+
+    // 改行した行が文の続きかどうか(続きなら1段深くする)
+    fn continues(&self, last: Option<&Piece>, piece: &Piece) -> bool {
+        let Some(last) = last else {
+            return false;
+        };
+        if matches!(
+            last.token_type,
+            TokenType::Semi | TokenType::LeftCurlyBrace | TokenType::RightCurlyBrace
+        ) || piece.is(TokenType::RightCurlyBrace)
+        {
+            return false;
+        }
+        match self.brackets.last() {
+            Some(Bracket::List { multiline: true }) | Some(Bracket::Struct)
+                if last.is(TokenType::Conma)
+                    || last.is(TokenType::LeftParen)
+                    || last.is(TokenType::LeftSquareBrace) =>
+            {
+                return false;
+            }
+            _ => {}
+        }
+        if matches!(
+            piece.token_type,
+            TokenType::RightParen | TokenType::RightSquareBrace
+        ) {
+            return false;
+        }
+        !last.ends_value() || piece.is_binary_operator()
+    }
+
+    // 同じ行で直前との間を空けるかどうか
+    fn spaced(&self, last: Option<&Piece>, previous: &Piece, piece: &Piece) -> bool {
+        if previous.is_comment() || piece.is_comment() {
+            return true;
+        }
+        let Some(last) = last else {
+            return false;
+        };
+        // 前置の演算子にはつなげる(& & のように続けると別の演算子になる場合は空ける)
+        if self.prefix {
+            return piece.token_type == last.token_type;
+        }
+        match piece.token_type {
+            TokenType::RightParen
+            | TokenType::RightSquareBrace
+            | TokenType::Conma
+            | TokenType::Semi
+            | TokenType::Colon
+            | TokenType::ScopeResolution
+            | TokenType::Range => return false,
+            TokenType::Increment | TokenType::Decrement if last.ends_value() => return false,
+            TokenType::RightCurlyBrace if last.is(TokenType::LeftCurlyBrace) => return false,
+            // 呼び出しと添字
+            TokenType::LeftParen | TokenType::LeftSquareBrace if last.is(TokenType::Ident) => {
+                return false;
+            }
+            _ => {}
+        }
+        !matches!(
+            last.token_type,
+            TokenType::LeftParen
+                | TokenType::LeftSquareBrace
+                | TokenType::AtSign
+                | TokenType::ScopeResolution
+                | TokenType::Range
+        )
+    }
+}
+
+// 位置の情報を除いた構文木(整形の前後で比べる)
+fn shape(node: &Node) -> Value {
+    let mut value = serde_json::to_value(node).unwrap_or_default();
+    strip_positions(&mut value);
+    value
+}
+
+fn strip_positions(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            map.remove("line");
+            map.remove("column");
+            map.remove("span");
+            for (key, value) in map.iter_mut() {
+                // コメントのノードは(内容,(行,列))を持つ
+                if key == "SingleComment" || key == "MultiComment" {
+                    if let Some(position) = value.get_mut(1) {
+                        *position = Value::Null;
+                    }
+                }
+                strip_positions(value);
+            }
+        }
+        Value::Array(values) => values.iter_mut().for_each(strip_positions),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn format(source: &str) -> String {
+        format_source("test.sc", source).unwrap()
+    }
+
+    // 整形した結果をもう一度整形しても変わらない
+    fn assert_idempotent(source: &str) {
+        let once = format(source);
+        let twice = format(&once);
+        assert_eq!(once, twice, "formatting is not idempotent for:\n{}", source);
+    }
+
+    fn scripts(dir: &Path, found: &mut Vec<std::path::PathBuf>) {
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                scripts(&path, found);
+            } else if path.extension().is_some_and(|ext| ext == "sc") {
+                found.push(path);
+            }
+        }
+    }
+
+    const CASES: &[&str] = &[
+        "fn main(){\n  let a=1;\n      let b = a+2;\n@println(a,b);\n}\n",
+        "struct P{x:i32,y:i32,}\nimpl P{fn get(&self){return 1;}}\n",
+        "fn f(n){\n    if n==0{return 0;}\n    else if n==1{ return 1; }\n    else { return f(n-1)+f(n - 2); }\n}\n",
+        "fn main() {\n    let r = &mut x;\n    *r = 5;\n    for i in 0..3 { @println(i); }\n    loop { break; }\n    while x < 3 { x += 1; }\n}\n",
+        "// 先頭\nfn main() { // 行末\n    /* ブロック\n       コメント */\n    let a = 1; // 値\n\n\n\n    let b = 2;\n}\n",
+        "/// 説明\nfn add(a, b) {\n    return a + b;\n}\nfn main() {\n    let s = f\"x=${ add(1, 2) } ${ f\"in${s}\" }\";\n    let r = r\"C:\\path\";\n}\n",
+        "fn main() {\n    let paths = [\n  \"a\",\n        \"b\",\n    ];\n    @println(add(1,\n        2));\n    let total = 1 +\n  2;\n}\n",
+        "@include \"std.sc\"\n@include \"example/test.sc\"\nfn main() {\n    let c: char = 'x';\n    let n = 10u8;\n    ;\n}\n",
+    ];
+
+    #[test]
+    fn formats_to_canonical_layout() {
+        assert_eq!(
+            format("fn main(){\n  let a=1;\n      if a==1{@println(\"one\")}\n}\nstruct P{x:i32,y:i32,}\n"),
+            "fn main() {\n    let a = 1;\n    if a == 1 {\n        @println(\"one\")\n    }\n}\n\nstruct P {\n    x: i32,\n    y: i32,\n}\n"
+        );
+        assert_eq!(
+            format("fn f(&mut self){\n}\nfn g(){ return f(1)+2; }\n"),
+            "fn f(&mut self) {}\n\nfn g() {\n    return f(1) + 2;\n}\n"
+        );
+    }
+
+    #[test]
+    fn keeps_comments_and_blank_lines() {
+        let source = "// 先頭\nfn main() { // 行末\n    /* ブロック\n       コメント */\n    let a = 1; // 値\n\n\n\n    let b = 2;\n}\n";
+        assert_eq!(
+            format(source),
+            "// 先頭\nfn main() { // 行末\n    /* ブロック\n       コメント */\n    let a = 1; // 値\n\n    let b = 2;\n}\n"
+        );
+    }
+
+    #[test]
+    fn keeps_literals_as_written() {
+        let formatted = format("fn main(){let s=r\"C:\\dir\";let t=f\"${ 1+2 }\";let n=0x1F;}\n");
+        assert!(formatted.contains("r\"C:\\dir\""));
+        assert!(formatted.contains("f\"${ 1+2 }\""));
+        assert!(formatted.contains("0x1F"));
+    }
+
+    #[test]
+    fn is_idempotent() {
+        for case in CASES {
+            assert_idempotent(case);
+        }
+    }
+
+    // 構文エラーを確かめるためのスクリプトは除いて、解析できるものはすべて確かめる
+    #[test]
+    fn is_idempotent_on_scripts() {
+        let mut found = Vec::new();
+        scripts(
+            &Path::new(env!("CARGO_MANIFEST_DIR")).join("script"),
+            &mut found,
+        );
+        assert!(!found.is_empty());
+        for path in found {
+            let Ok(source) = crate::encoding::read_source(&path, None) else {
+                continue;
+            };
+            if format_source(&path.display().to_string(), &source).is_ok() {
+                assert_idempotent(&source);
+            }
+        }
+    }
+
+    // 字句エラーがあるファイルは整形結果を返さない(fmt は書き戻さずに終了する)
+    #[test]
+    fn leaves_files_with_lexical_errors_unchanged() {
+        for (source, code) in [
+            ("fn main(){\n  let a=1 # 2;\n}\n", "E0001"),
+            ("fn main(){\n  let s=\"abc;\n}\n", "E0002"),
+        ] {
+            let error = format_source("test.sc", source).unwrap_err();
+            let codes: Vec<_> = error
+                .diagnostics()
+                .iter()
+                .filter_map(|diagnostic| diagnostic.code.as_deref())
+                .collect();
+            assert_eq!(codes, [code], "for:\n{}", source);
+        }
+    }
+
+    #[test]
+    fn reports_changed_lines() {
+        let diff = diff("a.sc", "fn main(){\n}\n", "fn main() {}\n");
+        assert_eq!(
+            diff,
+            "Diff in a.sc at line 1:\n-fn main(){\n-}\n+fn main() {}\n"
+        );
+    }
+}
@@ -1,2 +1,4 @@
 #[cfg(any(feature = "full", feature = "parser"))]
+pub mod formatter;
+#[cfg(any(feature = "full", feature = "parser"))]
 pub mod syntax;
@@ -1,6 +1,8 @@
 use crate::compile_error;
 use crate::error::*;
-use crate::lexer::tokenizer::Token;
+use crate::lexer::tokenizer::{Token, NUMBER_SUFFIXES};
+use crate::msg;
+use crate::source_map::{with_source_map, Span};
 use crate::types::*;
 use anyhow::{anyhow, Context, Result as R};
 use log::{error, info, warn};
@@ -19,6 +21,12 @@ pub struct Node {
     pub column: usize,
     #[property(get)]
     pub is_statement: bool,
+    #[property(get)]
+    #[serde(default)]
+    pub span: Span,
+    #[property(get)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub doc: Option<String>, // 宣言に付いたドキュメントコメント
 }
 pub struct NodeIter<'a> {
     current: Option<&'a Node>,
@@ -52,6 +60,8 @@ impl Default for Node {
             line: 0,
             column: 0,
             is_statement: false,
+            span: Span::default(),
+            doc: None,
         }
     }
 }
@@ -64,11 +74,324 @@ impl Node {
             line,
             column,
             is_statement: false,
+            span: Span::default(),
+            doc: None,
         }
     }
     pub fn is_next(&self) -> bool {
         self.next.is_some()
     }
+
+    // 直下の子ノードを返す(nextで連結された後続の文は含まない)
+    pub fn children(&self) -> Vec<&Node> {
+        let mut children: Vec<&Node> = Vec::new();
+        match &self.value {
+            NodeValue::ControlFlow(control_flow) => match control_flow {
+                ControlFlow::If(a, b) => {
+                    children.extend([a.as_ref(), b.as_ref()]);
+                    // else / else if は条件式のnextにつながっている
+                    children.extend(a.next.iter().flat_map(|next| next.iter()));
+                }
+                ControlFlow::ElseIf(a, b) | ControlFlow::While(a, b) => {
+                    children.extend([a.as_ref(), b.as_ref()])
+                }
+                ControlFlow::Else(a) | ControlFlow::Loop(a) | ControlFlow::Return(a) => {
+                    children.push(a)
+                }
+                ControlFlow::For(a, b, c) => children.extend([a.as_ref(), b.as_ref(), c.as_ref()]),
+                ControlFlow::Break | ControlFlow::Continue => {}
+            },
+            NodeValue::Operator(operator) => match operator {
+                Operator::Increment(a) | Operator::Decrement(a) | Operator::BitNot(a) => {
+                    children.push(a)
+                }
+                Operator::Eq(a, b)
+                | Operator::Ne(a, b)
+                | Operator::Lt(a, b)
+                | Operator::Gt(a, b)
+                | Operator::Le(a, b)
+                | Operator::Ge(a, b)
+                | Operator::And(a, b)
+                | Operator::Or(a, b)
+                | Operator::Add(a, b)
+                | Operator::Sub(a, b)
+                | Operator::Mul(a, b)
+                | Operator::Div(a, b)
+                | Operator::AddAssign(a, b)
+                | Operator::SubAssign(a, b)
+                | Operator::MulAssign(a, b)
+                | Operator::DivAssign(a, b)
+                | Operator::BitAnd(a, b)
+                | Operator::BitOr(a, b)
+                | Operator::BitXor(a, b)
+                | Operator::ShiftLeft(a, b)
+                | Operator::ShiftRight(a, b)
+                | Operator::BitAndAssign(a, b)
+                | Operator::BitOrAssign(a, b)
+                | Operator::BitXorAssign(a, b)
+                | Operator::ShiftLeftAssign(a, b)
+                | Operator::ShiftRightAssign(a, b)
+                | Operator::Range(a, b) => children.extend([a.as_ref(), b.as_ref()]),
+            },
+            NodeValue::Declaration(declaration) => match declaration {
+                Declaration::Variable(a, b, c, _, _) => {
+                    children.extend([a.as_ref(), b.as_ref(), c.as_ref()])
+                }
+                Declaration::Struct(_, members) | Declaration::Impl(_, members) => {
+                    children.extend(members.iter().flat_map(|m| m.iter()))
+                }
+                Declaration::Function(_, args, body, return_type, _)
+                | Declaration::CallBackFunction(_, args, body, return_type, _) => {
+                    children.extend(args.iter().map(|(data_type, _, _)| data_type.as_ref()));
+                    children.extend([body.as_ref(), return_type.as_ref()]);
+                }
+                Declaration::Type(a, b) => children.extend([a.as_ref(), b.as_ref()]),
+                Declaration::Array(a, values) => {
+                    children.push(a);
+                    children.extend(values.iter().map(|v| v.as_ref()));
+                }
+            },
+            NodeValue::Assign(a, b, c) => children.extend([a.as_ref(), b.as_ref(), c.as_ref()]),
+            NodeValue::Block(block)
+            | NodeValue::ScopeResolution(block)
+            | NodeValue::Interpolation(block) => {
+                children.extend(block.iter().flat_map(|b| b.iter()))
+            }
+            NodeValue::ModDeclaration(_, block) => {
+                children.extend(block.iter().flat_map(|b| b.iter()))
+            }
+            NodeValue::Variable(a, _, _, _) | NodeValue::Dereference(a) | NodeValue::Use(_, a) => {
+                children.push(a)
+            }
+            NodeValue::Call(_, args, _) => children.extend(args.iter()),
+            NodeValue::DataType(_)
+            | NodeValue::MultiComment(_, _)
+            | NodeValue::SingleComment(_, _)
+            | NodeValue::Include(_)
+            | NodeValue::Mod(_)
+            | NodeValue::EndStatement
+            | NodeValue::Null
+            | NodeValue::Unknown => {}
+        }
+        children
+    }
+
+    // 直接の子ノードを可変参照で返す(children と違い、next でつながる後続は含まない)
+    // if の else / else if は条件式の next にあり、条件式と同時には可変で借りられないので含めない
+    // (条件式を返せば、fill_spans のように next をたどる走査で else の連なりも辿れる)
+    pub fn children_mut(&mut self) -> Vec<&mut Node> {
+        let mut children: Vec<&mut Node> = Vec::new();
+        match &mut self.value {
+            NodeValue::ControlFlow(control_flow) => match control_flow {
+                ControlFlow::If(a, b) | ControlFlow::ElseIf(a, b) | ControlFlow::While(a, b) => {
+                    children.extend([a.as_mut(), b.as_mut()])
+                }
+                ControlFlow::Else(a) | ControlFlow::Loop(a) | ControlFlow::Return(a) => {
+                    children.push(a.as_mut())
+                }
+                ControlFlow::For(a, b, c) => children.extend([a.as_mut(), b.as_mut(), c.as_mut()]),
+                ControlFlow::Break | ControlFlow::Continue => {}
+            },
+            NodeValue::Operator(operator) => match operator {
+                Operator::Increment(a) | Operator::Decrement(a) | Operator::BitNot(a) => {
+                    children.push(a.as_mut())
+                }
+                Operator::Eq(a, b)
+                | Operator::Ne(a, b)
+                | Operator::Lt(a, b)
+                | Operator::Gt(a, b)
+                | Operator::Le(a, b)
+                | Operator::Ge(a, b)
+                | Operator::And(a, b)
+                | Operator::Or(a, b)
+                | Operator::Add(a, b)
+                | Operator::Sub(a, b)
+                | Operator::Mul(a, b)
+                | Operator::Div(a, b)
+                | Operator::AddAssign(a, b)
+                | Operator::SubAssign(a, b)
+                | Operator::MulAssign(a, b)
+                | Operator::DivAssign(a, b)
+                | Operator::BitAnd(a, b)
+                | Operator::BitOr(a, b)
+                | Operator::BitXor(a, b)
+                | Operator::ShiftLeft(a, b)
+                | Operator::ShiftRight(a, b)
+                | Operator::BitAndAssign(a, b)
+                | Operator::BitOrAssign(a, b)
+                | Operator::BitXorAssign(a, b)
+                | Operator::ShiftLeftAssign(a, b)
+                | Operator::ShiftRightAssign(a, b)
+                | Operator::Range(a, b) => children.extend([a.as_mut(), b.as_mut()]),
+            },
+            NodeValue::Declaration(declaration) => match declaration {
+                Declaration::Variable(a, b, c, _, _) => {
+                    children.extend([a.as_mut(), b.as_mut(), c.as_mut()])
+                }
+                Declaration::Struct(_, members) | Declaration::Impl(_, members) => {
+                    children.extend(members.iter_mut().map(|m| m.as_mut()))
+                }
+                Declaration::Function(_, args, body, return_type, _)
+                | Declaration::CallBackFunction(_, args, body, return_type, _) => {
+                    children.extend(args.iter_mut().map(|(data_type, _, _)| data_type.as_mut()));
+                    children.extend([body.as_mut(), return_type.as_mut()]);
+                }
+                Declaration::Type(a, b) => children.extend([a.as_mut(), b.as_mut()]),
+                Declaration::Array(a, values) => {
+                    children.push(a.as_mut());
+                    children.extend(values.iter_mut().map(|v| v.as_mut()));
+                }
+            },
+            NodeValue::Assign(a, b, c) => children.extend([a.as_mut(), b.as_mut(), c.as_mut()]),
+            NodeValue::Block(block)
+            | NodeValue::ScopeResolution(block)
+            | NodeValue::Interpolation(block) => {
+                children.extend(block.iter_mut().map(|b| b.as_mut()))
+            }
+            NodeValue::ModDeclaration(_, block) => {
+                children.extend(block.iter_mut().map(|b| b.as_mut()))
+            }
+            NodeValue::Variable(a, _, _, _) | NodeValue::Dereference(a) | NodeValue::Use(_, a) => {
+                children.push(a.as_mut())
+            }
+            NodeValue::Call(_, args, _) => children.extend(args.iter_mut()),
+            NodeValue::DataType(_)
+            | NodeValue::MultiComment(_, _)
+            | NodeValue::SingleComment(_, _)
+            | NodeValue::Include(_)
+            | NodeValue::Mod(_)
+            | NodeValue::EndStatement
+            | NodeValue::Null
+            | NodeValue::Unknown => {}
+        }
+        children
+    }
+
+    // 範囲を持たないノードに親(先頭では引数)の範囲を引き継がせる
+    pub fn fill_spans(&mut self, parent: Span) {
+        let mut current = Some(self);
+        while let Some(node) = current {
+            if node.span.is_dummy() {
+                node.span = parent;
+            }
+            let span = node.span;
+            // 子の next(if の条件式につながる else の連なりを含む)も同じ範囲で埋める
+            for child in node.children_mut() {
+                child.fill_spans(span);
+            }
+            current = node.next.as_deref_mut();
+        }
+    }
+}
+
+// 宣言に付いたドキュメントコメント
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DocItem {
+    pub kind: String, // 宣言の種類(fn,callback,struct,field,impl,method,type)
+    pub name: String, // 宣言名(フィールドは 構造体名.フィールド名、メソッドは 構造体名::関数名)
+    pub doc: String,
+    pub line: usize,
+    pub column: usize,
+    pub span: Span,
+}
+
+// 文の連なりからドキュメントコメント付きの宣言を集める(スクリプトを実行せずに参照できる)
+pub fn collect_docs(node: &Node) -> Vec<DocItem> {
+    let mut items = Vec::new();
+    for node in node.iter() {
+        collect_declaration_docs(node, "", &mut items);
+    }
+    items
+}
+
+fn collect_declaration_docs(node: &Node, owner: &str, items: &mut Vec<DocItem>) {
+    let NodeValue::Declaration(declaration) = &node.value else {
+        return;
+    };
+    let index = items.len(); // 宣言自身はメンバーより前に並べる
+    let (kind, name) = match declaration {
+        Declaration::Function(name, ..) if owner.is_empty() => ("fn", name.clone()),
+        Declaration::Function(name, ..) => ("method", format!("{}::{}", owner, name)),
+        Declaration::CallBackFunction(name, ..) => ("callback", name.clone()),
+        Declaration::Struct(name, members) => {
+            for member in members {
+                if let (Some(doc), Some(field)) = (&member.doc, declared_name(member)) {
+                    items.push(DocItem::new(
+                        "field",
+                        format!("{}.{}", name, field),
+                        doc,
+                        member,
+                    ));
+                }
+            }
+            ("struct", name.clone())
+        }
+        Declaration::Impl(name, members) => {
+            for member in members {
+                collect_declaration_docs(member, name, items);
+            }
+            ("impl", name.clone())
+        }
+        Declaration::Type(name, _) => ("type", declared_name(name).unwrap_or_default()),
+        _ => return,
+    };
+    if let Some(doc) = &node.doc {
+        items.insert(index, DocItem::new(kind, name, doc, node));
+    }
+}
+
+// 宣言された名前(変数や代入の左辺の名前)
+fn declared_name(node: &Node) -> Option<String> {
+    match &node.value {
+        NodeValue::Variable(_, name, _, _) => Some(name.clone()),
+        NodeValue::DataType(DataType::String(name)) => Some(name.clone()),
+        NodeValue::Assign(left, _, _) => declared_name(left),
+        _ => None,
+    }
+}
+
+impl DocItem {
+    fn new(kind: &str, name: String, doc: &str, node: &Node) -> Self {
+        // 位置は範囲の先頭から求め、範囲がなければノードの位置を使う
+        let (line, column) =
+            with_source_map(|map| map.line_column(node.span.file_id, node.span.start_byte))
+                .map_or((node.line, node.column), |position| {
+                    (position.line, position.column)
+                });
+        DocItem {
+            kind: kind.to_string(),
+            name,
+            doc: doc.to_string(),
+            line,
+            column,
+            span: node.span,
+        }
+    }
+}
+
+// エラーメッセージ中でのトークンの表記
+fn describe(token: &Token) -> String {
+    match token.token_type() {
+        TokenType::Eof => msg!("parse.token.end_of_input"),
+        TokenType::TemplateExprEnd => msg!("parse.token.embed_end"),
+        TokenType::TemplateEnd => msg!("parse.token.template_end"),
+        _ if token.text().is_empty() => format!("{:?}", token.token_type()),
+        _ => format!("'{}'", token.text()),
+    }
+}
+
+// 整数型の値の範囲(最小値,最大値)
+fn integer_range(type_name: &str) -> (i128, i128) {
+    match type_name {
+        "i8" => (i8::MIN as i128, i8::MAX as i128),
+        "i16" => (i16::MIN as i128, i16::MAX as i128),
+        "i32" => (i32::MIN as i128, i32::MAX as i128),
+        "u8" => (0, u8::MAX as i128),
+        "u16" => (0, u16::MAX as i128),
+        "u32" => (0, u32::MAX as i128),
+        "u64" => (0, u64::MAX as i128),
+        _ => (i64::MIN as i128, i64::MAX as i128),
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -78,6 +401,8 @@ pub struct Parser<'a> {
     tokens: &'a Vec<Token>,
     i: usize,
     is_statement: bool,
+    literal_type: Option<String>, // 初期化式の数値リテラルに適用する宣言された型
+    diagnostics: Vec<ScriptError>, // 読み飛ばした文の構文エラー
 }
 
 impl<'a> Parser<'a> {
@@ -88,6 +413,8 @@ impl<'a> Parser<'a> {
             input_path: input_path.to_string(),
             input_content,
             is_statement: false,
+            literal_type: None,
+            diagnostics: Vec::new(),
         }
     }
     pub fn input_content(&self) -> String {
@@ -166,6 +493,16 @@ impl<'a> Parser<'a> {
         Box::new(node)
     }
 
+    pub fn new_char(value: char, line: usize, column: usize) -> Box<Node> {
+        let node = Node::new(
+            NodeValue::DataType(DataType::Char(value)),
+            None,
+            line,
+            column,
+        );
+        Box::new(node)
+    }
+
     pub fn new_bool(value: bool, line: usize, column: usize) -> Box<Node> {
         let node = Node::new(
             NodeValue::DataType(DataType::Bool(value)),
@@ -188,7 +525,7 @@ impl<'a> Parser<'a> {
 
     pub fn new_function(
         func_name: String,
-        args: Vec<(Box<Node>, String)>,
+        args: Vec<(Box<Node>, String, bool)>,
         body: Box<Node>,
         return_type: Box<Node>,
         is_system: bool,
@@ -269,7 +606,7 @@ impl<'a> Parser<'a> {
         tokens: &Vec<Token>,
         input_path: &str,
         input_content: String,
-    ) -> R<Box<Node>, String> {
+    ) -> R<Box<Node>, ScriptError> {
         let mut parser = Parser::new(tokens, input_path, input_content);
         parser.parse()
     }
@@ -297,7 +634,73 @@ impl<'a> Parser<'a> {
         self.i += 1;
     }
 
-    fn term(&mut self) -> R<Box<Node>, String> {
+    // 名前を書く位置のトークンが識別子であることを確認してその名前を返す(トークンは進めない)
+    fn check_identifier(&self, what: &str) -> R<String, ScriptError> {
+        let token = self.current_token().unwrap();
+        match token.token_type() {
+            TokenType::Ident => Ok(token.token_value()),
+            TokenType::Keyword(_) => Err(self.format_diagnostic(
+                self.token_diagnostic(
+                    "E0102",
+                    token,
+                    &msg!("parse.keyword_as_name", what, token.text()),
+                    &msg!("parse.keyword_as_name.label"),
+                )
+                .help(&msg!("parse.keyword_as_name.help", token.text())),
+            )),
+            _ => Err(self.format_diagnostic(self.token_diagnostic(
+                "E0102",
+                token,
+                &msg!("parse.expected_name", what, describe(token)),
+                &msg!("parse.expected_name.label", what),
+            ))),
+        }
+    }
+
+    // トークンの範囲を指摘する診断
+    fn token_diagnostic(
+        &self,
+        code: &str,
+        token: &Token,
+        message: &str,
+        label: &str,
+    ) -> Diagnostic {
+        Diagnostic::new("error", message).code(code).primary_span(
+            token.span(),
+            token.line(),
+            token.column(),
+            label,
+        )
+    }
+
+    fn format_diagnostic(&self, diagnostic: Diagnostic) -> ScriptError {
+        diagnostic.into_error(&self.input_path, &self.input_content)
+    }
+
+    // start番目のトークンから直前に読んだトークンまでの範囲
+    fn span_from(&self, start: usize) -> Span {
+        let first = self.tokens.get(start).map(|t| t.span()).unwrap_or_default();
+        if self.i <= start {
+            return Span::new(first.file_id, first.start_byte, first.start_byte);
+        }
+        let last = self
+            .tokens
+            .get(self.i - 1)
+            .map(|t| t.span())
+            .unwrap_or_default();
+        first.to(last)
+    }
+
+    // 範囲が未設定のノードに、start番目のトークンから直前に読んだトークンまでの範囲を設定する
+    fn with_span(&self, start: usize, mut node: Box<Node>) -> Box<Node> {
+        if node.span.is_dummy() {
+            node.span = self.span_from(start);
+        }
+        node
+    }
+
+    fn term(&mut self) -> R<Box<Node>, ScriptError> {
+        let start = self.i;
         let mut node = self.factor()?;
         while matches!(
             self.current_token().unwrap().token_type(),
@@ -315,13 +718,17 @@ impl<'a> Parser<'a> {
                     _ => panic!(
                         "{}",
                         compile_error!(
+                            code = "E0101",
                             "error",
                             op.line(),
                             op.column(),
                             &self.input_path(),
                             &self.input_content(),
-                            "Unexpected token: {:?}",
-                            self.current_token().unwrap()
+                            "{}",
+                            msg!(
+                                "parse.unexpected_token",
+                                format!("{:?}", self.current_token().unwrap())
+                            )
                         )
                     ),
                 }),
@@ -329,11 +736,13 @@ impl<'a> Parser<'a> {
                 self.current_token().unwrap().line(),
                 self.current_token().unwrap().column(),
             ));
+            node = self.with_span(start, node);
         }
         Ok(node)
     }
 
-    fn expr(&mut self) -> R<Box<Node>, String> {
+    fn expr(&mut self) -> R<Box<Node>, ScriptError> {
+        let start = self.i;
         let mut node = self.term()?;
         while matches!(
             self.current_token().unwrap().token_type(),
@@ -368,13 +777,17 @@ impl<'a> Parser<'a> {
                     _ => panic!(
                         "{}",
                         compile_error!(
+                            code = "E0101",
                             "error",
                             op.line(),
                             op.column(),
                             &self.input_path(),
                             &self.input_content(),
-                            "Unexpected token: {:?}",
-                            self.current_token().unwrap()
+                            "{}",
+                            msg!(
+                                "parse.unexpected_token",
+                                format!("{:?}", self.current_token().unwrap())
+                            )
                         )
                     ),
                 }),
@@ -382,17 +795,128 @@ impl<'a> Parser<'a> {
                 self.current_token().unwrap().line(),
                 self.current_token().unwrap().column(),
             ));
+            node = self.with_span(start, node);
         }
         Ok(node)
     }
 
-    fn factor(&mut self) -> R<Box<Node>, String> {
+    // 数値リテラルを型接尾辞付きのデータ型にし、その型(接尾辞がなければ宣言された型、
+    // それもなければi64/f64)の範囲に収まるか検査する
+    fn parse_number(&self, token: &Token) -> R<DataType, ScriptError> {
+        let text = token.token_value();
+        let split = text.find(['i', 'u', 'f']).unwrap_or(text.len());
+        let (number, suffix) = text.split_at(split);
+        let is_float = number.contains(['.', 'e', 'E']) || suffix.starts_with('f');
+        let declared = self
+            .literal_type
+            .as_deref()
+            .map(|t| t.trim_start_matches('[').trim_end_matches(']'))
+            .filter(|t| NUMBER_SUFFIXES.contains(t) && t.starts_with('f') == is_float);
+        let type_name = match (suffix, declared) {
+            ("", Some(t)) => t,
+            ("", None) if is_float => "f64",
+            ("", None) => "i64",
+            (s, _) => s,
+        };
+
+        let in_range = if is_float {
+            let value = number.parse::<f64>().unwrap_or(f64::INFINITY);
+            let max = if type_name == "f32" {
+                f32::MAX as f64
+            } else {
+                f64::MAX
+            };
+            value.abs() <= max
+        } else {
+            let (min, max) = integer_range(type_name);
+            number
+                .parse::<i128>()
+                .map_or(false, |value| min <= value && value <= max)
+        };
+        if !in_range {
+            let diagnostic = self.token_diagnostic(
+                "E0104",
+                token,
+                &msg!("parse.literal_out_of_range", number, type_name),
+                &msg!("parse.literal_out_of_range.label", type_name),
+            );
+            let diagnostic = if is_float {
+                diagnostic
+            } else {
+                let (min, max) = integer_range(type_name);
+                if matches!(type_name, "i64" | "u64") {
+                    diagnostic.help(&msg!(
+                        "parse.literal_out_of_range.range",
+                        type_name,
+                        min,
+                        max
+                    ))
+                } else {
+                    diagnostic.help(&msg!(
+                        "parse.literal_out_of_range.wider",
+                        type_name,
+                        min,
+                        max
+                    ))
+                }
+            };
+            return Err(self.format_diagnostic(diagnostic));
+        }
+
+        let value = if is_float {
+            DataType::Float(number.parse::<f64>().unwrap_or_default())
+        } else if let Ok(value) = number.parse::<i64>() {
+            DataType::Int(value)
+        } else {
+            DataType::UInt(number.parse::<u64>().unwrap_or_default())
+        };
+        if suffix.is_empty() {
+            Ok(value)
+        } else {
+            Ok(DataType::Typed(Box::new(value), suffix.to_string()))
+        }
+    }
+
+    // 現在位置の式が数値リテラル(負号付きを含む)だけかどうか
+    fn is_literal_operand(&self) -> bool {
+        let mut i = self.i;
+        if self.tokens.get(i).map(|t| t.token_type()) == Some(TokenType::Sub) {
+            i += 1;
+        }
+        let is_number = self.tokens.get(i).map(|t| t.token_type()) == Some(TokenType::Number);
+        is_number
+            && matches!(
+                self.tokens.get(i + 1).map(|t| t.token_type()),
+                Some(
+                    TokenType::Semi
+                        | TokenType::Conma
+                        | TokenType::RightSquareBrace
+                        | TokenType::RightCurlyBrace
+                        | TokenType::Eof
+                )
+            )
+    }
+
+    // 宣言された型を適用しない位置(呼び出しの引数,添字,ブロック)を解析する
+    fn without_literal_type<T>(&mut self, parse: impl FnOnce(&mut Self) -> T) -> T {
+        let literal_type = self.literal_type.take();
+        let result = parse(self);
+        self.literal_type = literal_type;
+        result
+    }
+
+    fn factor(&mut self) -> R<Box<Node>, ScriptError> {
+        let start = self.i;
+        let node = self.parse_factor()?;
+        Ok(self.with_span(start, node))
+    }
+
+    fn parse_factor(&mut self) -> R<Box<Node>, ScriptError> {
         let mut token = self.current_token().unwrap().clone();
         let mut is_system = false;
         let mut node = Node::default();
         let mut is_reference = false;
         let mut is_mutable = false;
-        let mut is_dereference = false;
         if token.token_type() == TokenType::AtSign {
             self.next_token();
             token = self.current_token().unwrap().clone();
@@ -402,18 +926,22 @@ impl<'a> Parser<'a> {
             self.next_token();
             token = self.current_token().unwrap().clone();
             is_reference = true;
-            if token.token_value() == "mut" {
+            if token.token_type() == TokenType::Keyword(Keyword::Mut) {
                 self.next_token();
                 token = self.current_token().unwrap().clone();
                 is_mutable = true;
             }
         }
         if token.token_type() == TokenType::Mul {
+            // *式 は参照外し
             self.next_token();
-            token = self.current_token().unwrap().clone();
-            is_dereference = true;
-            return Ok(self.parse_single_statement().unwrap()?);
-            // panic!("{:?}",self.current_token());
+            let operand = self.factor()?;
+            return Ok(Box::new(Node::new(
+                NodeValue::Dereference(operand),
+                None,
+                token.line(),
+                token.column(),
+            )));
         }
 
         match self.current_token().unwrap().token_type() {
@@ -435,7 +963,17 @@ impl<'a> Parser<'a> {
                     self.current_token().unwrap().column(),
                 );
             }
-            TokenType::DoubleQuote | TokenType::SingleQuote => {
+            TokenType::SingleQuote => {
+                // 1文字であることは字句解析で検査済み
+                let c = token.token_value().chars().next().unwrap_or_default();
+                self.next_token();
+                node = *Parser::<'a>::new_char(
+                    c,
+                    self.current_token().unwrap().line(),
+                    self.current_token().unwrap().column(),
+                );
+            }
+            TokenType::DoubleQuote => {
                 if let Ok(string) = token.token_value().parse::<String>() {
                     self.next_token();
                     node = Node::new(
@@ -446,35 +984,33 @@ impl<'a> Parser<'a> {
                     );
                 } else {
                     return Err(compile_error!(
-                    "error",
-                    self.current_token().unwrap().line(),
-                    self.current_token().unwrap().column(),
-                    &self.input_path(),
-                    &self.input_content(),
-                    "Unexpected end of input_content, no closing DoubleQuote or SingleQuote found: {:?}",
-                    self.current_token().unwrap()
-                ));
-                }
-            }
-            TokenType::Number => {
-                if let Ok(number) = token.token_value().parse::<i64>() {
-                    self.next_token();
-                    node = Node::new(
-                        NodeValue::DataType(DataType::Int(number)),
-                        None,
-                        self.current_token().unwrap().line(),
-                        self.current_token().unwrap().column(),
-                    );
-                } else if let Ok(number) = token.token_value().parse::<f64>() {
-                    self.next_token();
-                    node = Node::new(
-                        NodeValue::DataType(DataType::Float(number)),
-                        None,
+                        code = "E0103",
+                        "error",
                         self.current_token().unwrap().line(),
                         self.current_token().unwrap().column(),
-                    );
+                        &self.input_path(),
+                        &self.input_content(),
+                        "{}",
+                        msg!(
+                            "parse.unclosed_double_quote",
+                            format!("{:?}", self.current_token().unwrap())
+                        )
+                    ));
                 }
             }
+            TokenType::TemplateStart => {
+                node = *self.parse_interpolation()?;
+            }
+            TokenType::Number => {
+                let data_type = self.parse_number(&token)?;
+                self.next_token();
+                node = Node::new(
+                    NodeValue::DataType(data_type),
+                    None,
+                    self.current_token().unwrap().line(),
+                    self.current_token().unwrap().column(),
+                );
+            }
             TokenType::Ident => {
                 if let Ok(bool_value) = token.token_value().parse::<bool>() {
                     self.next_token();
@@ -516,18 +1052,47 @@ impl<'a> Parser<'a> {
                     }
                 }
             }
+            // 型名のキーワードは型を書く位置で識別子と同じように扱う
+            TokenType::Keyword(keyword) if keyword.is_type() => {
+                self.next_token();
+                node = Node::new(
+                    NodeValue::Variable(
+                        Parser::<'a>::new_null(token.line(), token.column()),
+                        token.token_value(),
+                        false,
+                        false,
+                    ),
+                    None,
+                    self.current_token().unwrap().line(),
+                    self.current_token().unwrap().column(),
+                );
+            }
+            TokenType::Keyword(_) => {
+                return Err(self.format_diagnostic(self.token_diagnostic(
+                    "E0101",
+                    &token,
+                    &msg!("parse.keyword_as_expression", token.text()),
+                    &msg!("parse.expected_expression.label"),
+                )));
+            }
             TokenType::LeftParen => {
                 self.next_token();
                 node = *self.expr()?;
                 if self.current_token().unwrap().token_type() != TokenType::RightParen {
-                    return Err(compile_error!(
-                        "error",
-                        self.current_token().unwrap().line(),
-                        self.current_token().unwrap().column(),
-                        &self.input_path(),
-                        &self.input_content(),
-                        "no closing parenthesis in factor: {:?}",
-                        self.current_token().unwrap()
+                    let found = self.current_token().unwrap();
+                    return Err(self.format_diagnostic(
+                        self.token_diagnostic(
+                            "E0103",
+                            found,
+                            &msg!("parse.expected_close_paren", describe(found)),
+                            &msg!("parse.expected_close_paren.label"),
+                        )
+                        .secondary_span(
+                            token.span(),
+                            token.line(),
+                            token.column(),
+                            &msg!("parse.unclosed_delimiter.label"),
+                        ),
                     ));
                 } else {
                     self.next_token();
@@ -547,22 +1112,93 @@ impl<'a> Parser<'a> {
                 node = *self.parse_array(&data_type)?;
                 return Ok(Box::new(node));
             }
+            TokenType::Error => {
+                // 字句のエラーは字句解析で報告済み
+                return Err(self.format_diagnostic(self.token_diagnostic(
+                    "E0101",
+                    &token,
+                    &msg!("parse.invalid_token", token.text()),
+                    &msg!("parse.invalid_token.label"),
+                )));
+            }
 
             _ => {
-                return Err(compile_error!(
-                    "error",
-                    self.current_token().unwrap().line(),
-                    self.current_token().unwrap().column(),
-                    &self.input_path(),
-                    &self.input_content(),
-                    "Unexpected token in factor: {:?}",
-                    self.current_token()
-                ));
+                return Err(self.format_diagnostic(self.token_diagnostic(
+                    "E0101",
+                    &token,
+                    &msg!("parse.expected_expression", describe(&token)),
+                    &msg!("parse.expected_expression.label"),
+                )));
             }
         }
         Ok(Box::new(node))
     }
-    fn parse_scope_resolution(&mut self) -> R<Box<Node>, String> {
+    // 補間文字列のトークン列(リテラル部分と埋め込み式)を解析
+    fn parse_interpolation(&mut self) -> R<Box<Node>, ScriptError> {
+        let start = self.current_token().unwrap().clone();
+        self.next_token(); // TemplateStart をスキップ
+        let mut parts = Vec::new();
+        loop {
+            let token = self.current_token().unwrap().clone();
+            match token.token_type() {
+                TokenType::TemplateText => {
+                    let mut text =
+                        Parser::<'a>::new_string(token.token_value(), token.line(), token.column());
+                    text.span = token.span();
+                    parts.push(text);
+                    self.next_token();
+                }
+                TokenType::TemplateExprStart => {
+                    self.next_token();
+                    let expr = self.expr()?;
+                    if self.current_token().unwrap().token_type() != TokenType::TemplateExprEnd {
+                        return Err(compile_error!(
+                            code = "E0006",
+                            "error",
+                            self.current_token().unwrap().line(),
+                            self.current_token().unwrap().column(),
+                            &self.input_path(),
+                            &self.input_content(),
+                            "{}",
+                            msg!(
+                                "parse.malformed_interpolation",
+                                format!("{:?}", self.current_token().unwrap().token_type())
+                            )
+                        ));
+                    }
+                    parts.push(expr);
+                    self.next_token();
+                }
+                TokenType::TemplateEnd => {
+                    self.next_token();
+                    break;
+                }
+                _ => {
+                    return Err(compile_error!(
+                        code = "E0101",
+                        "error",
+                        token.line(),
+                        token.column(),
+                        &self.input_path(),
+                        &self.input_content(),
+                        "{}",
+                        msg!(
+                            "parse.unexpected_interpolation_token",
+                            format!("{:?}", token.token_type())
+                        )
+                    ));
+                }
+            }
+        }
+        Ok(Box::new(Node::new(
+            NodeValue::Interpolation(parts),
+            None,
+            start.line(),
+            start.column(),
+        )))
+    }
+
+    fn parse_scope_resolution(&mut self) -> R<Box<Node>, ScriptError> {
         let mut scope_resolution = vec![];
         let ident = self.current_token().unwrap();
         scope_resolution.push(Box::new(Node::new(
@@ -589,6 +1225,7 @@ impl<'a> Parser<'a> {
             /*         if let NodeValue::Variable(data_type, name) = &scope.value() {
             } else {
                 return Err(compile_error!(
+                    code = "E0101",
                     "error",
                     self.current_token().unwrap().line(),
                     self.current_token().unwrap().column(),
@@ -608,11 +1245,11 @@ impl<'a> Parser<'a> {
             self.current_token().unwrap().column(),
         )));
     }
-    fn parse_function_call(&mut self, token: Token, is_system: bool) -> R<Box<Node>, String> {
+    fn parse_function_call(&mut self, token: Token, is_system: bool) -> R<Box<Node>, ScriptError> {
         self.next_token(); // '(' をスキップ
         let mut args = Vec::new();
         while self.current_token().unwrap().token_type() != TokenType::RightParen {
-            let arg = self.expr()?;
+            let arg = self.without_literal_type(|parser| parser.expr())?;
             args.push(*arg);
             if self.current_token().unwrap().token_type() == TokenType::Conma {
                 self.next_token(); // ',' をスキップ
@@ -629,12 +1266,14 @@ impl<'a> Parser<'a> {
             line: self.current_token().unwrap().line(),
             column: self.current_token().unwrap().column(),
             is_statement: self.is_statement,
+            span: Span::default(),
+            doc: None,
         }))
     }
 
-    fn parse_callback_function_definition(&mut self) -> R<Box<Node>, String> {
+    fn parse_callback_function_definition(&mut self) -> R<Box<Node>, ScriptError> {
         self.next_token(); // 'callback' をスキップ
-        if self.current_token().unwrap().token_value() == "fn" {
+        if self.current_token().unwrap().token_type() == TokenType::Keyword(Keyword::Fn) {
             self.next_token(); // 'fn' をスキップ
             let mut is_system = false;
             if self.current_token().unwrap().token_type() == TokenType::AtSign {
@@ -642,15 +1281,27 @@ impl<'a> Parser<'a> {
                 is_system = true;
             }
 
-            let name = self.current_token().unwrap().token_value().clone();
+            let name = self.check_identifier(&msg!("parse.name.function"))?;
             self.next_token(); // 関数名をスキップ
             self.next_token(); // '(' をスキップ
-            let mut args: Vec<(Box<Node>, String)> = Vec::new();
+            let mut args: Vec<(Box<Node>, String, bool)> = Vec::new();
             let mut return_type = Parser::<'a>::new_null(
                 self.current_token().unwrap().line(),
                 self.current_token().unwrap().column(),
             );
             while self.current_token().unwrap().token_type() != TokenType::RightParen {
+                let mut is_mutable = false;
+                if self.current_token().unwrap().token_type() == TokenType::Keyword(Keyword::Mut) {
+                    self.next_token(); // 'mut' をスキップ
+                    is_mutable = true;
+                }
+                // キーワードは引数名に使えない(&selfなどはそのまま式として解析する)
+                if matches!(
+                    self.current_token().unwrap().token_type(),
+                    TokenType::Keyword(_)
+                ) {
+                    self.check_identifier(&msg!("parse.name.parameter"))?;
+                }
                 let arg = self.expr()?;
                 let mut data_type = Parser::<'a>::new_null(
                     self.current_token().unwrap().line(),
@@ -668,9 +1319,20 @@ impl<'a> Parser<'a> {
                 }
                 let arg_name = match arg.value() {
                     NodeValue::Variable(_, ref name, _, _) => name.clone(),
-                    _ => return Err("Invalid argument name".to_string()),
+                    _ => {
+                        return Err(compile_error!(
+                            code = "E0102",
+                            "error",
+                            arg.line(),
+                            arg.column(),
+                            &self.input_path(),
+                            &self.input_content(),
+                            "{}",
+                            msg!("parse.invalid_argument_name")
+                        ))
+                    }
                 };
-                args.push((data_type, arg_name));
+                args.push((data_type, arg_name, is_mutable));
                 if self.current_token().unwrap().token_type() == TokenType::Conma {
                     self.next_token(); // ',' をスキップ
                 }
@@ -697,22 +1359,34 @@ impl<'a> Parser<'a> {
         Ok(Box::new(Node::default()))
     }
 
-    fn parse_function_definition(&mut self) -> R<Box<Node>, String> {
+    fn parse_function_definition(&mut self) -> R<Box<Node>, ScriptError> {
         self.next_token(); // 'fn' をスキップ
         let mut is_system = false;
         if self.current_token().unwrap().token_type() == TokenType::AtSign {
             self.next_token(); // '@' をスキップ
             is_system = true;
         }
-        let name = self.current_token().unwrap().token_value().clone();
+        let name = self.check_identifier(&msg!("parse.name.function"))?;
         self.next_token(); // 関数名をスキップ
         self.next_token(); // '(' をスキップ
-        let mut args: Vec<(Box<Node>, String)> = Vec::new();
+        let mut args: Vec<(Box<Node>, String, bool)> = Vec::new();
         let mut return_type = Parser::<'a>::new_null(
             self.current_token().unwrap().line(),
             self.current_token().unwrap().column(),
         );
         while self.current_token().unwrap().token_type() != TokenType::RightParen {
+            let mut is_mutable = false;
+            if self.current_token().unwrap().token_type() == TokenType::Keyword(Keyword::Mut) {
+                self.next_token(); // 'mut' をスキップ
+                is_mutable = true;
+            }
+            // キーワードは引数名に使えない(&selfなどはそのまま式として解析する)
+            if matches!(
+                self.current_token().unwrap().token_type(),
+                TokenType::Keyword(_)
+            ) {
+                self.check_identifier(&msg!("parse.name.parameter"))?;
+            }
             let arg = self.expr()?;
             let mut data_type = Parser::<'a>::new_null(
                 self.current_token().unwrap().line(),
@@ -730,9 +1404,20 @@ impl<'a> Parser<'a> {
             }
             let arg_name = match arg.value() {
                 NodeValue::Variable(_, ref name, _, _) => name.clone(),
-                _ => return Err("Invalid argument name".to_string()),
+                _ => {
+                    return Err(compile_error!(
+                        code = "E0102",
+                        "error",
+                        arg.line(),
+                        arg.column(),
+                        &self.input_path(),
+                        &self.input_content(),
+                        "{}",
+                        msg!("parse.invalid_argument_name")
+                    ))
+                }
             };
-            args.push((data_type, arg_name));
+            args.push((data_type, arg_name, is_mutable));
             if self.current_token().unwrap().token_type() == TokenType::Conma {
                 self.next_token(); // ',' をスキップ
             }
@@ -757,7 +1442,7 @@ impl<'a> Parser<'a> {
         )))
     }
 
-    fn parse_condition(&mut self) -> R<Box<Node>, String> {
+    fn parse_condition(&mut self) -> R<Box<Node>, ScriptError> {
         let mut node = self.expr()?; // 基本の式を解析
 
         while matches!(
@@ -789,13 +1474,17 @@ impl<'a> Parser<'a> {
                     _ => panic!(
                         "{}",
                         compile_error!(
+                            code = "E0101",
                             "error",
                             op.line(),
                             op.column(),
                             &self.input_path(),
                             &self.input_content(),
-                            "Unexpected token: {:?}",
-                            self.current_token().unwrap()
+                            "{}",
+                            msg!(
+                                "parse.unexpected_token",
+                                format!("{:?}", self.current_token().unwrap())
+                            )
                         )
                     ),
                 },
@@ -807,7 +1496,7 @@ impl<'a> Parser<'a> {
         Ok(node)
     }
 
-    fn parse_loop_statement(&mut self) -> R<Box<Node>, String> {
+    fn parse_loop_statement(&mut self) -> R<Box<Node>, ScriptError> {
         self.next_token(); // 'loop' をスキップ
         self.next_token(); // { をスキップ
 
@@ -820,7 +1509,7 @@ impl<'a> Parser<'a> {
         )))
     }
 
-    fn parse_break(&mut self) -> R<Box<Node>, String> {
+    fn parse_break(&mut self) -> R<Box<Node>, ScriptError> {
         self.next_token(); // break
         Ok(Box::new(Node::new(
             NodeValue::ControlFlow(ControlFlow::Break),
@@ -830,7 +1519,7 @@ impl<'a> Parser<'a> {
         )))
     }
 
-    fn parse_continue(&mut self) -> R<Box<Node>, String> {
+    fn parse_continue(&mut self) -> R<Box<Node>, ScriptError> {
         self.next_token(); // continue
         Ok(Box::new(Node::new(
             NodeValue::ControlFlow(ControlFlow::Continue),
@@ -840,7 +1529,7 @@ impl<'a> Parser<'a> {
         )))
     }
 
-    fn parse_if_statement(&mut self) -> R<Box<Node>, String> {
+    fn parse_if_statement(&mut self) -> R<Box<Node>, ScriptError> {
         self.next_token(); // 'if' をスキップ
         let mut condition = Parser::<'a>::new_null(
             self.current_token().unwrap().line(),
@@ -852,48 +1541,60 @@ impl<'a> Parser<'a> {
         self.next_token(); // { をスキップ
         let body = self.parse_block()?; // ブロックの解析
 
-        let mut if_node = Node {
+        // 'else' または 'else if' の処理(続く分岐は条件式のnextにつなぐ)
+        if self.current_token().unwrap().token_type() == TokenType::Keyword(Keyword::Else) {
+            self.next_token(); // 'else' をスキップ
+            if self.current_token().unwrap().token_type() == TokenType::Keyword(Keyword::If) {
+                // 'else if' の処理
+                let start = self.i;
+                let else_if_node = self.parse_if_statement()?;
+                condition.next = Some(self.with_span(start, else_if_node));
+            } else {
+                // 'else' の処理
+                let start = self.i;
+                let else_body = self.parse_block()?;
+                let else_node = Box::new(Node {
+                    value: NodeValue::ControlFlow(ControlFlow::Else(Box::new(*else_body))),
+                    next: None,
+                    line: self.current_token().unwrap().line(),
+                    column: self.current_token().unwrap().column(),
+                    is_statement: true,
+                    span: Span::default(),
+                    doc: None,
+                });
+                condition.next = Some(self.with_span(start, else_node));
+            }
+        }
+
+        let if_node = Node {
             value: NodeValue::ControlFlow(ControlFlow::If(Box::new(*condition), Box::new(*body))),
             next: None,
             line: self.current_token().unwrap().line(),
             column: self.current_token().unwrap().column(),
             is_statement: true,
+            span: Span::default(),
+            doc: None,
         };
 
-        // 'else' または 'else if' の処理
-        if let Some(next_token) = self.peek_next_token(1) {
-            if next_token.token_value() == "else" {
-                self.next_token(); // 'else' をスキップ
-                if let Some(next_token) = self.peek_next_token(1) {
-                    if next_token.token_value() == "if" {
-                        // 'else if' の処理
-                        self.next_token(); // 'if' をスキップ
-                        let else_if_node = self.parse_if_statement()?;
-                        if_node.next = Some(else_if_node);
-                    } else {
-                        // 'else' の処理
-                        self.next_token(); // { をスキップ
-                        let else_body = self.parse_block()?;
-                        let else_node = Node {
-                            value: NodeValue::ControlFlow(ControlFlow::Else(Box::new(*else_body))),
-                            next: None,
-                            line: self.current_token().unwrap().line(),
-                            column: self.current_token().unwrap().column(),
-                            is_statement: true,
-                        };
-                        if_node.next = Some(Box::new(else_node));
-                    }
-                }
-            }
-        }
-
         Ok(Box::new(if_node))
     }
 
-    fn parse_for_statement(&mut self) -> R<Box<Node>, String> {
+    fn parse_for_statement(&mut self) -> R<Box<Node>, ScriptError> {
         self.next_token(); // for
-        let var = self.current_token().unwrap().token_value().clone();
+        let var = self.check_identifier(&msg!("parse.name.loop_variable"))?;
         self.next_token(); // var
+        if self.current_token().unwrap().token_type() != TokenType::Keyword(Keyword::In) {
+            let found = self.current_token().unwrap();
+            return Err(self.format_diagnostic(
+                self.token_diagnostic(
+                    "E0105",
+                    found,
+                    &msg!("parse.expected_in", describe(found)),
+                    &msg!("parse.expected_in.label"),
+                )
+                .help(&msg!("parse.expected_in.help", var)),
+            ));
+        }
         self.next_token(); // in
 
         let start_token = self.current_token().unwrap().token_value().clone();
@@ -963,7 +1664,7 @@ impl<'a> Parser<'a> {
         )))
     }
 
-    fn parse_return_type(&mut self) -> R<Box<Node>, String> {
+    fn parse_return_type(&mut self) -> R<Box<Node>, ScriptError> {
         self.next_token(); // '->' をスキップ
         let return_type = self.expr()?;
         Ok(Box::new(Node::new(
@@ -974,7 +1675,7 @@ impl<'a> Parser<'a> {
         )))
     }
 
-    fn parse_while_statement(&mut self) -> R<Box<Node>, String> {
+    fn parse_while_statement(&mut self) -> R<Box<Node>, ScriptError> {
         self.next_token(); // 'while' をスキップ
         let mut condition = Parser::<'a>::new_null(
             self.current_token().unwrap().line(),
@@ -993,35 +1694,58 @@ impl<'a> Parser<'a> {
         )))
     }
 
-    fn parse_block(&mut self) -> R<Box<Node>, String> {
+    // ブロックの中は初期化式のリテラルの型を引き継がない
+    fn parse_block(&mut self) -> R<Box<Node>, ScriptError> {
+        self.without_literal_type(|parser| parser.parse_block_statements())
+    }
+
+    fn parse_block_statements(&mut self) -> R<Box<Node>, ScriptError> {
         if self.current_token().unwrap().token_type() == TokenType::LeftCurlyBrace {
             self.next_token(); // '{' をスキップ
         }
+        // 閉じ忘れの指摘に使う開き括弧(呼び出し側で読み飛ばしている場合もある)
+        let open = self
+            .i
+            .checked_sub(1)
+            .and_then(|i| self.tokens.get(i))
+            .filter(|t| t.token_type() == TokenType::LeftCurlyBrace)
+            .cloned();
         let mut nodes = Vec::new();
         while self.current_token().unwrap().token_type() != TokenType::RightCurlyBrace {
             if self.current_token().unwrap().token_type() == TokenType::Eof {
-                return Err(compile_error!(
-                    "error",
-                    self.current_token().unwrap().line(),
-                    self.current_token().unwrap().column(),
-                    &self.input_path(),
-                    &self.input_content(),
-                    "Unexpected end of input, no closing curly brace found: {:?}",
-                    self.current_token().unwrap()
-                ));
+                let diagnostic = self.token_diagnostic(
+                    "E0103",
+                    self.current_token().unwrap(),
+                    &msg!("parse.unclosed_block"),
+                    &msg!("parse.unclosed_block.label"),
+                );
+                let diagnostic = match open {
+                    Some(open) => diagnostic.secondary_span(
+                        open.span(),
+                        open.line(),
+                        open.column(),
+                        &msg!("parse.unclosed_delimiter.label"),
+                    ),
+                    None => diagnostic,
+                };
+                return Err(self.format_diagnostic(diagnostic));
             }
             let statements = self.parse_statement()?;
             nodes.push(statements);
         }
         if self.current_token().unwrap().token_type() != TokenType::RightCurlyBrace {
             return Err(compile_error!(
+                code = "E0103",
                 "error",
                 self.current_token().unwrap().line(),
                 self.current_token().unwrap().column(),
                 &self.input_path(),
                 &self.input_content(),
-                "no closing curly brace in block: {:?}",
-                self.current_token().unwrap()
+                "{}",
+                msg!(
+                    "parse.unclosed_block_at",
+                    format!("{:?}", self.current_token().unwrap())
+                )
             ));
         } else {
             self.next_token(); // '}' をスキップ
@@ -1034,7 +1758,7 @@ impl<'a> Parser<'a> {
         }
     }
 
-    fn parse_data_type(&mut self) -> R<Box<Node>, String> {
+    fn parse_data_type(&mut self) -> R<Box<Node>, ScriptError> {
         if self.peek_next_token(1).unwrap().token_type() != TokenType::Eof
             && self.peek_next_token(1).unwrap().token_type() != TokenType::Conma
         {
@@ -1050,10 +1774,25 @@ impl<'a> Parser<'a> {
         )))
     }
 
-    fn parse_type_declaration(&mut self) -> R<Box<Node>, String> {
+    fn parse_type_declaration(&mut self) -> R<Box<Node>, ScriptError> {
         self.next_token();
-        let _type_name = self.current_token().unwrap().token_value().clone();
+        // 組み込みの型名(i32など)は標準ライブラリで型として宣言できる
+        let _type_name = match self.current_token().unwrap().token_type() {
+            TokenType::Keyword(keyword) if keyword.is_type() => {
+                self.current_token().unwrap().token_value()
+            }
+            _ => self.check_identifier(&msg!("parse.name.type"))?,
+        };
         self.next_token();
+        if self.current_token().unwrap().token_type() != TokenType::Equals {
+            let found = self.current_token().unwrap();
+            return Err(self.format_diagnostic(self.token_diagnostic(
+                "E0105",
+                found,
+                &msg!("parse.expected_type_equals", describe(found)),
+                &msg!("parse.expected_type_equals.label"),
+            )));
+        }
         self.next_token();
         let value_node = self.expr()?;
         Ok(Box::new(Node {
@@ -1078,19 +1817,19 @@ impl<'a> Parser<'a> {
             line: self.current_token().unwrap().line(),
             column: self.current_token().unwrap().column(),
             is_statement: self.is_statement,
+            span: Span::default(),
+            doc: None,
         }))
     }
 
-    fn parse_variable_declaration(&mut self) -> R<Box<Node>, String> {
+    fn parse_variable_declaration(&mut self) -> R<Box<Node>, ScriptError> {
         self.next_token();
         let mut is_mutable = false;
-        if let Some(token) = self.current_token() {
-            if token.token_value() == "mut" || token.token_value() == "mutable" {
-                self.next_token();
-                is_mutable = true;
-            }
+        if self.current_token().unwrap().token_type() == TokenType::Keyword(Keyword::Mut) {
+            self.next_token();
+            is_mutable = true;
         }
-        let var = self.current_token().unwrap().token_value().clone();
+        let var = self.check_identifier(&msg!("parse.name.variable"))?;
         let mut data_type = Box::new(Node::new(
             NodeValue::DataType(DataType::from(Parser::<'a>::new_null(
                 self.current_token().unwrap().line(),
@@ -1148,6 +1887,8 @@ impl<'a> Parser<'a> {
                 line: self.current_token().unwrap().line(),
                 column: self.current_token().unwrap().column(),
                 is_statement: self.is_statement,
+                span: Span::default(),
+                doc: None,
             }));
         }
         self.next_token();
@@ -1194,9 +1935,25 @@ impl<'a> Parser<'a> {
                 line: self.current_token().unwrap().line(),
                 column: self.current_token().unwrap().column(),
                 is_statement: self.is_statement,
+                span: Span::default(),
+                doc: None,
             }));
         }
-        value_node = self.expr()?;
+        // 初期化式がリテラル(またはリテラルの配列)なら宣言された型の範囲で検査する
+        self.literal_type = match data_type.value {
+            NodeValue::DataType(DataType::String(ref v))
+                if self.is_literal_operand()
+                    || (v.starts_with('[')
+                        && self.current_token().unwrap().token_type()
+                            == TokenType::LeftSquareBrace) =>
+            {
+                Some(v.clone())
+            }
+            _ => None,
+        };
+        let value = self.expr();
+        self.literal_type = None;
+        value_node = value?;
         if self.current_token().unwrap().token_type() == TokenType::Semi {
             self.is_statement = true;
         }
@@ -1238,14 +1995,23 @@ impl<'a> Parser<'a> {
             line: self.current_token().unwrap().line(),
             column: self.current_token().unwrap().column(),
             is_statement: self.is_statement,
+            span: Span::default(),
+            doc: None,
         }))
     }
 
-    fn parse_array(&mut self, data_type: &Box<Node>) -> R<Box<Node>, String> {
+    fn parse_array(&mut self, data_type: &Box<Node>) -> R<Box<Node>, ScriptError> {
         self.next_token(); // [ をスキップ
         let mut value_vec = vec![];
+        let literal_type = self.literal_type.clone();
         while self.current_token().unwrap().token_type() != TokenType::RightSquareBrace {
-            value_vec.push(self.expr()?);
+            // 宣言された型は要素がリテラル(または入れ子の配列)の場合だけ適用する
+            let is_literal = self.is_literal_operand()
+                || self.current_token().unwrap().token_type() == TokenType::LeftSquareBrace;
+            self.literal_type = literal_type.clone().filter(|_| is_literal);
+            let value = self.expr();
+            self.literal_type = literal_type.clone();
+            value_vec.push(value?);
             if self.current_token().unwrap().token_type() == TokenType::Conma {
                 self.next_token(); // ',' をスキップ
             }
@@ -1259,7 +2025,7 @@ impl<'a> Parser<'a> {
         )))
     }
 
-    fn parse_assign_variable(&mut self) -> R<Box<Node>, String> {
+    fn parse_assign_variable(&mut self) -> R<Box<Node>, ScriptError> {
         let var = self.current_token().unwrap().token_value().clone();
         let data_type = Parser::<'a>::new_null(
             self.current_token().unwrap().line(),
@@ -1277,7 +2043,7 @@ impl<'a> Parser<'a> {
         self.next_token(); // var
         if self.current_token().unwrap().token_type() == TokenType::LeftSquareBrace {
             self.next_token(); // [
-            index = self.expr()?;
+            index = self.without_literal_type(|parser| parser.expr())?;
 
             self.next_token(); // ]
             self.next_token(); // =
@@ -1306,6 +2072,8 @@ impl<'a> Parser<'a> {
                 line: self.current_token().unwrap().line(),
                 column: self.current_token().unwrap().column(),
                 is_statement: self.is_statement,
+                span: Span::default(),
+                doc: None,
             }))
         } else {
             self.next_token(); // =
@@ -1338,11 +2106,46 @@ impl<'a> Parser<'a> {
                 line: self.current_token().unwrap().line(),
                 column: self.current_token().unwrap().column(),
                 is_statement: self.is_statement,
+                span: Span::default(),
+                doc: None,
             }))
         }
     }
 
-    fn parse_return(&mut self) -> R<Box<Node>, String> {
+    // *参照 = 値 の代入(代入でなければ通常の式として解析)
+    fn parse_assign_dereference(&mut self) -> R<Box<Node>, ScriptError> {
+        let start = self.i;
+        let target = self.factor()?;
+        if self.current_token().unwrap().token_type() != TokenType::Equals {
+            self.i = start;
+            self.is_statement = false;
+            return self.expr();
+        }
+        self.next_token(); // =
+
+        let value_node = self.expr()?;
+        if self.current_token().unwrap().token_type() == TokenType::Semi {
+            self.is_statement = true;
+        }
+        Ok(Box::new(Node {
+            value: NodeValue::Assign(
+                target,
+                value_node,
+                Parser::<'a>::new_null(
+                    self.current_token().unwrap().line(),
+                    self.current_token().unwrap().column(),
+                ),
+            ),
+            next: None,
+            line: self.current_token().unwrap().line(),
+            column: self.current_token().unwrap().column(),
+            is_statement: self.is_statement,
+            span: Span::default(),
+            doc: None,
+        }))
+    }
+
+    fn parse_return(&mut self) -> R<Box<Node>, ScriptError> {
         self.next_token();
         let mut ret_value = Box::new(Node::default());
         ret_value = self.expr()?;
@@ -1353,10 +2156,12 @@ impl<'a> Parser<'a> {
             line: self.current_token().unwrap().line(),
             column: self.current_token().unwrap().column(),
             is_statement: self.is_statement,
+            span: Span::default(),
+            doc: None,
         }))
     }
 
-    fn parse_include(&mut self) -> R<Box<Node>, String> {
+    fn parse_include(&mut self) -> R<Box<Node>, ScriptError> {
         self.next_token(); // @
         self.next_token(); // include
         let include_file_path = self.current_token().unwrap().token_value().clone();
@@ -1369,9 +2174,57 @@ impl<'a> Parser<'a> {
         self.next_token();
         Ok(Box::new(include_node))
     }
-    fn parse_impl_definition(&mut self) -> R<Box<Node>, String> {
+    // @encoding "..." はスクリプトの読み込み時に解釈済みなので、文字列であることだけを確かめる
+    // 読み込み時に見るのはファイル先頭だけなので、それより後に書いたものはエラーにする
+    fn parse_encoding_pragma(&mut self) -> R<Box<Node>, ScriptError> {
+        let start = self.i;
+        self.next_token(); // @
+        self.next_token(); // encoding
+        let is_first = self.tokens[..start].iter().all(|token| {
+            matches!(
+                token.token_type(),
+                TokenType::SingleComment(..)
+                    | TokenType::MultiComment(..)
+                    | TokenType::DocComment(_)
+            )
+        });
+        if !is_first {
+            let pragma = &self.tokens[start];
+            return Err(self.format_diagnostic(
+                Diagnostic::new("error", &msg!("parse.misplaced_encoding_pragma"))
+                    .code("E0101")
+                    .primary_span(
+                        self.span_from(start),
+                        pragma.line(),
+                        pragma.column(),
+                        &msg!("parse.misplaced_encoding_pragma.label"),
+                    )
+                    .help(&msg!("parse.misplaced_encoding_pragma.help")),
+            ));
+        }
+        let token = self.current_token().unwrap().clone();
+        if token.token_type() != TokenType::DoubleQuote {
+            return Err(self.format_diagnostic(
+                self.token_diagnostic(
+                    "E0105",
+                    &token,
+                    &msg!("parse.expected_encoding_name", token.text()),
+                    &msg!("parse.expected_encoding_name.label"),
+                )
+                .help(&msg!("parse.expected_encoding_name.help")),
+            ));
+        }
+        self.next_token();
+        Ok(Box::new(Node::new(
+            NodeValue::Null,
+            None,
+            token.line(),
+            token.column(),
+        )))
+    }
+    fn parse_impl_definition(&mut self) -> R<Box<Node>, ScriptError> {
         self.next_token(); // impl
-        let var = self.current_token().unwrap().token_value().clone();
+        let var = self.check_identifier(&msg!("parse.name.struct"))?;
         let mut member: Vec<Box<Node>> = Vec::new();
 
         self.next_token(); // var
@@ -1389,20 +2242,28 @@ impl<'a> Parser<'a> {
                 self.current_token().unwrap().column(),
             )))
         } else {
-            Err(String::from(""))
+            let found = self.current_token().unwrap();
+            Err(self.format_diagnostic(self.token_diagnostic(
+                "E0105",
+                found,
+                &msg!("parse.expected_impl_brace", var, describe(found)),
+                &msg!("parse.expected_impl_brace.label"),
+            )))
         }
     }
 
-    fn parse_struct_definition(&mut self) -> R<Box<Node>, String> {
+    fn parse_struct_definition(&mut self) -> R<Box<Node>, ScriptError> {
         self.next_token(); // struct
-        let var = self.current_token().unwrap().token_value().clone();
+        let var = self.check_identifier(&msg!("parse.name.struct"))?;
         let mut member: Vec<Box<Node>> = Vec::new();
 
         self.next_token(); // var
         if self.current_token().unwrap().token_type() == TokenType::LeftCurlyBrace {
             self.next_token(); // {
             while self.current_token().unwrap().token_type() != TokenType::RightCurlyBrace {
-                let member_value = self.expr()?;
+                let doc = self.parse_doc_comments();
+                let mut member_value = self.expr()?;
+                member_value.doc = doc;
                 member.push(member_value);
                 if self.current_token().unwrap().token_type() == TokenType::Conma {
                     self.next_token(); // ',' をスキップ
@@ -1434,112 +2295,392 @@ impl<'a> Parser<'a> {
         }
     }
 
-    fn parse_single_statement(&mut self) -> Option<R<Box<Node>, String>> {
-        let result = if self.current_token().unwrap().token_value() == "callback" {
-            self.parse_callback_function_definition()
-        } else if self.current_token().unwrap().token_value() == "struct" {
-            self.parse_struct_definition()
-        } else if self.current_token().unwrap().token_value() == "impl" {
-            self.parse_impl_definition()
-        } else if self.current_token().unwrap().token_value() == "fn" {
-            self.parse_function_definition()
-        } else if self.current_token().unwrap().token_value() == "while" {
-            self.parse_while_statement()
-        } else if self.current_token().unwrap().token_value() == "if" {
-            self.parse_if_statement()
-        } else if self.current_token().unwrap().token_type() == TokenType::Ident
-            && self.current_token().unwrap().token_value() == "for"
-            && self.peek_next_token(2).unwrap().token_value() == "in"
-        {
-            self.parse_for_statement()
-        } else if self.current_token().unwrap().token_value() == "loop" {
-            self.parse_loop_statement()
-        } else if self.current_token().unwrap().token_type() == TokenType::Ident
-            && (self.current_token().unwrap().token_value() == "let"
-                || self.current_token().unwrap().token_value() == "var"
-                || self.current_token().unwrap().token_value() == "l"
-                || self.current_token().unwrap().token_value() == "v")
-        {
-            self.parse_variable_declaration()
-        } else if self.current_token().unwrap().token_type() == TokenType::Ident
-            && self.peek_next_token(2).unwrap().token_type() == TokenType::Equals
-            && self.current_token().unwrap().token_value() == "type"
-        {
-            self.parse_type_declaration()
-        } else if self.current_token().unwrap().token_type() == TokenType::Ident
-            && self.peek_next_token(1).unwrap().token_type() == TokenType::Equals
-            || self.current_token().unwrap().token_type() == TokenType::Ident
-                && self.peek_next_token(1).unwrap().token_type() == TokenType::LeftSquareBrace
-        {
-            self.parse_assign_variable()
-        } else if self.current_token().unwrap().token_type() == TokenType::Ident
-            && self.current_token().unwrap().token_value() == "return"
-        {
-            self.parse_return()
-        } else if self.current_token().unwrap().token_type() == TokenType::Ident
-            && self.current_token().unwrap().token_value() == "break"
-        {
-            self.parse_break()
-        } else if self.current_token().unwrap().token_type() == TokenType::Ident
-            && self.current_token().unwrap().token_value() == "continue"
-        {
-            self.parse_continue()
-        } else if self.current_token().unwrap().token_type() == TokenType::AtSign
-            && self.peek_next_token(1).unwrap().token_type() == TokenType::Ident
-            && self.peek_next_token(1).unwrap().token_value() == "include"
-        {
-            self.parse_include()
-        } else if self.current_token().unwrap().token_type() == TokenType::LeftCurlyBrace {
-            self.parse_block()
-        } else if self.current_token().unwrap().token_type() == TokenType::Semi {
-            self.is_statement = true;
+    // 続くドキュメントコメントをまとめて読み取る
+    fn parse_doc_comments(&mut self) -> Option<String> {
+        let mut lines = Vec::new();
+        while let TokenType::DocComment(text) = self.current_token().unwrap().token_type() {
+            lines.push(text);
             self.next_token();
-            return Some(Ok(Box::new(Node::new(
-                NodeValue::EndStatement,
+        }
+        if lines.is_empty() {
+            None
+        } else {
+            Some(lines.join("\n"))
+        }
+    }
+
+    // ドキュメントコメントを直後の宣言(fn,callback,struct,impl,type)に付ける
+    // 宣言が続かない場合は通常のコメントとして扱う
+    fn parse_documented_declaration(&mut self) -> R<Box<Node>, ScriptError> {
+        let token = self.current_token().unwrap().clone();
+        let doc = self.parse_doc_comments().unwrap_or_default();
+        match self.current_token().unwrap().token_type() {
+            TokenType::Keyword(
+                Keyword::Fn | Keyword::Callback | Keyword::Struct | Keyword::Impl | Keyword::Type,
+            ) => {
+                let mut node = self.parse_single_statement().unwrap()?;
+                node.doc = Some(doc);
+                Ok(node)
+            }
+            _ => Ok(Box::new(Node::new(
+                NodeValue::MultiComment(
+                    doc.lines().map(|line| line.to_string()).collect(),
+                    (token.line(), token.column()),
+                ),
                 None,
                 self.current_token().unwrap().line(),
                 self.current_token().unwrap().column(),
-            )))); // ステートメントを終了
-        } else {
-            self.is_statement = false;
-            self.expr()
+            ))),
+        }
+    }
+
+    fn parse_single_statement(&mut self) -> Option<R<Box<Node>, ScriptError>> {
+        let start = self.i;
+        let result = match self.current_token().unwrap().token_type() {
+            TokenType::Keyword(Keyword::Callback) => self.parse_callback_function_definition(),
+            TokenType::Keyword(Keyword::Struct) => self.parse_struct_definition(),
+            TokenType::Keyword(Keyword::Impl) => self.parse_impl_definition(),
+            TokenType::Keyword(Keyword::Fn) => self.parse_function_definition(),
+            TokenType::Keyword(Keyword::While) => self.parse_while_statement(),
+            TokenType::Keyword(Keyword::If) => self.parse_if_statement(),
+            TokenType::Keyword(Keyword::For) => self.parse_for_statement(),
+            TokenType::Keyword(Keyword::Loop) => self.parse_loop_statement(),
+            TokenType::Keyword(Keyword::Let) | TokenType::Keyword(Keyword::Var) => {
+                self.parse_variable_declaration()
+            }
+            TokenType::Keyword(Keyword::Type) => self.parse_type_declaration(),
+            TokenType::Keyword(Keyword::Return) => self.parse_return(),
+            TokenType::Keyword(Keyword::Break) => self.parse_break(),
+            TokenType::Keyword(Keyword::Continue) => self.parse_continue(),
+            TokenType::DocComment(_) => self.parse_documented_declaration(),
+            TokenType::Ident
+                if matches!(
+                    self.peek_next_token(1).unwrap().token_type(),
+                    TokenType::Equals | TokenType::LeftSquareBrace
+                ) =>
+            {
+                self.parse_assign_variable()
+            }
+            TokenType::Mul => self.parse_assign_dereference(),
+            TokenType::AtSign
+                if self.peek_next_token(1).unwrap().token_type() == TokenType::Ident
+                    && self.peek_next_token(1).unwrap().text() == "include" =>
+            {
+                self.parse_include()
+            }
+            TokenType::AtSign
+                if self.peek_next_token(1).unwrap().token_type() == TokenType::Ident
+                    && self.peek_next_token(1).unwrap().text() == "encoding" =>
+            {
+                self.parse_encoding_pragma()
+            }
+            TokenType::LeftCurlyBrace => self.parse_block(),
+            TokenType::Semi => {
+                self.is_statement = true;
+                self.next_token();
+                let node = Box::new(Node::new(
+                    NodeValue::EndStatement,
+                    None,
+                    self.current_token().unwrap().line(),
+                    self.current_token().unwrap().column(),
+                ));
+                return Some(Ok(self.with_span(start, node))); // ステートメントを終了
+            }
+            _ => {
+                self.is_statement = false;
+                self.expr()
+            }
+        };
+
+        Some(result.map(|node| self.with_span(start, node)))
+    }
+
+    // ブロックの終わり(} かファイルの終わり)までの文を解析する
+    // 構文エラーのある文は診断を記録して読み飛ばし、続きの文の解析を続ける
+    fn parse_statement(&mut self) -> R<Box<Node>, ScriptError> {
+        let mut head: Option<Box<Node>> = None;
+        let mut recovered = false;
+        loop {
+            let token = self.current_token().unwrap().clone();
+            if token.token_type() == TokenType::Eof
+                || token.token_type() == TokenType::RightCurlyBrace
+            {
+                return match head {
+                    Some(node) => Ok(node),
+                    // すべての文が構文エラーだった場合は報告済みなので空の文にする
+                    None if recovered => Ok(Parser::new_null(token.line(), token.column())),
+                    None => Err(self.statement_error(&msg!("parse.no_statements"))),
+                };
+            }
+            let start = self.i;
+            let result = match self.parse_single_statement() {
+                Some(result) => result,
+                None => Err(self.statement_error(&msg!("parse.failed_statement"))),
+            };
+            match result {
+                Ok(node) => match head.as_mut() {
+                    Some(head) => {
+                        // ここで新しいノードを追加する
+                        let mut current = head.as_mut();
+                        while current.next.is_some() {
+                            current = current.next.as_mut().unwrap();
+                        }
+                        current.set_next(Some(node));
+                    }
+                    None => head = Some(node),
+                },
+                Err(e) => {
+                    self.synchronize();
+                    // Errorトークンを含む文のエラーは字句解析で報告済み
+                    let has_lexical_error = self.tokens[start..self.i]
+                        .iter()
+                        .any(|token| token.token_type() == TokenType::Error);
+                    if !has_lexical_error {
+                        self.diagnostics.push(e);
+                    }
+                    recovered = true;
+                }
+            }
+        }
+    }
+    // 構文エラーのあと、次の文の始まりまで読み飛ばす
+    // (; の次か、途中で開いたブロックを閉じる } の次。外側のブロックを閉じる } は読まない)
+    fn synchronize(&mut self) {
+        let mut depth = 0;
+        loop {
+            match self.current_token().unwrap().token_type() {
+                TokenType::Eof => return,
+                TokenType::LeftCurlyBrace => depth += 1,
+                TokenType::RightCurlyBrace if depth == 0 => return,
+                TokenType::RightCurlyBrace => {
+                    depth -= 1;
+                    if depth == 0 {
+                        self.next_token();
+                        return;
+                    }
+                }
+                TokenType::Semi if depth == 0 => {
+                    self.next_token();
+                    return;
+                }
+                _ => {}
+            }
+            self.next_token();
+        }
+    }
+    // 現在のトークンの位置で文の構文エラーを生成
+    fn statement_error(&self, message: &str) -> ScriptError {
+        let token = self.current_token().unwrap();
+        compile_error!(
+            code = "E0101",
+            "error",
+            token.line(),
+            token.column(),
+            &self.input_path(),
+            &self.input_content(),
+            "{}",
+            message
+        )
+    }
+    // 構文エラーがあればすべての診断をまとめてエラーとして返す
+    pub fn parse(&mut self) -> R<Box<Node>, ScriptError> {
+        let (node, diagnostics) = self.parse_all();
+        match (ScriptError::merge(diagnostics), node) {
+            (Some(error), _) => Err(error),
+            (None, Some(node)) => Ok(node),
+            (None, None) => Err(self.statement_error(&msg!("parse.no_statements"))),
+        }
+    }
+    // 構文エラーのある文を読み飛ばして最後まで解析し、解析できた文と構文エラーの診断を返す
+    // (Errorトークンを含む文は診断を返さずに読み飛ばすので、字句の診断は Lexer から得る)
+    pub fn parse_all(&mut self) -> (Option<Box<Node>>, Vec<ScriptError>) {
+        self.diagnostics.clear();
+        let node = match self.parse_statement() {
+            Ok(mut node) => {
+                node.fill_spans(Span::default());
+                Some(node)
+            }
+            Err(e) => {
+                self.diagnostics.push(e);
+                None
+            }
         };
+        (node, std::mem::take(&mut self.diagnostics))
+    }
+}
 
-        Some(result)
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::tokenizer::Lexer;
+
+    // トップレベルで宣言した関数の名前
+    fn function_names(node: &Node) -> Vec<String> {
+        node.iter()
+            .filter_map(|node| match &node.value {
+                NodeValue::Declaration(Declaration::Function(name, ..)) => Some(name.clone()),
+                _ => None,
+            })
+            .collect()
     }
 
-    fn parse_statement(&mut self) -> R<Box<Node>, String> {
-        self.parse_statement_recursive(None)
+    #[test]
+    fn reports_every_lexical_error_and_parses_later_declarations() {
+        let source = "let a = \"abc;\nlet b = 1 # 2;\nfn after() {\n    return 1;\n}\n";
+        let mut lexer = Lexer::new_with_value("recover_lex.sc", source.to_string());
+        let tokens = lexer.tokenize_all();
+        assert_eq!(lexer.diagnostics().len(), 2);
+        let (node, diagnostics) =
+            Parser::new(&tokens, "recover_lex.sc", source.to_string()).parse_all();
+        assert!(diagnostics.is_empty());
+        assert_eq!(function_names(&node.unwrap()), ["after"]);
     }
 
-    fn parse_statement_recursive(
-        &mut self,
-        previous_node: Option<Box<Node>>,
-    ) -> R<Box<Node>, String> {
-        if self.current_token().unwrap().token_type() == TokenType::Eof
-            || self.current_token().unwrap().token_type() == TokenType::RightCurlyBrace
-        {
-            return previous_node.ok_or("No statements found".to_string());
+    #[test]
+    fn reports_every_syntax_error_and_parses_later_declarations() {
+        let source = "let = 1;\nfn broken() {\n    for i 0..3 { }\n}\nfn after() {\n}\n";
+        let tokens = Lexer::from_tokenize("recover_parse.sc", source.to_string()).unwrap();
+        let error =
+            Parser::from_parse(&tokens, "recover_parse.sc", source.to_string()).unwrap_err();
+        let codes: Vec<_> = error
+            .diagnostics()
+            .iter()
+            .filter_map(|diagnostic| diagnostic.code.clone())
+            .collect();
+        assert_eq!(codes, ["E0102", "E0105"]);
+        let (node, diagnostics) =
+            Parser::new(&tokens, "recover_parse.sc", source.to_string()).parse_all();
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(function_names(&node.unwrap()), ["broken", "after"]);
+    }
+
+    // 構文解析で報告された診断コード
+    fn parse_codes(source: &str) -> Vec<String> {
+        let tokens = Lexer::from_tokenize("literal_test.sc", source.to_string()).unwrap();
+        match Parser::from_parse(&tokens, "literal_test.sc", source.to_string()) {
+            Ok(_) => Vec::new(),
+            Err(e) => e
+                .diagnostics()
+                .iter()
+                .filter_map(|diagnostic| diagnostic.code.clone())
+                .collect(),
         }
+    }
 
-        if let Some(statement) = self.parse_single_statement() {
-            let node = statement?;
-            if let Some(mut prev) = previous_node {
-                // ここで新しいノードを追加する
-                let mut current = prev.as_mut();
-                while current.next.is_some() {
-                    current = current.next.as_mut().unwrap();
-                }
-                current.set_next(Some(node.clone()));
-                self.parse_statement_recursive(Some(prev))
-            } else {
-                self.parse_statement_recursive(Some(node))
-            }
-        } else {
-            Err("Failed to parse statement".to_string())
+    // 構文解析で報告された最初の診断のコードとメッセージ
+    fn first_diagnostic(source: &str) -> (String, String) {
+        let tokens = Lexer::from_tokenize("keyword_test.sc", source.to_string()).unwrap();
+        let error = Parser::from_parse(&tokens, "keyword_test.sc", source.to_string()).unwrap_err();
+        let diagnostic = &error.diagnostics()[0];
+        (
+            diagnostic.code.clone().unwrap_or_default(),
+            diagnostic.message.clone(),
+        )
+    }
+
+    #[test]
+    fn rejects_keywords_as_names() {
+        let cases = [
+            ("let fn = 1;\n", "parse.name.variable", "fn"),
+            ("let mut v = 1;\n", "parse.name.variable", "v"),
+            ("fn let() {\n}\n", "parse.name.function", "let"),
+            ("fn f(mut in) {\n}\n", "parse.name.parameter", "in"),
+            ("struct if {\n}\n", "parse.name.struct", "if"),
+            ("for l in 0..3 {\n}\n", "parse.name.loop_variable", "l"),
+        ];
+        for (source, what, keyword) in cases {
+            assert_eq!(
+                first_diagnostic(source),
+                (
+                    "E0102".to_string(),
+                    msg!("parse.keyword_as_name", msg!(what), keyword)
+                ),
+                "{}",
+                source
+            );
         }
+        assert!(parse_codes("let value = 1;\nlet lets = value;\n").is_empty());
+    }
+
+    #[test]
+    fn requires_in_and_equals_in_declarations() {
+        assert_eq!(
+            first_diagnostic("for i 0..3 {\n}\n"),
+            ("E0105".to_string(), msg!("parse.expected_in", "'0'"))
+        );
+        let (code, message) = first_diagnostic("type Int \"i32\";\n");
+        assert_eq!(code, "E0105");
+        assert!(message.starts_with(msg!("parse.expected_type_equals", "").trim_end()));
+        assert!(parse_codes("type Int = \"i32\";\nfor i in 0..3 {\n}\n").is_empty());
+    }
+
+    #[test]
+    fn accepts_literals_in_the_declared_range() {
+        assert!(
+            parse_codes("let a: u8 = 255;\nlet b: i8 = -128;\nlet c: [u8] = [0, 255];\n")
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn reports_literals_out_of_the_declared_range() {
+        assert_eq!(parse_codes("let a: u8 = 256;\n"), ["E0104"]);
+        assert_eq!(parse_codes("let b: u8 = -1;\n"), ["E0104"]);
+        assert_eq!(parse_codes("let c: [u8] = [1, 300];\n"), ["E0104"]);
+        assert_eq!(parse_codes("let d = 1u8 + 256u8;\n"), ["E0104"]);
     }
-    pub fn parse(&mut self) -> R<Box<Node>, String> {
-        self.parse_statement()
+
+    // 宣言された型は初期化式そのものがリテラルの場合だけ適用する
+    #[test]
+    fn does_not_apply_the_declared_type_to_nested_literals() {
+        assert!(parse_codes("let x: u8 = f(1000);\n").is_empty());
+        assert!(parse_codes("let x: u8 = 1 + 1000;\n").is_empty());
+        assert!(parse_codes("let x: [u8] = [1, f(300)];\n").is_empty());
+        assert!(parse_codes("let x: u8 = 1;\narr[300] = x;\n").is_empty());
+        assert!(parse_codes("fn f() {\n    let x: u8 = 1;\n    return 1000;\n}\n").is_empty());
+    }
+
+    #[test]
+    fn attaches_doc_comments_to_declarations() {
+        let source = "/// 足し算\n/// 2行目\nfn add(a, b) {\n    return a + b;\n}\n/**\n * 点\n */\nstruct Point {\n    /// 横\n    x: i32,\n    y: i32,\n}\nimpl Point {\n    /// 長さ\n    fn len(&self) {\n        return 0;\n    }\n}\n// 普通のコメント\nfn plain() {}\n//// 区切り\nfn other() {}\n/// 型\ntype Int = \"i32\";\n";
+        let tokens = Lexer::from_tokenize("doc_test.sc", source.to_string()).unwrap();
+        let node = Parser::from_parse(&tokens, "doc_test.sc", source.to_string()).unwrap();
+        let docs: Vec<_> = collect_docs(&node)
+            .into_iter()
+            .map(|item| (item.kind, item.name, item.doc, item.line, item.column))
+            .collect();
+        let expected = [
+            ("fn", "add", "足し算\n2行目", 3, 1),
+            ("struct", "Point", "点", 9, 1),
+            ("field", "Point.x", "横", 11, 5),
+            ("method", "Point::len", "長さ", 16, 5),
+            ("type", "Int", "型", 25, 1),
+        ];
+        let expected: Vec<_> = expected
+            .iter()
+            .map(|(kind, name, doc, line, column)| {
+                (
+                    kind.to_string(),
+                    name.to_string(),
+                    doc.to_string(),
+                    *line,
+                    *column,
+                )
+            })
+            .collect();
+        assert_eq!(docs, expected);
+    }
+
+    #[test]
+    fn accepts_the_encoding_pragma_only_before_other_code() {
+        assert!(parse_codes(
+            "// 先頭\n/* 説明\n   2行目 */\n@encoding \"shift_jis\"\nlet a = 1;\n"
+        )
+        .is_empty());
+        assert_eq!(
+            parse_codes("let a = 1;\n@encoding \"shift_jis\"\nlet b = 2;\n"),
+            ["E0101"]
+        );
+        assert_eq!(parse_codes("@encoding shift_jis\n"), ["E0105"]);
     }
 }
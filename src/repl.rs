@@ -0,0 +1,347 @@
+use crate::decoder::interpreter::{display_value, Decoder};
+use crate::encoding;
+use crate::error::{Diagnostic, ScriptError};
+use crate::lexer::tokenizer::{Lexer, Token};
+use crate::msg;
+use crate::parser::syntax::{Node, Parser};
+use crate::source_map::with_source_map;
+use crate::types::*;
+use anyhow::Result as R;
+use rustyline::error::ReadlineError;
+use rustyline::DefaultEditor;
+use serde_json::Value;
+
+const HISTORY_FILE: &str = "./.tanucc_history"; // 入力履歴の保存先(スクリプトのディレクトリ)
+const PROMPT: &str = ">> ";
+const CONTINUATION_PROMPT: &str = ".. "; // 括弧が閉じていない間の続きの行
+
+// 対話的にスクリプトを評価する(入力ごとに同じ Decoder に追加して評価する)
+pub fn run(decoder: Decoder, source_encoding: Option<&str>) -> R<(), String> {
+    let mut editor = DefaultEditor::new().map_err(|e| msg!("repl.editor_failed", e))?;
+    // 初回は履歴ファイルがないので読めなくてもよい
+    let _ = editor.load_history(HISTORY_FILE);
+    let mut repl = Repl {
+        decoder,
+        source_encoding: source_encoding.map(str::to_string),
+        count: 0,
+    };
+    println!("{}", msg!("repl.banner"));
+
+    let mut input = String::new();
+    loop {
+        let prompt = if input.is_empty() {
+            PROMPT
+        } else {
+            CONTINUATION_PROMPT
+        };
+        let line = match editor.readline(prompt) {
+            Ok(line) => line,
+            // Ctrl-C は入力中の行を捨てる
+            Err(ReadlineError::Interrupted) => {
+                input.clear();
+                continue;
+            }
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(msg!("repl.editor_failed", e)),
+        };
+        if !input.is_empty() {
+            input.push('\n');
+        }
+        input.push_str(&line);
+        if input.trim().is_empty() {
+            input.clear();
+            continue;
+        }
+        // コマンド以外は括弧が閉じるまで続きの行を読む
+        if !input.trim_start().starts_with(':') && is_incomplete(&input) {
+            continue;
+        }
+        let entry = std::mem::take(&mut input);
+        let _ = editor.add_history_entry(entry.as_str());
+        if !repl.execute(entry.trim())? {
+            break;
+        }
+    }
+
+    if let Err(e) = editor.save_history(HISTORY_FILE) {
+        eprintln!("{}", msg!("repl.history_failed", HISTORY_FILE, e));
+    }
+    Ok(())
+}
+
+struct Repl {
+    decoder: Decoder,
+    source_encoding: Option<String>,
+    count: usize, // これまでの入力の数(入力ごとのファイル名に使う)
+}
+
+impl Repl {
+    // 1回分の入力を処理する(終了するときは false)
+    fn execute(&mut self, entry: &str) -> R<bool, String> {
+        let (command, argument) = match entry.strip_prefix(':') {
+            Some(command) => match command.split_once(char::is_whitespace) {
+                Some((command, argument)) => (command, argument.trim()),
+                None => (command, ""),
+            },
+            None => {
+                let file_name = self.next_file_name();
+                print_result(self.decoder.eval_text(&file_name, entry));
+                self.decoder.discard_text(&file_name);
+                return Ok(true);
+            }
+        };
+        let requires_argument = ["type", "ast", "load"];
+        if requires_argument.contains(&command) && argument.is_empty() {
+            eprintln!(
+                "{}",
+                msg!("repl.requires_argument", format!(":{}", command))
+            );
+            return Ok(true);
+        }
+        match command {
+            "quit" | "q" => return Ok(false),
+            "help" | "h" => println!("{}", msg!("repl.help")),
+            "type" => match self.type_of(argument) {
+                Ok(type_name) => println!("{}", type_name),
+                Err(e) => eprintln!("{}", e),
+            },
+            "ast" => {
+                let file_name = self.next_file_name();
+                match parse(&file_name, argument) {
+                    Ok(json) => println!("{}", json),
+                    Err(e) => eprintln!("{}", e),
+                }
+                with_source_map(|map| map.remove_file(&file_name));
+            }
+            "vars" => self.print_variables(),
+            "load" => {
+                let result = encoding::read_source(argument, self.source_encoding.as_deref())
+                    .and_then(|content| self.decoder.eval_text(argument, &content));
+                print_result(result);
+            }
+            _ => eprintln!("{}", msg!("repl.unknown_command", entry)),
+        }
+        Ok(true)
+    }
+
+    // 入力ごとに別のファイルとして扱う(エラーの位置を入力単位で表示するため)
+    fn next_file_name(&mut self) -> String {
+        self.count += 1;
+        format!("<repl:{}>", self.count)
+    }
+
+    // 変数またはリテラルの型(宣言した変数はその型、それ以外は値から推定した型)
+    // 関数呼び出しなどの副作用を起こさないように、式は評価しない
+    fn type_of(&mut self, expr: &str) -> R<String, ScriptError> {
+        let context = self.decoder.context();
+        let variable = context
+            .local_context
+            .get(expr)
+            .or_else(|| context.global_context.get(expr));
+        if let Some(variable) = variable {
+            return Ok(match variable.data_type.as_str() {
+                Some(type_name) if !type_name.is_empty() => type_name.to_string(),
+                _ => infer_type(&variable.value),
+            });
+        }
+        let file_name = self.next_file_name();
+        let result = literal_type(&file_name, expr);
+        with_source_map(|map| map.remove_file(&file_name));
+        result
+    }
+
+    // 定義済みの変数・関数・構造体(グローバル、トップレベルのローカルの順)
+    fn print_variables(&self) {
+        let context = self.decoder.context();
+        let variables: Vec<String> = context
+            .global_context
+            .iter()
+            .chain(context.local_context.iter())
+            .filter(|(name, _)| !name.starts_with('@'))
+            .map(|(name, variable)| match &variable.data_type {
+                Value::String(kind) if kind == "Function" => format!("fn {}", name),
+                Value::String(kind) if kind == "CallBackFunction" => format!("callback {}", name),
+                Value::Null if variable.address.is_nil() => format!("struct {}", name),
+                data_type => {
+                    let type_name = match data_type.as_str() {
+                        Some(type_name) if !type_name.is_empty() => type_name.to_string(),
+                        _ => self.decoder.infer_type(&variable.value),
+                    };
+                    let mutable = if variable.is_mutable { "mut " } else { "" };
+                    format!(
+                        "{}{}: {} = {}",
+                        mutable,
+                        name,
+                        type_name,
+                        show_value(&variable.value)
+                    )
+                }
+            })
+            .collect();
+        if variables.is_empty() {
+            println!("{}", msg!("repl.no_variables"));
+        }
+        for variable in variables {
+            println!("{}", variable);
+        }
+    }
+}
+
+// 評価せずに構文木をJSONで返す
+fn parse(file_name: &str, content: &str) -> R<String, ScriptError> {
+    let tokens = Lexer::from_tokenize(file_name, content.to_string())?;
+    let node = Parser::from_parse(&tokens, file_name, content.to_string())?;
+    serde_json::to_string_pretty(&node).map_err(|e| ScriptError::io(file_name, e))
+}
+
+// 1つのリテラルだけの入力の型
+fn literal_type(file_name: &str, content: &str) -> R<String, ScriptError> {
+    let tokens = Lexer::from_tokenize(file_name, content.to_string())?;
+    let node = Parser::from_parse(&tokens, file_name, content.to_string())?;
+    let statements: Vec<&Node> = node
+        .iter()
+        .filter(|node| !matches!(node.value, NodeValue::EndStatement | NodeValue::Null))
+        .collect();
+    let type_name = match statements.as_slice() {
+        [node] => match &node.value {
+            NodeValue::DataType(data_type) => Some(
+                data_type
+                    .literal_type()
+                    .unwrap_or_else(|| "void".to_string()),
+            ),
+            NodeValue::Declaration(Declaration::Array(..)) => Some("array".to_string()),
+            _ => None,
+        },
+        _ => None,
+    };
+    type_name.ok_or_else(|| {
+        Diagnostic::new("error", &msg!("repl.type_requires_value", content))
+            .into_error(file_name, content)
+    })
+}
+
+fn print_result(result: R<Value, ScriptError>) {
+    match result {
+        Ok(Value::Null) => {}
+        Ok(value) => println!("{}", show_value(&value)),
+        Err(e) => eprintln!("{}", e),
+    }
+}
+
+// 評価結果の表示(文字列は他の値と区別できるように引用符を付ける)
+fn show_value(value: &Value) -> String {
+    match value {
+        Value::String(_) => value.to_string(),
+        _ => display_value(value),
+    }
+}
+
+// 括弧・複数行の文字列・複数行コメントが閉じていない入力かどうか
+fn is_incomplete(input: &str) -> bool {
+    let mut lexer = Lexer::new_with_value("<repl>", input.to_string());
+    let tokens: Vec<Token> = lexer.tokenize_all();
+    with_source_map(|map| map.remove_file("<repl>"));
+    let unclosed = [
+        msg!("lex.unclosed_triple_quote"),
+        msg!("lex.unclosed_comment"),
+    ];
+    if lexer
+        .diagnostics()
+        .iter()
+        .flat_map(|e| e.diagnostics().iter())
+        .any(|diagnostic| unclosed.contains(&diagnostic.message))
+    {
+        return true;
+    }
+    let mut depth = 0i32;
+    for token in &tokens {
+        match token.token_type() {
+            TokenType::LeftCurlyBrace | TokenType::LeftParen | TokenType::LeftSquareBrace => {
+                depth += 1
+            }
+            TokenType::RightCurlyBrace | TokenType::RightParen | TokenType::RightSquareBrace => {
+                depth -= 1
+            }
+            _ => {}
+        }
+    }
+    depth > 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unbalanced_input_continues_on_the_next_line() {
+        assert!(is_incomplete("fn f() {"));
+        assert!(is_incomplete("let a = [1,\n2"));
+        assert!(is_incomplete("/* comment"));
+        assert!(!is_incomplete("fn f() {\n}"));
+        assert!(!is_incomplete("let s = \"{\";"));
+        assert!(!is_incomplete("// {"));
+    }
+
+    #[test]
+    fn evaluation_keeps_state_between_inputs() {
+        let mut decoder = Decoder::new();
+        let value = decoder.eval_text("<repl:1>", "let x = 20;").unwrap();
+        assert_eq!(value, Value::Null);
+        decoder
+            .eval_text("<repl:2>", "fn twice(n) {\n    return n * 2;\n}")
+            .unwrap();
+        let value = decoder.eval_text("<repl:3>", "twice(x) + 2").unwrap();
+        assert_eq!(value, serde_json::json!(42));
+        assert!(decoder.eval_text("<repl:4>", "let x = 1;").is_err());
+        let value = decoder.eval_text("<repl:5>", "x").unwrap();
+        assert_eq!(value, serde_json::json!(20));
+    }
+
+    fn repl() -> Repl {
+        Repl {
+            decoder: Decoder::new(),
+            source_encoding: None,
+            count: 0,
+        }
+    }
+
+    #[test]
+    fn type_of_does_not_evaluate_the_expression() {
+        let mut repl = repl();
+        repl.decoder
+            .eval_text(
+                "<repl:setup>",
+                "let mut calls = 0;\nfn f() {\n    calls = calls + 1;\n    return 1;\n}",
+            )
+            .unwrap();
+        assert!(repl.type_of("f()").is_err());
+        assert!(repl.type_of("calls + 1").is_err());
+        assert_eq!(
+            repl.decoder.eval_text("<repl:check>", "calls").unwrap(),
+            serde_json::json!(0)
+        );
+        assert_eq!(repl.type_of("calls").unwrap(), "i32");
+        assert_eq!(repl.type_of("1.5").unwrap(), "f32");
+        assert_eq!(repl.type_of("\"s\"").unwrap(), "string");
+        assert_eq!(repl.type_of("'c'").unwrap(), "char");
+        assert_eq!(repl.type_of("[1, 2]").unwrap(), "array");
+    }
+
+    #[test]
+    fn evaluated_inputs_are_discarded_unless_they_define_items() {
+        let mut repl = repl();
+        repl.execute("let x = 1;").unwrap();
+        repl.execute("fn twice(n) {\n    return n * 2;\n}").unwrap();
+        repl.execute("twice(x)").unwrap();
+        repl.execute("let = ;").unwrap();
+        let files: Vec<String> = repl.decoder.ast_map().keys().cloned().collect();
+        assert_eq!(files, ["<repl:2>"]);
+        let contents: Vec<String> = repl.decoder.file_contents().keys().cloned().collect();
+        assert_eq!(contents, ["<repl:2>"]);
+        // 残した関数は後の入力からも呼び出せる
+        assert_eq!(
+            repl.decoder.eval_text("<repl:5>", "twice(x)").unwrap(),
+            serde_json::json!(2)
+        );
+    }
+}
@@ -0,0 +1,328 @@
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex, OnceLock};
+
+// ソース上のバイト範囲(file_idはソースマップに登録したファイルの番号で、0は位置情報なし)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct Span {
+    pub file_id: usize,
+    pub start_byte: usize,
+    pub end_byte: usize,
+}
+
+impl Span {
+    pub fn new(file_id: usize, start_byte: usize, end_byte: usize) -> Self {
+        Span {
+            file_id,
+            start_byte,
+            end_byte,
+        }
+    }
+    // 位置情報を持たない(合成されたノードなど)かどうか
+    pub fn is_dummy(&self) -> bool {
+        self.file_id == 0
+    }
+    // 2つの範囲をまとめて覆う範囲
+    pub fn to(&self, other: Span) -> Span {
+        if self.is_dummy() {
+            return other;
+        }
+        if other.is_dummy() || other.file_id != self.file_id {
+            return *self;
+        }
+        Span::new(
+            self.file_id,
+            self.start_byte.min(other.start_byte),
+            self.end_byte.max(other.end_byte),
+        )
+    }
+}
+
+// 行・列の位置(いずれも1始まり)
+// columnは文字数(\rは数えない)、utf8_columnはバイト数、utf16_columnはUTF-16のコード単位数で数える
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct LineColumn {
+    pub line: usize,
+    pub column: usize,
+    pub utf8_column: usize,
+    pub utf16_column: usize,
+}
+
+#[derive(Debug)]
+pub struct SourceFile {
+    pub id: usize,
+    pub name: String,
+    pub content: String,
+    line_starts: Vec<usize>, // 各行の先頭のバイト位置
+    hash: u64,               // 内容のハッシュ(登録済みかどうかを内容を比べずに調べる)
+}
+
+impl SourceFile {
+    fn new(id: usize, name: &str, content: &str) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(content.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        SourceFile {
+            id,
+            name: name.to_string(),
+            content: content.to_string(),
+            line_starts,
+            hash: content_hash(content),
+        }
+    }
+
+    // バイト位置を行・列に変換する
+    pub fn line_column(&self, byte: usize) -> LineColumn {
+        let byte = byte.min(self.content.len());
+        let line_index = self.line_starts.partition_point(|&start| start <= byte) - 1;
+        let line_start = self.line_starts[line_index];
+        let before = self.content.get(line_start..byte).unwrap_or_default();
+        LineColumn {
+            line: line_index + 1,
+            column: before.chars().filter(|&c| c != '\r').count() + 1,
+            utf8_column: before.len() + 1,
+            utf16_column: before.encode_utf16().count() + 1,
+        }
+    }
+
+    // 字句解析器の(行,列)をバイト位置に変換する(列は文字数で\rは数えない)
+    pub fn byte_offset(&self, line: usize, column: usize) -> usize {
+        let Some(&line_start) = self.line_starts.get(line.saturating_sub(1)) else {
+            return self.content.len();
+        };
+        let mut remaining = column.saturating_sub(1);
+        for (i, c) in self.content[line_start..].char_indices() {
+            if remaining == 0 || c == '\n' {
+                return line_start + i;
+            }
+            if c != '\r' {
+                remaining -= 1;
+            }
+        }
+        self.content.len()
+    }
+
+    // 行とUTF-16のコード単位数で数えた列(いずれも1始まり)をバイト位置に変換する
+    pub fn utf16_byte_offset(&self, line: usize, utf16_column: usize) -> usize {
+        let Some(&line_start) = self.line_starts.get(line.saturating_sub(1)) else {
+            return self.content.len();
+        };
+        let mut remaining = utf16_column.saturating_sub(1);
+        for (i, c) in self.content[line_start..].char_indices() {
+            if remaining == 0 || c == '\n' {
+                return line_start + i;
+            }
+            remaining = remaining.saturating_sub(c.len_utf16());
+        }
+        self.content.len()
+    }
+
+    // (行,列)の組で表した範囲をバイト範囲に変換する
+    pub fn span(&self, start: (usize, usize), end: (usize, usize)) -> Span {
+        let start_byte = self.byte_offset(start.0, start.1);
+        let end_byte = self.byte_offset(end.0, end.1).max(start_byte);
+        Span::new(self.id, start_byte, end_byte)
+    }
+}
+
+fn content_hash(content: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    content.hash(&mut hasher);
+    hasher.finish()
+}
+
+// 字句解析・構文解析・診断で共有するソースファイルの一覧
+#[derive(Debug, Default)]
+pub struct SourceMap {
+    files: Vec<Option<Arc<SourceFile>>>, // 番号-1の位置に登録したファイル(削除した番号はNone)
+    by_name: HashMap<String, Vec<usize>>, // 名前ごとに登録したファイルの番号(登録順)
+    free_ids: Vec<usize>,                // 削除して再利用できる番号
+}
+
+impl SourceMap {
+    // ファイルを登録する(同じ名前・内容のファイルが登録済みならそれを返し、内容は複製しない)
+    pub fn add_file(&mut self, name: &str, content: &str) -> Arc<SourceFile> {
+        let hash = content_hash(content);
+        let registered = self.by_name.get(name).and_then(|ids| {
+            ids.iter()
+                .rev()
+                .filter_map(|&id| self.get(id))
+                .find(|file| file.hash == hash && file.content == content)
+        });
+        if let Some(file) = registered {
+            return file;
+        }
+        let id = match self.free_ids.pop() {
+            Some(id) => id,
+            None => {
+                self.files.push(None);
+                self.files.len()
+            }
+        };
+        let file = Arc::new(SourceFile::new(id, name, content));
+        self.files[id - 1] = Some(file.clone());
+        self.by_name.entry(name.to_string()).or_default().push(id);
+        file
+    }
+    // 同じ名前で登録した最後のファイルの内容を差し替える(番号はそのまま使う)
+    // エディタで編集中のファイルのように何度も読み直す場合に、古い内容が溜まらないようにする
+    pub fn replace_file(&mut self, name: &str, content: &str) -> Arc<SourceFile> {
+        let Some(&id) = self.by_name.get(name).and_then(|ids| ids.last()) else {
+            return self.add_file(name, content);
+        };
+        let slot = &mut self.files[id - 1];
+        if slot
+            .as_ref()
+            .is_none_or(|file| file.hash != content_hash(content) || file.content != content)
+        {
+            *slot = Some(Arc::new(SourceFile::new(id, name, content)));
+        }
+        slot.clone().unwrap()
+    }
+    // 同じ名前で登録したファイルをすべて削除し、番号を再利用できるようにする
+    // (REPLの入力のように一度だけ使うファイルが溜まらないようにする)
+    pub fn remove_file(&mut self, name: &str) {
+        for id in self.by_name.remove(name).unwrap_or_default() {
+            self.files[id - 1] = None;
+            self.free_ids.push(id);
+        }
+    }
+    pub fn get(&self, file_id: usize) -> Option<Arc<SourceFile>> {
+        self.files.get(file_id.checked_sub(1)?)?.clone()
+    }
+    pub fn line_column(&self, file_id: usize, byte: usize) -> Option<LineColumn> {
+        Some(self.get(file_id)?.line_column(byte))
+    }
+}
+
+// プロセス全体で共有するソースマップを操作する
+pub fn with_source_map<T>(f: impl FnOnce(&mut SourceMap) -> T) -> T {
+    static SOURCE_MAP: OnceLock<Mutex<SourceMap>> = OnceLock::new();
+    let mut source_map = SOURCE_MAP
+        .get_or_init(|| Mutex::new(SourceMap::default()))
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    f(&mut source_map)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::tokenizer::Lexer;
+    use crate::parser::syntax::Parser;
+
+    #[test]
+    fn maps_bytes_to_columns_in_each_unit() {
+        let file = SourceFile::new(1, "test.sc", "let a = 1;\r\nlet 名前 = \"😀x\";\n");
+        // 2行目の "x" は 名前(2文字,6バイト) と 😀(1文字,4バイト,UTF-16で2単位) の後ろ
+        let x = file.content.find('x').unwrap();
+        assert_eq!(
+            file.line_column(x),
+            LineColumn {
+                line: 2,
+                column: 12,
+                utf8_column: 19,
+                utf16_column: 13,
+            }
+        );
+        // \r は列に数えない
+        assert_eq!(
+            file.line_column(file.content.find('\r').unwrap()).column,
+            11
+        );
+        assert_eq!(
+            file.line_column(file.content.find('\n').unwrap()).column,
+            11
+        );
+        // 範囲外は末尾に丸める
+        assert_eq!(file.line_column(usize::MAX).line, 3);
+    }
+
+    #[test]
+    fn maps_columns_back_to_bytes() {
+        let file = SourceFile::new(1, "test.sc", "let a = 1;\r\nlet 名前 = \"😀x\";\n");
+        let x = file.content.find('x').unwrap();
+        assert_eq!(file.byte_offset(2, 12), x);
+        assert_eq!(file.utf16_byte_offset(2, 13), x);
+        // サロゲートペアの途中を指す列は次の文字に進める
+        assert_eq!(file.utf16_byte_offset(2, 12), x);
+        // 行末や範囲外の行
+        assert_eq!(
+            file.byte_offset(1, 100),
+            file.content.find('\r').unwrap() + 1
+        );
+        assert_eq!(file.byte_offset(10, 1), file.content.len());
+        assert_eq!(file.span((1, 5), (1, 6)), Span::new(1, 4, 5));
+    }
+
+    #[test]
+    fn joins_spans() {
+        let a = Span::new(1, 4, 8);
+        let b = Span::new(1, 10, 12);
+        assert_eq!(a.to(b), Span::new(1, 4, 12));
+        assert_eq!(b.to(a), Span::new(1, 4, 12));
+        assert_eq!(Span::default().to(b), b);
+        assert_eq!(a.to(Span::default()), a);
+        assert_eq!(a.to(Span::new(2, 0, 1)), a);
+    }
+
+    #[test]
+    fn registers_files_once() {
+        let mut source_map = SourceMap::default();
+        let first = source_map.add_file("a.sc", "let a = 1;");
+        assert_eq!(source_map.add_file("a.sc", "let a = 1;").id, first.id);
+        let edited = source_map.replace_file("a.sc", "let a = 2;");
+        assert_eq!(edited.id, first.id);
+        assert_eq!(source_map.get(first.id).unwrap().content, "let a = 2;");
+        assert_ne!(source_map.add_file("b.sc", "").id, first.id);
+        assert!(source_map.get(0).is_none());
+        // 同じ内容を読み直しても登録は増えない
+        let again = source_map.add_file("a.sc", "let a = 2;");
+        assert!(Arc::ptr_eq(&again, &edited));
+        assert_eq!(source_map.files.len(), 2);
+        // 削除した番号は次に登録するファイルで使う
+        source_map.remove_file("a.sc");
+        assert!(source_map.get(first.id).is_none());
+        assert_eq!(source_map.add_file("c.sc", "").id, first.id);
+        assert_eq!(source_map.files.len(), 2);
+    }
+
+    #[test]
+    fn tokens_cover_their_source_text() {
+        let content = "let 名前 = \"😀\";\r\nlet b = 名前;";
+        let tokens = Lexer::from_tokenize("span_test.sc", content.to_string()).unwrap();
+        let texts: Vec<&str> = tokens
+            .iter()
+            .map(|token| token.span())
+            .filter(|span| span.start_byte < span.end_byte)
+            .map(|span| &content[span.start_byte..span.end_byte])
+            .collect();
+        assert_eq!(
+            texts,
+            ["let", "名前", "=", "\"😀\"", ";", "let", "b", "=", "名前", ";"]
+        );
+    }
+
+    #[test]
+    fn nodes_cover_their_source_text() {
+        let content = "fn add(a, b) {\n    return a + b;\n}\nlet total = add(1, 2);";
+        let tokens = Lexer::from_tokenize("node_span_test.sc", content.to_string()).unwrap();
+        let node = Parser::from_parse(&tokens, "node_span_test.sc", content.to_string()).unwrap();
+        // 文の区切りの ; も独立したノードになる
+        let texts: Vec<&str> = node
+            .iter()
+            .map(|node| &content[node.span.start_byte..node.span.end_byte])
+            .filter(|text| !text.is_empty())
+            .collect();
+        assert_eq!(
+            texts,
+            [
+                "fn add(a, b) {\n    return a + b;\n}",
+                "let total = add(1, 2)",
+                ";"
+            ]
+        );
+    }
+}
@@ -48,3 +48,9 @@ impl Size for Value {
         }
     }
 }
+
+impl Size for String {
+    fn size(&self) -> usize {
+        std::mem::size_of::<String>() + self.len()
+    }
+}
@@ -1,16 +1,100 @@
-// 予約済みキーワード
-pub static RESERVED_WORDS: &[&str] = &[
-    "if", "else", "while", "for", "break", "continue", "i32", "i64", "f32", "f64", "u32", "u64",
-    "type", "let", "l", "var", "v", "fn", "mut", "loop", "=", "+", "++", "-", "--", "+=", "-=",
-    "*", "*=", "/", "/=", "{", "}", "[", "]", "mod", "use", "bool", "struct", "enum", "%", "&",
-    "&=", "|", "|=", "^", "~", "^=",
-];
-
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
 #[cfg(any(feature = "full", feature = "parser"))]
 use crate::parser::syntax::Node;
+// キーワード
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Serialize)]
+pub enum Keyword {
+    Fn,       // 関数定義
+    Callback, // コールバック関数定義
+    Let,      // 変数定義
+    Var,      // 変数定義
+    Mut,      // 可変
+    If,       // 条件分岐
+    Else,     // 条件分岐(それ以外)
+    While,    // 条件付きループ
+    For,      // 反復
+    In,       // 反復対象
+    Loop,     // 無限ループ
+    Break,    // ループの中断
+    Continue, // ループの継続
+    Return,   // 関数の戻り値
+    Struct,   // 構造体定義
+    Impl,     // 構造体実装
+    Enum,     // 列挙型定義
+    Type,     // 型定義
+    Mod,      // モジュール
+    Use,      // モジュールの使用
+    I32,      // 型名
+    I64,      // 型名
+    F32,      // 型名
+    F64,      // 型名
+    U32,      // 型名
+    U64,      // 型名
+    Bool,     // 型名
+    Char,     // 型名
+}
+
+// キーワードの表(綴り,種類)
+// 字句解析でこの表にある語をキーワードのトークンにするため、変数名などの識別子には使えない
+pub static KEYWORDS: &[(&str, Keyword)] = &[
+    ("fn", Keyword::Fn),
+    ("callback", Keyword::Callback),
+    ("let", Keyword::Let),
+    ("l", Keyword::Let),
+    ("var", Keyword::Var),
+    ("v", Keyword::Var),
+    ("mut", Keyword::Mut),
+    ("mutable", Keyword::Mut),
+    ("if", Keyword::If),
+    ("else", Keyword::Else),
+    ("while", Keyword::While),
+    ("for", Keyword::For),
+    ("in", Keyword::In),
+    ("loop", Keyword::Loop),
+    ("break", Keyword::Break),
+    ("continue", Keyword::Continue),
+    ("return", Keyword::Return),
+    ("struct", Keyword::Struct),
+    ("impl", Keyword::Impl),
+    ("enum", Keyword::Enum),
+    ("type", Keyword::Type),
+    ("mod", Keyword::Mod),
+    ("use", Keyword::Use),
+    ("i32", Keyword::I32),
+    ("i64", Keyword::I64),
+    ("f32", Keyword::F32),
+    ("f64", Keyword::F64),
+    ("u32", Keyword::U32),
+    ("u64", Keyword::U64),
+    ("bool", Keyword::Bool),
+    ("char", Keyword::Char),
+];
+
+impl Keyword {
+    pub fn from_word(word: &str) -> Option<Keyword> {
+        KEYWORDS
+            .iter()
+            .find(|(spelling, _)| *spelling == word)
+            .map(|(_, keyword)| *keyword)
+    }
+    // 型名として使えるキーワードかどうか
+    pub fn is_type(&self) -> bool {
+        matches!(
+            self,
+            Keyword::I32
+                | Keyword::I64
+                | Keyword::F32
+                | Keyword::F64
+                | Keyword::U32
+                | Keyword::U64
+                | Keyword::Bool
+                | Keyword::Char
+        )
+    }
+}
+
 // トークンの種類
 #[derive(PartialEq, Debug, Clone, Serialize)]
 pub enum TokenType {
@@ -44,6 +128,7 @@ pub enum TokenType {
     ShiftLeftAssign,                           // ビットシフト左と代入
     ShiftRightAssign,                          // ビットシフト右と代入
     Ident,                                     // 識別子
+    Keyword(Keyword),                          // キーワード
     Number,                                    // 数値
     LeftParen,                                 // 左括弧
     RightParen,                                // 右括弧
@@ -60,10 +145,17 @@ pub enum TokenType {
     SingleQuote,                               // シングルクオーテーション
     SingleComment(String, (usize, usize)),     // 単一コメント
     MultiComment(Vec<String>, (usize, usize)), // 複数行コメント
+    DocComment(String),                        // ドキュメントコメント(/// と /** */)
     RightArrow,                                // 右矢印
     Eof,                                       // トークンの終わり
     Range,                                     // 範囲指定
     ScopeResolution,                           //  スコープ解決
+    TemplateStart,                             // 補間文字列の開始
+    TemplateText,                              // 補間文字列のリテラル部分
+    TemplateExprStart,                         // 補間文字列の埋め込み式の開始
+    TemplateExprEnd,                           // 補間文字列の埋め込み式の終わり
+    TemplateEnd,                               // 補間文字列の終わり
+    Error,                                     // 字句のエラー(読めなかった字句)
 }
 // 制御構造
 #[cfg(any(feature = "full", feature = "parser"))]
@@ -119,11 +211,62 @@ pub enum Operator {
 #[cfg(any(feature = "full", feature = "parser"))]
 #[derive(PartialEq, Debug, Clone, Serialize, Deserialize)]
 pub enum DataType {
-    Int(i64),       // 数値型(64bit整数値)
-    Float(f64),     // 浮動小数点型(64bit小数値)
-    String(String), // 文字列型(String)
-    Bool(bool),     // ブーリアン値(bool)
-    Unit(()),       // Unit値(())
+    Int(i64),                     // 数値型(64bit整数値)
+    Float(f64),                   // 浮動小数点型(64bit小数値)
+    String(String),               // 文字列型(String)
+    Char(char),                   // 文字型(Unicodeスカラー値1つ)
+    UInt(u64),                    // i64に収まらない符号なし整数値
+    Typed(Box<DataType>, String), // 型接尾辞付きの数値リテラル(値,型名) 例: 10u8
+    Bool(bool),                   // ブーリアン値(bool)
+    Unit(()),                     // Unit値(())
+}
+
+#[cfg(any(feature = "full", feature = "parser"))]
+impl DataType {
+    // リテラルの型(値から推定した型と同じ規則で決め、文字と型接尾辞はリテラルの表記から決める)
+    pub fn literal_type(&self) -> Option<String> {
+        let value = match self {
+            DataType::Int(number) => Value::from(*number),
+            DataType::UInt(number) => Value::from(*number),
+            DataType::Float(number) => Value::from(*number),
+            DataType::String(text) => Value::from(text.as_str()),
+            DataType::Bool(b) => Value::from(*b),
+            DataType::Char(_) => return Some("char".to_string()),
+            DataType::Typed(_, type_name) => return Some(type_name.clone()),
+            DataType::Unit(_) => return None,
+        };
+        Some(infer_type(&value))
+    }
+}
+
+// 値から型を推定する(REPLの :type や :vars と、エディタのホバーで同じ型を表示する)
+pub fn infer_type(value: &Value) -> String {
+    match value {
+        Value::Array(_) => "array".to_string(),
+        Value::Null => "void".to_string(),
+        Value::Number(num) => {
+            if let Some(i_value) = num.as_i64() {
+                if i_value >= i32::MIN as i64 && i_value <= i32::MAX as i64 {
+                    "i32".to_string()
+                } else {
+                    "i64".to_string()
+                }
+            } else if num.is_u64() {
+                "u64".to_string()
+            } else if let Some(f_value) = num.as_f64() {
+                if f_value >= f32::MIN as f64 && f_value <= f32::MAX as f64 {
+                    "f32".to_string()
+                } else {
+                    "f64".to_string()
+                }
+            } else {
+                "unknown".to_string()
+            }
+        }
+        Value::String(_) => "string".to_string(),
+        Value::Bool(_) => "bool".to_string(),
+        _ => "unknown".to_string(),
+    }
 }
 
 // 定義
@@ -133,10 +276,22 @@ pub enum Declaration {
     Variable(Box<Node>, Box<Node>, Box<Node>, bool, bool), // 変数定義()
     Struct(String, Vec<Box<Node>>),                        // 構造体定義()
     Impl(String, Vec<Box<Node>>),                          // 構造体実装()
-    Function(String, Vec<(Box<Node>, String)>, Box<Node>, Box<Node>, bool), // 関数定義()
-    CallBackFunction(String, Vec<(Box<Node>, String)>, Box<Node>, Box<Node>, bool), // コールバック関数定義()
-    Type(Box<Node>, Box<Node>),       // 型定義,型エイリアス()
-    Array(Box<Node>, Vec<Box<Node>>), // 配列(型名,値)
+    Function(
+        String,
+        Vec<(Box<Node>, String, bool)>,
+        Box<Node>,
+        Box<Node>,
+        bool,
+    ), // 関数定義(引数は(型,引数名,可変性フラグ))
+    CallBackFunction(
+        String,
+        Vec<(Box<Node>, String, bool)>,
+        Box<Node>,
+        Box<Node>,
+        bool,
+    ), // コールバック関数定義()
+    Type(Box<Node>, Box<Node>),                            // 型定義,型エイリアス()
+    Array(Box<Node>, Vec<Box<Node>>),                      // 配列(型名,値)
 }
 
 // NodeValue
@@ -150,6 +305,8 @@ pub enum NodeValue {
     Assign(Box<Node>, Box<Node>, Box<Node>),   // 代入
     Block(Vec<Box<Node>>),                     // ブロック
     Variable(Box<Node>, String, bool, bool),   // 変数(型名,変数名,可変性フラグ,参照型フラグ)
+    Dereference(Box<Node>),                    // 参照外し(参照を返す式)
+    Interpolation(Vec<Box<Node>>),             // 補間文字列(リテラル部分と埋め込み式)
     Call(String, Vec<Node>, bool),             // 関数呼び出し
     ScopeResolution(Vec<Box<Node>>),           // スコープ解決
     MultiComment(Vec<String>, (usize, usize)), // 複数行コメント
@@ -193,6 +350,14 @@ impl From<Box<Node>> for SystemValue {
                 value: NodeValue::DataType(DataType::String(ref value)),
                 ..
             } => SystemValue::String(value.clone()),
+            Node {
+                value: NodeValue::DataType(DataType::Char(ref value)),
+                ..
+            } => SystemValue::String(value.to_string()),
+            Node {
+                value: NodeValue::DataType(DataType::UInt(ref value)),
+                ..
+            } => SystemValue::Usize(*value as usize),
 
             Node {
                 value: NodeValue::DataType(DataType::Int(ref value)),
@@ -257,6 +422,10 @@ impl From<Box<Node>> for SystemValue {
                             value: NodeValue::DataType(DataType::String(ref value)),
                             ..
                         } => SystemValue::String(value.clone()),
+                        Node {
+                            value: NodeValue::DataType(DataType::Char(ref value)),
+                            ..
+                        } => SystemValue::String(value.to_string()),
 
                         Node {
                             value: NodeValue::DataType(DataType::Int(ref value)),
@@ -345,6 +514,19 @@ impl From<Box<Node>> for DataType {
                 value: NodeValue::Variable(_, ref name, _, _),
                 ..
             } => DataType::String(name.clone()),
+            // [型名] は配列の要素型として "[型名]" で表す
+            Node {
+                value: NodeValue::Declaration(Declaration::Array(_, ref values)),
+                ..
+            } => match values.as_slice() {
+                [element] => match element.value {
+                    NodeValue::Variable(_, ref name, _, _) => {
+                        DataType::String(format!("[{}]", name))
+                    }
+                    _ => DataType::Unit(()),
+                },
+                _ => DataType::Unit(()),
+            },
             _ => DataType::Unit(()), // 他のケースに対するデフォルト処理
         }
     }